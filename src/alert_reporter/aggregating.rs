@@ -0,0 +1,241 @@
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::alert_reporter::AlertReporter;
+use crate::duration::deserialize_duration;
+use crate::watcher::ActiveAlert;
+
+fn default_flush_window() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_max_alerts() -> usize {
+    20
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AggregationConfiguration {
+    /// How long to wait after the first buffered alert before reporting the buffer.
+    #[serde(
+        default = "default_flush_window",
+        deserialize_with = "deserialize_duration"
+    )]
+    flush_window: Duration,
+    /// Report the buffer right away once it holds this many alerts.
+    #[serde(default = "default_max_alerts")]
+    max_alerts: usize,
+}
+
+#[derive(Default)]
+struct Buffer {
+    alerts: Vec<ActiveAlert>,
+    /// When the first alert of the buffer was received.
+    opened_at: Option<Instant>,
+    stopped: bool,
+}
+
+impl Buffer {
+    fn take(&mut self) -> Vec<ActiveAlert> {
+        self.opened_at = None;
+        mem::take(&mut self.alerts)
+    }
+}
+
+struct Shared<R> {
+    reporter: R,
+    buffer: Mutex<Buffer>,
+    changed: Condvar,
+}
+
+impl<R: AlertReporter> Shared<R> {
+    fn lock(&self) -> Result<MutexGuard<'_, Buffer>> {
+        self.buffer
+            .lock()
+            .map_err(|_| anyhow!("Alert buffer lock is poisoned"))
+    }
+
+    fn flush(&self, alerts: Vec<ActiveAlert>) -> Result<()> {
+        match combine(alerts) {
+            Some(alert) => self.reporter.report(&alert),
+            None => Ok(()),
+        }
+    }
+
+    /// Reports the buffer whenever its flush window closes, until the reporter is dropped.
+    fn run(&self, window: Duration) -> Result<()> {
+        let mut buffer = self.lock()?;
+        loop {
+            let deadline = buffer.opened_at.map(|opened_at| opened_at + window);
+            let now = Instant::now();
+            buffer = match deadline {
+                _ if buffer.stopped => break,
+                Some(deadline) if deadline <= now => {
+                    let alerts = buffer.take();
+                    drop(buffer);
+                    if let Err(e) = self.flush(alerts) {
+                        warn!(reporter_error = ?e);
+                    }
+                    self.lock()?
+                }
+                Some(deadline) => {
+                    self.changed
+                        .wait_timeout(buffer, deadline - now)
+                        .map_err(|_| anyhow!("Alert buffer lock is poisoned"))?
+                        .0
+                }
+                None => self
+                    .changed
+                    .wait(buffer)
+                    .map_err(|_| anyhow!("Alert buffer lock is poisoned"))?,
+            };
+        }
+        let alerts = buffer.take();
+        drop(buffer);
+        self.flush(alerts)
+    }
+}
+
+/// Combines alerts into a single one listing all of them, with the highest of their severities
+/// and the labels they all share. A single alert is returned unchanged.
+pub(crate) fn combine(mut alerts: Vec<ActiveAlert>) -> Option<ActiveAlert> {
+    if alerts.len() <= 1 {
+        return alerts.pop();
+    }
+    let first = alerts.iter().min_by_key(|a| a.detected_at)?;
+    let mut message = format!("📦 {} alerts:", alerts.len());
+    for alert in &alerts {
+        message.push_str(&format!("\n[{:?}] {}", alert.severity, alert.message));
+    }
+    Some(ActiveAlert {
+        message,
+        severity: alerts.iter().map(|a| a.severity).max()?,
+        detected_at: first.detected_at,
+        hostname: first.hostname.clone(),
+        labels: first
+            .labels
+            .iter()
+            .filter(|(k, v)| alerts.iter().all(|a| a.labels.get(*k) == Some(v)))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        kind: match alerts.iter().all(|a| a.kind == first.kind) {
+            true => first.kind.clone(),
+            false => String::new(),
+        },
+        fingerprint: alerts
+            .iter()
+            .map(|a| a.fingerprint.as_str())
+            .collect::<Vec<_>>()
+            .join("+"),
+        escalation: first
+            .escalation
+            .clone()
+            .filter(|e| alerts.iter().all(|a| a.escalation.as_ref() == Some(e))),
+        description: first
+            .description
+            .clone()
+            .filter(|d| alerts.iter().all(|a| a.description.as_ref() == Some(d))),
+        runbook_url: first
+            .runbook_url
+            .clone()
+            .filter(|r| alerts.iter().all(|a| a.runbook_url.as_ref() == Some(r))),
+        annotations: first
+            .annotations
+            .iter()
+            .filter(|(k, v)| alerts.iter().all(|a| a.annotations.get(*k) == Some(v)))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+    })
+}
+
+/// Buffers alerts and reports them as a single combined alert, so that a burst of alerts only
+/// results in one notification.
+///
+/// A background thread reports the buffer once the flush window, started by the first buffered
+/// alert, closes. The buffer is reported early, from the thread reporting the alert, once it
+/// holds `max_alerts` alerts. Resolved alerts are not buffered: the buffer is reported before
+/// them so that they are never reported before the alert they resolve. Whatever is still
+/// buffered when the reporter is dropped gets reported before the drop returns.
+pub struct AggregatingReporter<R: AlertReporter + Send + Sync + 'static> {
+    shared: Arc<Shared<R>>,
+    max_alerts: usize,
+    flusher: Option<JoinHandle<()>>,
+}
+
+impl<R: AlertReporter + Send + Sync + 'static> AggregatingReporter<R> {
+    pub fn new(reporter: R, configuration: AggregationConfiguration) -> Self {
+        let shared = Arc::new(Shared {
+            reporter,
+            buffer: Mutex::new(Buffer::default()),
+            changed: Condvar::new(),
+        });
+        let window = configuration.flush_window;
+        let flusher = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                if let Err(e) = shared.run(window) {
+                    warn!(aggregation_error = ?e);
+                }
+            })
+        };
+        AggregatingReporter {
+            shared,
+            max_alerts: configuration.max_alerts,
+            flusher: Some(flusher),
+        }
+    }
+
+    fn flush_now(&self) -> Result<()> {
+        let alerts = self.shared.lock()?.take();
+        self.shared.flush(alerts)
+    }
+}
+
+impl<R: AlertReporter + Send + Sync + 'static> AlertReporter for AggregatingReporter<R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        let mut buffer = self.shared.lock()?;
+        buffer.alerts.push(alert.clone());
+        if buffer.alerts.len() >= self.max_alerts {
+            let alerts = buffer.take();
+            drop(buffer);
+            return self.shared.flush(alerts);
+        }
+        if buffer.opened_at.is_none() {
+            buffer.opened_at = Some(Instant::now());
+            self.shared.changed.notify_one();
+        }
+        Ok(())
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        if let Err(e) = self.flush_now() {
+            warn!(reporter_error = ?e);
+        }
+        self.shared.reporter.report_resolved(alert)
+    }
+
+    /// Only passes the end of the cycle on: the buffer is reported once its window closes.
+    fn flush(&self) -> Result<()> {
+        self.shared.reporter.flush()
+    }
+}
+
+impl<R: AlertReporter + Send + Sync + 'static> Drop for AggregatingReporter<R> {
+    fn drop(&mut self) {
+        match self.shared.lock() {
+            Ok(mut buffer) => buffer.stopped = true,
+            Err(e) => warn!(aggregation_error = ?e),
+        }
+        self.shared.changed.notify_one();
+        if let Some(flusher) = self.flusher.take() {
+            if flusher.join().is_err() {
+                warn!("Alert aggregation thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use anyhow::{anyhow, Result};
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::watcher::ActiveAlert;
+
+/// Lets at most `max_in_flight` reports through to a reporter at once, e.g. for SMTP servers or
+/// webhook endpoints turning down concurrent connections. Reports beyond wait for one of the
+/// reports in flight to complete, in no particular order. Resolutions and flushes count as
+/// reports.
+pub struct ConcurrencyLimitedReporter<R: AlertReporter> {
+    reporter: R,
+    max_in_flight: usize,
+    in_flight: Mutex<usize>,
+    completed: Condvar,
+}
+
+/// A report in flight, completed when dropped, even if the reporter panicked.
+struct InFlight<'a, R: AlertReporter>(&'a ConcurrencyLimitedReporter<R>);
+
+impl<R: AlertReporter> Drop for InFlight<'_, R> {
+    fn drop(&mut self) {
+        *self.0.in_flight() -= 1;
+        self.0.completed.notify_one();
+    }
+}
+
+impl<R: AlertReporter> ConcurrencyLimitedReporter<R> {
+    pub fn new(reporter: R, max_in_flight: usize) -> Result<Self> {
+        if max_in_flight == 0 {
+            return Err(anyhow!("'max_in_flight' of a reporter must be positive"));
+        }
+        Ok(ConcurrencyLimitedReporter {
+            reporter,
+            max_in_flight,
+            in_flight: Mutex::new(0),
+            completed: Condvar::new(),
+        })
+    }
+
+    fn in_flight(&self) -> MutexGuard<'_, usize> {
+        // The count is only ever incremented and decremented, a panic can not leave it
+        // inconsistent.
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Waits until fewer than `max_in_flight` reports are in flight, then counts one more.
+    fn start(&self) -> InFlight<'_, R> {
+        let mut in_flight = self
+            .completed
+            .wait_while(self.in_flight(), |n| *n >= self.max_in_flight)
+            .unwrap_or_else(|e| e.into_inner());
+        *in_flight += 1;
+        InFlight(self)
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for ConcurrencyLimitedReporter<R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        let _in_flight = self.start();
+        self.reporter.report(alert)
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        let _in_flight = self.start();
+        self.reporter.report_batch(alerts)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        let _in_flight = self.start();
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let _in_flight = self.start();
+        self.reporter.flush()
+    }
+
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let _in_flight = self.start();
+        self.reporter.report_diff(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::Severity;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Takes 100 ms per report, telling when each one starts and completes.
+    #[derive(Default)]
+    struct TimelineReporter(Mutex<Vec<String>>);
+
+    impl AlertReporter for TimelineReporter {
+        fn report(&self, alert: &ActiveAlert) -> Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("start {}", alert.message));
+            thread::sleep(Duration::from_millis(100));
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("end {}", alert.message));
+            Ok(())
+        }
+
+        fn report_resolved(&self, _alert: &ActiveAlert) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn the_second_report_starts_once_the_first_completed() {
+        let reporter = ConcurrencyLimitedReporter::new(TimelineReporter::default(), 1).unwrap();
+
+        thread::scope(|scope| {
+            for message in ["first", "second"] {
+                let reporter = &reporter;
+                scope.spawn(move || {
+                    reporter
+                        .report(&ActiveAlert::new(message.to_string(), Severity::Warning))
+                        .unwrap()
+                });
+            }
+        });
+
+        // Either alert may get through first, but never while the other one is in flight.
+        let timeline = reporter.reporter.0.into_inner().unwrap();
+        assert_eq!(timeline.len(), 4);
+        for report in timeline.chunks(2) {
+            assert_eq!(
+                report[0].strip_prefix("start "),
+                report[1].strip_prefix("end "),
+                "{:?}",
+                timeline
+            );
+        }
+    }
+
+    #[test]
+    fn reports_under_the_limit_are_in_flight_together() {
+        let reporter = ConcurrencyLimitedReporter::new(TimelineReporter::default(), 2).unwrap();
+
+        thread::scope(|scope| {
+            for message in ["first", "second"] {
+                let reporter = &reporter;
+                scope.spawn(move || {
+                    reporter
+                        .report(&ActiveAlert::new(message.to_string(), Severity::Warning))
+                        .unwrap()
+                });
+            }
+        });
+
+        let timeline = reporter.reporter.0.into_inner().unwrap();
+        assert!(timeline[1].starts_with("start "), "{:?}", timeline);
+    }
+
+    #[test]
+    fn a_limit_of_zero_is_rejected() {
+        assert!(ConcurrencyLimitedReporter::new(TimelineReporter::default(), 0).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::watcher::{hostname, ActiveAlert, Severity};
+
+fn default_after_failures() -> u32 {
+    3
+}
+
+/// Reporter told when another reporter keeps failing, such as a console when Slack is down.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FallbackConfiguration {
+    /// Name of the reporter receiving the alerts about the failing ones.
+    reporter: String,
+    /// Failures in a row of a reporter before the fallback is told.
+    #[serde(default = "default_after_failures")]
+    after_failures: u32,
+}
+
+impl FallbackConfiguration {
+    pub fn validate(&self) -> Result<()> {
+        if self.after_failures == 0 {
+            return Err(anyhow!("after_failures of the fallback must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// What the fallback is told after a report.
+pub enum Outcome {
+    /// The reporter just reached the failures in a row of the configuration.
+    Failing(ActiveAlert),
+    /// The reporter succeeded again after the fallback was told it was failing.
+    Recovered(ActiveAlert),
+}
+
+/// Failures in a row of a reporter.
+#[derive(Default)]
+struct Failing {
+    count: u32,
+    /// Alert the fallback was told, once `count` reached `after_failures`.
+    reported: Option<ActiveAlert>,
+}
+
+/// Failures in a row of each reporter, by name. The fallback is told once when a reporter
+/// reaches `after_failures`, then once when it succeeds again. Its own failures are not counted,
+/// so that a failing fallback is only logged instead of reporting about itself.
+pub struct Failures {
+    configuration: FallbackConfiguration,
+    hostname: String,
+    failing: Mutex<BTreeMap<String, Failing>>,
+}
+
+impl Failures {
+    pub fn new(configuration: FallbackConfiguration) -> Self {
+        Failures {
+            configuration,
+            hostname: hostname(),
+            failing: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn fallback(&self) -> &str {
+        &self.configuration.reporter
+    }
+
+    fn failing(&self) -> MutexGuard<'_, BTreeMap<String, Failing>> {
+        // Reporters are only ever inserted and removed, a panic can not leave them inconsistent.
+        self.failing.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Counts the result of a report of `reporter`, returning what the fallback is to be told.
+    pub fn record(&self, reporter: &str, result: &Result<()>) -> Option<Outcome> {
+        if reporter == self.fallback() {
+            return None;
+        }
+        let mut failing = self.failing();
+        let Err(e) = result else {
+            return failing.remove(reporter)?.reported.map(Outcome::Recovered);
+        };
+        let failures = failing.entry(reporter.to_string()).or_default();
+        failures.count = failures.count.saturating_add(1);
+        if failures.count != self.configuration.after_failures {
+            return None;
+        }
+        let alert = ActiveAlert::new(
+            format!(
+                "📮 Reporter {} failed {} times in a row, last with: {:#}",
+                reporter, failures.count, e
+            ),
+            Severity::Critical,
+        )
+        .annotated([("failures", failures.count.to_string())])
+        .detected(
+            &self.hostname,
+            SystemTime::now(),
+            &BTreeMap::from([("reporter".to_string(), reporter.to_string())]),
+            "reporter",
+            format!("reporter:{}", reporter),
+        );
+        failures.reported = Some(alert.clone());
+        Some(Outcome::Failing(alert))
+    }
+}
@@ -0,0 +1,94 @@
+//! Prefixes put before the messages of alerts by the reporters writing them as plain text, for
+//! whatever reads these lines to tell severities apart from the first word, and the precision of
+//! the numbers in the messages.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::watcher::{self, ActiveAlert, Severity};
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Configuration {
+    /// Prefix of the messages of each severity, such as `[CRIT]` or `🔴`. Messages of the
+    /// severities not listed are left as is.
+    #[serde(default)]
+    prefixes: BTreeMap<Severity, String>,
+    /// Decimal places of the measured values and thresholds in the messages and annotations of
+    /// alerts, 1 by default.
+    #[serde(default)]
+    precision: Option<usize>,
+}
+
+static CONFIGURATION: OnceLock<Configuration> = OnceLock::new();
+
+/// Formats the messages of every alert from now on. Only the first configuration is kept: a
+/// reload of the watchers leaves it as is, and changing it needs a restart.
+pub fn init(configuration: Configuration) {
+    if let Some(precision) = configuration.precision {
+        watcher::init_precision(precision);
+    }
+    let _ = CONFIGURATION.set(configuration);
+}
+
+impl Configuration {
+    fn prefixed_message(&self, alert: &ActiveAlert) -> String {
+        match self.prefixes.get(&alert.severity) {
+            Some(prefix) => format!("{} {}", prefix, alert.message),
+            None => alert.message.clone(),
+        }
+    }
+}
+
+/// Message of the alert, after the prefix of its severity if there is one. The message of the
+/// alert itself is left untouched, for structured reporters to send as is.
+pub fn prefixed_message(alert: &ActiveAlert) -> String {
+    match CONFIGURATION.get() {
+        Some(configuration) => configuration.prefixed_message(alert),
+        None => alert.message.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn prefixes_follow_the_severity() {
+        let configuration: Configuration = serde_json::from_value(json!({
+            "prefixes": { "Critical": "[CRIT]", "Warning": "🟠" },
+        }))
+        .unwrap();
+        let alert = |severity| ActiveAlert::new("Disk / is 91.5% full".to_string(), severity);
+
+        assert_eq!(
+            configuration.prefixed_message(&alert(Severity::Critical)),
+            "[CRIT] Disk / is 91.5% full"
+        );
+        assert_eq!(
+            configuration.prefixed_message(&alert(Severity::Warning)),
+            "🟠 Disk / is 91.5% full"
+        );
+        assert_eq!(
+            configuration.prefixed_message(&alert(Severity::Info)),
+            "Disk / is 91.5% full"
+        );
+    }
+
+    #[test]
+    fn the_message_of_the_alert_is_left_untouched() {
+        let configuration: Configuration =
+            serde_json::from_value(json!({ "prefixes": { "Critical": "[CRIT]" } })).unwrap();
+        let alert = ActiveAlert::new("Disk / is 91.5% full".to_string(), Severity::Critical);
+
+        configuration.prefixed_message(&alert);
+
+        assert_eq!(alert.message, "Disk / is 91.5% full");
+        assert_eq!(
+            serde_json::to_value(&alert).unwrap()["message"],
+            "Disk / is 91.5% full"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::mem;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::alert_reporter::aggregating::combine;
+use crate::alert_reporter::AlertReporter;
+use crate::watcher::ActiveAlert;
+
+/// What to do with the alerts that lack the grouping label.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ungrouped {
+    /// Report each of them on its own.
+    #[default]
+    Separately,
+    /// Report them together, as a group of their own.
+    Together,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupingConfiguration {
+    /// Label whose value the alerts are grouped by, such as `service`.
+    label: String,
+    #[serde(default)]
+    ungrouped: Ungrouped,
+}
+
+/// Holds the alerts reported during a scheduler cycle, then reports a single combined alert for
+/// each value of the grouping label once the cycle ends, see [`AlertReporter::flush`]. Groups are
+/// reported in the order their first alert was. Resolved alerts are reported right away, as the
+/// alerts they resolve were reported by an earlier cycle. Whatever is still held when the
+/// reporter is dropped gets reported.
+pub struct GroupingReporter<R: AlertReporter> {
+    reporter: R,
+    configuration: GroupingConfiguration,
+    pending: Mutex<Vec<ActiveAlert>>,
+}
+
+impl<R: AlertReporter> GroupingReporter<R> {
+    pub fn new(reporter: R, configuration: GroupingConfiguration) -> Self {
+        GroupingReporter {
+            reporter,
+            configuration,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Vec<ActiveAlert>>> {
+        self.pending
+            .lock()
+            .map_err(|_| anyhow!("Grouped alerts lock is poisoned"))
+    }
+
+    /// Splits alerts by the value of the grouping label, keeping their order.
+    fn group(&self, alerts: Vec<ActiveAlert>) -> Vec<Vec<ActiveAlert>> {
+        let mut groups: Vec<(Option<String>, Vec<ActiveAlert>)> = Vec::new();
+        for alert in alerts {
+            let value = alert.labels.get(&self.configuration.label).cloned();
+            if value.is_none() && self.configuration.ungrouped == Ungrouped::Separately {
+                groups.push((None, vec![alert]));
+                continue;
+            }
+            match groups.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, group)) => group.push(alert),
+                None => groups.push((value, vec![alert])),
+            }
+        }
+        groups.into_iter().map(|(_, group)| group).collect()
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for GroupingReporter<R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.lock()?.push(alert.clone());
+        Ok(())
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        self.lock()?.extend_from_slice(alerts);
+        Ok(())
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let alerts = mem::take(&mut *self.lock()?);
+        let combined = self
+            .group(alerts)
+            .into_iter()
+            .filter_map(combine)
+            .collect::<Vec<_>>();
+        if !combined.is_empty() {
+            self.reporter.report_batch(&combined)?;
+        }
+        self.reporter.flush()
+    }
+}
+
+impl<R: AlertReporter> Drop for GroupingReporter<R> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!(reporter_error = ?e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::watcher::Severity;
+    use std::collections::BTreeMap;
+
+    fn alert(message: &str, service: Option<&str>) -> ActiveAlert {
+        ActiveAlert {
+            labels: service
+                .map(|s| BTreeMap::from([("service".to_string(), s.to_string())]))
+                .unwrap_or_default(),
+            ..ActiveAlert::new(message.to_string(), Severity::Warning)
+        }
+    }
+
+    fn grouping(
+        reporter: &RecordingReporter,
+        ungrouped: Ungrouped,
+    ) -> GroupingReporter<&RecordingReporter> {
+        GroupingReporter::new(
+            reporter,
+            GroupingConfiguration {
+                label: "service".to_string(),
+                ungrouped,
+            },
+        )
+    }
+
+    #[test]
+    fn alerts_sharing_the_label_are_reported_as_one() {
+        let recording = RecordingReporter::new();
+        let grouping = grouping(&recording, Ungrouped::Separately);
+
+        grouping
+            .report(&alert("Disk is full", Some("payments")))
+            .unwrap();
+        grouping
+            .report(&alert("CPU is busy", Some("search")))
+            .unwrap();
+        grouping
+            .report(&alert("Memory is low", Some("payments")))
+            .unwrap();
+        assert!(recording.alerts().is_empty());
+        grouping.flush().unwrap();
+
+        let alerts = recording.alerts();
+        assert_eq!(alerts.len(), 2, "{:?}", alerts);
+        assert_eq!(
+            alerts[0].message,
+            "📦 2 alerts:\n[Warning] Disk is full\n[Warning] Memory is low"
+        );
+        assert_eq!(alerts[0].labels["service"], "payments");
+        assert_eq!(alerts[1].message, "CPU is busy");
+    }
+
+    #[test]
+    fn alerts_without_the_label_are_reported_as_configured() {
+        let reported = |ungrouped| {
+            let recording = RecordingReporter::new();
+            let grouping = grouping(&recording, ungrouped);
+            grouping
+                .report_batch(&[alert("Disk is full", None), alert("CPU is busy", None)])
+                .unwrap();
+            grouping.flush().unwrap();
+            recording.alerts().len()
+        };
+
+        assert_eq!(reported(Ungrouped::Separately), 2);
+        assert_eq!(reported(Ungrouped::Together), 1);
+    }
+
+    #[test]
+    fn resolutions_are_reported_right_away() {
+        let recording = RecordingReporter::new();
+        let grouping = grouping(&recording, Ungrouped::Separately);
+
+        grouping
+            .report_resolved(&alert("Disk is full", Some("payments")))
+            .unwrap();
+
+        assert_eq!(recording.resolved().len(), 1);
+    }
+}
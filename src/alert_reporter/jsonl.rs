@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::alert_reporter::AlertReporter;
+use crate::stable_hash::StableHasher;
+use crate::watcher::{ActiveAlert, Severity};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    /// File the alerts are appended to, created if it does not exist.
+    path: PathBuf,
+}
+
+#[derive(Serialize, Debug)]
+struct Event<'a> {
+    /// Same for a report of an alert and its resolution, to correlate them.
+    id: String,
+    fingerprint: &'a str,
+    timestamp: String,
+    hostname: &'a str,
+    severity: Severity,
+    message: &'a str,
+    resolved: bool,
+    labels: &'a BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runbook_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: &'a BTreeMap<String, String>,
+}
+
+/// Identifies an alert from its host, message and detection time, the same across restarts and
+/// upgrades so that lines written by another process correlate too.
+fn alert_id(alert: &ActiveAlert) -> String {
+    let mut hasher = StableHasher::new();
+    alert.hostname.hash(&mut hasher);
+    alert.message.hash(&mut hasher);
+    alert
+        .detected_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Where the JSON lines go: any [`Write`], such as the standard output, or a [`FileSink`].
+pub trait Sink: Send {
+    /// Writes a line, without its line feed, and flushes it.
+    fn write_line(&mut self, line: &str) -> Result<()>;
+}
+
+impl<W: Write + Send> Sink for W {
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self, "{}", line)
+            .and_then(|_| self.flush())
+            .context("Could not write alert")
+    }
+}
+
+/// Identity of a file, to notice when its path points to another one.
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Without file identities, the file is opened again for every alert.
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+struct OpenFile {
+    writer: BufWriter<File>,
+    id: Option<(u64, u64)>,
+}
+
+/// Appends to a file, opened in append mode so that truncating it in place is harmless. When it
+/// is moved away or deleted, e.g. by logrotate, the path is opened again on the next line.
+pub struct FileSink {
+    path: PathBuf,
+    file: Option<OpenFile>,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> Self {
+        FileSink { path, file: None }
+    }
+
+    fn open(&self) -> Result<OpenFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Could not open file: {}", self.path.display()))?;
+        let id = file.metadata().ok().as_ref().and_then(file_id);
+        Ok(OpenFile {
+            writer: BufWriter::new(file),
+            id,
+        })
+    }
+}
+
+impl Sink for FileSink {
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        let current_id = fs::metadata(&self.path).ok().as_ref().and_then(file_id);
+        let mut open = match self.file.take() {
+            Some(open) if current_id.is_some() && open.id == current_id => open,
+            _ => {
+                info!(opening = %self.path.display());
+                self.open()?
+            }
+        };
+        // A file that failed is opened again for the next line.
+        open.writer
+            .write_line(line)
+            .with_context(|| format!("Could not write alert to {}", self.path.display()))?;
+        self.file = Some(open);
+        Ok(())
+    }
+}
+
+/// Writes alerts as JSON lines, for log pipelines to pick them up, to the file of its
+/// configuration or to another [`Sink`].
+pub struct Jsonl<S = FileSink> {
+    sink: Mutex<S>,
+}
+
+impl Jsonl {
+    pub fn new(configuration: Configuration) -> Self {
+        Jsonl::with_sink(FileSink::new(configuration.path))
+    }
+}
+
+impl<S: Sink> Jsonl<S> {
+    pub fn with_sink(sink: S) -> Self {
+        Jsonl {
+            sink: Mutex::new(sink),
+        }
+    }
+
+    fn write(&self, alert: &ActiveAlert, resolved: bool) -> Result<()> {
+        let event = Event {
+            id: alert_id(alert),
+            fingerprint: &alert.fingerprint,
+            timestamp: DateTime::<Utc>::from(alert.detected_at)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            hostname: &alert.hostname,
+            severity: alert.severity,
+            message: &alert.message,
+            resolved,
+            labels: &alert.labels,
+            description: alert.description.as_deref(),
+            runbook_url: alert.runbook_url.as_deref(),
+            annotations: &alert.annotations,
+        };
+        let line = serde_json::to_string(&event).context("Could not serialize alert.")?;
+        self.sink
+            .lock()
+            .map_err(|_| anyhow!("JSON lines sink lock is poisoned"))?
+            .write_line(&line)
+    }
+}
+
+impl<S: Sink> AlertReporter for Jsonl<S> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.write(alert, false)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.write(alert, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    fn alert(message: &str) -> ActiveAlert {
+        let mut alert = ActiveAlert::new(message.to_string(), Severity::Critical);
+        alert.fingerprint = format!("test:{}", message);
+        alert.hostname = "host".to_string();
+        alert.detected_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        alert
+    }
+
+    fn read_lines(path: &Path) -> Vec<Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// A directory of its own for a test, emptied first.
+    fn directory(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("monitrust-jsonl-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn writes_alerts_back_as_json_lines() {
+        let directory = directory("read-back");
+        let path = directory.join("alerts.jsonl");
+        let jsonl = Jsonl::new(Configuration { path: path.clone() });
+        jsonl.report(&alert("Disk is full")).unwrap();
+        jsonl.report_resolved(&alert("Disk is full")).unwrap();
+
+        let lines = read_lines(&path);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["fingerprint"], "test:Disk is full");
+        assert_eq!(lines[0]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(lines[0]["severity"], "Critical");
+        assert_eq!(
+            (&lines[0]["resolved"], &lines[1]["resolved"]),
+            (&Value::Bool(false), &Value::Bool(true))
+        );
+        // Stable across releases, so that lines of other processes correlate.
+        assert_eq!(lines[0]["id"], "876c6a964f855e9f");
+        assert_eq!(lines[0]["id"], lines[1]["id"]);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn opens_the_path_again_once_rotated() {
+        let directory = directory("rotation");
+        let (path, rotated) = (directory.join("alerts.jsonl"), directory.join("alerts.1"));
+        let jsonl = Jsonl::new(Configuration { path: path.clone() });
+        jsonl.report(&alert("Before")).unwrap();
+        fs::rename(&path, &rotated).unwrap();
+        jsonl.report(&alert("After")).unwrap();
+        jsonl.report(&alert("Still after")).unwrap();
+
+        let messages = |path| {
+            read_lines(path)
+                .iter()
+                .map(|line| line["message"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(messages(&rotated), ["Before"]);
+        assert_eq!(messages(&path), ["After", "Still after"]);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    /// A buffer the test keeps reading while the reporter writes to it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_to_any_writer() {
+        let buffer = Shared::default();
+        let jsonl = Jsonl::with_sink(buffer.clone());
+        let mut detected = alert("Load is high");
+        detected.detected_at = SystemTime::now();
+        jsonl.report(&detected).unwrap();
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line = serde_json::from_str::<Value>(written.strip_suffix('\n').unwrap()).unwrap();
+        assert_eq!(line["message"], "Load is high");
+    }
+}
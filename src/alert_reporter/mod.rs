@@ -1,51 +1,628 @@
-use crate::watcher::ActiveAlert;
-use anyhow::Result;
+use crate::alert_reporter::aggregating::{AggregatingReporter, AggregationConfiguration};
+use crate::alert_reporter::concurrency_limiting::ConcurrencyLimitedReporter;
+use crate::alert_reporter::grouping::{GroupingConfiguration, GroupingReporter};
+use crate::alert_reporter::jsonl::Jsonl;
+use crate::alert_reporter::null::NullReporter;
+use crate::alert_reporter::queueing::{QueueConfiguration, QueueingReporter};
+use crate::alert_reporter::quiet_hours::{QuietHoursConfiguration, QuietHoursReporter};
+use crate::alert_reporter::rate_limiting::{RateLimitConfiguration, RateLimitingReporter};
+use crate::alert_reporter::retrying::{RetryConfiguration, RetryingReporter};
+use crate::watcher::{ActiveAlert, Severity};
+use anyhow::{anyhow, Result};
 use enum_dispatch::enum_dispatch;
+use reporters::console::{self, Console};
+#[cfg(feature = "email")]
+use reporters::email::{self, Email};
+#[cfg(feature = "pagerduty")]
+use reporters::pagerduty::{self, PagerDuty};
+#[cfg(feature = "slack")]
+use reporters::slack::{self, Slack};
+#[cfg(feature = "sqlite")]
+use reporters::sqlite::{self, Sqlite};
+#[cfg(unix)]
+use reporters::syslog::{self, Syslog};
+#[cfg(feature = "teams")]
+use reporters::teams::{self, Teams};
 #[cfg(feature = "telegram")]
 use reporters::telegram::{self, Telegram};
+#[cfg(feature = "webhook")]
+use reporters::webhook::{self, Webhook};
 use serde::{Deserialize, Serialize};
 use tracing::warn;
+pub mod aggregating;
+pub mod concurrency_limiting;
+pub mod fallback;
+pub mod format;
+pub mod grouping;
+pub mod jsonl;
+pub mod null;
+pub mod queueing;
+pub mod quiet_hours;
+pub mod rate_limiting;
 pub mod reporters;
+pub mod retrying;
+pub mod routing;
+
+/// What changed about an alert since the previous run of its watcher, see
+/// [`AlertReporter::report_diff`].
+#[derive(Debug, Clone)]
+pub enum AlertChange {
+    /// The alert started firing.
+    Firing(ActiveAlert),
+    /// The alert keeps firing, with another severity than during the previous run.
+    SeverityChanged {
+        alert: ActiveAlert,
+        previous: Severity,
+    },
+    /// The alert stopped firing.
+    Resolved(ActiveAlert),
+}
+
+impl AlertChange {
+    /// The alert that fires, unless it resolved.
+    pub fn firing(&self) -> Option<&ActiveAlert> {
+        match self {
+            AlertChange::Firing(alert) | AlertChange::SeverityChanged { alert, .. } => Some(alert),
+            AlertChange::Resolved(_) => None,
+        }
+    }
+
+    /// The alert that changed, firing or resolved.
+    pub fn alert(&self) -> &ActiveAlert {
+        match self {
+            AlertChange::Firing(alert)
+            | AlertChange::SeverityChanged { alert, .. }
+            | AlertChange::Resolved(alert) => alert,
+        }
+    }
+}
 
 #[enum_dispatch(Reporter)]
 pub trait AlertReporter {
     fn report(&self, alert: &ActiveAlert) -> Result<()>;
-}
 
-pub struct MultiReporter(pub Vec<Reporter>);
+    /// Reports the alerts triggered by a single check. Reporters with a bulk API can send them
+    /// at once. By default, each alert is reported on its own, and a failing one does not
+    /// prevent the next ones from being reported.
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        let errors = alerts
+            .iter()
+            .filter_map(|a| self.report(a).err())
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Could not report {} of {} alerts: {:?}",
+                errors.len(),
+                alerts.len(),
+                errors
+            ))
+        }
+    }
 
-impl AlertReporter for MultiReporter {
-    fn report(&self, alert: &ActiveAlert) -> Result<()> {
-        self.0
+    /// Called when an alert that fired during the previous run is no longer triggered.
+    fn report_resolved(&self, _alert: &ActiveAlert) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called by the scheduler once it ran every watcher that was due, so that reporters holding
+    /// alerts back can report the ones of the whole cycle together.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Reports what changed since the previous run of a watcher with `report_diff` set, instead
+    /// of the alerts it triggered. By default, the resolved alerts are reported one by one, then
+    /// the firing ones as a batch.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let mut errors = changes
             .iter()
-            .map(|a| a.report(alert))
-            .filter_map(|r| match r {
-                Ok(_) => None,
-                Err(e) => Some(e),
+            .filter_map(|change| match change {
+                AlertChange::Resolved(alert) => self.report_resolved(alert).err(),
+                _ => None,
             })
-            .for_each(|e| {
-                warn!(reporter_error = ?e);
-            });
-        Ok(())
+            .collect::<Vec<_>>();
+        let firing = changes
+            .iter()
+            .filter_map(AlertChange::firing)
+            .cloned()
+            .collect::<Vec<_>>();
+        if !firing.is_empty() {
+            errors.extend(self.report_batch(&firing).err());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Could not report {} of {} changes: {:?}",
+                errors.len(),
+                changes.len(),
+                errors
+            ))
+        }
+    }
+}
+
+impl<R: AlertReporter + ?Sized> AlertReporter for Box<R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.as_ref().report(alert)
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        self.as_ref().report_batch(alerts)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.as_ref().report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.as_ref().flush()
+    }
+
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        self.as_ref().report_diff(changes)
+    }
+}
+
+/// Fans alerts out to several reporters, by name. A failing reporter does not prevent the other
+/// ones from receiving the alert; an error is only returned if all of them failed. Escalated
+/// alerts only go to the reporter of their escalation, if there is one with that name.
+pub struct CompositeReporter(pub Vec<(String, Box<dyn AlertReporter + Send + Sync>)>);
+
+impl CompositeReporter {
+    fn is_routed_to(&self, alert: &ActiveAlert, name: &str) -> bool {
+        match alert
+            .escalation
+            .as_ref()
+            .and_then(|e| e.reporter.as_deref())
+        {
+            Some(reporter) if self.0.iter().any(|(n, _)| n == reporter) => reporter == name,
+            Some(reporter) => {
+                warn!(unknown_reporter = reporter, "Escalating to every reporter");
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Reports with each reporter that `report` returns a result for.
+    fn fan_out(
+        &self,
+        report: impl Fn(&str, &dyn AlertReporter) -> Option<Result<()>>,
+    ) -> Result<()> {
+        let results = self
+            .0
+            .iter()
+            .filter_map(|(name, r)| report(name, r.as_ref()))
+            .collect::<Vec<_>>();
+        let errors = results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .inspect(|e| warn!(reporter_error = ?e))
+            .collect::<Vec<_>>();
+        if !results.is_empty() && errors.len() == results.len() {
+            Err(anyhow!(
+                "All {} reporters failed to report the alert: {:?}",
+                errors.len(),
+                errors
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reports each alert to the reporters `route` selects, among the ones its escalation is
+    /// routed to.
+    fn report_routed(
+        &self,
+        alerts: &[ActiveAlert],
+        route: impl Fn(&ActiveAlert, &str) -> bool,
+    ) -> Result<()> {
+        self.fan_out(|name, r| {
+            let routed = alerts
+                .iter()
+                .filter(|a| self.is_routed_to(a, name) && route(a, name))
+                .cloned()
+                .collect::<Vec<_>>();
+            (!routed.is_empty()).then(|| r.report_batch(&routed))
+        })
+    }
+
+    /// Resolves the alert with the reporters `route` selects.
+    fn resolve_routed(&self, alert: &ActiveAlert, route: impl Fn(&str) -> bool) -> Result<()> {
+        self.fan_out(|name, r| route(name).then(|| r.report_resolved(alert)))
+    }
+}
+
+impl AlertReporter for CompositeReporter {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.fan_out(|name, r| self.is_routed_to(alert, name).then(|| r.report(alert)))
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        self.report_routed(alerts, |_, _| true)
+    }
+
+    /// Resolutions go to every reporter, including the ones an escalation was reported to.
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.resolve_routed(alert, |_| true)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.fan_out(|_, r| Some(r.flush()))
+    }
+
+    /// Changes of escalated alerts only go to the reporters of their escalation, as their
+    /// reports would, resolutions to every reporter.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        self.fan_out(|name, r| {
+            let routed = changes
+                .iter()
+                .filter(|c| c.firing().is_none_or(|a| self.is_routed_to(a, name)))
+                .cloned()
+                .collect::<Vec<_>>();
+            (!routed.is_empty()).then(|| r.report_diff(&routed))
+        })
     }
 }
 
 #[enum_dispatch]
 pub enum Reporter {
+    Console,
+    Jsonl,
+    #[cfg(unix)]
+    Syslog,
     #[cfg(feature = "telegram")]
     Telegram,
+    #[cfg(feature = "webhook")]
+    Webhook,
+    #[cfg(feature = "email")]
+    Email,
+    #[cfg(feature = "pagerduty")]
+    PagerDuty,
+    #[cfg(feature = "slack")]
+    Slack,
+    #[cfg(feature = "teams")]
+    Teams,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// A reporter configuration, along with the options that apply to every kind of reporter.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TargetConfiguration<C> {
+    #[serde(flatten)]
+    configuration: C,
+    /// Name that escalations refer to the reporter by, instead of its kind.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Retry failed reports, instead of giving up on the first failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry: Option<RetryConfiguration>,
+    /// Combine alerts reported within a short window into a single report.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aggregate: Option<AggregationConfiguration>,
+    /// Combine the alerts of a scheduler cycle sharing the value of a label into a single report.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<GroupingConfiguration>,
+    /// Hours during which alerts below a severity are not reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quiet_hours: Option<QuietHoursConfiguration>,
+    /// Cap on the reports sent per interval, e.g. to stay within the quota of an API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rate_limit: Option<RateLimitConfiguration>,
+    /// Pass reports on from a thread of their own, so that watchers do not wait for a slow
+    /// reporter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    queue: Option<QueueConfiguration>,
+    /// Reports let through to the reporter at once, the next ones waiting for them to complete.
+    /// Unbounded by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_in_flight: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum AlertTargetConfiguration {
+    Console(TargetConfiguration<console::Configuration>),
+    Jsonl(TargetConfiguration<jsonl::Configuration>),
+    #[cfg(unix)]
+    Syslog(TargetConfiguration<syslog::Configuration>),
     #[cfg(feature = "telegram")]
-    Telegram(telegram::Configuration),
+    Telegram(TargetConfiguration<telegram::Configuration>),
+    #[cfg(feature = "webhook")]
+    Webhook(TargetConfiguration<webhook::Configuration>),
+    #[cfg(feature = "email")]
+    Email(TargetConfiguration<email::Configuration>),
+    #[cfg(feature = "pagerduty")]
+    PagerDuty(TargetConfiguration<pagerduty::Configuration>),
+    #[cfg(feature = "slack")]
+    Slack(TargetConfiguration<slack::Configuration>),
+    #[cfg(feature = "teams")]
+    Teams(TargetConfiguration<teams::Configuration>),
+    #[cfg(feature = "sqlite")]
+    Sqlite(TargetConfiguration<sqlite::Configuration>),
+}
+
+impl AlertTargetConfiguration {
+    /// Name of the reporter: the configured one, or its kind such as `Slack`.
+    pub fn name(&self) -> String {
+        let (name, kind) = match self {
+            AlertTargetConfiguration::Console(c) => (&c.name, "Console"),
+            AlertTargetConfiguration::Jsonl(c) => (&c.name, "Jsonl"),
+            #[cfg(unix)]
+            AlertTargetConfiguration::Syslog(c) => (&c.name, "Syslog"),
+            #[cfg(feature = "telegram")]
+            AlertTargetConfiguration::Telegram(c) => (&c.name, "Telegram"),
+            #[cfg(feature = "webhook")]
+            AlertTargetConfiguration::Webhook(c) => (&c.name, "Webhook"),
+            #[cfg(feature = "email")]
+            AlertTargetConfiguration::Email(c) => (&c.name, "Email"),
+            #[cfg(feature = "pagerduty")]
+            AlertTargetConfiguration::PagerDuty(c) => (&c.name, "PagerDuty"),
+            #[cfg(feature = "slack")]
+            AlertTargetConfiguration::Slack(c) => (&c.name, "Slack"),
+            #[cfg(feature = "teams")]
+            AlertTargetConfiguration::Teams(c) => (&c.name, "Teams"),
+            #[cfg(feature = "sqlite")]
+            AlertTargetConfiguration::Sqlite(c) => (&c.name, "Sqlite"),
+        };
+        name.clone().unwrap_or_else(|| kind.to_string())
+    }
+
+    pub fn into_reporter(self) -> Result<Box<dyn AlertReporter + Send + Sync>> {
+        let name = self.name();
+        let (reporter, max_in_flight, retry, rate_limit, group, aggregate, quiet_hours, queue) =
+            match self {
+                AlertTargetConfiguration::Console(c) => (
+                    Reporter::Console(Console::new(c.configuration)),
+                    c.max_in_flight,
+                    c.retry,
+                    c.rate_limit,
+                    c.group,
+                    c.aggregate,
+                    c.quiet_hours,
+                    c.queue,
+                ),
+                AlertTargetConfiguration::Jsonl(j) => (
+                    Reporter::Jsonl(Jsonl::new(j.configuration)),
+                    j.max_in_flight,
+                    j.retry,
+                    j.rate_limit,
+                    j.group,
+                    j.aggregate,
+                    j.quiet_hours,
+                    j.queue,
+                ),
+                #[cfg(unix)]
+                AlertTargetConfiguration::Syslog(s) => (
+                    Reporter::Syslog(Syslog::new(s.configuration)),
+                    s.max_in_flight,
+                    s.retry,
+                    s.rate_limit,
+                    s.group,
+                    s.aggregate,
+                    s.quiet_hours,
+                    s.queue,
+                ),
+                #[cfg(feature = "telegram")]
+                AlertTargetConfiguration::Telegram(t) => (
+                    Reporter::Telegram(Telegram::new(t.configuration)),
+                    t.max_in_flight,
+                    t.retry,
+                    t.rate_limit,
+                    t.group,
+                    t.aggregate,
+                    t.quiet_hours,
+                    t.queue,
+                ),
+                #[cfg(feature = "webhook")]
+                AlertTargetConfiguration::Webhook(w) => (
+                    Reporter::Webhook(Webhook::from_configuration(w.configuration)?),
+                    w.max_in_flight,
+                    w.retry,
+                    w.rate_limit,
+                    w.group,
+                    w.aggregate,
+                    w.quiet_hours,
+                    w.queue,
+                ),
+                #[cfg(feature = "email")]
+                AlertTargetConfiguration::Email(e) => (
+                    Reporter::Email(Email::new(e.configuration)?),
+                    e.max_in_flight,
+                    e.retry,
+                    e.rate_limit,
+                    e.group,
+                    e.aggregate,
+                    e.quiet_hours,
+                    e.queue,
+                ),
+                #[cfg(feature = "pagerduty")]
+                AlertTargetConfiguration::PagerDuty(p) => (
+                    Reporter::PagerDuty(PagerDuty::new(p.configuration)?),
+                    p.max_in_flight,
+                    p.retry,
+                    p.rate_limit,
+                    p.group,
+                    p.aggregate,
+                    p.quiet_hours,
+                    p.queue,
+                ),
+                #[cfg(feature = "slack")]
+                AlertTargetConfiguration::Slack(s) => (
+                    Reporter::Slack(Slack::new(s.configuration)?),
+                    s.max_in_flight,
+                    s.retry,
+                    s.rate_limit,
+                    s.group,
+                    s.aggregate,
+                    s.quiet_hours,
+                    s.queue,
+                ),
+                #[cfg(feature = "teams")]
+                AlertTargetConfiguration::Teams(t) => (
+                    Reporter::Teams(Teams::new(t.configuration)?),
+                    t.max_in_flight,
+                    t.retry,
+                    t.rate_limit,
+                    t.group,
+                    t.aggregate,
+                    t.quiet_hours,
+                    t.queue,
+                ),
+                #[cfg(feature = "sqlite")]
+                AlertTargetConfiguration::Sqlite(s) => (
+                    Reporter::Sqlite(Sqlite::new(s.configuration)?),
+                    s.max_in_flight,
+                    s.retry,
+                    s.rate_limit,
+                    s.group,
+                    s.aggregate,
+                    s.quiet_hours,
+                    s.queue,
+                ),
+            };
+        let reporter: Box<dyn AlertReporter + Send + Sync> = match max_in_flight {
+            Some(max_in_flight) => {
+                Box::new(ConcurrencyLimitedReporter::new(reporter, max_in_flight)?)
+            }
+            None => Box::new(reporter),
+        };
+        let reporter: Box<dyn AlertReporter + Send + Sync> = match retry {
+            Some(retry) => Box::new(RetryingReporter::new(reporter, retry)),
+            None => reporter,
+        };
+        let reporter: Box<dyn AlertReporter + Send + Sync> = match rate_limit {
+            Some(rate_limit) => Box::new(RateLimitingReporter::new(reporter, rate_limit)?),
+            None => reporter,
+        };
+        let reporter: Box<dyn AlertReporter + Send + Sync> = match group {
+            Some(group) => Box::new(GroupingReporter::new(reporter, group)),
+            None => reporter,
+        };
+        let reporter: Box<dyn AlertReporter + Send + Sync> = match aggregate {
+            Some(aggregate) => Box::new(AggregatingReporter::new(reporter, aggregate)),
+            None => reporter,
+        };
+        let reporter: Box<dyn AlertReporter + Send + Sync> = match quiet_hours {
+            Some(quiet_hours) => Box::new(QuietHoursReporter::new(reporter, quiet_hours)?),
+            None => reporter,
+        };
+        Ok(match queue {
+            Some(queue) => Box::new(QueueingReporter::new(reporter, name, queue)?),
+            None => reporter,
+        })
+    }
+}
+
+/// Builds the reporter given on the command line with `--report`, instead of the ones of
+/// `reporters.json`: `console`, `null`, which drops every alert, or `webhook` along with the
+/// `url` to post alerts to.
+pub fn from_flags(kind: &str, url: Option<&str>) -> Result<Box<dyn AlertReporter + Send + Sync>> {
+    if url.is_some() && kind != "webhook" {
+        return Err(anyhow!("--url only applies to '--report webhook'"));
+    }
+    let configuration = match kind {
+        "null" => return Ok(Box::new(NullReporter::new())),
+        "console" => serde_json::json!({ "Console": {} }),
+        #[cfg(feature = "webhook")]
+        "webhook" => {
+            let url = url.ok_or_else(|| anyhow!("'--report webhook' needs a --url"))?;
+            serde_json::json!({ "Webhook": { "url": url } })
+        }
+        #[cfg(not(feature = "webhook"))]
+        "webhook" => return Err(anyhow!("Monitrust was built without the 'webhook' feature")),
+        _ => {
+            return Err(anyhow!(
+                "Unknown reporter '{}', expected 'console', 'null' or 'webhook'",
+                kind
+            ))
+        }
+    };
+    serde_json::from_value::<AlertTargetConfiguration>(configuration)?.into_reporter()
 }
 
-impl From<AlertTargetConfiguration> for Reporter {
-    fn from(value: AlertTargetConfiguration) -> Self {
-        match value {
-            AlertTargetConfiguration::Telegram(t) => Reporter::Telegram(Telegram::new(t)),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use std::sync::Arc;
+
+    /// Fails every report, as a reporter whose service is down would.
+    struct FailingReporter;
+
+    impl AlertReporter for FailingReporter {
+        fn report(&self, _alert: &ActiveAlert) -> Result<()> {
+            Err(anyhow!("Service is down"))
+        }
+
+        fn report_resolved(&self, _alert: &ActiveAlert) -> Result<()> {
+            Err(anyhow!("Service is down"))
+        }
+    }
+
+    /// Shares a recording reporter with the composite reporter owning it.
+    struct Shared(Arc<RecordingReporter>);
+
+    impl AlertReporter for Shared {
+        fn report(&self, alert: &ActiveAlert) -> Result<()> {
+            self.0.report(alert)
+        }
+
+        fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+            self.0.report_resolved(alert)
         }
     }
+
+    fn alert() -> ActiveAlert {
+        ActiveAlert::new("Disk is full".to_string(), Severity::Critical)
+    }
+
+    #[test]
+    fn failing_reporter_does_not_prevent_the_other_ones() {
+        let recording = Arc::new(RecordingReporter::new());
+        let composite = CompositeReporter(vec![
+            ("failing".to_string(), Box::new(FailingReporter)),
+            ("recording".to_string(), Box::new(Shared(recording.clone()))),
+        ]);
+
+        assert!(composite.report(&alert()).is_ok());
+        assert!(composite.report_batch(&[alert(), alert()]).is_ok());
+        assert!(composite.report_resolved(&alert()).is_ok());
+
+        assert_eq!(recording.alerts().len(), 3);
+        assert_eq!(recording.resolved().len(), 1);
+    }
+
+    #[test]
+    fn fails_once_every_reporter_failed() {
+        let composite = CompositeReporter(vec![
+            ("first".to_string(), Box::new(FailingReporter)),
+            ("second".to_string(), Box::new(FailingReporter)),
+        ]);
+
+        let error = composite.report(&alert()).unwrap_err().to_string();
+        assert!(error.starts_with("All 2 reporters failed"), "{}", error);
+        assert_eq!(error.matches("Service is down").count(), 2, "{}", error);
+        assert!(composite.report_resolved(&alert()).is_err());
+    }
+
+    #[test]
+    fn reporters_of_the_command_line_are_checked() {
+        let error =
+            |kind: &str, url: Option<&str>| from_flags(kind, url).err().unwrap().to_string();
+
+        assert!(from_flags("null", None).is_ok());
+        assert!(from_flags("console", None).is_ok());
+        assert_eq!(
+            error("null", Some("http://localhost")),
+            "--url only applies to '--report webhook'"
+        );
+        assert_eq!(
+            error("pager", None),
+            "Unknown reporter 'pager', expected 'console', 'null' or 'webhook'"
+        );
+    }
 }
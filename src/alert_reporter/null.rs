@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+
+use crate::alert_reporter::AlertReporter;
+use crate::watcher::ActiveAlert;
+
+/// Drops every alert, only counting them. Useful to run Monitrust as a metrics exporter only.
+#[derive(Debug, Default)]
+pub struct NullReporter {
+    received: AtomicUsize,
+}
+
+impl NullReporter {
+    pub fn new() -> Self {
+        NullReporter::default()
+    }
+
+    /// Number of alerts reported so far, not counting resolutions.
+    pub fn received(&self) -> usize {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+impl AlertReporter for NullReporter {
+    fn report(&self, _alert: &ActiveAlert) -> Result<()> {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Keeps every alert it gets, for tests to check which alerts fired and resolved.
+#[derive(Debug, Default)]
+pub struct RecordingReporter {
+    alerts: Mutex<Vec<ActiveAlert>>,
+    resolved: Mutex<Vec<ActiveAlert>>,
+}
+
+impl RecordingReporter {
+    pub fn new() -> Self {
+        RecordingReporter::default()
+    }
+
+    /// Alerts reported so far, in the order they were reported.
+    pub fn alerts(&self) -> Vec<ActiveAlert> {
+        self.alerts.lock().map(|a| a.clone()).unwrap_or_default()
+    }
+
+    /// Alerts resolved so far, in the order they were resolved.
+    pub fn resolved(&self) -> Vec<ActiveAlert> {
+        self.resolved.lock().map(|a| a.clone()).unwrap_or_default()
+    }
+}
+
+fn record(alerts: &Mutex<Vec<ActiveAlert>>, alert: &ActiveAlert) -> Result<()> {
+    alerts
+        .lock()
+        .map_err(|_| anyhow!("Recorded alerts lock is poisoned"))?
+        .push(alert.clone());
+    Ok(())
+}
+
+impl AlertReporter for RecordingReporter {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        record(&self.alerts, alert)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        record(&self.resolved, alert)
+    }
+}
+
+/// Lends the recording reporter to a reporter wrapping it, so that tests can still check what
+/// went through once it is wrapped.
+impl AlertReporter for &RecordingReporter {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        (*self).report(alert)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        (*self).report_resolved(alert)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_store::memory::Memory;
+    use crate::watcher::{memory, MultiWatcher, Severity, Watcher};
+    use serde_json::json;
+
+    fn alert(message: &str) -> ActiveAlert {
+        ActiveAlert::new(message.to_string(), Severity::Warning)
+    }
+
+    #[test]
+    fn null_reporter_counts_the_alerts_only() {
+        let reporter = NullReporter::new();
+
+        reporter.report(&alert("Disk is full")).unwrap();
+        reporter
+            .report_batch(&[alert("Swap is full"), alert("CPU is busy")])
+            .unwrap();
+        reporter.report_resolved(&alert("Disk is full")).unwrap();
+
+        assert_eq!(reporter.received(), 3);
+    }
+
+    #[test]
+    fn recording_reporter_keeps_what_fired_and_resolved() {
+        let reporter = RecordingReporter::new();
+
+        reporter.report(&alert("Disk is full")).unwrap();
+        reporter.report(&alert("Swap is full")).unwrap();
+        reporter.report_resolved(&alert("Disk is full")).unwrap();
+
+        let messages =
+            |alerts: Vec<ActiveAlert>| alerts.into_iter().map(|a| a.message).collect::<Vec<_>>();
+        assert_eq!(
+            messages(reporter.alerts()),
+            ["Disk is full", "Swap is full"]
+        );
+        assert_eq!(messages(reporter.resolved()), ["Disk is full"]);
+    }
+
+    #[test]
+    fn recording_reporter_tells_what_a_watcher_fired() {
+        // Some memory is always available.
+        let mut watcher = MultiWatcher::<memory::Alert>::new(
+            serde_json::from_value(json!({
+                "configuration": { "period": "1m" },
+                "alerts": [{ "threshold": {
+                    "metric": "Available", "value": 0, "unit": "MB", "operator": ">="
+                } }]
+            }))
+            .unwrap(),
+        );
+        let reporter = RecordingReporter::new();
+
+        watcher
+            .run(&reporter, &Memory::new(Default::default()))
+            .unwrap();
+
+        let alerts = reporter.alerts();
+        assert_eq!(alerts.len(), 1, "{:?}", alerts);
+        assert!(
+            alerts[0].message.starts_with("📝 Available memory"),
+            "{}",
+            alerts[0].message
+        );
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::metrics::{self, Sample};
+use crate::status;
+use crate::watcher::ActiveAlert;
+
+fn default_capacity() -> usize {
+    100
+}
+
+/// What happens to the alerts reported while the queue is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Drop the alert, counting it in `monitrust_reporter_dropped_alerts_total`.
+    #[default]
+    Drop,
+    /// Wait for room in the queue, holding back the watcher reporting the alert.
+    Block,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueConfiguration {
+    /// Reports waiting for the reporter, beyond which the policy applies.
+    #[serde(default = "default_capacity")]
+    capacity: usize,
+    #[serde(default)]
+    policy: QueuePolicy,
+}
+
+enum Message {
+    Report(ActiveAlert),
+    Batch(Vec<ActiveAlert>),
+    Resolved(ActiveAlert),
+    Diff(Vec<AlertChange>),
+    Flush,
+}
+
+/// Hands reports over to a thread passing them on to a slow reporter, such as email over a
+/// flaky link, so that watchers do not wait for it. Reports beyond the `capacity` of the queue
+/// are dropped, or wait for room with the `Block` policy. Resolutions and flushes always wait
+/// for room, as dropping them would leave alerts firing, but the resolutions of dropped alerts
+/// are dropped too. Queued reports are passed on before the reporter is dropped. Once the
+/// reporter fails a report it was passed, the next ones fail too until it succeeds again, so
+/// that a fallback is told when it keeps failing, only a little later than without a queue.
+pub struct QueueingReporter {
+    sender: Option<SyncSender<Message>>,
+    drain: Option<JoinHandle<()>>,
+    name: String,
+    policy: QueuePolicy,
+    /// Fingerprints of the alerts that were dropped, whose resolution is dropped too.
+    dropped: Mutex<BTreeSet<String>>,
+    dropped_count: AtomicU64,
+    /// Length of the queue, for the self-monitor to tell when alerts pile up.
+    queued: Arc<AtomicUsize>,
+    /// Error of the last report passed on, if it failed.
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl QueueingReporter {
+    /// Starts the thread draining the queue into `reporter`, named `name` in logs and metrics.
+    pub fn new<R: AlertReporter + Send + 'static>(
+        reporter: R,
+        name: String,
+        configuration: QueueConfiguration,
+    ) -> Result<Self> {
+        if configuration.capacity == 0 {
+            return Err(anyhow!("'capacity' of a queue must be positive"));
+        }
+        let (sender, receiver) = mpsc::sync_channel(configuration.capacity);
+        let queued = status::register_queue();
+        let failure = Arc::new(Mutex::new(None));
+        let drain = {
+            let (queued, failure) = (queued.clone(), failure.clone());
+            std::thread::Builder::new()
+                .name(format!("queue-{}", name))
+                .spawn(move || drain(reporter, receiver, &queued, &failure))
+                .with_context(|| format!("Could not start the queue of {}", name))?
+        };
+        Ok(QueueingReporter {
+            sender: Some(sender),
+            drain: Some(drain),
+            name,
+            policy: configuration.policy,
+            dropped: Mutex::new(BTreeSet::new()),
+            dropped_count: AtomicU64::new(0),
+            queued,
+            failure,
+        })
+    }
+
+    /// Alerts dropped since the reporter was created.
+    pub fn dropped(&self) -> u64 {
+        self.dropped_count.load(Ordering::Relaxed)
+    }
+
+    fn dropped_alerts(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        // Fingerprints are only ever inserted and removed, a panic can not leave them
+        // inconsistent.
+        self.dropped.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Fails if the last report passed on to the reporter did.
+    fn last_report(&self) -> Result<()> {
+        match &*lock(&self.failure) {
+            Some(e) => Err(anyhow!("Last report of {} failed: {}", self.name, e)),
+            None => Ok(()),
+        }
+    }
+
+    /// The draining thread stops only once the reporter is dropped, or if its reporter panicked.
+    fn closed(&self) -> anyhow::Error {
+        anyhow!("The queue of {} stopped", self.name)
+    }
+
+    /// Queues a resolution or a flush, waiting for room.
+    fn send(&self, message: Message) -> Result<()> {
+        let Some(sender) = &self.sender else {
+            return Ok(());
+        };
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        sender.send(message).map_err(|_| self.closed())?;
+        self.last_report()
+    }
+
+    /// Queues the message reporting `alerts`, dropping them if the queue is full, unless the
+    /// policy blocks.
+    fn enqueue(&self, message: Message, alerts: &[ActiveAlert]) -> Result<()> {
+        let sent = match self.policy {
+            QueuePolicy::Block => self.send(message),
+            QueuePolicy::Drop => {
+                let Some(sender) = &self.sender else {
+                    return Ok(());
+                };
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                match sender.try_send(message) {
+                    Err(TrySendError::Full(_)) => {
+                        self.queued.fetch_sub(1, Ordering::Relaxed);
+                        self.drop_alerts(alerts);
+                        return Ok(());
+                    }
+                    sent => sent
+                        .map_err(|_| self.closed())
+                        .and_then(|()| self.last_report()),
+                }
+            }
+        };
+        // An alert dropped before and queued now gets resolved again.
+        let mut dropped = self.dropped_alerts();
+        for alert in alerts {
+            dropped.remove(&alert.fingerprint);
+        }
+        sent
+    }
+
+    fn drop_alerts(&self, alerts: &[ActiveAlert]) {
+        let mut dropped = self.dropped_alerts();
+        for alert in alerts {
+            let count = self.dropped_count.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(dropped_alert = ?alert, dropped = count, reporter = self.name, "Queue is full");
+            dropped.insert(alert.fingerprint.clone());
+        }
+        metrics::record(
+            "reporter",
+            &BTreeMap::new(),
+            vec![Sample::new(
+                "monitrust_reporter_dropped_alerts_total",
+                &[("reporter", self.name.as_str())],
+                self.dropped() as f64,
+            )],
+        );
+    }
+}
+
+fn lock(failure: &Mutex<Option<String>>) -> MutexGuard<'_, Option<String>> {
+    // The error is only ever replaced, a panic can not leave it inconsistent.
+    failure.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn drain<R: AlertReporter>(
+    reporter: R,
+    receiver: Receiver<Message>,
+    queued: &AtomicUsize,
+    failure: &Mutex<Option<String>>,
+) {
+    for message in receiver {
+        let reported = match message {
+            Message::Report(alert) => reporter.report(&alert),
+            Message::Batch(alerts) => reporter.report_batch(&alerts),
+            Message::Resolved(alert) => reporter.report_resolved(&alert),
+            Message::Diff(changes) => reporter.report_diff(&changes),
+            Message::Flush => reporter.flush(),
+        };
+        *lock(failure) = reported.as_ref().err().map(|e| format!("{:#}", e));
+        queued.fetch_sub(1, Ordering::Relaxed);
+        if let Err(e) = reported {
+            warn!(reporter_error = ?e);
+        }
+    }
+    // Reporters buffering alerts report them when dropped.
+    drop(reporter);
+}
+
+impl AlertReporter for QueueingReporter {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.enqueue(Message::Report(alert.clone()), std::slice::from_ref(alert))
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        self.enqueue(Message::Batch(alerts.to_vec()), alerts)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        if self.dropped_alerts().remove(&alert.fingerprint) {
+            return Ok(());
+        }
+        self.send(Message::Resolved(alert.clone()))
+    }
+
+    /// Queues the flush after the reports, without waiting for the reporter to be done.
+    fn flush(&self) -> Result<()> {
+        self.send(Message::Flush)
+    }
+
+    /// Queues the changes as a single message. A diff resolving alerts waits for room, as a
+    /// resolution does, while a diff of firing alerts only is dropped as a report would be.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let changes = {
+            let mut dropped = self.dropped_alerts();
+            changes
+                .iter()
+                .filter(|change| {
+                    change.firing().is_some() || !dropped.remove(&change.alert().fingerprint)
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let firing = changes
+            .iter()
+            .filter_map(AlertChange::firing)
+            .cloned()
+            .collect::<Vec<_>>();
+        if firing.len() < changes.len() {
+            let sent = self.send(Message::Diff(changes));
+            let mut dropped = self.dropped_alerts();
+            for alert in &firing {
+                dropped.remove(&alert.fingerprint);
+            }
+            return sent;
+        }
+        self.enqueue(Message::Diff(changes), &firing)
+    }
+}
+
+impl Drop for QueueingReporter {
+    /// Waits for the queued reports to be passed on.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(drain) = self.drain.take() {
+            if drain.join().is_err() {
+                warn!(reporter = self.name, "The queue of the reporter panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_reporter::routing::RoutingReporter;
+    use crate::alert_reporter::CompositeReporter;
+    use crate::watcher::Severity;
+    use std::time::{Duration, Instant};
+
+    /// Fails every report, as a reporter whose service is down would.
+    struct FailingReporter;
+
+    impl AlertReporter for FailingReporter {
+        fn report(&self, _alert: &ActiveAlert) -> Result<()> {
+            Err(anyhow!("Service is down"))
+        }
+
+        fn report_resolved(&self, _alert: &ActiveAlert) -> Result<()> {
+            Err(anyhow!("Service is down"))
+        }
+    }
+
+    /// Shares a recording reporter with the routing reporter owning it.
+    struct Shared(Arc<RecordingReporter>);
+
+    impl AlertReporter for Shared {
+        fn report(&self, alert: &ActiveAlert) -> Result<()> {
+            self.0.report(alert)
+        }
+
+        fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+            self.0.report_resolved(alert)
+        }
+    }
+
+    fn alert() -> ActiveAlert {
+        ActiveAlert::new("Disk is full".to_string(), Severity::Critical)
+    }
+
+    /// Takes 100 ms to pass each report on to the recording reporter, as a slow link would.
+    struct SlowReporter(Arc<RecordingReporter>);
+
+    impl AlertReporter for SlowReporter {
+        fn report(&self, alert: &ActiveAlert) -> Result<()> {
+            std::thread::sleep(Duration::from_millis(100));
+            self.0.report(alert)
+        }
+
+        fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+            std::thread::sleep(Duration::from_millis(100));
+            self.0.report_resolved(alert)
+        }
+    }
+
+    fn failing_queue() -> QueueingReporter {
+        let configuration = serde_json::from_value(serde_json::json!({})).unwrap();
+        QueueingReporter::new(FailingReporter, "Slack".to_string(), configuration).unwrap()
+    }
+
+    /// Waits for the queue to have passed on a failing report.
+    fn wait_for_failure(queue: &QueueingReporter) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lock(&queue.failure).is_none() {
+            assert!(Instant::now() < deadline, "The queue never failed");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn reports_fail_once_the_reporter_failed_one() {
+        let queue = failing_queue();
+        assert!(queue.report(&alert()).is_ok());
+        wait_for_failure(&queue);
+
+        let error = queue.report(&alert()).unwrap_err().to_string();
+
+        assert!(error.contains("Service is down"), "{}", error);
+    }
+
+    #[test]
+    fn fallback_is_told_when_a_queued_reporter_keeps_failing() {
+        let queue = failing_queue();
+        assert!(queue.report(&alert()).is_ok());
+        wait_for_failure(&queue);
+        let fallback = Arc::new(RecordingReporter::new());
+        let reporters = CompositeReporter(vec![
+            ("Slack".to_string(), Box::new(queue)),
+            ("Console".to_string(), Box::new(Shared(fallback.clone()))),
+        ]);
+        let configuration = serde_json::from_value(serde_json::json!({
+            "fallback": { "reporter": "Console", "after_failures": 2 }
+        }))
+        .unwrap();
+        let routing = RoutingReporter::new(reporters, configuration).unwrap();
+
+        for _ in 0..2 {
+            routing.report(&alert()).unwrap();
+        }
+
+        let told = fallback
+            .alerts()
+            .into_iter()
+            .filter(|a| a.kind == "reporter")
+            .collect::<Vec<_>>();
+        assert_eq!(told.len(), 1, "{:?}", told);
+        assert!(told[0].message.contains("Slack"), "{}", told[0].message);
+    }
+
+    #[test]
+    fn the_queue_absorbs_a_burst_without_holding_back_the_watchers() {
+        let recording = Arc::new(RecordingReporter::new());
+        let configuration =
+            serde_json::from_value(serde_json::json!({ "capacity": 2, "policy": "Drop" })).unwrap();
+        let queue = QueueingReporter::new(
+            SlowReporter(recording.clone()),
+            "Email".to_string(),
+            configuration,
+        )
+        .unwrap();
+        let started = Instant::now();
+
+        for i in 0..5 {
+            let alert = ActiveAlert {
+                fingerprint: format!("disk-{}", i),
+                ..alert()
+            };
+            queue.report(&alert).unwrap();
+        }
+        let reported_in = started.elapsed();
+        let dropped = queue.dropped();
+        // Passes the queued reports on before it is gone.
+        drop(queue);
+
+        assert!(
+            reported_in < Duration::from_millis(100),
+            "{:?}",
+            reported_in
+        );
+        // The reporter may have taken the first report off the queue before the next ones.
+        assert!((2..=3).contains(&dropped), "{}", dropped);
+        assert_eq!(recording.alerts().len() as u64 + dropped, 5);
+    }
+}
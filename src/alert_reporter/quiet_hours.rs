@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::clock::{Clock, SystemClock};
+use crate::watcher::{ActiveAlert, Severity};
+
+fn default_override_severity() -> Severity {
+    Severity::Critical
+}
+
+/// Hours during which a reporter stays quiet, on every day listed in `days` (every day if
+/// empty), from `start` to `end` such as `22:00` and `07:00`. Hours running past midnight belong
+/// to the day they start.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuietRange {
+    #[serde(default)]
+    days: Vec<String>,
+    start: String,
+    end: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuietHoursConfiguration {
+    ranges: Vec<QuietRange>,
+    /// Time zone of the ranges, such as `Europe/Paris`. Local time if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timezone: Option<String>,
+    /// Alerts of this severity or above are reported during quiet hours all the same.
+    #[serde(default = "default_override_severity")]
+    override_severity: Severity,
+    /// Report the alerts held back once quiet hours end, instead of dropping them.
+    #[serde(default)]
+    defer: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Range {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Range {
+    fn parse(range: &QuietRange) -> Result<Self> {
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .with_context(|| format!("Invalid time '{}' in quiet hours", time))
+        };
+        Ok(Range {
+            days: range
+                .days
+                .iter()
+                .map(|d| {
+                    d.parse::<Weekday>()
+                        .map_err(|_| anyhow!("Invalid day of the week '{}' in quiet hours", d))
+                })
+                .collect::<Result<_>>()?,
+            start: time(&range.start)?,
+            end: time(&range.end)?,
+        })
+    }
+
+    fn is_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    fn contains<T: TimeZone>(&self, now: DateTime<T>) -> bool {
+        let (time, day) = (now.time(), now.weekday());
+        if self.start <= self.end {
+            self.is_on(day) && self.start <= time && time < self.end
+        } else {
+            (self.is_on(day) && self.start <= time) || (self.is_on(day.pred()) && time < self.end)
+        }
+    }
+}
+
+/// Alerts held back during quiet hours.
+#[derive(Default)]
+struct Held {
+    /// Alerts to report once quiet hours end, by fingerprint.
+    deferred: BTreeMap<String, ActiveAlert>,
+    /// Alerts that were dropped, whose resolution is dropped too.
+    dropped: BTreeSet<String>,
+}
+
+/// Keeps a reporter quiet during some hours: alerts below the override severity are dropped, or
+/// reported once quiet hours end if they should be deferred. Resolutions of alerts that were
+/// reported are passed on at any hour, so that the reporter is never left with an alert that
+/// does not fire anymore.
+pub struct QuietHoursReporter<R: AlertReporter> {
+    reporter: R,
+    ranges: Vec<Range>,
+    timezone: Option<Tz>,
+    override_severity: Severity,
+    defer: bool,
+    held: Mutex<Held>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<R: AlertReporter> QuietHoursReporter<R> {
+    pub fn new(reporter: R, configuration: QuietHoursConfiguration) -> Result<Self> {
+        let timezone = configuration
+            .timezone
+            .map(|tz| {
+                tz.parse::<Tz>()
+                    .map_err(|e| anyhow!("Invalid time zone '{}': {}", tz, e))
+            })
+            .transpose()?;
+        Ok(QuietHoursReporter {
+            reporter,
+            ranges: configuration
+                .ranges
+                .iter()
+                .map(Range::parse)
+                .collect::<Result<_>>()?,
+            timezone,
+            override_severity: configuration.override_severity,
+            defer: configuration.defer,
+            held: Mutex::new(Held::default()),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Reads the time from `clock` rather than from the system, e.g. to test quiet hours.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn held(&self) -> MutexGuard<'_, Held> {
+        // Alerts are only ever moved in and out, a panic can not leave them inconsistent.
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_quiet(&self) -> bool {
+        let now = DateTime::<Local>::from(self.clock.system_now());
+        match &self.timezone {
+            Some(tz) => self
+                .ranges
+                .iter()
+                .any(|r| r.contains(now.with_timezone(tz))),
+            None => self.ranges.iter().any(|r| r.contains(now)),
+        }
+    }
+
+    /// Holds the alert back if quiet hours apply to it, telling whether it was.
+    fn hold(&self, alert: &ActiveAlert, quiet: bool) -> bool {
+        if !quiet || alert.severity >= self.override_severity {
+            return false;
+        }
+        let mut held = self.held();
+        if self.defer {
+            info!(deferred_alert = ?alert, "Quiet hours");
+            held.deferred
+                .insert(alert.fingerprint.clone(), alert.clone());
+        } else {
+            info!(dropped_alert = ?alert, "Quiet hours");
+            held.dropped.insert(alert.fingerprint.clone());
+        }
+        true
+    }
+
+    /// Forgets a resolved alert, telling whether it was held back, in which case its resolution
+    /// is not passed on.
+    fn was_held(&self, alert: &ActiveAlert) -> bool {
+        let mut held = self.held();
+        held.dropped.remove(&alert.fingerprint)
+            || held.deferred.remove(&alert.fingerprint).is_some()
+    }
+
+    /// Reports the deferred alerts once quiet hours have ended.
+    fn release(&self, quiet: bool) -> Result<()> {
+        if quiet {
+            return Ok(());
+        }
+        let deferred = mem::take(&mut self.held().deferred);
+        if deferred.is_empty() {
+            return Ok(());
+        }
+        let alerts = deferred.into_values().collect::<Vec<_>>();
+        self.reporter.report_batch(&alerts)
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for QuietHoursReporter<R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        let quiet = self.is_quiet();
+        let released = self.release(quiet);
+        if !self.hold(alert, quiet) {
+            self.reporter.report(alert)?;
+        }
+        released
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        let quiet = self.is_quiet();
+        let released = self.release(quiet);
+        let reported = alerts
+            .iter()
+            .filter(|a| !self.hold(a, quiet))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !reported.is_empty() {
+            self.reporter.report_batch(&reported)?;
+        }
+        released
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        if self.was_held(alert) {
+            return Ok(());
+        }
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.release(self.is_quiet())?;
+        self.reporter.flush()
+    }
+
+    /// Holds the changes back the same as reports and resolutions. An alert held back with its
+    /// previous severity is reported as firing once its new severity overrides quiet hours.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let quiet = self.is_quiet();
+        let released = self.release(quiet);
+        let passed = changes
+            .iter()
+            .filter_map(|change| match change {
+                AlertChange::Resolved(alert) => (!self.was_held(alert)).then(|| change.clone()),
+                AlertChange::Firing(alert) => (!self.hold(alert, quiet)).then(|| change.clone()),
+                AlertChange::SeverityChanged { alert, .. } => {
+                    let held = self.held();
+                    let was_held = held.dropped.contains(&alert.fingerprint)
+                        || held.deferred.contains_key(&alert.fingerprint);
+                    drop(held);
+                    (!self.hold(alert, quiet)).then(|| match was_held {
+                        true => AlertChange::Firing(alert.clone()),
+                        false => change.clone(),
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+        if !passed.is_empty() {
+            self.reporter.report_diff(&passed)?;
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::test_util::FakeClock;
+    use chrono::Utc;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Quiet from 22:00 to 07:00 UTC, with the clock on a Tuesday at 22:13.
+    fn quiet_hours(
+        reporter: &RecordingReporter,
+        defer: bool,
+    ) -> (FakeClock, QuietHoursReporter<&RecordingReporter>) {
+        let configuration = serde_json::from_value(json!({
+            "ranges": [{ "start": "22:00", "end": "07:00" }],
+            "timezone": "UTC",
+            "defer": defer,
+        }))
+        .unwrap();
+        let clock = FakeClock::at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let quiet_hours = QuietHoursReporter::new(reporter, configuration)
+            .unwrap()
+            .with_clock(Arc::new(clock.clone()));
+        (clock, quiet_hours)
+    }
+
+    fn alert(fingerprint: &str, severity: Severity) -> ActiveAlert {
+        ActiveAlert {
+            fingerprint: fingerprint.to_string(),
+            ..ActiveAlert::new(fingerprint.to_string(), severity)
+        }
+    }
+
+    #[test]
+    fn only_critical_alerts_are_reported_during_quiet_hours() {
+        let recording = RecordingReporter::new();
+        let (_, quiet_hours) = quiet_hours(&recording, false);
+
+        let warning = alert("warning", Severity::Warning);
+        quiet_hours.report(&warning).unwrap();
+        quiet_hours
+            .report(&alert("critical", Severity::Critical))
+            .unwrap();
+        quiet_hours.report_resolved(&warning).unwrap();
+
+        let alerts = recording.alerts();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].fingerprint, "critical");
+        assert!(recording.resolved().is_empty());
+    }
+
+    #[test]
+    fn deferred_alerts_are_reported_once_quiet_hours_end() {
+        let recording = RecordingReporter::new();
+        let (clock, quiet_hours) = quiet_hours(&recording, true);
+
+        quiet_hours
+            .report(&alert("warning", Severity::Warning))
+            .unwrap();
+        quiet_hours.flush().unwrap();
+        assert!(recording.alerts().is_empty());
+
+        clock.advance(Duration::from_secs(9 * 60 * 60));
+        quiet_hours.flush().unwrap();
+
+        let alerts = recording.alerts();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].fingerprint, "warning");
+    }
+
+    #[test]
+    fn hours_past_midnight_belong_to_the_day_they_start() {
+        let range = Range::parse(&QuietRange {
+            days: vec!["Mon".to_string()],
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+        })
+        .unwrap();
+        let at = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+
+        assert!(range.contains(at("2023-11-13T23:00:00Z")));
+        assert!(range.contains(at("2023-11-14T02:00:00Z")));
+        assert!(!range.contains(at("2023-11-14T07:00:00Z")));
+        assert!(!range.contains(at("2023-11-14T23:00:00Z")));
+        assert!(!range.contains(at("2023-11-13T02:00:00Z")));
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::clock::{Clock, SystemClock};
+use crate::duration::deserialize_duration;
+use crate::status;
+use crate::watcher::ActiveAlert;
+
+fn default_max_queued() -> usize {
+    100
+}
+
+/// What happens to the alerts reported while the bucket is empty.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Queue the alert, dropping the oldest queued one once the queue is full.
+    #[default]
+    DropOldest,
+    /// Queue the alert, dropping the newest queued one once the queue is full.
+    DropNewest,
+    /// Wait until the bucket refills, holding back the watcher reporting the alert.
+    Block,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RateLimitConfiguration {
+    /// Reports allowed per interval.
+    reports: u32,
+    #[serde(deserialize_with = "deserialize_duration")]
+    interval: Duration,
+    /// Reports allowed at once after a quiet period, `reports` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    burst: Option<u32>,
+    #[serde(default)]
+    policy: OverflowPolicy,
+    /// Alerts waiting for the bucket to refill, beyond which the policy drops some.
+    #[serde(default = "default_max_queued")]
+    max_queued: usize,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    queued: VecDeque<ActiveAlert>,
+    /// Fingerprints of the alerts that were dropped, whose resolution is dropped too.
+    dropped: BTreeSet<String>,
+    dropped_count: u64,
+}
+
+/// Caps the reports a reporter sends with a token bucket, e.g. to stay within the quota of an
+/// external API: each alert takes a token, and tokens come back at `reports` per interval, up to
+/// `burst`. Alerts reported while the bucket is empty wait in a queue, reported on the next
+/// report or flush the bucket has tokens for, or block the caller with the `Block` policy.
+/// Resolutions are not limited, but the ones of alerts still queued or dropped are not passed
+/// on.
+pub struct RateLimitingReporter<R: AlertReporter> {
+    reporter: R,
+    /// Tokens coming back per second.
+    rate: f64,
+    burst: f64,
+    policy: OverflowPolicy,
+    max_queued: usize,
+    bucket: Mutex<Bucket>,
+    /// Length of the queue, for the self-monitor to tell when alerts pile up.
+    queued: Arc<AtomicUsize>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<R: AlertReporter> RateLimitingReporter<R> {
+    pub fn new(reporter: R, configuration: RateLimitConfiguration) -> Result<Self> {
+        if configuration.reports == 0 || configuration.interval.is_zero() {
+            return Err(anyhow!(
+                "'reports' and 'interval' of a rate limit must be positive"
+            ));
+        }
+        let burst = configuration.burst.unwrap_or(configuration.reports);
+        if burst == 0 {
+            return Err(anyhow!("'burst' of a rate limit must be positive"));
+        }
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        Ok(RateLimitingReporter {
+            reporter,
+            rate: configuration.reports as f64 / configuration.interval.as_secs_f64(),
+            burst: burst as f64,
+            policy: configuration.policy,
+            max_queued: configuration.max_queued,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                refilled_at: clock.now(),
+                queued: VecDeque::new(),
+                dropped: BTreeSet::new(),
+                dropped_count: 0,
+            }),
+            queued: status::register_queue(),
+            clock,
+        })
+    }
+
+    /// Reads the time from `clock` rather than from the system, e.g. to test the rate limit.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.bucket().refilled_at = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Alerts dropped since the reporter was created.
+    pub fn dropped(&self) -> u64 {
+        self.bucket().dropped_count
+    }
+
+    fn bucket(&self) -> MutexGuard<'_, Bucket> {
+        // Tokens and alerts are only ever moved in and out, a panic can not leave them
+        // inconsistent.
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst);
+        bucket.refilled_at = now;
+    }
+
+    /// Waits for a token, with the bucket locked so that other callers wait too.
+    fn take_blocking(&self, bucket: &mut MutexGuard<'_, Bucket>) {
+        loop {
+            self.refill(bucket);
+            if bucket.tokens >= 1. {
+                bucket.tokens -= 1.;
+                return;
+            }
+            let missing = (1. - bucket.tokens) / self.rate;
+            sleep(Duration::from_secs_f64(missing).max(Duration::from_millis(1)));
+        }
+    }
+
+    /// Alerts to report now: the queued ones first, then the new ones, as long as there are
+    /// tokens left. The other ones are queued, or dropped if the queue is full.
+    fn admit(&self, alerts: &[ActiveAlert]) -> Vec<ActiveAlert> {
+        let mut bucket = self.bucket();
+        if self.policy == OverflowPolicy::Block {
+            for _ in alerts {
+                self.take_blocking(&mut bucket);
+            }
+            return alerts.to_vec();
+        }
+        self.refill(&mut bucket);
+        bucket.queued.extend(alerts.iter().cloned());
+        let mut admitted = Vec::new();
+        while bucket.tokens >= 1. {
+            let Some(alert) = bucket.queued.pop_front() else {
+                break;
+            };
+            bucket.tokens -= 1.;
+            admitted.push(alert);
+        }
+        while bucket.queued.len() > self.max_queued {
+            let dropped = match self.policy {
+                OverflowPolicy::DropNewest => bucket.queued.pop_back(),
+                _ => bucket.queued.pop_front(),
+            };
+            if let Some(alert) = dropped {
+                bucket.dropped_count += 1;
+                warn!(dropped_alert = ?alert, dropped = bucket.dropped_count, "Rate limited");
+                bucket.dropped.insert(alert.fingerprint);
+            }
+        }
+        self.queued.store(bucket.queued.len(), Ordering::Relaxed);
+        admitted
+    }
+
+    /// Forgets a resolved alert, telling whether it was still queued or dropped, in which case
+    /// its resolution is not passed on.
+    fn was_held(&self, alert: &ActiveAlert) -> bool {
+        let mut bucket = self.bucket();
+        let queued = bucket.queued.len();
+        bucket
+            .queued
+            .retain(|queued| queued.fingerprint != alert.fingerprint);
+        self.queued.store(bucket.queued.len(), Ordering::Relaxed);
+        bucket.dropped.remove(&alert.fingerprint) || bucket.queued.len() < queued
+    }
+
+    fn report_admitted(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        match alerts {
+            [] => Ok(()),
+            [alert] => self.reporter.report(alert),
+            alerts => self.reporter.report_batch(alerts),
+        }
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for RateLimitingReporter<R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.report_admitted(&self.admit(std::slice::from_ref(alert)))
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        self.report_admitted(&self.admit(alerts))
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        if self.was_held(alert) {
+            return Ok(());
+        }
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.report_admitted(&self.admit(&[]))?;
+        self.reporter.flush()
+    }
+
+    /// Limits the firing alerts of the changes as reports, resolutions not being limited. The
+    /// admitted alerts are passed on as firing, but for the changes of alerts that the reporter
+    /// was already told about.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let mut passed = changes
+            .iter()
+            .filter(|change| change.firing().is_none() && !self.was_held(change.alert()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let held = {
+            let bucket = self.bucket();
+            bucket
+                .queued
+                .iter()
+                .map(|alert| &alert.fingerprint)
+                .chain(&bucket.dropped)
+                .cloned()
+                .collect::<BTreeSet<_>>()
+        };
+        let mut changed = changes
+            .iter()
+            .filter(|change| change.firing().is_some())
+            .filter(|change| !held.contains(&change.alert().fingerprint))
+            .map(|change| (change.alert().fingerprint.clone(), change))
+            .collect::<BTreeMap<_, _>>();
+        let firing = changes
+            .iter()
+            .filter_map(AlertChange::firing)
+            .cloned()
+            .collect::<Vec<_>>();
+        passed.extend(self.admit(&firing).into_iter().map(|alert| {
+            match changed.remove(&alert.fingerprint) {
+                Some(change) => change.clone(),
+                None => AlertChange::Firing(alert),
+            }
+        }));
+        if passed.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_diff(&passed)
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::alert_reporter::{format, AlertReporter};
+use crate::watcher::{ActiveAlert, Severity};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum Stream {
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    #[serde(default)]
+    stream: Stream,
+    /// Write each alert as a line of JSON instead of a human-readable line.
+    #[serde(default)]
+    json: bool,
+}
+
+/// Writes alerts to the console, mostly useful to try Monitrust out or to run it in a container.
+pub struct Console {
+    configuration: Configuration,
+}
+
+#[derive(Serialize, Debug)]
+struct Line<'a> {
+    timestamp: String,
+    hostname: &'a str,
+    severity: Severity,
+    message: &'a str,
+    resolved: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    labels: &'a BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runbook_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: &'a BTreeMap<String, String>,
+}
+
+impl Console {
+    pub fn new(configuration: Configuration) -> Self {
+        Console { configuration }
+    }
+
+    fn format(&self, alert: &ActiveAlert, resolved: bool) -> Result<String> {
+        let line = Line {
+            timestamp: DateTime::<Utc>::from(alert.detected_at)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            hostname: &alert.hostname,
+            severity: alert.severity,
+            message: &alert.message,
+            resolved,
+            labels: &alert.labels,
+            description: alert.description.as_deref(),
+            runbook_url: alert.runbook_url.as_deref(),
+            annotations: &alert.annotations,
+        };
+        if self.configuration.json {
+            serde_json::to_string(&line).context("Could not serialize alert.")
+        } else {
+            Ok(format!(
+                "{} [{:?}] {}: {}{}",
+                line.timestamp,
+                line.severity,
+                line.hostname,
+                if resolved { "resolved: " } else { "" },
+                format::prefixed_message(alert)
+            ))
+        }
+    }
+
+    fn write(&self, line: &str) -> Result<()> {
+        match self.configuration.stream {
+            Stream::Stdout => writeln!(std::io::stdout().lock(), "{}", line),
+            Stream::Stderr => writeln!(std::io::stderr().lock(), "{}", line),
+        }
+        .context("Could not write alert to the console.")
+    }
+}
+
+impl AlertReporter for Console {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.write(&self.format(alert, false)?)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.write(&self.format(alert, true)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn alert() -> ActiveAlert {
+        ActiveAlert {
+            detected_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            hostname: "web-1".to_string(),
+            labels: BTreeMap::from([("env".to_string(), "prod".to_string())]),
+            ..ActiveAlert::new("Disk / is 91.5% full".to_string(), Severity::Critical)
+        }
+    }
+
+    fn console(json: bool) -> Console {
+        Console::new(Configuration {
+            stream: Stream::Stderr,
+            json,
+        })
+    }
+
+    #[test]
+    fn formats_readable_lines() {
+        let console = console(false);
+
+        assert_eq!(
+            console.format(&alert(), false).unwrap(),
+            "2023-11-14T22:13:20Z [Critical] web-1: Disk / is 91.5% full"
+        );
+        assert_eq!(
+            console.format(&alert(), true).unwrap(),
+            "2023-11-14T22:13:20Z [Critical] web-1: resolved: Disk / is 91.5% full"
+        );
+    }
+
+    #[test]
+    fn formats_json_lines() {
+        let line = console(true).format(&alert(), true).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+            serde_json::json!({
+                "timestamp": "2023-11-14T22:13:20Z",
+                "hostname": "web-1",
+                "severity": "Critical",
+                "message": "Disk / is 91.5% full",
+                "resolved": true,
+                "labels": { "env": "prod" },
+            })
+        );
+    }
+}
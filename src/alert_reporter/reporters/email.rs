@@ -0,0 +1,279 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials as SmtpCredentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::transport::smtp::extension::ClientId;
+use lettre::transport::smtp::PoolConfig;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::alert_reporter::AlertReporter;
+use crate::duration::deserialize_duration;
+use crate::secret::Secret;
+use crate::watcher::ActiveAlert;
+
+fn default_port() -> u16 {
+    587
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// Length of the alert message summarized in the subject.
+const SUBJECT_SUMMARY_LENGTH: usize = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum TlsMode {
+    /// Plain-text connection, only suitable for a relay on the same host.
+    None,
+    /// Plain-text connection upgraded with `STARTTLS`, usually on port 587.
+    #[default]
+    StartTls,
+    /// TLS from the start of the connection, usually on port 465.
+    Implicit,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Credentials {
+    username: String,
+    password: Secret,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    server: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    #[serde(default)]
+    tls: TlsMode,
+    #[serde(default)]
+    credentials: Option<Credentials>,
+    from: String,
+    to: Vec<String>,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+    /// Keep the connection to the server open between alerts, instead of opening one per alert.
+    #[serde(default)]
+    reuse_connection: bool,
+}
+
+fn mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| anyhow!("Invalid mail address '{}': {}", address, e))
+}
+
+/// Sends alerts by mail through an SMTP server.
+pub struct Email {
+    server: String,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    transport: SmtpTransport,
+}
+
+impl Email {
+    pub fn new(configuration: Configuration) -> Result<Self> {
+        let server = &configuration.server;
+        let tls = match configuration.tls {
+            TlsMode::None => Tls::None,
+            mode => {
+                let parameters =
+                    TlsParameters::new(server.clone()).context("Could not create TLS connector")?;
+                match mode {
+                    TlsMode::Implicit => Tls::Wrapper(parameters),
+                    _ => Tls::Required(parameters),
+                }
+            }
+        };
+        // A pool of no connection closes each one once its mail is sent.
+        let pool = PoolConfig::new().max_size(configuration.reuse_connection as u32);
+        let mut transport = SmtpTransport::builder_dangerous(server)
+            .port(configuration.port)
+            .tls(tls)
+            .timeout(Some(configuration.timeout))
+            .hello_name(ClientId::Domain(crate::watcher::hostname()))
+            .pool_config(pool);
+        if let Some(credentials) = &configuration.credentials {
+            transport = transport.credentials(SmtpCredentials::new(
+                credentials.username.clone(),
+                credentials.password.expose().to_string(),
+            ));
+        }
+        Ok(Email {
+            from: mailbox(&configuration.from)?,
+            to: configuration
+                .to
+                .iter()
+                .map(|to| mailbox(to))
+                .collect::<Result<_>>()?,
+            transport: transport.build(),
+            server: configuration.server,
+        })
+    }
+
+    fn format(&self, alert: &ActiveAlert, resolved: bool) -> Result<Message> {
+        let status = if resolved {
+            "Resolved".to_string()
+        } else {
+            format!("{:?}", alert.severity)
+        };
+        let summary = match alert.message.char_indices().nth(SUBJECT_SUMMARY_LENGTH) {
+            Some((end, _)) => format!("{}…", &alert.message[..end]),
+            None => alert.message.clone(),
+        };
+        let subject = format!(
+            "[{}] {}: {}",
+            status,
+            alert.hostname,
+            summary.lines().next().unwrap_or_default()
+        );
+        let mut body = format!(
+            "Host: {}\nSeverity: {:?}\nDetected at: {}\n",
+            alert.hostname,
+            alert.severity,
+            DateTime::<Utc>::from(alert.detected_at).to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        if resolved {
+            body.push_str("Status: resolved\n");
+        }
+        if !alert.labels.is_empty() {
+            body.push_str("Labels:\n");
+            for (key, value) in &alert.labels {
+                body.push_str(&format!("  {}: {}\n", key, value));
+            }
+        }
+        body.push('\n');
+        body.push_str(&alert.message);
+        if let Some(description) = &alert.description {
+            body.push_str(&format!("\n\n{}", description));
+        }
+        if let Some(runbook_url) = &alert.runbook_url {
+            body.push_str(&format!("\n\nRunbook: {}", runbook_url));
+        }
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .date_now()
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        message.body(body).context("Could not build mail")
+    }
+
+    fn send(&self, alert: &ActiveAlert, resolved: bool) -> Result<()> {
+        self.format(alert, resolved)
+            .and_then(|mail| {
+                self.transport
+                    .send(&mail)
+                    .map_err(|e| anyhow!("SMTP error: {}", e))
+            })
+            .map(|_| ())
+            .with_context(|| format!("Could not send alert by mail via {}", self.server))
+    }
+}
+
+impl AlertReporter for Email {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting = "email");
+        self.send(alert, false)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting_resolved = "email");
+        self.send(alert, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::Severity;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answers the commands of a single SMTP session, returning the mail it received.
+    fn serve_once(listener: TcpListener) -> thread::JoinHandle<String> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut reply = |line: &str| write!(writer, "{}\r\n", line).unwrap();
+            reply("220 localhost ESMTP");
+            let mut mail = String::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                match line.trim_end() {
+                    command if command.starts_with("EHLO") => reply("250 localhost"),
+                    "DATA" => {
+                        reply("354 Go ahead");
+                        let mut data = String::new();
+                        while data != ".\r\n" {
+                            data.clear();
+                            reader.read_line(&mut data).unwrap();
+                            mail.push_str(&data);
+                        }
+                        reply("250 Queued");
+                    }
+                    "QUIT" => {
+                        reply("221 Bye");
+                        break;
+                    }
+                    _ => reply("250 Ok"),
+                }
+                line.clear();
+            }
+            mail
+        })
+    }
+
+    #[test]
+    fn sends_alerts_over_smtp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = serve_once(listener);
+        let email = Email::new(
+            serde_json::from_value(serde_json::json!({
+                "server": "127.0.0.1",
+                "port": port,
+                "tls": "None",
+                "from": "monitrust@example.com",
+                "to": ["ops@example.com"]
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let mut alert = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+        alert.hostname = "host".to_string();
+        alert.description = Some(".dotfiles are big".to_string());
+        email.report(&alert).unwrap();
+
+        let mail = server.join().unwrap();
+        assert!(
+            mail.contains("Subject: [Critical] host: Disk is full\r\n"),
+            "{}",
+            mail
+        );
+        assert!(mail.contains("To: ops@example.com\r\n"), "{}", mail);
+        // Escaped, so that the line does not end the mail.
+        assert!(mail.contains("\r\n..dotfiles are big\r\n"), "{}", mail);
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        let configuration = serde_json::from_value(serde_json::json!({
+            "server": "localhost",
+            "from": "monitrust",
+            "to": []
+        }))
+        .unwrap();
+        assert!(Email::new(configuration).is_err());
+    }
+}
@@ -1,2 +1,17 @@
+pub mod console;
+#[cfg(feature = "email")]
+pub mod email;
+#[cfg(feature = "pagerduty")]
+pub mod pagerduty;
+#[cfg(feature = "slack")]
+pub mod slack;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(unix)]
+pub mod syslog;
+#[cfg(feature = "teams")]
+pub mod teams;
 #[cfg(feature = "telegram")]
 pub mod telegram;
+#[cfg(feature = "webhook")]
+pub mod webhook;
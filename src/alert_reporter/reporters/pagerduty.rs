@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::alert_reporter::AlertReporter;
+use crate::duration::deserialize_duration;
+use crate::secret::Secret;
+use crate::templating::render_template;
+use crate::watcher::{ActiveAlert, Severity};
+
+fn default_url() -> String {
+    "https://events.pagerduty.com/v2/enqueue".to_string()
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// Longest summary accepted by the Events API.
+const SUMMARY_LENGTH: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    /// Integration key of the PagerDuty service, for the alerts `route` does not route to
+    /// another one.
+    routing_key: Secret,
+    /// Template rendered from the labels of each alert, such as `"{team}"`, naming the entry of
+    /// `routing_keys` with the integration key of its service.
+    #[serde(default)]
+    route: Option<String>,
+    /// Integration keys of other services, by route, such as `payments`.
+    #[serde(default)]
+    routing_keys: BTreeMap<String, Secret>,
+    /// Where the alerts come from, as shown in PagerDuty. Defaults to the alert's host.
+    #[serde(default)]
+    source: Option<String>,
+    /// Events API endpoint, e.g. to use PagerDuty's EU service region.
+    #[serde(default = "default_url")]
+    url: String,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Trigger,
+    Resolve,
+}
+
+#[derive(Serialize, Debug)]
+struct EventPayload<'a> {
+    summary: String,
+    source: &'a str,
+    severity: &'static str,
+    timestamp: String,
+    /// Labels of the alert, along with its description.
+    custom_details: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(Serialize, Debug)]
+struct Link<'a> {
+    href: &'a str,
+    text: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+struct Event<'a> {
+    routing_key: &'a str,
+    event_action: Action,
+    dedup_key: String,
+    /// Only sent with triggers, resolutions are matched with them by their deduplication key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<EventPayload<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    links: Vec<Link<'a>>,
+}
+
+fn pagerduty_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+/// Triggers and resolves PagerDuty incidents through the Events API v2.
+///
+/// Alerts are deduplicated by host and fingerprint, so that an alert that keeps firing updates
+/// a single incident, which gets resolved along with the alert.
+pub struct PagerDuty {
+    client: Client,
+    configuration: Configuration,
+}
+
+impl PagerDuty {
+    pub fn new(configuration: Configuration) -> Result<Self> {
+        if configuration.route.is_some() == configuration.routing_keys.is_empty() {
+            return Err(anyhow!(
+                "'route' and 'routing_keys' of PagerDuty must be configured together"
+            ));
+        }
+        let client = Client::builder()
+            .timeout(configuration.timeout)
+            .build()
+            .context("Could not create HTTP client.")?;
+        Ok(PagerDuty {
+            client,
+            configuration,
+        })
+    }
+
+    /// Integration key of the service of the alert: the one its route names, or the default one
+    /// if it has no entry, e.g. because the alert lacks a label of the route.
+    fn routing_key(&self, alert: &ActiveAlert) -> &str {
+        let configuration = &self.configuration;
+        configuration
+            .route
+            .as_ref()
+            .and_then(|route| {
+                configuration
+                    .routing_keys
+                    .get(&render_template(route, &alert.labels))
+            })
+            .unwrap_or(&configuration.routing_key)
+            .expose()
+    }
+
+    fn event<'a>(&'a self, alert: &'a ActiveAlert, action: Action) -> Event<'a> {
+        let payload = (action == Action::Trigger).then(|| EventPayload {
+            summary: alert.message.chars().take(SUMMARY_LENGTH).collect(),
+            source: self
+                .configuration
+                .source
+                .as_deref()
+                .unwrap_or(&alert.hostname),
+            severity: pagerduty_severity(alert.severity),
+            timestamp: DateTime::<Utc>::from(alert.detected_at)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            custom_details: alert
+                .labels
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .chain(alert.description.as_deref().map(|d| ("description", d)))
+                .chain(
+                    alert
+                        .annotations
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.as_str())),
+                )
+                .collect(),
+        });
+        let links = match (action, &alert.runbook_url) {
+            (Action::Trigger, Some(runbook_url)) => vec![Link {
+                href: runbook_url,
+                text: "Runbook",
+            }],
+            _ => Vec::new(),
+        };
+        Event {
+            routing_key: self.routing_key(alert),
+            event_action: action,
+            dedup_key: format!("{}:{}", alert.hostname, alert.fingerprint),
+            payload,
+            links,
+        }
+    }
+
+    fn send(&self, event: &Event) -> Result<()> {
+        let url = &self.configuration.url;
+        let response = self
+            .client
+            .post(url)
+            .json(event)
+            .send()
+            .with_context(|| format!("Could not send event to PagerDuty at {}", url))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "PagerDuty answered with status {}: {}",
+                response.status(),
+                response.text().unwrap_or_default()
+            ))
+        }
+    }
+}
+
+impl AlertReporter for PagerDuty {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting = "pagerduty");
+        self.send(&self.event(alert, Action::Trigger))
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting_resolved = "pagerduty");
+        self.send(&self.event(alert, Action::Resolve))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::time::UNIX_EPOCH;
+
+    fn pagerduty(configuration: Value) -> PagerDuty {
+        PagerDuty::new(serde_json::from_value(configuration).unwrap()).unwrap()
+    }
+
+    fn alert() -> ActiveAlert {
+        ActiveAlert {
+            hostname: "web-1".to_string(),
+            fingerprint: "disk_space:0123456789abcdef:/".to_string(),
+            detected_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            labels: BTreeMap::from([("team".to_string(), "ops".to_string())]),
+            runbook_url: Some("https://wiki/disk".to_string()),
+            ..ActiveAlert::new("Disk is full".to_string(), Severity::Warning)
+        }
+    }
+
+    fn event(pagerduty: &PagerDuty, alert: &ActiveAlert, action: Action) -> Value {
+        serde_json::to_value(pagerduty.event(alert, action)).unwrap()
+    }
+
+    #[test]
+    fn triggers_with_the_payload_of_the_alert() {
+        let pagerduty = pagerduty(json!({ "routing_key": "key", "source": "monitrust" }));
+
+        assert_eq!(
+            event(&pagerduty, &alert(), Action::Trigger),
+            json!({
+                "routing_key": "key",
+                "event_action": "trigger",
+                "dedup_key": "web-1:disk_space:0123456789abcdef:/",
+                "payload": {
+                    "summary": "Disk is full",
+                    "source": "monitrust",
+                    "severity": "warning",
+                    "timestamp": "2023-11-14T22:13:20Z",
+                    "custom_details": { "team": "ops" },
+                },
+                "links": [{ "href": "https://wiki/disk", "text": "Runbook" }],
+            })
+        );
+    }
+
+    #[test]
+    fn resolves_with_the_same_dedup_key() {
+        let pagerduty = pagerduty(json!({ "routing_key": "key" }));
+        let alert = alert();
+
+        let trigger = event(&pagerduty, &alert, Action::Trigger);
+        let resolve = event(&pagerduty, &alert, Action::Resolve);
+
+        assert_eq!(trigger["payload"]["source"], "web-1");
+        assert_eq!(
+            resolve,
+            json!({
+                "routing_key": "key",
+                "event_action": "resolve",
+                "dedup_key": trigger["dedup_key"],
+            })
+        );
+    }
+
+    #[test]
+    fn alerts_are_routed_to_the_key_of_their_team() {
+        let pagerduty = pagerduty(json!({
+            "routing_key": "default",
+            "route": "{team}",
+            "routing_keys": { "payments": "payments-key", "infra": "infra-key" },
+        }));
+        let routing_key = |labels: &[(&str, &str)]| {
+            let alert = ActiveAlert {
+                labels: labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                ..alert()
+            };
+            event(&pagerduty, &alert, Action::Trigger)["routing_key"].clone()
+        };
+
+        assert_eq!(routing_key(&[("team", "payments")]), "payments-key");
+        assert_eq!(routing_key(&[("team", "infra")]), "infra-key");
+        assert_eq!(routing_key(&[("team", "ops")]), "default");
+        assert_eq!(routing_key(&[]), "default");
+    }
+
+    #[test]
+    fn routes_need_their_routing_keys() {
+        let configuration = json!({ "routing_key": "default", "route": "{team}" });
+
+        assert!(PagerDuty::new(serde_json::from_value(configuration).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+use std::thread::sleep;
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::alert_reporter::AlertReporter;
+use crate::duration::deserialize_duration;
+use crate::secret::Secret;
+use crate::watcher::{ActiveAlert, Severity};
+
+const POST_MESSAGE_URL: &str = "https://slack.com/api/chat.postMessage";
+
+/// Sends rate limited by Slack are retried this many times at most.
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+/// Longest wait asked by Slack that is honored, instead of failing the report.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Most fields allowed in a section block.
+const MAX_FIELDS: usize = 10;
+
+const RED: &str = "#d50200";
+const YELLOW: &str = "#de9e31";
+const GREEN: &str = "#2eb886";
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// Messages are posted either through an incoming webhook, which has its own channel, or with a
+/// bot token to the given channel.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Target {
+    Webhook { webhook_url: Secret },
+    Bot { token: Secret, channel: String },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    #[serde(flatten)]
+    target: Target,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+/// Color of the bar along the message.
+fn color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => RED,
+        Severity::Warning => YELLOW,
+        Severity::Info => GREEN,
+    }
+}
+
+/// Builds a message of Block Kit blocks, in an attachment so that it gets a colored bar. `text`
+/// is shown in notifications.
+fn message(alert: &ActiveAlert, text: &str, color: &str) -> Value {
+    let mut blocks = vec![json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": text },
+    })];
+    let documentation = [
+        alert.description.clone(),
+        alert
+            .runbook_url
+            .as_ref()
+            .map(|url| format!("📖 <{}|Runbook>", url)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    if !documentation.is_empty() {
+        blocks.push(json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": documentation.join("\n") },
+        }));
+    }
+    if !alert.labels.is_empty() {
+        let fields = alert
+            .labels
+            .iter()
+            .take(MAX_FIELDS)
+            .map(|(name, value)| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", name, value) }))
+            .collect::<Vec<_>>();
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+    let timestamp = alert
+        .detected_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // Slack shows the date in the reader's time zone, or the fallback after the `|`.
+    blocks.push(json!({
+        "type": "context",
+        "elements": [{
+            "type": "mrkdwn",
+            "text": format!(
+                "{} · <!date^{}^{{date_short_pretty}} {{time_secs}}|{}>",
+                alert.hostname, timestamp, timestamp
+            ),
+        }],
+    }));
+    json!({
+        "text": text,
+        "attachments": [{ "color": color, "blocks": blocks }],
+    })
+}
+
+/// Posts alerts to Slack, along with their resolution.
+pub struct Slack {
+    client: Client,
+    target: Target,
+}
+
+impl Slack {
+    pub fn new(configuration: Configuration) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(configuration.timeout)
+            .build()
+            .context("Could not create HTTP client.")?;
+        Ok(Slack {
+            client,
+            target: configuration.target,
+        })
+    }
+
+    fn request(&self, mut message: Value) -> RequestBuilder {
+        match &self.target {
+            Target::Webhook { webhook_url } => {
+                self.client.post(webhook_url.expose()).json(&message)
+            }
+            Target::Bot { token, channel } => {
+                message["channel"] = json!(channel);
+                self.client
+                    .post(POST_MESSAGE_URL)
+                    .bearer_auth(token.expose())
+                    .json(&message)
+            }
+        }
+    }
+
+    /// Posts a message, waiting as long as Slack asks to when it is rate limited.
+    fn post(&self, message: Value) -> Result<()> {
+        let mut retries = 0;
+        let response = loop {
+            let response = self
+                .request(message.clone())
+                .send()
+                .context("Could not send message to Slack")?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                break response;
+            }
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok()?.parse().ok())
+                .map_or(Duration::from_secs(1), Duration::from_secs);
+            if retries == MAX_RATE_LIMITED_RETRIES || retry_after > MAX_RETRY_AFTER {
+                return Err(anyhow!(
+                    "Slack is rate limiting messages, retry after {:?}",
+                    retry_after
+                ));
+            }
+            warn!(slack_rate_limited = ?retry_after);
+            sleep(retry_after);
+            retries += 1;
+        };
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        if !status.is_success() {
+            return Err(anyhow!("Slack answered with status {}: {}", status, body));
+        }
+        // The Web API answers errors with a success status, telling them in the body.
+        if let Target::Bot { .. } = self.target {
+            let answer: Value = serde_json::from_str(&body)
+                .with_context(|| format!("Invalid answer from Slack: {}", body))?;
+            if answer["ok"] != json!(true) {
+                return Err(anyhow!("Slack refused the message: {}", answer["error"]));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AlertReporter for Slack {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting = "slack");
+        self.post(message(alert, &alert.message, color(alert.severity)))
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting_resolved = "slack");
+        self.post(message(
+            alert,
+            &format!("✅ Resolved: {}", alert.message),
+            GREEN,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn alert() -> ActiveAlert {
+        ActiveAlert {
+            hostname: "web-1".to_string(),
+            detected_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            labels: BTreeMap::from([("team".to_string(), "ops".to_string())]),
+            ..ActiveAlert::new("Disk is full".to_string(), Severity::Warning)
+        }
+    }
+
+    #[test]
+    fn colors_follow_the_severity() {
+        assert_eq!(color(Severity::Critical), RED);
+        assert_eq!(color(Severity::Warning), YELLOW);
+        assert_eq!(color(Severity::Info), GREEN);
+    }
+
+    #[test]
+    fn messages_are_made_of_blocks() {
+        let alert = alert();
+
+        assert_eq!(
+            message(&alert, &alert.message, color(alert.severity)),
+            json!({
+                "text": "Disk is full",
+                "attachments": [{
+                    "color": YELLOW,
+                    "blocks": [
+                        {
+                            "type": "section",
+                            "text": { "type": "mrkdwn", "text": "Disk is full" },
+                        },
+                        {
+                            "type": "section",
+                            "fields": [{ "type": "mrkdwn", "text": "*team*\nops" }],
+                        },
+                        {
+                            "type": "context",
+                            "elements": [{
+                                "type": "mrkdwn",
+                                "text": "web-1 · <!date^1700000000^{date_short_pretty} {time_secs}|1700000000>",
+                            }],
+                        },
+                    ],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn waits_as_asked_when_rate_limited() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let webhook_url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in ["429 Too Many Requests\r\nRetry-After: 0", "200 OK"] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                let (mut line, mut length) = (String::new(), 0);
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                write!(
+                    writer,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+                bodies.push(serde_json::from_slice::<Value>(&body).unwrap());
+            }
+            bodies
+        });
+        let slack =
+            Slack::new(serde_json::from_value(json!({ "webhook_url": webhook_url })).unwrap())
+                .unwrap();
+
+        slack.report_resolved(&alert()).unwrap();
+
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies[0], bodies[1]);
+        assert_eq!(bodies[1]["text"], "✅ Resolved: Disk is full");
+        assert_eq!(bodies[1]["attachments"][0]["color"], GREEN);
+    }
+}
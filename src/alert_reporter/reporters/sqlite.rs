@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::alert_reporter::AlertReporter;
+use crate::watcher::ActiveAlert;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    /// Database the alerts are written to, created along with its table if it does not exist.
+    /// `:memory:` keeps them in memory.
+    path: PathBuf,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS alerts (
+        id INTEGER PRIMARY KEY,
+        fingerprint TEXT NOT NULL,
+        hostname TEXT NOT NULL,
+        message TEXT NOT NULL,
+        severity TEXT NOT NULL,
+        labels TEXT NOT NULL,
+        fired_at TEXT NOT NULL,
+        resolved_at TEXT
+    );
+    CREATE INDEX IF NOT EXISTS open_alerts ON alerts (fingerprint) WHERE resolved_at IS NULL;
+";
+
+/// Times are written as RFC 3339 dates in UTC, which SQLite date functions read.
+fn timestamp(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Writes alerts to the `alerts` table of a SQLite database, for a queryable history of the
+/// alerts of a host without any alerting system.
+///
+/// Each report adds a row, with its labels as a JSON object. Rows are never deleted: a resolution
+/// only sets the `resolved_at` of the open rows of the alert, so that an alert reported again
+/// while open, e.g. escalated, is resolved along with its first report.
+pub struct Sqlite {
+    connection: Mutex<Connection>,
+}
+
+impl Sqlite {
+    pub fn new(configuration: Configuration) -> Result<Self> {
+        let path = &configuration.path;
+        let connection = Connection::open(path)
+            .with_context(|| format!("Could not open database: {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| format!("Could not create the alerts table in {}", path.display()))?;
+        Ok(Sqlite {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| anyhow!("SQLite connection lock is poisoned"))
+    }
+}
+
+fn insert(connection: &Connection, alert: &ActiveAlert) -> Result<()> {
+    connection
+        .execute(
+            "INSERT INTO alerts (fingerprint, hostname, message, severity, labels, fired_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                alert.fingerprint,
+                alert.hostname,
+                alert.message,
+                format!("{:?}", alert.severity),
+                serde_json::to_string(&alert.labels).context("Could not serialize labels.")?,
+                timestamp(alert.detected_at),
+            ],
+        )
+        .context("Could not insert alert")?;
+    Ok(())
+}
+
+impl AlertReporter for Sqlite {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        insert(&*self.connection()?, alert)
+    }
+
+    /// The alerts of a check are inserted in a single transaction.
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection
+            .transaction()
+            .context("Could not start transaction")?;
+        for alert in alerts {
+            insert(&transaction, alert)?;
+        }
+        transaction.commit().context("Could not insert alerts")
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.connection()?
+            .execute(
+                "UPDATE alerts SET resolved_at = ?1 \
+                 WHERE fingerprint = ?2 AND resolved_at IS NULL",
+                params![timestamp(SystemTime::now()), alert.fingerprint],
+            )
+            .context("Could not resolve alert")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::Severity;
+
+    fn alert(fingerprint: &str) -> ActiveAlert {
+        ActiveAlert {
+            fingerprint: fingerprint.to_string(),
+            labels: [("env".to_string(), "prod".to_string())].into(),
+            ..ActiveAlert::new(format!("Disk {} is full", fingerprint), Severity::Critical)
+        }
+    }
+
+    /// Rows of the alerts table: fingerprint, message, severity, labels and whether resolved.
+    fn rows(sqlite: &Sqlite) -> Vec<(String, String, String, String, bool)> {
+        let connection = sqlite.connection().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT fingerprint, message, severity, labels, resolved_at IS NOT NULL \
+                 FROM alerts ORDER BY id",
+            )
+            .unwrap();
+        statement
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn resolutions_close_the_rows_of_the_alert() {
+        let sqlite =
+            Sqlite::new(serde_json::from_value(serde_json::json!({ "path": ":memory:" })).unwrap())
+                .unwrap();
+
+        sqlite.report(&alert("/")).unwrap();
+        sqlite.report_batch(&[alert("/var"), alert("/")]).unwrap();
+        sqlite.report_resolved(&alert("/")).unwrap();
+
+        let row = |fingerprint: &str, resolved| {
+            (
+                fingerprint.to_string(),
+                format!("Disk {} is full", fingerprint),
+                "Critical".to_string(),
+                r#"{"env":"prod"}"#.to_string(),
+                resolved,
+            )
+        };
+        assert_eq!(
+            rows(&sqlite),
+            [row("/", true), row("/var", false), row("/", true)]
+        );
+    }
+}
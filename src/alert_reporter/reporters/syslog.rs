@@ -0,0 +1,183 @@
+use std::os::unix::net::UnixDatagram;
+use std::process;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::alert_reporter::{format, AlertReporter};
+use crate::watcher::{ActiveAlert, Severity};
+
+fn default_tag() -> String {
+    "monitrust".to_string()
+}
+
+fn default_socket() -> String {
+    "/dev/log".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum Facility {
+    User,
+    #[default]
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    fn code(&self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    #[serde(default)]
+    facility: Facility,
+    /// Identifies Monitrust in the system log.
+    #[serde(default = "default_tag")]
+    tag: String,
+    /// Socket of the system logger.
+    #[serde(default = "default_socket")]
+    socket: String,
+}
+
+/// Syslog severity of an alert, as defined in RFC 5424.
+fn syslog_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Info => 6,
+        Severity::Warning => 4,
+        Severity::Critical => 2,
+    }
+}
+
+/// Severity of resolved alerts, which call for attention but are not a problem anymore.
+const NOTICE: u8 = 5;
+
+/// Writes alerts to the system logger, so that they get forwarded wherever syslog is.
+///
+/// Messages are sent in the BSD syslog format straight to the logger socket rather than through
+/// `syslog(3)`, which silently drops messages when the logger is unavailable.
+pub struct Syslog {
+    configuration: Configuration,
+}
+
+impl Syslog {
+    pub fn new(configuration: Configuration) -> Self {
+        Syslog { configuration }
+    }
+
+    fn send(&self, alert: &ActiveAlert, severity: u8, message: &str) -> Result<()> {
+        let line = format!(
+            "<{}>{} {}[{}]: {}",
+            self.configuration.facility.code() * 8 + severity,
+            DateTime::<Local>::from(alert.detected_at).format("%b %e %H:%M:%S"),
+            self.configuration.tag,
+            process::id(),
+            message
+        );
+        UnixDatagram::unbound()
+            .and_then(|socket| socket.send_to(line.as_bytes(), &self.configuration.socket))
+            .with_context(|| {
+                format!(
+                    "Could not send alert to syslog at '{}'",
+                    self.configuration.socket
+                )
+            })?;
+        Ok(())
+    }
+}
+
+impl AlertReporter for Syslog {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.send(
+            alert,
+            syslog_severity(alert.severity),
+            &format::prefixed_message(alert),
+        )
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        let message = format::prefixed_message(alert);
+        self.send(alert, NOTICE, &format!("Resolved: {}", message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_severities_to_syslog_priorities() {
+        assert_eq!(syslog_severity(Severity::Critical), 2);
+        assert_eq!(syslog_severity(Severity::Warning), 4);
+        assert_eq!(syslog_severity(Severity::Info), 6);
+    }
+
+    #[test]
+    fn sends_alerts_with_their_priority_to_the_socket() {
+        let path = std::env::temp_dir().join(format!("monitrust-syslog-{}", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let logger = UnixDatagram::bind(&path).unwrap();
+        let syslog = Syslog::new(Configuration {
+            facility: Facility::Local0,
+            tag: "monitrust".to_string(),
+            socket: path.to_string_lossy().into_owned(),
+        });
+        let alert = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+
+        syslog.report(&alert).unwrap();
+        syslog.report_resolved(&alert).unwrap();
+
+        let mut buffer = [0; 1024];
+        let mut received = || {
+            let length = logger.recv(&mut buffer).unwrap();
+            String::from_utf8_lossy(&buffer[..length]).into_owned()
+        };
+        let (reported, resolved) = (received(), received());
+        let tag = format!(" monitrust[{}]: ", process::id());
+        assert!(reported.starts_with("<130>"), "{}", reported);
+        assert!(
+            reported.ends_with(&format!("{}Disk is full", tag)),
+            "{}",
+            reported
+        );
+        assert!(resolved.starts_with("<133>"), "{}", resolved);
+        assert!(
+            resolved.ends_with(&format!("{}Resolved: Disk is full", tag)),
+            "{}",
+            resolved
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn fails_without_a_logger() {
+        let syslog = Syslog::new(Configuration {
+            facility: Facility::Daemon,
+            tag: default_tag(),
+            socket: "/nonexistent/monitrust/log".to_string(),
+        });
+        let alert = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+
+        assert!(syslog.report(&alert).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::alert_reporter::AlertReporter;
+use crate::duration::deserialize_duration;
+use crate::secret::Secret;
+use crate::watcher::{ActiveAlert, Severity};
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    /// Incoming webhook of the channel, from its connectors or from a Workflows flow.
+    webhook_url: Secret,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+/// Color of the title, among the ones of the Adaptive Cards host theme.
+fn color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "Attention",
+        Severity::Warning => "Warning",
+        Severity::Info => "Accent",
+    }
+}
+
+/// Builds a message holding an Adaptive Card, with the labels of the alert as facts.
+fn card(alert: &ActiveAlert, title: &str, color: &str) -> Value {
+    let detected_at =
+        DateTime::<Utc>::from(alert.detected_at).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut body = vec![
+        json!({
+            "type": "TextBlock",
+            "text": title,
+            "weight": "Bolder",
+            "size": "Medium",
+            "color": color,
+            "wrap": true,
+        }),
+        json!({ "type": "TextBlock", "text": alert.message, "wrap": true }),
+    ];
+    if let Some(description) = &alert.description {
+        body.push(json!({ "type": "TextBlock", "text": description, "wrap": true }));
+    }
+    if !alert.labels.is_empty() {
+        let facts = alert
+            .labels
+            .iter()
+            .map(|(name, value)| json!({ "title": name, "value": value }))
+            .collect::<Vec<_>>();
+        body.push(json!({ "type": "FactSet", "facts": facts }));
+    }
+    // Teams shows the date in the reader's time zone.
+    body.push(json!({
+        "type": "TextBlock",
+        "text": format!(
+            "{} · {{{{DATE({}, SHORT)}}}} {{{{TIME({})}}}}",
+            alert.hostname, detected_at, detected_at
+        ),
+        "isSubtle": true,
+        "size": "Small",
+        "wrap": true,
+    }));
+    let mut content = json!({
+        "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+        "type": "AdaptiveCard",
+        "version": "1.4",
+        "body": body,
+    });
+    if let Some(url) = &alert.runbook_url {
+        content["actions"] = json!([{ "type": "Action.OpenUrl", "title": "Runbook", "url": url }]);
+    }
+    json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "content": content,
+        }],
+    })
+}
+
+/// Whether Teams accepted the message. Connectors answer `1`, and Workflows nothing at all, but
+/// connectors also answer some failures with a success status, telling them in the body.
+fn is_accepted(status: StatusCode, body: &str) -> bool {
+    status.is_success() && matches!(body.trim(), "" | "1")
+}
+
+/// Posts alerts to a Microsoft Teams channel, along with their resolution.
+pub struct Teams {
+    client: Client,
+    webhook_url: Secret,
+}
+
+impl Teams {
+    pub fn new(configuration: Configuration) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(configuration.timeout)
+            .build()
+            .context("Could not create HTTP client.")?;
+        Ok(Teams {
+            client,
+            webhook_url: configuration.webhook_url,
+        })
+    }
+
+    fn post(&self, message: Value) -> Result<()> {
+        let response = self
+            .client
+            .post(self.webhook_url.expose())
+            .json(&message)
+            .send()
+            .context("Could not send message to Teams")?;
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        if !is_accepted(status, &body) {
+            return Err(anyhow!("Teams answered with status {}: {}", status, body));
+        }
+        Ok(())
+    }
+}
+
+impl AlertReporter for Teams {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting = "teams");
+        let title = format!("{:?} alert on {}", alert.severity, alert.hostname);
+        self.post(card(alert, &title, color(alert.severity)))
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting_resolved = "teams");
+        let title = format!("✅ Resolved on {}", alert.hostname);
+        self.post(card(alert, &title, "Good"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::time::UNIX_EPOCH;
+
+    fn alert() -> ActiveAlert {
+        ActiveAlert {
+            hostname: "web-1".to_string(),
+            detected_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            labels: BTreeMap::from([("team".to_string(), "ops".to_string())]),
+            runbook_url: Some("https://wiki.example.com/disk".to_string()),
+            ..ActiveAlert::new("Disk is full".to_string(), Severity::Critical)
+        }
+    }
+
+    #[test]
+    fn cards_hold_the_message_and_the_labels_as_facts() {
+        let message = card(
+            &alert(),
+            "Critical alert on web-1",
+            color(Severity::Critical),
+        );
+
+        assert_eq!(message["type"], "message");
+        let attachment = &message["attachments"][0];
+        assert_eq!(
+            attachment["contentType"],
+            "application/vnd.microsoft.card.adaptive"
+        );
+        let content = &attachment["content"];
+        assert_eq!(content["type"], "AdaptiveCard");
+        assert_eq!(
+            content["body"][0],
+            json!({
+                "type": "TextBlock",
+                "text": "Critical alert on web-1",
+                "weight": "Bolder",
+                "size": "Medium",
+                "color": "Attention",
+                "wrap": true,
+            })
+        );
+        assert_eq!(content["body"][1]["text"], "Disk is full");
+        assert_eq!(
+            content["body"][2],
+            json!({ "type": "FactSet", "facts": [{ "title": "team", "value": "ops" }] })
+        );
+        assert_eq!(
+            content["body"][3]["text"],
+            "web-1 · {{DATE(2023-11-14T22:13:20Z, SHORT)}} {{TIME(2023-11-14T22:13:20Z)}}"
+        );
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://wiki.example.com/disk"
+        );
+    }
+
+    #[test]
+    fn only_empty_or_one_bodies_are_accepted() {
+        assert!(is_accepted(StatusCode::OK, "1"));
+        assert!(is_accepted(StatusCode::OK, "1\n"));
+        assert!(is_accepted(StatusCode::ACCEPTED, ""));
+        assert!(!is_accepted(
+            StatusCode::OK,
+            "Webhook message delivery failed with error: Microsoft Teams endpoint returned HTTP error 429"
+        ));
+        assert!(!is_accepted(StatusCode::BAD_REQUEST, "1"));
+    }
+}
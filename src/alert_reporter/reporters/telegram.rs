@@ -1,11 +1,12 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use teloxide::Bot;
 use teloxide::prelude::{ChatId, Requester};
 use teloxide::types::Recipient;
+use teloxide::Bot;
 use tracing::info;
 
 use crate::alert_reporter::AlertReporter;
+use crate::secret::Secret;
 use crate::watcher::ActiveAlert;
 
 pub struct Telegram {
@@ -15,22 +16,52 @@ pub struct Telegram {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Configuration {
-    api: String,
+    api: Secret,
     chat_id: i64,
 }
 
 impl Telegram {
     pub fn new(configuration: Configuration) -> Self {
-        let bot = Bot::new(configuration.api);
-        Telegram { bot, chat_id: configuration.chat_id }
+        let bot = Bot::new(configuration.api.expose());
+        Telegram {
+            bot,
+            chat_id: configuration.chat_id,
+        }
+    }
+
+    fn send(&self, message: &str) -> Result<()> {
+        tokio::runtime::Runtime::new()
+            .context("Could not create tokio runtime.")?
+            .block_on(async {
+                self.bot
+                    .send_message(Recipient::Id(ChatId(self.chat_id)), message)
+                    .await
+                    .with_context(|| format!("Could not send message to chat id {}", self.chat_id))
+                    .map(|_| ())
+            })
     }
 }
 
+/// The message of an alert, followed by its description and runbook if it has them.
+fn text(alert: &ActiveAlert) -> String {
+    let mut text = alert.message.clone();
+    if let Some(description) = &alert.description {
+        text.push_str(&format!("\n\n{}", description));
+    }
+    if let Some(runbook_url) = &alert.runbook_url {
+        text.push_str(&format!("\n📖 Runbook: {}", runbook_url));
+    }
+    text
+}
+
 impl AlertReporter for Telegram {
     fn report(&self, alert: &ActiveAlert) -> Result<()> {
         info!(reporting = "telegram");
-        tokio::runtime::Runtime::new().context("Could not create tokio runtime.")?.block_on(async {
-            self.bot.send_message(Recipient::Id(ChatId(self.chat_id)), &alert.message).await.with_context(|| format!("Could not send message to chat id {}", self.chat_id)).map(|_| ())
-        })
+        self.send(&text(alert))
     }
-}
\ No newline at end of file
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting_resolved = "telegram");
+        self.send(&format!("✅ Resolved: {}", alert.message))
+    }
+}
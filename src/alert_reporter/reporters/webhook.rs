@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::alert_reporter::AlertReporter;
+use crate::duration::deserialize_duration;
+use crate::secret::Secret;
+use crate::watcher::{ActiveAlert, Severity};
+
+pub struct Webhook {
+    client: Client,
+    url: String,
+    headers: HeaderMap,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Configuration {
+    url: String,
+    #[serde(default)]
+    headers: HashMap<String, Secret>,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Firing,
+    Resolved,
+}
+
+#[derive(Serialize, Debug)]
+struct Payload<'a> {
+    status: Status,
+    message: &'a str,
+    severity: Severity,
+    /// Seconds since the UNIX epoch.
+    timestamp: u64,
+    labels: &'a BTreeMap<String, String>,
+    fingerprint: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runbook_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: &'a BTreeMap<String, String>,
+}
+
+impl Webhook {
+    pub fn new(client: Client, url: String, headers: HeaderMap) -> Self {
+        Webhook {
+            client,
+            url,
+            headers,
+        }
+    }
+
+    pub fn from_configuration(configuration: Configuration) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(configuration.timeout)
+            .build()
+            .context("Could not create HTTP client.")?;
+        let headers = configuration
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    HeaderName::try_from(name)
+                        .with_context(|| format!("Invalid header name: {}", name))?,
+                    HeaderValue::try_from(value.expose())
+                        .with_context(|| format!("Invalid value for header {}", name))?,
+                ))
+            })
+            .collect::<Result<HeaderMap>>()?;
+        Ok(Webhook::new(client, configuration.url, headers))
+    }
+}
+
+impl Webhook {
+    /// Posts the alert, with the same fields whether it fires or resolved so that the receiver
+    /// tells them apart by their `status` and correlates them by their `fingerprint`.
+    fn post(&self, alert: &ActiveAlert, status: Status) -> Result<()> {
+        let payload = Payload {
+            status,
+            message: &alert.message,
+            severity: alert.severity,
+            timestamp: alert
+                .detected_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            labels: &alert.labels,
+            fingerprint: &alert.fingerprint,
+            description: alert.description.as_deref(),
+            runbook_url: alert.runbook_url.as_deref(),
+            annotations: &alert.annotations,
+        };
+        let response = self
+            .client
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .json(&payload)
+            .send()
+            .with_context(|| format!("Could not send alert to webhook {}", self.url))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Webhook {} answered with status {}",
+                self.url,
+                response.status()
+            ))
+        }
+    }
+}
+
+impl AlertReporter for Webhook {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting = "webhook");
+        self.post(alert, Status::Firing)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        info!(reporting_resolved = "webhook");
+        self.post(alert, Status::Resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answers `count` HTTP requests, returning their bodies.
+    fn serve(listener: TcpListener, count: usize) -> thread::JoinHandle<Vec<Value>> {
+        thread::spawn(move || {
+            (0..count)
+                .map(|_| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut writer = stream.try_clone().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let (mut line, mut length) = (String::new(), 0);
+                    while reader.read_line(&mut line).unwrap() > 2 {
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                        line.clear();
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    // Each request gets a connection of its own, as the client could otherwise
+                    // reuse the one closed once answered.
+                    write!(
+                        writer,
+                        "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+                    serde_json::from_slice(&body).unwrap()
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn posts_resolutions_along_with_alerts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = serve(listener, 2);
+        let webhook = Webhook::from_configuration(
+            serde_json::from_value(serde_json::json!({ "url": url })).unwrap(),
+        )
+        .unwrap();
+        let mut alert = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+        alert.fingerprint = "disk_space:0123456789abcdef:/".to_string();
+        alert.detected_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        webhook.report(&alert).unwrap();
+        webhook.report_resolved(&alert).unwrap();
+
+        let payloads = server.join().unwrap();
+        assert_eq!(payloads[0]["status"], "firing");
+        assert_eq!(payloads[1]["status"], "resolved");
+        for payload in &payloads {
+            assert_eq!(payload["message"], "Disk is full");
+            assert_eq!(payload["severity"], "Critical");
+            assert_eq!(payload["timestamp"], 1_700_000_000);
+            assert_eq!(payload["fingerprint"], "disk_space:0123456789abcdef:/");
+        }
+    }
+
+    #[test]
+    fn documents_alerts_only_when_they_have_documentation() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = serve(listener, 2);
+        let webhook = Webhook::from_configuration(
+            serde_json::from_value(serde_json::json!({ "url": url })).unwrap(),
+        )
+        .unwrap();
+        let undocumented = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+        let documented = ActiveAlert {
+            description: Some("The root filesystem is almost full".to_string()),
+            runbook_url: Some("https://wiki/disk".to_string()),
+            ..undocumented.clone()
+        };
+
+        webhook.report(&documented).unwrap();
+        webhook.report(&undocumented).unwrap();
+
+        let payloads = server.join().unwrap();
+        assert_eq!(
+            payloads[0]["description"],
+            "The root filesystem is almost full"
+        );
+        assert_eq!(payloads[0]["runbook_url"], "https://wiki/disk");
+        let fields = payloads[1].as_object().unwrap();
+        assert!(!fields.contains_key("description"), "{:?}", fields);
+        assert!(!fields.contains_key("runbook_url"), "{:?}", fields);
+    }
+
+    #[test]
+    fn annotations_are_sent_when_there_are_some() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = serve(listener, 2);
+        let webhook = Webhook::from_configuration(
+            serde_json::from_value(serde_json::json!({ "url": url })).unwrap(),
+        )
+        .unwrap();
+        let plain = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+        let annotated = plain.clone().annotated([
+            ("mount", "/var".to_string()),
+            ("used_pct", "91.3".to_string()),
+        ]);
+
+        webhook.report(&annotated).unwrap();
+        webhook.report(&plain).unwrap();
+
+        let payloads = server.join().unwrap();
+        assert_eq!(
+            payloads[0]["annotations"],
+            serde_json::json!({ "mount": "/var", "used_pct": "91.3" })
+        );
+        let fields = payloads[1].as_object().unwrap();
+        assert!(!fields.contains_key("annotations"), "{:?}", fields);
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::watcher::ActiveAlert;
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_max_delay_ms() -> u64 {
+    10_000
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetryConfiguration {
+    #[serde(default = "default_max_attempts")]
+    max_attempts: u32,
+    /// Delay before the first retry, doubled after each failed attempt.
+    #[serde(default = "default_base_delay_ms")]
+    base_delay_ms: u64,
+    #[serde(default = "default_max_delay_ms")]
+    max_delay_ms: u64,
+}
+
+/// Retries failed reports with an exponential backoff, returning the last error if all attempts
+/// failed.
+pub struct RetryingReporter<R: AlertReporter> {
+    reporter: R,
+    configuration: RetryConfiguration,
+}
+
+impl<R: AlertReporter> RetryingReporter<R> {
+    pub fn new(reporter: R, configuration: RetryConfiguration) -> Self {
+        RetryingReporter {
+            reporter,
+            configuration,
+        }
+    }
+
+    /// Delay before the given retry, picked randomly between half and all of the backoff so that
+    /// retries of several reporters do not all happen at once.
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .configuration
+            .base_delay_ms
+            .saturating_mul(1 << retry.min(32))
+            .min(self.configuration.max_delay_ms);
+        let jitter = RandomState::new().build_hasher().finish() % (backoff / 2 + 1);
+        Duration::from_millis(backoff - jitter)
+    }
+
+    fn retry(&self, report: impl Fn(&R) -> Result<()>) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match report(&self.reporter) {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.configuration.max_attempts => return Err(e),
+                Err(e) => {
+                    let delay = self.delay(attempt - 1);
+                    warn!(attempt, retry_in = ?delay, reporter_error = ?e);
+                    sleep(delay);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for RetryingReporter<R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.retry(|r| r.report(alert))
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.retry(|r| r.report_resolved(alert))
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.reporter.flush()
+    }
+
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        self.retry(|r| r.report_diff(changes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::Severity;
+    use anyhow::anyhow;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails its first `failures` reports, as a webhook answering 503 for a moment would.
+    struct FlakyReporter {
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    impl FlakyReporter {
+        fn new(failures: u32) -> Self {
+            FlakyReporter {
+                failures,
+                attempts: AtomicU32::new(0),
+            }
+        }
+    }
+
+    impl AlertReporter for FlakyReporter {
+        fn report(&self, _alert: &ActiveAlert) -> Result<()> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            if attempt <= self.failures {
+                return Err(anyhow!("Attempt {} failed", attempt));
+            }
+            Ok(())
+        }
+
+        fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+            self.report(alert)
+        }
+    }
+
+    fn retrying(failures: u32) -> RetryingReporter<FlakyReporter> {
+        let configuration = RetryConfiguration {
+            max_attempts: 3,
+            base_delay_ms: 1,
+            max_delay_ms: 2,
+        };
+        RetryingReporter::new(FlakyReporter::new(failures), configuration)
+    }
+
+    fn alert() -> ActiveAlert {
+        ActiveAlert::new("Disk is full".to_string(), Severity::Critical)
+    }
+
+    #[test]
+    fn retries_until_the_report_succeeds() {
+        let reporter = retrying(2);
+
+        reporter.report(&alert()).unwrap();
+
+        assert_eq!(reporter.reporter.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn returns_the_last_error_once_out_of_attempts() {
+        let reporter = retrying(5);
+
+        let error = reporter.report(&alert()).unwrap_err();
+
+        assert_eq!(error.to_string(), "Attempt 3 failed");
+        assert_eq!(reporter.reporter.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_max_delay() {
+        let reporter = RetryingReporter::new(
+            FlakyReporter::new(0),
+            RetryConfiguration {
+                max_attempts: 5,
+                base_delay_ms: 100,
+                max_delay_ms: 300,
+            },
+        );
+
+        for (retry, backoff) in [(0, 100), (1, 200), (2, 300), (40, 300)] {
+            let delay = reporter.delay(retry);
+            assert!(
+                delay >= Duration::from_millis(backoff / 2)
+                    && delay <= Duration::from_millis(backoff),
+                "{:?} for retry {}",
+                delay,
+                retry
+            );
+        }
+    }
+}
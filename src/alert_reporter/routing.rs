@@ -0,0 +1,354 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::alert_reporter::fallback::{Failures, FallbackConfiguration, Outcome};
+use crate::alert_reporter::{AlertChange, AlertReporter, CompositeReporter};
+use crate::watcher::{ActiveAlert, Severity};
+
+/// Sends the alerts it matches to some reporters only, such as critical alerts to PagerDuty.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Route {
+    /// Severities of the alerts the route applies to, any if empty.
+    #[serde(default)]
+    severities: Vec<Severity>,
+    /// Labels the alerts must all have, such as `{ "team": "db" }`.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    /// Names of the reporters receiving the alerts.
+    reporters: Vec<String>,
+}
+
+impl Route {
+    fn matches(&self, alert: &ActiveAlert) -> bool {
+        (self.severities.is_empty() || self.severities.contains(&alert.severity))
+            && self
+                .labels
+                .iter()
+                .all(|(key, value)| alert.labels.get(key) == Some(value))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RoutingConfiguration {
+    /// Routes tried in turn, the first one matching an alert deciding its reporters.
+    #[serde(default)]
+    routes: Vec<Route>,
+    /// Reporters of the alerts no route matches, all of them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_reporters: Option<Vec<String>>,
+    /// Reporter told when another one keeps failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fallback: Option<FallbackConfiguration>,
+}
+
+/// Reports each alert to the reporters of the first route matching it, by name. Escalations
+/// naming a reporter go to that reporter whatever the routes. Resolutions go to the reporters
+/// the alert was reported to, or to the ones of its route if it was reported before Monitrust
+/// started. Reporters failing too many times in a row are reported to the fallback, if any.
+pub struct RoutingReporter {
+    reporters: CompositeReporter,
+    configuration: RoutingConfiguration,
+    failures: Option<Failures>,
+    /// Reporters each firing alert was reported to, by fingerprint.
+    reported_to: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl RoutingReporter {
+    /// Fails if a route or the fallback names a reporter that does not exist.
+    pub fn new(reporters: CompositeReporter, configuration: RoutingConfiguration) -> Result<Self> {
+        let names = configuration
+            .routes
+            .iter()
+            .flat_map(|route| &route.reporters)
+            .chain(configuration.default_reporters.iter().flatten());
+        for name in names {
+            if !reporters.0.iter().any(|(n, _)| n == name) {
+                return Err(anyhow!("Alerts are routed to unknown reporter '{}'", name));
+            }
+        }
+        let failures = match configuration.fallback.clone() {
+            Some(fallback) => {
+                fallback.validate()?;
+                let failures = Failures::new(fallback);
+                if !reporters.0.iter().any(|(n, _)| n == failures.fallback()) {
+                    return Err(anyhow!(
+                        "Fallback is unknown reporter '{}'",
+                        failures.fallback()
+                    ));
+                }
+                Some(failures)
+            }
+            None => None,
+        };
+        Ok(RoutingReporter {
+            reporters,
+            configuration,
+            failures,
+            reported_to: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    fn reported_to(&self) -> MutexGuard<'_, BTreeMap<String, BTreeSet<String>>> {
+        // Reporters are only ever inserted and removed, a panic can not leave them inconsistent.
+        self.reported_to.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether the routes send the alert to the reporter.
+    fn is_routed_to(&self, alert: &ActiveAlert, name: &str) -> bool {
+        let reporters = self
+            .configuration
+            .routes
+            .iter()
+            .find(|route| route.matches(alert))
+            .map(|route| &route.reporters)
+            .or(self.configuration.default_reporters.as_ref());
+        reporters.is_none_or(|reporters| reporters.iter().any(|r| r == name))
+    }
+
+    /// Whether the alert goes to the reporter, the reporter of its escalation overriding the
+    /// routes.
+    fn sends_to(&self, alert: &ActiveAlert, name: &str) -> bool {
+        let escalated = alert
+            .escalation
+            .as_ref()
+            .and_then(|e| e.reporter.as_deref())
+            .is_some_and(|reporter| self.reporters.0.iter().any(|(n, _)| n == reporter));
+        self.reporters.is_routed_to(alert, name) && (escalated || self.is_routed_to(alert, name))
+    }
+
+    /// Counts the result of a report of the reporter, telling the fallback if it starts or stops
+    /// failing.
+    fn counted(&self, name: &str, result: Option<Result<()>>) -> Option<Result<()>> {
+        let (Some(failures), Some(reported)) = (&self.failures, &result) else {
+            return result;
+        };
+        let Some(outcome) = failures.record(name, reported) else {
+            return result;
+        };
+        let told = self
+            .reporters
+            .0
+            .iter()
+            .find(|(n, _)| n == failures.fallback())
+            .context("Fallback reporter is gone")
+            .and_then(|(_, fallback)| match &outcome {
+                Outcome::Failing(alert) => fallback.report(alert),
+                Outcome::Recovered(alert) => fallback.report_resolved(alert),
+            });
+        if let Err(e) = told {
+            warn!(fallback_error = ?e);
+        }
+        result
+    }
+
+    fn remember(&self, alerts: &[ActiveAlert]) {
+        let mut reported_to = self.reported_to();
+        for alert in alerts {
+            let names = self
+                .reporters
+                .0
+                .iter()
+                .map(|(name, _)| name)
+                .filter(|name| self.sends_to(alert, name))
+                .cloned();
+            reported_to
+                .entry(alert.fingerprint.clone())
+                .or_default()
+                .extend(names);
+        }
+    }
+}
+
+impl AlertReporter for RoutingReporter {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.remember(std::slice::from_ref(alert));
+        self.reporters.fan_out(|name, r| {
+            self.counted(name, self.sends_to(alert, name).then(|| r.report(alert)))
+        })
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        self.remember(alerts);
+        self.reporters.fan_out(|name, r| {
+            let routed = alerts
+                .iter()
+                .filter(|a| self.sends_to(a, name))
+                .cloned()
+                .collect::<Vec<_>>();
+            self.counted(name, (!routed.is_empty()).then(|| r.report_batch(&routed)))
+        })
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        let names = self.reported_to().remove(&alert.fingerprint);
+        self.reporters.fan_out(|name, r| {
+            let routed = match &names {
+                Some(names) => names.contains(name),
+                None => self.is_routed_to(alert, name),
+            };
+            self.counted(name, routed.then(|| r.report_resolved(alert)))
+        })
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.reporters
+            .fan_out(|name, r| self.counted(name, Some(r.flush())))
+    }
+
+    /// Routes each change as its own report or resolution would be. A severity change is reported
+    /// as firing to a reporter that did not receive the alert with its previous severity.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let names = {
+            let mut reported_to = self.reported_to();
+            changes
+                .iter()
+                .map(|change| {
+                    let fingerprint = &change.alert().fingerprint;
+                    match change.firing() {
+                        Some(_) => reported_to.get(fingerprint).cloned(),
+                        None => reported_to.remove(fingerprint),
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let firing = changes
+            .iter()
+            .filter_map(AlertChange::firing)
+            .cloned()
+            .collect::<Vec<_>>();
+        self.remember(&firing);
+        self.reporters.fan_out(|name, r| {
+            let routed = changes
+                .iter()
+                .zip(&names)
+                .filter_map(|(change, names)| {
+                    let was_reported = match names {
+                        Some(names) => names.contains(name),
+                        None => self.is_routed_to(change.alert(), name),
+                    };
+                    match change {
+                        AlertChange::Resolved(_) => was_reported.then(|| change.clone()),
+                        AlertChange::Firing(alert) => {
+                            self.sends_to(alert, name).then(|| change.clone())
+                        }
+                        AlertChange::SeverityChanged { alert, .. } => self
+                            .sends_to(alert, name)
+                            .then(|| match names.is_some() && was_reported {
+                                true => change.clone(),
+                                false => AlertChange::Firing(alert.clone()),
+                            }),
+                    }
+                })
+                .collect::<Vec<_>>();
+            self.counted(name, (!routed.is_empty()).then(|| r.report_diff(&routed)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use std::sync::Arc;
+
+    /// Shares a recording reporter with the routing reporter owning it.
+    struct Shared(Arc<RecordingReporter>);
+
+    impl AlertReporter for Shared {
+        fn report(&self, alert: &ActiveAlert) -> Result<()> {
+            self.0.report(alert)
+        }
+
+        fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+            self.0.report_resolved(alert)
+        }
+    }
+
+    fn alert(fingerprint: &str, severity: Severity) -> ActiveAlert {
+        ActiveAlert {
+            fingerprint: fingerprint.to_string(),
+            ..ActiveAlert::new(format!("{:?} alert", severity), severity)
+        }
+    }
+
+    /// A routing reporter sending critical alerts to PagerDuty and the other ones to Slack.
+    fn routing() -> (
+        RoutingReporter,
+        Arc<RecordingReporter>,
+        Arc<RecordingReporter>,
+    ) {
+        let (pagerduty, slack) = (
+            Arc::new(RecordingReporter::new()),
+            Arc::new(RecordingReporter::new()),
+        );
+        let reporters = CompositeReporter(vec![
+            ("PagerDuty".to_string(), Box::new(Shared(pagerduty.clone()))),
+            ("Slack".to_string(), Box::new(Shared(slack.clone()))),
+        ]);
+        let configuration = serde_json::from_value(serde_json::json!({
+            "routes": [{ "severities": ["Critical"], "reporters": ["PagerDuty"] }],
+            "default_reporters": ["Slack"],
+        }))
+        .unwrap();
+        let routing = RoutingReporter::new(reporters, configuration).unwrap();
+        (routing, pagerduty, slack)
+    }
+
+    fn fingerprints(alerts: Vec<ActiveAlert>) -> Vec<String> {
+        alerts.into_iter().map(|a| a.fingerprint).collect()
+    }
+
+    #[test]
+    fn alerts_go_to_the_reporters_of_their_route() {
+        let (routing, pagerduty, slack) = routing();
+
+        routing.report(&alert("disk", Severity::Critical)).unwrap();
+        routing
+            .report_batch(&[
+                alert("memory", Severity::Warning),
+                alert("load", Severity::Critical),
+            ])
+            .unwrap();
+
+        assert_eq!(fingerprints(pagerduty.alerts()), ["disk", "load"]);
+        assert_eq!(fingerprints(slack.alerts()), ["memory"]);
+    }
+
+    #[test]
+    fn resolutions_go_to_the_reporters_the_alert_was_reported_to() {
+        let (routing, pagerduty, slack) = routing();
+        routing.report(&alert("disk", Severity::Critical)).unwrap();
+
+        // Even with a severity the route to PagerDuty does not match.
+        routing
+            .report_resolved(&alert("disk", Severity::Warning))
+            .unwrap();
+        routing
+            .report_resolved(&alert("memory", Severity::Warning))
+            .unwrap();
+
+        assert_eq!(fingerprints(pagerduty.resolved()), ["disk"]);
+        assert_eq!(fingerprints(slack.resolved()), ["memory"]);
+    }
+
+    #[test]
+    fn routes_to_unknown_reporters_are_rejected() {
+        let configuration = serde_json::from_value(serde_json::json!({
+            "routes": [{ "reporters": ["OpsGenie"] }],
+        }))
+        .unwrap();
+
+        let error = RoutingReporter::new(CompositeReporter(Vec::new()), configuration)
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "Alerts are routed to unknown reporter 'OpsGenie'"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::alert_store::{default_ttl, AlertStore, WatcherSnapshot};
+use crate::duration::deserialize_duration;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// JSON file the active alerts are saved to, created if it does not exist.
+    path: PathBuf,
+    #[serde(default = "default_ttl", deserialize_with = "deserialize_duration")]
+    ttl: Duration,
+}
+
+/// Keeps the active alerts of this process in memory, saving them to a file on each change so
+/// that they are still known after a restart, along with what their watchers remember of them.
+#[derive(Debug)]
+pub struct File {
+    path: PathBuf,
+    ttl: Duration,
+    saved: Mutex<Saved>,
+}
+
+/// Content of the file, with wall-clock times which remain meaningful once saved.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Saved {
+    /// When each active alert expires, by fingerprint.
+    pub expirations: HashMap<String, SystemTime>,
+    /// What each watcher remembers of its alerts, by watcher, along with when it expires.
+    pub watchers: HashMap<String, (SystemTime, WatcherSnapshot)>,
+}
+
+impl Saved {
+    /// Forgets the alerts and watchers that expired by `now`.
+    fn forget_expired(&mut self, now: SystemTime) {
+        self.expirations.retain(|_, expiration| *expiration > now);
+        self.watchers.retain(|_, (expiration, _)| *expiration > now);
+    }
+}
+
+fn seconds(time: &SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn time(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedWatcher {
+    expiration: u64,
+    state: WatcherSnapshot,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedFile {
+    /// Files written before the watchers were saved, with only the expirations.
+    Expirations(HashMap<String, u64>),
+    Saved {
+        expirations: HashMap<String, u64>,
+        #[serde(default)]
+        watchers: HashMap<String, SavedWatcher>,
+    },
+}
+
+/// Reads what was written by [`save`], nothing if the file does not exist yet.
+pub fn load(path: &Path) -> Result<Saved> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Saved::default()),
+        Err(e) => {
+            return Err(e).context(format!(
+                "Could not read alert store at '{}'",
+                path.display()
+            ))
+        }
+    };
+    let saved: SavedFile = serde_json::from_str(&content).context(format!(
+        "Could not parse alert store at '{}'",
+        path.display()
+    ))?;
+    let (expirations, watchers) = match saved {
+        SavedFile::Expirations(expirations) => (expirations, HashMap::new()),
+        SavedFile::Saved {
+            expirations,
+            watchers,
+        } => (expirations, watchers),
+    };
+    Ok(Saved {
+        expirations: expirations
+            .into_iter()
+            .map(|(fingerprint, expiration)| (fingerprint, time(expiration)))
+            .collect(),
+        watchers: watchers
+            .into_iter()
+            .map(|(watcher, saved)| (watcher, (time(saved.expiration), saved.state)))
+            .collect(),
+    })
+}
+
+/// Writes the expirations, as seconds since the epoch by fingerprint, and the watchers. They are
+/// written to a temporary file renamed over the previous one, so that a crash never leaves a
+/// truncated file.
+pub fn save(path: &Path, saved: &Saved) -> Result<()> {
+    let expirations = saved
+        .expirations
+        .iter()
+        .map(|(fingerprint, expiration)| (fingerprint.as_str(), seconds(expiration)))
+        .collect::<BTreeMap<_, _>>();
+    let watchers = saved
+        .watchers
+        .iter()
+        .map(|(watcher, (expiration, state))| {
+            let saved = SavedWatcher {
+                expiration: seconds(expiration),
+                state: state.clone(),
+            };
+            (watcher.as_str(), saved)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let content = serde_json::to_vec(&serde_json::json!({
+        "expirations": expirations,
+        "watchers": watchers,
+    }))?;
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, content).context(format!(
+        "Could not write alert store to '{}'",
+        Path::new(&temporary).display()
+    ))?;
+    std::fs::rename(&temporary, path).context(format!(
+        "Could not replace alert store at '{}'",
+        path.display()
+    ))
+}
+
+impl File {
+    /// Loads the alerts saved by a previous process. A file that can not be read leaves no
+    /// alert active, and is overwritten on the next change.
+    pub fn new(configuration: Configuration) -> Self {
+        let saved = load(&configuration.path).unwrap_or_else(|e| {
+            warn!(alert_store = ?e, "Starting with no active alerts");
+            Saved::default()
+        });
+        File {
+            path: configuration.path,
+            ttl: configuration.ttl,
+            saved: Mutex::new(saved),
+        }
+    }
+
+    fn saved(&self) -> Result<MutexGuard<'_, Saved>> {
+        self.saved
+            .lock()
+            .map_err(|_| anyhow!("Alert store lock is poisoned"))
+    }
+}
+
+impl AlertStore for File {
+    fn mark_active(&self, fingerprint: &str) -> Result<()> {
+        let now = SystemTime::now();
+        let mut saved = self.saved()?;
+        saved.forget_expired(now);
+        saved
+            .expirations
+            .insert(fingerprint.to_string(), now + self.ttl);
+        save(&self.path, &saved)
+    }
+
+    fn is_active(&self, fingerprint: &str) -> Result<bool> {
+        Ok(self
+            .saved()?
+            .expirations
+            .get(fingerprint)
+            .is_some_and(|expiration| *expiration > SystemTime::now()))
+    }
+
+    fn expire(&self, fingerprint: &str) -> Result<()> {
+        let mut saved = self.saved()?;
+        if saved.expirations.remove(fingerprint).is_some() {
+            save(&self.path, &saved)?;
+        }
+        Ok(())
+    }
+
+    fn save_state(&self, watcher: &str, state: &WatcherSnapshot) -> Result<()> {
+        let now = SystemTime::now();
+        let mut saved = self.saved()?;
+        saved.forget_expired(now);
+        saved
+            .watchers
+            .insert(watcher.to_string(), (now + self.ttl, state.clone()));
+        save(&self.path, &saved)
+    }
+
+    fn load_state(&self, watcher: &str) -> Result<Option<WatcherSnapshot>> {
+        let now = SystemTime::now();
+        Ok(self
+            .saved()?
+            .watchers
+            .get(watcher)
+            .filter(|(expiration, _)| *expiration > now)
+            .map(|(_, state)| state.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_store::{SavedFiring, SavedPending};
+    use crate::watcher::{ActiveAlert, Severity};
+
+    /// A file of its own for a test, removed first.
+    fn path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "monitrust-alert-store-{}-{}.json",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn saved_watchers_load_back() {
+        let path = path("round-trip");
+        let at = time(1_700_000_000);
+        let mut alert = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+        alert.fingerprint = "disk_space:0123456789abcdef:/".to_string();
+        alert.detected_at = at;
+        let state = WatcherSnapshot {
+            firing: vec![SavedFiring {
+                alert: 0,
+                subject: "/".to_string(),
+                active: alert,
+                last_reported: at,
+                since: at - Duration::from_secs(600),
+                escalated: 1,
+            }],
+            pending: vec![SavedPending {
+                alert: 1,
+                subject: "/data".to_string(),
+                since: at,
+            }],
+        };
+        let saved = Saved {
+            expirations: HashMap::from([("disk_space:0123456789abcdef:/".to_string(), at)]),
+            watchers: HashMap::from([("disk_space:fedcba9876543210".to_string(), (at, state))]),
+        };
+
+        save(&path, &saved).unwrap();
+
+        assert_eq!(load(&path).unwrap(), saved);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn files_with_only_expirations_still_load() {
+        let path = path("expirations-only");
+        std::fs::write(&path, r#"{"cpu:0123456789abcdef": 1700000000}"#).unwrap();
+
+        let saved = load(&path).unwrap();
+
+        assert_eq!(
+            saved.expirations,
+            HashMap::from([("cpu:0123456789abcdef".to_string(), time(1_700_000_000))])
+        );
+        assert!(saved.watchers.is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::alert_store::{default_ttl, AlertStore};
+use crate::duration::deserialize_duration;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    #[serde(default = "default_ttl", deserialize_with = "deserialize_duration")]
+    ttl: Duration,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration { ttl: default_ttl() }
+    }
+}
+
+/// Keeps the active alerts of this process only.
+#[derive(Debug)]
+pub struct Memory {
+    ttl: Duration,
+    /// When each active alert expires.
+    expirations: Mutex<HashMap<String, Instant>>,
+}
+
+impl Memory {
+    pub fn new(configuration: Configuration) -> Self {
+        Memory {
+            ttl: configuration.ttl,
+            expirations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn expirations(&self) -> Result<MutexGuard<'_, HashMap<String, Instant>>> {
+        self.expirations
+            .lock()
+            .map_err(|_| anyhow!("Alert store lock is poisoned"))
+    }
+}
+
+impl AlertStore for Memory {
+    fn mark_active(&self, fingerprint: &str) -> Result<()> {
+        let now = Instant::now();
+        let mut expirations = self.expirations()?;
+        // Expired alerts are only dropped here, before they could pile up.
+        expirations.retain(|_, expiration| *expiration > now);
+        expirations.insert(fingerprint.to_string(), now + self.ttl);
+        Ok(())
+    }
+
+    fn is_active(&self, fingerprint: &str) -> Result<bool> {
+        Ok(self
+            .expirations()?
+            .get(fingerprint)
+            .is_some_and(|expiration| *expiration > Instant::now()))
+    }
+
+    fn expire(&self, fingerprint: &str) -> Result<()> {
+        self.expirations()?.remove(fingerprint);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(ttl: Duration) -> Memory {
+        Memory::new(Configuration { ttl })
+    }
+
+    #[test]
+    fn alerts_expire_after_their_ttl() {
+        let store = memory(Duration::from_millis(50));
+
+        store.mark_active("disk_space:/").unwrap();
+        assert!(store.is_active("disk_space:/").unwrap());
+        assert!(!store.is_active("disk_space:/var").unwrap());
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert!(!store.is_active("disk_space:/").unwrap());
+    }
+
+    #[test]
+    fn marking_again_renews_the_ttl() {
+        let store = memory(Duration::from_millis(200));
+
+        store.mark_active("disk_space:/").unwrap();
+        std::thread::sleep(Duration::from_millis(120));
+        store.mark_active("disk_space:/").unwrap();
+        std::thread::sleep(Duration::from_millis(120));
+
+        assert!(store.is_active("disk_space:/").unwrap());
+    }
+
+    #[test]
+    fn expired_alerts_are_forgotten() {
+        let store = memory(Duration::from_secs(60));
+
+        store.mark_active("disk_space:/").unwrap();
+        store.expire("disk_space:/").unwrap();
+
+        assert!(!store.is_active("disk_space:/").unwrap());
+    }
+}
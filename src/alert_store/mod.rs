@@ -0,0 +1,105 @@
+//! Remembers which alerts are active, so that an alert already reported is not reported again
+//! by a watcher that starts anew, e.g. after a restart or a reload. Backed by a file, the alerts
+//! survive restarts, along with what the watchers remember of them; backed by Redis, they are
+//! also shared by every Monitrust process using the same server.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::watcher::ActiveAlert;
+
+use file::File;
+use memory::Memory;
+use redis::Redis;
+
+pub mod file;
+pub mod memory;
+pub mod redis;
+
+/// Active alerts, by fingerprint. Each one expires after a time to live unless it is marked
+/// active again, so that alerts of watchers that went away do not stay active forever.
+pub trait AlertStore: Send + Sync {
+    /// Marks the alert active, for another time to live.
+    fn mark_active(&self, fingerprint: &str) -> Result<()>;
+    fn is_active(&self, fingerprint: &str) -> Result<bool>;
+    /// Forgets the alert, once it resolved.
+    fn expire(&self, fingerprint: &str) -> Result<()>;
+    /// Saves what the watcher identified by `watcher` remembers of its alerts, for another time
+    /// to live. Stores that do not survive the process save nothing.
+    fn save_state(&self, _watcher: &str, _state: &WatcherSnapshot) -> Result<()> {
+        Ok(())
+    }
+    /// What was last saved for the watcher, if it did not expire.
+    fn load_state(&self, _watcher: &str) -> Result<Option<WatcherSnapshot>> {
+        Ok(None)
+    }
+}
+
+/// What a watcher remembers of its alerts, so that a watcher started anew with the same
+/// configuration picks up where the previous one left off: alerts that cleared in the meantime
+/// get resolved, and cooldowns and `for_duration` timers keep running. Times are wall-clock
+/// times, which remain meaningful once saved.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct WatcherSnapshot {
+    pub firing: Vec<SavedFiring>,
+    pub pending: Vec<SavedPending>,
+}
+
+/// An alert firing for a subject.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedFiring {
+    /// Position of the alert in the configuration of the watcher.
+    pub alert: usize,
+    pub subject: String,
+    /// What the alert triggered when it last ran.
+    pub active: ActiveAlert,
+    /// When the alert was last reported, for its cooldown.
+    pub last_reported: SystemTime,
+    /// When the alert started firing, for its escalation.
+    pub since: SystemTime,
+    /// Escalation tiers the alert went through.
+    pub escalated: usize,
+}
+
+/// An alert triggering for a subject, that did not trigger for its `for_duration` yet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedPending {
+    /// Position of the alert in the configuration of the watcher.
+    pub alert: usize,
+    pub subject: String,
+    /// Since when the alert has been triggering.
+    pub since: SystemTime,
+}
+
+/// Default time to live of the alerts, which are marked active again each time their watcher
+/// runs. It must be longer than the period of the watchers.
+fn default_ttl() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Configuration {
+    /// Alerts are kept in memory, and forgotten on restart.
+    Memory(memory::Configuration),
+    /// Alerts are kept in memory, and saved to a file so that they are known again on restart.
+    File(file::Configuration),
+    Redis(redis::Configuration),
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration::Memory(memory::Configuration::default())
+    }
+}
+
+impl Configuration {
+    pub fn into_store(self) -> Box<dyn AlertStore> {
+        match self {
+            Configuration::Memory(configuration) => Box::new(Memory::new(configuration)),
+            Configuration::File(configuration) => Box::new(File::new(configuration)),
+            Configuration::Redis(configuration) => Box::new(Redis::new(configuration)),
+        }
+    }
+}
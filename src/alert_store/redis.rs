@@ -0,0 +1,172 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::alert_store::{default_ttl, AlertStore};
+use crate::duration::deserialize_duration;
+use crate::secret::Secret;
+use crate::watcher::hostname;
+
+fn default_key_prefix() -> String {
+    "monitrust:alert:".to_string()
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(2)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// Address of the Redis server, such as `127.0.0.1:6379`.
+    address: String,
+    #[serde(default)]
+    password: Option<Secret>,
+    /// Prepended to the hostname and the fingerprint, to name the key of each alert.
+    #[serde(default = "default_key_prefix")]
+    key_prefix: String,
+    #[serde(default = "default_ttl", deserialize_with = "deserialize_duration")]
+    ttl: Duration,
+    /// How long to wait for the server to connect or to answer.
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+/// Replies of the Redis protocol (RESP) to the commands of the store.
+#[derive(Debug)]
+enum Reply {
+    Status,
+    Integer(i64),
+}
+
+/// Keeps the active alerts as keys expiring along with them, in a Redis server that may be
+/// shared by several Monitrust processes.
+#[derive(Debug)]
+pub struct Redis {
+    configuration: Configuration,
+    /// Fingerprints only identify alerts on a given host.
+    hostname: String,
+    /// Connection to the server, opened on the first command and again after a failure.
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+fn read_line(connection: &mut BufReader<TcpStream>) -> Result<String> {
+    let mut line = String::new();
+    connection
+        .read_line(&mut line)
+        .context("Could not read Redis reply")?;
+    match line.strip_suffix("\r\n") {
+        Some(line) => Ok(line.to_string()),
+        None => Err(anyhow!("Connection to Redis closed")),
+    }
+}
+
+fn read_reply(connection: &mut BufReader<TcpStream>) -> Result<Reply> {
+    let line = read_line(connection)?;
+    let (kind, value) = line.split_at(line.len().min(1));
+    match kind {
+        "+" => Ok(Reply::Status),
+        "-" => Err(anyhow!("Redis error: {}", value)),
+        ":" => value
+            .parse()
+            .map(Reply::Integer)
+            .with_context(|| format!("Invalid Redis integer: {}", value)),
+        _ => Err(anyhow!("Unexpected Redis reply: {}", line)),
+    }
+}
+
+impl Redis {
+    pub fn new(configuration: Configuration) -> Self {
+        Redis {
+            configuration,
+            hostname: hostname(),
+            connection: Mutex::new(None),
+        }
+    }
+
+    fn connect(&self) -> Result<BufReader<TcpStream>> {
+        let address = self
+            .configuration
+            .address
+            .to_socket_addrs()
+            .with_context(|| {
+                format!(
+                    "Could not resolve Redis address: {}",
+                    self.configuration.address
+                )
+            })?
+            .next()
+            .ok_or_else(|| anyhow!("Redis address resolved to no address"))?;
+        let stream = TcpStream::connect_timeout(&address, self.configuration.timeout)
+            .with_context(|| format!("Could not connect to Redis at {}", address))?;
+        stream.set_read_timeout(Some(self.configuration.timeout))?;
+        stream.set_write_timeout(Some(self.configuration.timeout))?;
+        let mut connection = BufReader::new(stream);
+        if let Some(password) = &self.configuration.password {
+            send(&mut connection, &["AUTH", password.expose()])?;
+        }
+        Ok(connection)
+    }
+
+    /// Sends a command, connecting first if needed. The connection is dropped after a failure,
+    /// as it may have been left in the middle of a reply.
+    fn command(&self, arguments: &[&str]) -> Result<Reply> {
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|_| anyhow!("Alert store lock is poisoned"))?;
+        let mut open = match connection.take() {
+            Some(open) => open,
+            None => self.connect()?,
+        };
+        let reply = send(&mut open, arguments)?;
+        *connection = Some(open);
+        Ok(reply)
+    }
+
+    fn key(&self, fingerprint: &str) -> String {
+        format!(
+            "{}{}:{}",
+            self.configuration.key_prefix, self.hostname, fingerprint
+        )
+    }
+}
+
+fn send(connection: &mut BufReader<TcpStream>, arguments: &[&str]) -> Result<Reply> {
+    let mut request = format!("*{}\r\n", arguments.len());
+    for argument in arguments {
+        request.push_str(&format!("${}\r\n{}\r\n", argument.len(), argument));
+    }
+    connection
+        .get_mut()
+        .write_all(request.as_bytes())
+        .context("Could not send Redis command")?;
+    read_reply(connection)
+}
+
+impl AlertStore for Redis {
+    fn mark_active(&self, fingerprint: &str) -> Result<()> {
+        let ttl = self.configuration.ttl.as_millis().max(1).to_string();
+        match self.command(&["SET", &self.key(fingerprint), "1", "PX", &ttl])? {
+            Reply::Status => Ok(()),
+            reply => Err(anyhow!("Unexpected Redis reply to SET: {:?}", reply)),
+        }
+    }
+
+    fn is_active(&self, fingerprint: &str) -> Result<bool> {
+        match self.command(&["EXISTS", &self.key(fingerprint)])? {
+            Reply::Integer(count) => Ok(count > 0),
+            reply => Err(anyhow!("Unexpected Redis reply to EXISTS: {:?}", reply)),
+        }
+    }
+
+    fn expire(&self, fingerprint: &str) -> Result<()> {
+        match self.command(&["DEL", &self.key(fingerprint)])? {
+            Reply::Integer(_) => Ok(()),
+            reply => Err(anyhow!("Unexpected Redis reply to DEL: {:?}", reply)),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Where watchers read the time from, so that the timing of alerts (cooldowns, `for_duration`,
+//! flap detection, escalations) can be driven by hand, see `test_util::FakeClock`.
+
+use std::fmt::Debug;
+use std::time::{Instant, SystemTime};
+
+pub trait Clock: Debug + Send + Sync {
+    /// Monotonic time, which durations between runs are measured with.
+    fn now(&self) -> Instant;
+    /// Wall-clock time, which alerts are dated with.
+    fn system_now(&self) -> SystemTime;
+}
+
+/// The actual time, as used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -0,0 +1,368 @@
+//! Composite alerts, which fire while a combination of other alerts fire, such as high memory
+//! usage along with high swap usage, when neither alone is worth alerting about.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::watcher::{hostname, ActiveAlert, Severity};
+
+/// Condition on the alerts firing, from any watcher.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// Holds while an alert with all of these labels fires.
+    Firing(BTreeMap<String, String>),
+    /// Holds while every condition holds.
+    All(Vec<Condition>),
+    /// Holds while any of the conditions holds.
+    Any(Vec<Condition>),
+    /// Holds while the condition does not.
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    fn holds(&self, firing: &BTreeMap<String, ActiveAlert>) -> bool {
+        match self {
+            Condition::Firing(labels) => firing.values().any(|a| matches(labels, a)),
+            Condition::All(conditions) => conditions.iter().all(|c| c.holds(firing)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.holds(firing)),
+            Condition::Not(condition) => !condition.holds(firing),
+        }
+    }
+
+    /// Alerts matched by the `Firing` conditions, which the composite alert is made of, in the
+    /// order of the conditions.
+    fn matched<'a>(
+        &self,
+        firing: &'a BTreeMap<String, ActiveAlert>,
+        matched: &mut Vec<&'a ActiveAlert>,
+    ) {
+        match self {
+            Condition::Firing(labels) => {
+                for alert in firing.values().filter(|a| matches(labels, a)) {
+                    if !matched.iter().any(|m| m.fingerprint == alert.fingerprint) {
+                        matched.push(alert);
+                    }
+                }
+            }
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().for_each(|c| c.matched(firing, matched))
+            }
+            Condition::Not(_) => {}
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Condition::Firing(labels) if labels.is_empty() => Err(anyhow!(
+                "'Firing' needs at least one label, it would match any alert"
+            )),
+            Condition::All(conditions) | Condition::Any(conditions) if conditions.is_empty() => {
+                Err(anyhow!("'All' and 'Any' need at least one condition"))
+            }
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().try_for_each(Condition::validate)
+            }
+            Condition::Not(condition) => condition.validate(),
+            Condition::Firing(_) => Ok(()),
+        }
+    }
+}
+
+fn matches(labels: &BTreeMap<String, String>, alert: &ActiveAlert) -> bool {
+    labels
+        .iter()
+        .all(|(key, value)| alert.labels.get(key) == Some(value))
+}
+
+/// An alert firing while its `condition` holds, e.g. while alerts labelled `high_memory` and
+/// `high_swap` both fire.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CompositeRule {
+    /// Identifies the composite alert, in its fingerprint.
+    name: String,
+    condition: Condition,
+    #[serde(default)]
+    severity: Severity,
+    /// Message of the alert, followed by the messages of the alerts it is made of. Defaults to
+    /// the name of the rule.
+    #[serde(default)]
+    message: Option<String>,
+    /// Labels attached to the alert, for reporters to route it with.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    runbook_url: Option<String>,
+}
+
+impl CompositeRule {
+    fn alert(&self, firing: &BTreeMap<String, ActiveAlert>, hostname: &str) -> ActiveAlert {
+        let mut matched = Vec::new();
+        self.condition.matched(firing, &mut matched);
+        let messages = matched
+            .iter()
+            .map(|a| a.message.as_str())
+            .collect::<Vec<_>>();
+        let message = self.message.as_deref().unwrap_or(&self.name);
+        ActiveAlert {
+            description: self.description.clone(),
+            runbook_url: self.runbook_url.clone(),
+            ..ActiveAlert::new(
+                match messages.is_empty() {
+                    true => format!("🧩 {}", message),
+                    false => format!("🧩 {}: {}", message, messages.join("; ")),
+                },
+                self.severity,
+            )
+            .detected(
+                hostname,
+                SystemTime::now(),
+                &self.labels,
+                "composite",
+                format!("composite:{}", self.name),
+            )
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Configuration {
+    #[serde(default)]
+    rules: Vec<CompositeRule>,
+}
+
+impl Configuration {
+    /// Fails if two rules have the same name, or if a condition can not be meant as written.
+    pub fn validate(&self) -> Result<()> {
+        let mut names = BTreeSet::new();
+        for rule in &self.rules {
+            if !names.insert(&rule.name) {
+                return Err(anyhow!("Composite alert '{}' is defined twice", rule.name));
+            }
+            rule.condition
+                .validate()
+                .map_err(|e| e.context(format!("Composite alert '{}'", rule.name)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Alerts known to a [`Reporter`], by fingerprint.
+#[derive(Default)]
+struct Alerts {
+    /// Alerts that were reported and not resolved yet.
+    firing: BTreeMap<String, ActiveAlert>,
+    /// Composite alerts firing, by rule name.
+    composites: BTreeMap<String, ActiveAlert>,
+}
+
+/// Passes alerts on while keeping track of the ones firing, then evaluates the composite rules
+/// once each scheduler cycle ends, see [`AlertReporter::flush`], so that they see the alerts of
+/// every watcher that ran. Composite alerts are reported when their condition starts holding, and
+/// resolved when it stops.
+pub struct Reporter<'a, R: AlertReporter> {
+    configuration: &'a Configuration,
+    reporter: &'a R,
+    hostname: String,
+    alerts: Mutex<Alerts>,
+}
+
+impl<'a, R: AlertReporter> Reporter<'a, R> {
+    pub fn new(configuration: &'a Configuration, reporter: &'a R) -> Self {
+        Reporter {
+            configuration,
+            reporter,
+            hostname: match configuration.rules.is_empty() {
+                true => String::new(),
+                false => hostname(),
+            },
+            alerts: Mutex::new(Alerts::default()),
+        }
+    }
+
+    fn alerts(&self) -> MutexGuard<'_, Alerts> {
+        // Alerts are only ever inserted and removed, a panic can not leave them inconsistent.
+        self.alerts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Composite alerts that started and stopped firing since the previous evaluation.
+    fn evaluate(&self) -> (Vec<ActiveAlert>, Vec<ActiveAlert>) {
+        let mut known = self.alerts();
+        let mut previous = mem::take(&mut known.composites);
+        let mut fired = Vec::new();
+        for rule in &self.configuration.rules {
+            if !rule.condition.holds(&known.firing) {
+                continue;
+            }
+            let alert = match previous.remove(&rule.name) {
+                Some(alert) => alert,
+                None => {
+                    let alert = rule.alert(&known.firing, &self.hostname);
+                    info!(severity = ?alert.severity, composite_alert = ?alert);
+                    fired.push(alert.clone());
+                    alert
+                }
+            };
+            known.composites.insert(rule.name.clone(), alert);
+        }
+        (fired, previous.into_values().collect())
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for Reporter<'_, R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        if !self.configuration.rules.is_empty() {
+            self.alerts()
+                .firing
+                .insert(alert.fingerprint.clone(), alert.clone());
+        }
+        self.reporter.report(alert)
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        if !self.configuration.rules.is_empty() {
+            let mut known = self.alerts();
+            for alert in alerts {
+                known
+                    .firing
+                    .insert(alert.fingerprint.clone(), alert.clone());
+            }
+        }
+        self.reporter.report_batch(alerts)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.alerts().firing.remove(&alert.fingerprint);
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let (fired, resolved) = self.evaluate();
+        for alert in &resolved {
+            info!(composite_resolved_alert = ?alert);
+            if let Err(e) = self.reporter.report_resolved(alert) {
+                warn!(alert_reporter = ?e);
+            }
+        }
+        if !fired.is_empty() {
+            self.reporter.report_batch(&fired)?;
+        }
+        self.reporter.flush()
+    }
+
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        if !self.configuration.rules.is_empty() {
+            let mut known = self.alerts();
+            for change in changes {
+                match change.firing() {
+                    Some(alert) => known
+                        .firing
+                        .insert(alert.fingerprint.clone(), alert.clone()),
+                    None => known.firing.remove(&change.alert().fingerprint),
+                };
+            }
+        }
+        self.reporter.report_diff(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use serde_json::json;
+
+    fn configuration() -> Configuration {
+        serde_json::from_value(json!({ "rules": [{
+            "name": "memory_pressure",
+            "condition": { "All": [
+                { "Firing": { "alert": "high_memory" } },
+                { "Firing": { "alert": "high_swap" } },
+            ] },
+            "severity": "Critical",
+            "message": "Memory and swap are both running out",
+        }] }))
+        .unwrap()
+    }
+
+    fn alert(name: &str) -> ActiveAlert {
+        ActiveAlert {
+            fingerprint: name.to_string(),
+            labels: BTreeMap::from([("alert".to_string(), name.to_string())]),
+            ..ActiveAlert::new(format!("{} fires", name), Severity::Warning)
+        }
+    }
+
+    fn composites(recorded: Vec<ActiveAlert>) -> Vec<ActiveAlert> {
+        recorded
+            .into_iter()
+            .filter(|a| a.kind == "composite")
+            .collect()
+    }
+
+    #[test]
+    fn fires_only_while_both_alerts_fire() {
+        let configuration = configuration();
+        let recording = RecordingReporter::new();
+        let reporter = Reporter::new(&configuration, &recording);
+
+        reporter.report(&alert("high_memory")).unwrap();
+        reporter.flush().unwrap();
+        assert!(composites(recording.alerts()).is_empty());
+
+        reporter.report(&alert("high_swap")).unwrap();
+        reporter.flush().unwrap();
+        let fired = composites(recording.alerts());
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].severity, Severity::Critical);
+        assert_eq!(fired[0].fingerprint, "composite:memory_pressure");
+        assert_eq!(
+            fired[0].message,
+            "🧩 Memory and swap are both running out: high_memory fires; high_swap fires"
+        );
+
+        // Still firing, it is not reported again.
+        reporter.flush().unwrap();
+        assert_eq!(composites(recording.alerts()).len(), 1);
+
+        reporter.report_resolved(&alert("high_swap")).unwrap();
+        reporter.flush().unwrap();
+        let resolved = composites(recording.resolved());
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].fingerprint, "composite:memory_pressure");
+    }
+
+    #[test]
+    fn conditions_that_cannot_be_meant_are_rejected() {
+        for (rules, problem) in [
+            (
+                json!([{ "name": "any", "condition": { "Firing": {} } }]),
+                "Composite alert 'any': 'Firing' needs at least one label, it would match any alert",
+            ),
+            (
+                json!([{ "name": "none", "condition": { "Not": { "All": [] } } }]),
+                "Composite alert 'none': 'All' and 'Any' need at least one condition",
+            ),
+            (
+                json!([
+                    { "name": "twice", "condition": { "Firing": { "a": "b" } } },
+                    { "name": "twice", "condition": { "Firing": { "c": "d" } } },
+                ]),
+                "Composite alert 'twice' is defined twice",
+            ),
+        ] {
+            let configuration: Configuration =
+                serde_json::from_value(json!({ "rules": rules })).unwrap();
+            let error = configuration.validate().unwrap_err();
+            assert_eq!(format!("{:#}", error), problem);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+
+/// Parses durations such as `"90s"`, `"2m30s"` or `"1h"`. The supported units are `ms`, `s`, `m`,
+/// `h` and `d`; a bare number is a number of seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    if let Ok(seconds) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit_length = rest[digits..]
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len() - digits);
+        let (value, unit) = (&rest[..digits], &rest[digits..digits + unit_length]);
+        let value = value
+            .parse::<u64>()
+            .with_context(|| format!("Invalid duration: '{}'", s))?;
+        let unit_ms = match unit.trim() {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 3600 * 1000,
+            "d" => 86400 * 1000,
+            unit => return Err(anyhow!("Invalid unit '{}' in duration '{}'", unit, s)),
+        };
+        total += Duration::from_millis(value.saturating_mul(unit_ms));
+        rest = &rest[digits + unit_length..];
+    }
+    Ok(total)
+}
+
+/// Formats a duration in the syntax of [`parse_duration`], to the second, such as `"1d2h"`.
+pub fn format_duration(duration: Duration) -> String {
+    let mut seconds = duration.as_secs();
+    if seconds == 0 {
+        return "0s".to_string();
+    }
+    let mut formatted = String::new();
+    for (unit, unit_seconds) in [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)] {
+        if seconds >= unit_seconds {
+            formatted.push_str(&format!("{}{}", seconds / unit_seconds, unit));
+            seconds %= unit_seconds;
+        }
+    }
+    formatted
+}
+
+struct DurationVisitor;
+
+impl Visitor<'_> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a number of seconds or a duration such as \"5m\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+        Ok(Duration::from_secs(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Duration, E> {
+        parse_duration(v).map_err(E::custom)
+    }
+}
+
+/// Deserializes a [`Duration`] from a number of seconds or from a string parsed by
+/// [`parse_duration`].
+pub fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct RawPeriod {
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    period: Option<Duration>,
+    period_minutes: Option<u64>,
+}
+
+/// Deserializes an optional [`Duration`], for fields that also need `#[serde(default)]`.
+pub fn deserialize_optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    deserialize_duration(deserializer).map(Some)
+}
+
+/// Period of a checker, configured either as a `period` duration or, for backward
+/// compatibility, as a number of `period_minutes`. Meant to be flattened into configurations.
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "RawPeriod")]
+#[schemars(with = "RawPeriod")]
+pub struct Period(pub Duration);
+
+impl TryFrom<RawPeriod> for Period {
+    type Error = String;
+
+    fn try_from(value: RawPeriod) -> Result<Self, Self::Error> {
+        let period = match (value.period, value.period_minutes) {
+            (Some(period), None) => period,
+            (None, Some(minutes)) => Duration::from_secs(60 * minutes),
+            (Some(_), Some(_)) => {
+                return Err("only one of 'period' and 'period_minutes' can be set".into())
+            }
+            (None, None) => return Err("missing field 'period'".into()),
+        };
+        if period.is_zero() {
+            return Err("'period' must be longer than zero".into());
+        }
+        Ok(Period(period))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug)]
+    struct Configuration {
+        #[serde(flatten)]
+        period: Period,
+        #[serde(deserialize_with = "deserialize_duration")]
+        timeout: Duration,
+    }
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2m30s").unwrap(), Duration::from_secs(150));
+        assert_eq!(parse_duration("1d1h").unwrap(), Duration::from_secs(90000));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45));
+    }
+
+    #[test]
+    fn fails_on_invalid_durations() {
+        for invalid in ["5w", "m", "1.5h", "-5s"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn formats_what_it_parses() {
+        for duration in ["0s", "45s", "2m30s", "1d2h", "3h5s"] {
+            assert_eq!(format_duration(parse_duration(duration).unwrap()), duration);
+        }
+    }
+
+    #[test]
+    fn deserializes_strings_and_bare_integers() {
+        let configuration: Configuration =
+            serde_json::from_value(json!({ "period": "2m30s", "timeout": 10 })).unwrap();
+        assert_eq!(configuration.period, Period(Duration::from_secs(150)));
+        assert_eq!(configuration.timeout, Duration::from_secs(10));
+
+        let configuration: Configuration =
+            serde_json::from_value(json!({ "period": 90, "timeout": "1m" })).unwrap();
+        assert_eq!(configuration.period, Period(Duration::from_secs(90)));
+        assert_eq!(configuration.timeout, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn periods_can_still_be_set_in_minutes() {
+        let configuration: Configuration =
+            serde_json::from_value(json!({ "period_minutes": 5, "timeout": "5s" })).unwrap();
+        assert_eq!(configuration.period, Period(Duration::from_secs(300)));
+
+        for invalid in [
+            json!({ "period": "5m", "period_minutes": 5, "timeout": "5s" }),
+            json!({ "period": "0s", "timeout": "5s" }),
+            json!({ "timeout": "5s" }),
+        ] {
+            assert!(
+                serde_json::from_value::<Configuration>(invalid.clone()).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Inhibition rules, which keep alerts that follow from another firing alert from being reported,
+//! such as the alerts of the services of a host that is down.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::Result;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::watcher::{ActiveAlert, Severity};
+
+/// Alerts matching `target_match` are not reported while an alert matching `source_match` fires
+/// with the same value for each of the `equal` labels.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InhibitionRule {
+    /// Labels an alert must have to inhibit other alerts.
+    source_match: BTreeMap<String, String>,
+    /// Labels an alert must have to be inhibited.
+    target_match: BTreeMap<String, String>,
+    /// Labels whose values must be the same in both alerts, a label missing from both being the
+    /// same.
+    #[serde(default)]
+    equal: Vec<String>,
+}
+
+fn matches(labels: &BTreeMap<String, String>, alert: &ActiveAlert) -> bool {
+    labels
+        .iter()
+        .all(|(key, value)| alert.labels.get(key) == Some(value))
+}
+
+impl InhibitionRule {
+    fn inhibits(&self, source: &ActiveAlert, target: &ActiveAlert) -> bool {
+        source.fingerprint != target.fingerprint
+            && matches(&self.source_match, source)
+            && matches(&self.target_match, target)
+            && self
+                .equal
+                .iter()
+                .all(|label| source.labels.get(label) == target.labels.get(label))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Configuration {
+    #[serde(default)]
+    rules: Vec<InhibitionRule>,
+}
+
+impl Configuration {
+    /// Whether any rule inhibits `target` while one of the `firing` alerts fires.
+    pub fn inhibits<'a>(
+        &self,
+        mut firing: impl Iterator<Item = &'a ActiveAlert>,
+        target: &ActiveAlert,
+    ) -> bool {
+        firing.any(|source| self.rules.iter().any(|r| r.inhibits(source, target)))
+    }
+}
+
+/// Alerts known to a [`Reporter`], by fingerprint.
+#[derive(Default)]
+struct Alerts {
+    /// Alerts reported during the current cycle.
+    pending: Vec<ActiveAlert>,
+    /// Alerts that were reported and not resolved yet.
+    reported: BTreeMap<String, ActiveAlert>,
+    /// Alerts that fired while inhibited and were not reported.
+    inhibited: BTreeMap<String, ActiveAlert>,
+    /// Alerts of the cycle passed on as a diff, with their previous severity if they were
+    /// already reported with another one.
+    diffed: BTreeMap<String, Option<Severity>>,
+}
+
+/// Holds the alerts reported during a scheduler cycle, then reports the ones no other firing
+/// alert inhibits once the cycle ends, see [`AlertReporter::flush`]. Sources are looked for among
+/// the alerts of the cycle and the ones of earlier cycles still firing, so that an alert that
+/// fired first is inhibited all the same. Inhibited alerts are not resolved either, and get
+/// reported at the end of the first cycle where nothing inhibits them anymore. Without any rule,
+/// alerts are passed on right away.
+pub struct Reporter<'a, R: AlertReporter> {
+    configuration: &'a Configuration,
+    reporter: &'a R,
+    alerts: Mutex<Alerts>,
+}
+
+impl<'a, R: AlertReporter> Reporter<'a, R> {
+    pub fn new(configuration: &'a Configuration, reporter: &'a R) -> Self {
+        Reporter {
+            configuration,
+            reporter,
+            alerts: Mutex::new(Alerts::default()),
+        }
+    }
+
+    fn alerts(&self) -> MutexGuard<'_, Alerts> {
+        // Alerts are only ever moved between collections, a panic can not leave them
+        // inconsistent.
+        self.alerts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Forgets a resolved alert, telling whether its resolution is to be reported: it is not for
+    /// inhibited alerts, nor for the ones that fired and resolved within the cycle.
+    fn resolve(&self, alert: &ActiveAlert) -> bool {
+        let mut known = self.alerts();
+        let pending = known.pending.len();
+        known.pending.retain(|a| a.fingerprint != alert.fingerprint);
+        let was_pending = known.pending.len() < pending;
+        known.diffed.remove(&alert.fingerprint);
+        if known.inhibited.remove(&alert.fingerprint).is_some() {
+            info!(inhibited_resolved_alert = ?alert);
+            return false;
+        }
+        // Otherwise, it fired and resolved within the cycle and was never reported.
+        known.reported.remove(&alert.fingerprint).is_some() || !was_pending
+    }
+
+    /// Moves the inhibited alerts of the cycle aside, returning the ones to report.
+    fn inhibit(&self) -> Vec<ActiveAlert> {
+        let mut known = self.alerts();
+        let mut candidates = mem::take(&mut known.inhibited);
+        let already_inhibited = candidates.keys().cloned().collect::<BTreeSet<_>>();
+        for alert in mem::take(&mut known.pending) {
+            known.reported.remove(&alert.fingerprint);
+            candidates.insert(alert.fingerprint.clone(), alert);
+        }
+        let (inhibited, reported) = candidates
+            .values()
+            .cloned()
+            .partition::<Vec<_>, _>(|target| {
+                self.configuration
+                    .inhibits(known.reported.values().chain(candidates.values()), target)
+            });
+        for alert in inhibited {
+            if !already_inhibited.contains(&alert.fingerprint) {
+                info!(inhibited_alert = ?alert);
+            }
+            known.inhibited.insert(alert.fingerprint.clone(), alert);
+        }
+        for alert in &reported {
+            known
+                .reported
+                .insert(alert.fingerprint.clone(), alert.clone());
+        }
+        reported
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for Reporter<'_, R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        if self.configuration.rules.is_empty() {
+            return self.reporter.report(alert);
+        }
+        self.alerts().pending.push(alert.clone());
+        Ok(())
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        if self.configuration.rules.is_empty() {
+            return self.reporter.report_batch(alerts);
+        }
+        self.alerts().pending.extend_from_slice(alerts);
+        Ok(())
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        if !self.configuration.rules.is_empty() && !self.resolve(alert) {
+            return Ok(());
+        }
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let reported = self.inhibit();
+        let mut diffed = mem::take(&mut self.alerts().diffed);
+        let (changes, reported) = reported.into_iter().fold(
+            (Vec::new(), Vec::new()),
+            |(mut changes, mut reported), alert| {
+                match diffed.remove(&alert.fingerprint) {
+                    Some(Some(previous)) => {
+                        changes.push(AlertChange::SeverityChanged { alert, previous })
+                    }
+                    Some(None) => changes.push(AlertChange::Firing(alert)),
+                    None => reported.push(alert),
+                }
+                (changes, reported)
+            },
+        );
+        if !changes.is_empty() {
+            self.reporter.report_diff(&changes)?;
+        }
+        if !reported.is_empty() {
+            self.reporter.report_batch(&reported)?;
+        }
+        self.reporter.flush()
+    }
+
+    /// Resolutions are passed on right away, as with [`AlertReporter::report_resolved`], and the
+    /// firing alerts at the end of the cycle, as a diff for the ones that are not inhibited.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        if self.configuration.rules.is_empty() {
+            return self.reporter.report_diff(changes);
+        }
+        let mut resolved = Vec::new();
+        for change in changes {
+            let Some(alert) = change.firing() else {
+                if self.resolve(change.alert()) {
+                    resolved.push(change.clone());
+                }
+                continue;
+            };
+            let mut known = self.alerts();
+            // Only a change from what was reported, not from an inhibited alert.
+            let previous = match change {
+                AlertChange::SeverityChanged { previous, .. }
+                    if known.reported.contains_key(&alert.fingerprint) =>
+                {
+                    Some(*previous)
+                }
+                _ => None,
+            };
+            known.diffed.insert(alert.fingerprint.clone(), previous);
+            known.pending.push(alert.clone());
+        }
+        if resolved.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_diff(&resolved)
+    }
+}
+
+impl<R: AlertReporter> Drop for Reporter<'_, R> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!(reporter_error = ?e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use serde_json::json;
+
+    fn configuration() -> Configuration {
+        serde_json::from_value(json!({ "rules": [{
+            "source_match": { "alert": "host_down" },
+            "target_match": { "team": "services" },
+            "equal": ["host"],
+        }] }))
+        .unwrap()
+    }
+
+    fn alert(fingerprint: &str, labels: &[(&str, &str)]) -> ActiveAlert {
+        ActiveAlert {
+            fingerprint: fingerprint.to_string(),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..ActiveAlert::new(fingerprint.to_string(), Severity::Warning)
+        }
+    }
+
+    fn fingerprints(alerts: Vec<ActiveAlert>) -> Vec<String> {
+        alerts.into_iter().map(|a| a.fingerprint).collect()
+    }
+
+    #[test]
+    fn a_firing_source_inhibits_the_targets_sharing_its_labels() {
+        let configuration = configuration();
+        let recording = RecordingReporter::new();
+        let reporter = Reporter::new(&configuration, &recording);
+
+        let down = alert("down", &[("alert", "host_down"), ("host", "web-1")]);
+        let same_host = alert("api", &[("team", "services"), ("host", "web-1")]);
+        let other_host = alert("db", &[("team", "services"), ("host", "web-2")]);
+        let other_team = alert("disk", &[("team", "ops"), ("host", "web-1")]);
+        reporter
+            .report_batch(&[same_host.clone(), other_host, other_team])
+            .unwrap();
+        reporter.report(&down).unwrap();
+        assert!(recording.alerts().is_empty());
+        reporter.flush().unwrap();
+
+        assert_eq!(fingerprints(recording.alerts()), vec!["db", "disk", "down"]);
+
+        // Inhibited alerts resolve silently.
+        reporter.report_resolved(&same_host).unwrap();
+        assert!(recording.resolved().is_empty());
+    }
+
+    #[test]
+    fn inhibited_alerts_are_reported_once_the_source_resolves() {
+        let configuration = configuration();
+        let recording = RecordingReporter::new();
+        let reporter = Reporter::new(&configuration, &recording);
+
+        let down = alert("down", &[("alert", "host_down"), ("host", "web-1")]);
+        let api = alert("api", &[("team", "services"), ("host", "web-1")]);
+        reporter.report_batch(&[down.clone(), api.clone()]).unwrap();
+        reporter.flush().unwrap();
+        assert_eq!(fingerprints(recording.alerts()), vec!["down"]);
+
+        reporter.report_resolved(&down).unwrap();
+        reporter.report(&api).unwrap();
+        reporter.flush().unwrap();
+
+        assert_eq!(fingerprints(recording.resolved()), vec!["down"]);
+        assert_eq!(fingerprints(recording.alerts()), vec!["down", "api"]);
+    }
+}
@@ -0,0 +1,36 @@
+//! Monitrust watches the host it runs on and reports alerts, configured by `watchers.json` and
+//! `reporters.json` or built in code by programs embedding it, see the [`prelude`].
+
+pub mod alert_reporter;
+pub mod alert_store;
+pub mod clock;
+pub mod composite;
+pub mod duration;
+pub mod inhibition;
+#[cfg(feature = "logging")]
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod mute;
+pub mod observer;
+pub mod once;
+pub mod pattern;
+pub mod prelude;
+pub mod reload;
+#[cfg(feature = "http")]
+pub mod remote_config;
+mod scheduled_watcher;
+pub mod scheduler;
+pub mod schema;
+pub mod secret;
+pub mod severity_override;
+pub mod size;
+mod stable_hash;
+pub mod startup_grace;
+pub mod status;
+pub mod templating;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod validation;
+pub mod watcher;
+pub mod watcher_set;
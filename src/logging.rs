@@ -0,0 +1,60 @@
+//! Sets up where the logs of Monitrust go, for the binary and for programs embedding it that do
+//! not install a `tracing` subscriber of their own.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Lines meant to be read by people.
+    #[default]
+    Human,
+    /// A JSON object per line, for log aggregation.
+    Json,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Configuration {
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Level of the logs kept, or directives such as `monitrust=debug`, `info` by default.
+    /// `RUST_LOG` overrides it.
+    #[serde(default)]
+    pub level: Option<String>,
+}
+
+/// Logs to the standard output in `format`, keeping the logs of `level` and above, unless
+/// `RUST_LOG` is set. Returns whether the logs were set up: they are left alone if a subscriber
+/// is already installed, such as the one of an embedding program.
+pub fn init_logging(format: LogFormat, level: Option<&str>) -> Result<bool> {
+    init_logging_to(format, level, std::io::stdout)
+}
+
+/// Logs to `writer` rather than to the standard output, see [`init_logging`].
+pub fn init_logging_to<W>(format: LogFormat, level: Option<&str>, writer: W) -> Result<bool>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    if tracing::dispatcher::has_been_set() {
+        return Ok(false);
+    }
+    let filter = match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) => EnvFilter::try_new(&directives)
+            .with_context(|| format!("Invalid {}: {}", EnvFilter::DEFAULT_ENV, directives))?,
+        Err(_) => {
+            let level = level.unwrap_or("info");
+            EnvFilter::try_new(level).with_context(|| format!("Invalid log level: {}", level))?
+        }
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    let installed = match format {
+        LogFormat::Human => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    // Another subscriber was installed in the meantime.
+    Ok(installed.is_ok())
+}
@@ -1,86 +1,267 @@
-#![feature(thread_sleep_until)]
-
-use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashSet};
 use std::fs::File;
-use std::io::BufReader;
-use std::thread::sleep_until;
-use std::time::Instant;
+use std::io::{BufReader, ErrorKind};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 use anyhow::Context;
 use serde::Deserialize;
 use serde::Serialize;
-use tracing::error;
 
-use crate::alert_reporter::{AlertTargetConfiguration, MultiReporter, Reporter};
-use crate::scheduled_watcher::ScheduledWatcher;
-use crate::watcher::{Watcher, WatcherConfiguration, WatcherEnum};
+use monitrust::alert_reporter::routing::{RoutingConfiguration, RoutingReporter};
+use monitrust::alert_reporter::{
+    self, format, AlertReporter, AlertTargetConfiguration, CompositeReporter,
+};
+use monitrust::alert_store;
+use monitrust::composite;
+use monitrust::inhibition;
+#[cfg(feature = "logging")]
+use monitrust::logging;
+use monitrust::maintenance;
+#[cfg(feature = "otlp")]
+use monitrust::metrics::otlp;
+use monitrust::metrics::prometheus;
+use monitrust::mute;
+use monitrust::once;
+use monitrust::reload;
+#[cfg(feature = "http")]
+use monitrust::remote_config;
+#[cfg(feature = "nix")]
+use monitrust::scheduler::SchedulerHandle;
+use monitrust::scheduler::{self, Scheduler};
+use monitrust::schema;
+use monitrust::severity_override;
+use monitrust::status;
+use monitrust::validation;
 
-#[serde_with::serde_as]
-#[derive(Deserialize)]
-struct WatcherConfigurations(#[serde_as(as = "serde_with::EnumMap")] Vec<WatcherConfiguration>);
+/// Global settings, read from an optional `settings.json` file.
+#[derive(Deserialize, Default)]
+struct Settings {
+    /// Serve the latest measurements as Prometheus metrics.
+    #[serde(default)]
+    prometheus: Option<prometheus::Configuration>,
+    /// Push the measurements of each check to an OpenTelemetry collector.
+    #[cfg(feature = "otlp")]
+    #[serde(default)]
+    otlp: Option<otlp::Configuration>,
+    #[serde(default)]
+    scheduler: scheduler::Configuration,
+    /// Reload `watchers.json` when it changes.
+    #[serde(default)]
+    reload: Option<reload::Configuration>,
+    /// Headers, timeout and cache of the watcher configurations given as a URL.
+    #[cfg(feature = "http")]
+    #[serde(default)]
+    remote_config: remote_config::Configuration,
+    /// Serve the status of the watchers, and whether Monitrust is still running.
+    #[serde(default)]
+    status: Option<status::server::Configuration>,
+    /// Windows during which alerts are not reported, such as planned maintenance.
+    #[serde(default)]
+    maintenance: maintenance::Configuration,
+    /// Rules keeping known noisy alerts from being reported, possibly until they expire.
+    #[serde(default)]
+    mute: mute::Configuration,
+    /// Rules keeping alerts from being reported while another alert fires.
+    #[serde(default)]
+    inhibition: inhibition::Configuration,
+    /// Rules remapping the severity of alerts, e.g. by environment.
+    #[serde(default)]
+    severity_override: severity_override::Configuration,
+    /// Prefixes of the messages written as plain text, by severity.
+    #[serde(default)]
+    alert_format: format::Configuration,
+    /// Alerts firing while a combination of other alerts fire.
+    #[serde(default)]
+    composite: composite::Configuration,
+    /// Where active alerts are remembered, in memory by default.
+    #[serde(default)]
+    alert_store: alert_store::Configuration,
+    /// Exit codes of `--once`.
+    #[serde(default)]
+    once: once::Configuration,
+    /// Which reporters receive which alerts, every reporter all of them by default.
+    #[serde(default)]
+    routing: RoutingConfiguration,
+    /// Format and level of the logs.
+    #[cfg(feature = "logging")]
+    #[serde(default)]
+    logging: logging::Configuration,
+}
 
 #[serde_with::serde_as]
 #[derive(Serialize, Deserialize)]
 struct TargetConfigurations(#[serde_as(as = "serde_with::EnumMap")] Vec<AlertTargetConfiguration>);
 
-mod alert_reporter;
-mod scheduled_watcher;
-mod watcher;
+/// Shuts the scheduler down on SIGINT or SIGTERM. Must be called before any other thread is
+/// spawned, so that they all inherit the signal mask and the signals get delivered here.
+#[cfg(feature = "nix")]
+fn shutdown_on_signals(shutdown: SchedulerHandle) -> Result<(), anyhow::Error> {
+    use nix::sys::signal::{SigSet, Signal};
+    use tracing::{info, warn};
+
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    signals
+        .thread_block()
+        .context("Could not block shutdown signals")?;
+    std::thread::spawn(move || match signals.wait() {
+        Ok(signal) => {
+            info!(received_signal = ?signal);
+            shutdown.shutdown();
+        }
+        Err(e) => warn!(signal_error = ?e),
+    });
+    Ok(())
+}
+
+/// Watcher configuration files or directories given with `--config`, which can be repeated, or
+/// `watchers.json` by default.
+fn watcher_paths() -> Vec<PathBuf> {
+    let args = std::env::args().collect::<Vec<_>>();
+    let paths = args
+        .windows(2)
+        .filter(|pair| pair[0] == "--config")
+        .map(|pair| PathBuf::from(&pair[1]))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        vec![PathBuf::from("watchers.json")]
+    } else {
+        paths
+    }
+}
 
-fn main() -> Result<(), anyhow::Error> {
-    tracing_subscriber::fmt::init();
+/// Value of the last occurrence of a command line flag, such as `--report`.
+fn flag(name: &str) -> Option<String> {
+    let args = std::env::args().collect::<Vec<_>>();
+    args.windows(2)
+        .rev()
+        .find(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
+}
 
-    let watchers = {
-        let watchers_file = "watchers.json";
-        let file = File::open(watchers_file)
-            .with_context(|| format!("Could not open file: {}", watchers_file))?;
-        let buf_reader = BufReader::new(file);
-        let configurations: WatcherConfigurations = serde_json::from_reader(buf_reader)?;
+fn read_reporters(routing: &RoutingConfiguration) -> Result<RoutingReporter, anyhow::Error> {
+    let reporter_file = "reporters.json";
+    let file = File::open(reporter_file)
+        .with_context(|| format!("Could not open file: {}", reporter_file))?;
+    let buf_reader = BufReader::new(file);
+    let configurations: TargetConfigurations = serde_json::from_reader(buf_reader)?;
+    let reporters = CompositeReporter(
         configurations
             .0
             .into_iter()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .map(Into::<WatcherEnum>::into)
-            .collect::<Vec<_>>()
-    };
+            .map(|c| Ok((c.name(), c.into_reporter()?)))
+            .collect::<Result<_, anyhow::Error>>()?,
+    );
+    RoutingReporter::new(reporters, routing.clone()).context("Invalid routing")
+}
 
-    let reporters = {
-        let reporter_file = "reporters.json";
-        let file = File::open(reporter_file)
-            .with_context(|| format!("Could not open file: {}", reporter_file))?;
-        let buf_reader = BufReader::new(file);
-        let configurations: TargetConfigurations = serde_json::from_reader(buf_reader)?;
-        MultiReporter(
-            configurations
-                .0
-                .into_iter()
-                .map(Into::<Reporter>::into)
-                .collect(),
-        )
+fn main() -> Result<ExitCode, anyhow::Error> {
+    // Only print the JSON Schema of watcher files, which needs no configuration.
+    if std::env::args().any(|arg| arg == "--schema") {
+        println!("{:#}", schema::config_schema());
+        return Ok(ExitCode::SUCCESS);
+    }
+    let settings: Settings = {
+        let settings_file = "settings.json";
+        match File::open(settings_file) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file))
+                .with_context(|| format!("Could not parse file: {}", settings_file))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Could not open file: {}", settings_file))
+            }
+        }
     };
+    #[cfg(feature = "logging")]
+    logging::init_logging(settings.logging.format, settings.logging.level.as_deref())
+        .context("Invalid logging")?;
+    settings
+        .composite
+        .validate()
+        .context("Invalid composite alerts")?;
+    settings
+        .severity_override
+        .validate()
+        .context("Invalid severity overrides")?;
+    format::init(settings.alert_format);
+    severity_override::init(settings.severity_override);
+    #[cfg(feature = "http")]
+    remote_config::init(settings.remote_config);
 
-    let mut timers = BinaryHeap::new();
+    let watcher_paths = watcher_paths();
+    // Only check the configuration, e.g. in CI before deploying it.
+    if std::env::args().any(|arg| arg == "--check") {
+        for path in &watcher_paths {
+            for watchers_file in reload::config_files(path)? {
+                let disabled = validation::validate(&watchers_file)?;
+                println!("{} is valid", watchers_file.display());
+                for watcher in disabled {
+                    println!("{}", watcher);
+                }
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    // Run every watcher once, e.g. from cron, exiting with the worst outcome.
+    if std::env::args().any(|arg| arg == "--once") {
+        // `--report` replaces `reporters.json`, e.g. to try a watcher file out.
+        let reporters: Box<dyn AlertReporter + Send + Sync> = match flag("--report") {
+            Some(kind) => alert_reporter::from_flags(&kind, flag("--url").as_deref())
+                .context("Invalid --report")?,
+            None => Box::new(read_reporters(&settings.routing)?),
+        };
+        let exit_code = once::run_once(
+            reload::read_watchers(&watcher_paths)?,
+            reporters.as_ref(),
+            &settings.maintenance,
+            &settings.mute,
+            &settings.inhibition,
+            &settings.composite,
+            &settings.once,
+        );
+        // Reporters buffering alerts report them when dropped.
+        drop(reporters);
+        return Ok(ExitCode::from(exit_code));
+    }
+    let (scheduler, handle) =
+        Scheduler::new(reload::read_watchers(&watcher_paths)?, &settings.scheduler)
+            .context("Invalid watcher configuration")?;
+    // SIGHUP must be blocked before the shutdown signals spawn their thread.
+    #[cfg(feature = "nix")]
+    reload::SighupReload::install(watcher_paths.clone(), handle.clone())?;
+    #[cfg(feature = "nix")]
+    shutdown_on_signals(handle.clone())?;
 
-    let now = Instant::now();
-    for w in watchers {
-        timers.push(Reverse(ScheduledWatcher {
-            deadline: now,
-            watcher: w,
-        }));
+    if let Some(configuration) = &settings.reload {
+        reload::watch(watcher_paths.clone(), configuration, handle.clone());
     }
 
-    while let Some(Reverse(next)) = timers.pop() {
-        sleep_until(next.deadline);
-        if let Err(e) = next.watcher.run(&reporters) {
-            error!(watcher = ?e);
-        }
-        timers.push(Reverse(ScheduledWatcher {
-            deadline: Instant::now() + next.watcher.period(),
-            watcher: next.watcher,
-        }));
+    if let Some(configuration) = &settings.prometheus {
+        prometheus::serve(configuration)?;
     }
 
-    Ok(())
+    #[cfg(feature = "otlp")]
+    if let Some(configuration) = &settings.otlp {
+        otlp::start(configuration)?;
+    }
+
+    if let Some(configuration) = &settings.status {
+        status::server::serve(configuration, handle.clone())?;
+    }
+
+    let reporters = read_reporters(&settings.routing)?;
+
+    let alert_store = settings.alert_store.into_store();
+    scheduler.run(
+        &reporters,
+        &settings.maintenance,
+        &settings.mute,
+        &settings.inhibition,
+        &settings.composite,
+        alert_store.as_ref(),
+    );
+    drop(handle);
+
+    Ok(ExitCode::SUCCESS)
 }
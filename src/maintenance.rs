@@ -0,0 +1,297 @@
+//! Maintenance windows, during which alerts are not reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Days, FixedOffset, Local, NaiveTime, Weekday};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::duration::deserialize_duration;
+use crate::watcher::ActiveAlert;
+
+#[derive(Deserialize, JsonSchema)]
+enum RawSchedule {
+    Once {
+        start: String,
+        end: String,
+    },
+    Recurring {
+        #[serde(default)]
+        days: Vec<String>,
+        start: String,
+        #[schemars(schema_with = "crate::schema::duration")]
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+    },
+}
+
+/// When a maintenance window, or a scheduled threshold, is open.
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "RawSchedule")]
+#[schemars(with = "RawSchedule")]
+pub enum Schedule {
+    /// From `start` to `end`, given as RFC 3339 dates such as `2024-06-01T22:00:00+02:00`.
+    Once {
+        start: DateTime<FixedOffset>,
+        end: DateTime<FixedOffset>,
+    },
+    /// Every day listed in `days` (every day if empty), from `start`, given in local time such
+    /// as `02:30`, for `duration`. A window may run past midnight.
+    Recurring {
+        days: Vec<Weekday>,
+        start: NaiveTime,
+        duration: Duration,
+    },
+}
+
+impl TryFrom<RawSchedule> for Schedule {
+    type Error = String;
+
+    fn try_from(value: RawSchedule) -> Result<Self, Self::Error> {
+        match value {
+            RawSchedule::Once { start, end } => {
+                let parse = |date: &str| {
+                    DateTime::parse_from_rfc3339(date)
+                        .map_err(|e| format!("invalid date '{}': {}", date, e))
+                };
+                let (start, end) = (parse(&start)?, parse(&end)?);
+                if end <= start {
+                    return Err(format!("window ends at {} before it starts", end));
+                }
+                Ok(Schedule::Once { start, end })
+            }
+            RawSchedule::Recurring {
+                days,
+                start,
+                duration,
+            } => Ok(Schedule::Recurring {
+                days: days
+                    .iter()
+                    .map(|d| {
+                        d.parse::<Weekday>()
+                            .map_err(|_| format!("invalid day of the week '{}'", d))
+                    })
+                    .collect::<Result<_, _>>()?,
+                start: NaiveTime::parse_from_str(&start, "%H:%M")
+                    .map_err(|e| format!("invalid time '{}': {}", start, e))?,
+                duration,
+            }),
+        }
+    }
+}
+
+impl Schedule {
+    /// Whether the window is open at `now`. Windows include their start but not their end.
+    pub(crate) fn is_open(&self, now: DateTime<Local>) -> bool {
+        match self {
+            Schedule::Once { start, end } => *start <= now && now < *end,
+            Schedule::Recurring {
+                days,
+                start,
+                duration,
+            } => {
+                // Windows that started on previous days may still be open.
+                let days_back = duration.as_secs() / 86400 + 1;
+                (0..=days_back)
+                    .filter_map(|back| now.date_naive().checked_sub_days(Days::new(back)))
+                    .filter(|date| days.is_empty() || days.contains(&date.weekday()))
+                    .filter_map(|date| date.and_time(*start).and_local_timezone(Local).earliest())
+                    .any(|opening| opening <= now && now < opening + *duration)
+            }
+        }
+    }
+}
+
+/// A maintenance window, optionally restricted to some watchers.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    #[serde(flatten)]
+    schedule: Schedule,
+    /// Kinds of watchers the window applies to, such as `disk_space`. All of them if empty.
+    #[serde(default)]
+    kinds: Vec<String>,
+    /// Labels an alert must have for the window to apply to it.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+impl Window {
+    fn applies_to(&self, alert: &ActiveAlert) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&alert.kind))
+            && self
+                .labels
+                .iter()
+                .all(|(key, value)| alert.labels.get(key) == Some(value))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Configuration {
+    #[serde(default)]
+    windows: Vec<Window>,
+}
+
+impl Configuration {
+    /// Whether any maintenance window is open at `now`, whatever the watchers it applies to.
+    pub fn is_in_maintenance(&self, now: SystemTime) -> bool {
+        let now = DateTime::<Local>::from(now);
+        self.windows.iter().any(|w| w.schedule.is_open(now))
+    }
+
+    /// Whether an alert falls within a maintenance window open at `now`.
+    pub fn covers(&self, alert: &ActiveAlert, now: SystemTime) -> bool {
+        let now = DateTime::<Local>::from(now);
+        self.windows
+            .iter()
+            .any(|w| w.applies_to(alert) && w.schedule.is_open(now))
+    }
+}
+
+/// Alerts known to a [`Reporter`], by fingerprint.
+#[derive(Default)]
+struct Alerts {
+    /// Alerts that fired during a window and were not reported.
+    held_back: BTreeMap<String, ActiveAlert>,
+    /// Alerts that were reported and not resolved yet.
+    reported: BTreeSet<String>,
+}
+
+/// Holds back the alerts that fire during a maintenance window, while the checks keep running.
+///
+/// An alert that fires and resolves within a window is not reported at all. Alerts still firing
+/// once their window closes are reported then, as a single batch, by
+/// [`Reporter::report_closed`]. Alerts that fired before a window opened are not reported again
+/// during it, but their resolution is, so that they do not stay open.
+pub struct Reporter<'a, R> {
+    configuration: &'a Configuration,
+    reporter: &'a R,
+    alerts: Mutex<Alerts>,
+}
+
+impl<'a, R: AlertReporter> Reporter<'a, R> {
+    pub fn new(configuration: &'a Configuration, reporter: &'a R) -> Self {
+        Reporter {
+            configuration,
+            reporter,
+            alerts: Mutex::new(Alerts::default()),
+        }
+    }
+
+    fn alerts(&self) -> MutexGuard<'_, Alerts> {
+        // Alerts are only ever inserted or removed, a panic can not leave them inconsistent.
+        self.alerts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Holds back the alerts covered by a window, returning the ones to report.
+    fn hold_back(&self, alerts: &[ActiveAlert]) -> Vec<ActiveAlert> {
+        let now = SystemTime::now();
+        let mut known = self.alerts();
+        let mut reported = Vec::new();
+        for alert in alerts {
+            if !self.configuration.covers(alert, now) {
+                // Supersedes what was held back during the window.
+                known.held_back.remove(&alert.fingerprint);
+                known.reported.insert(alert.fingerprint.clone());
+                reported.push(alert.clone());
+            } else if known.reported.contains(&alert.fingerprint) {
+                info!(maintenance_alert = ?alert, "Already reported before the maintenance");
+            } else {
+                info!(maintenance_alert = ?alert);
+                known
+                    .held_back
+                    .insert(alert.fingerprint.clone(), alert.clone());
+            }
+        }
+        reported
+    }
+
+    /// Reports the held back alerts whose window closed since they fired.
+    pub fn report_closed(&self) {
+        let now = SystemTime::now();
+        let closed = {
+            let mut known = self.alerts();
+            let (open, closed) = mem::take(&mut known.held_back)
+                .into_iter()
+                .partition::<BTreeMap<_, _>, _>(|(_, a)| self.configuration.covers(a, now));
+            known.held_back = open;
+            known.reported.extend(closed.keys().cloned());
+            closed.into_values().collect::<Vec<_>>()
+        };
+        if closed.is_empty() {
+            return;
+        }
+        info!(maintenance_closed_alerts = closed.len());
+        if let Err(e) = self.reporter.report_batch(&closed) {
+            warn!(alert_reporter = ?e);
+        }
+    }
+
+    /// Forgets a resolved alert, telling whether its resolution is to be reported: it is not for
+    /// the alerts that were held back.
+    fn resolve(&self, alert: &ActiveAlert) -> bool {
+        let mut known = self.alerts();
+        if known.held_back.remove(&alert.fingerprint).is_some() {
+            info!(maintenance_resolved_alert = ?alert);
+            return false;
+        }
+        known.reported.remove(&alert.fingerprint);
+        true
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for Reporter<'_, R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        match self.hold_back(std::slice::from_ref(alert)).first() {
+            Some(alert) => self.reporter.report(alert),
+            None => Ok(()),
+        }
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        let reported = self.hold_back(alerts);
+        if reported.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_batch(&reported)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        if !self.resolve(alert) {
+            return Ok(());
+        }
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.reporter.flush()
+    }
+
+    /// Holds the changes back the same as reports and resolutions. An alert that passes without
+    /// having been reported before, such as one held back until now, is reported as firing.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let passed = changes
+            .iter()
+            .filter_map(|change| match change.firing() {
+                None => self.resolve(change.alert()).then(|| change.clone()),
+                Some(alert) => {
+                    let was_reported = self.alerts().reported.contains(&alert.fingerprint);
+                    let passed = !self.hold_back(std::slice::from_ref(alert)).is_empty();
+                    passed.then(|| match was_reported {
+                        true => change.clone(),
+                        false => AlertChange::Firing(alert.clone()),
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+        if passed.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_diff(&passed)
+    }
+}
@@ -0,0 +1,93 @@
+//! Latest values measured by the checkers, kept so that they can be exposed as metrics.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::sync::{Mutex, OnceLock};
+
+use tracing::warn;
+
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod prometheus;
+
+pub type Labels = Vec<(String, String)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Labels,
+    pub value: f64,
+}
+
+impl Sample {
+    pub fn new(name: &str, labels: &[(&str, &str)], value: f64) -> Self {
+        Sample {
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            value,
+        }
+    }
+}
+
+/// Samples of a single check, along with the watcher that took them.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    /// Kind of the watcher, such as `disk_space`.
+    pub kind: &'static str,
+    /// Labels of the watcher, such as `env` or `team`.
+    pub labels: BTreeMap<String, String>,
+    pub samples: Vec<Sample>,
+}
+
+/// Latest value of each metric, by name and labels.
+static LATEST: Mutex<BTreeMap<(String, Labels), f64>> = Mutex::new(BTreeMap::new());
+
+/// Where the samples of each check are pushed to, if anywhere.
+static EXPORTER: OnceLock<SyncSender<Batch>> = OnceLock::new();
+
+/// Sends the samples of every check recorded from now on, as they are recorded. Only the first
+/// exporter is kept.
+pub fn export_to(exporter: SyncSender<Batch>) {
+    let _ = EXPORTER.set(exporter);
+}
+
+/// Records the latest samples of a check, replacing the previous values of the same metrics.
+pub fn record(kind: &'static str, labels: &BTreeMap<String, String>, samples: Vec<Sample>) {
+    if let Ok(mut latest) = LATEST.lock() {
+        for sample in &samples {
+            latest.insert((sample.name.clone(), sample.labels.clone()), sample.value);
+        }
+    }
+    let Some(exporter) = EXPORTER.get() else {
+        return;
+    };
+    let batch = Batch {
+        kind,
+        labels: labels.clone(),
+        samples,
+    };
+    // Checks must not wait for a slow exporter.
+    if let Err(TrySendError::Full(batch)) = exporter.try_send(batch) {
+        warn!(dropped_metrics = batch.kind, "Metrics exporter is lagging");
+    }
+}
+
+/// The latest value of every metric recorded so far, sorted by name and labels.
+pub fn snapshot() -> Vec<Sample> {
+    LATEST
+        .lock()
+        .map(|latest| {
+            latest
+                .iter()
+                .map(|((name, labels), value)| Sample {
+                    name: name.clone(),
+                    labels: labels.clone(),
+                    value: *value,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,307 @@
+//! Pushes the samples of each check to an OpenTelemetry collector, as OTLP gauges over HTTP.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use opentelemetry::metrics::{Gauge, Meter, MeterProvider as _};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_otlp::{MetricExporter, Protocol, WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider, Temporality};
+use opentelemetry_sdk::Resource;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::duration::deserialize_duration;
+use crate::metrics::{export_to, Batch};
+use crate::secret::Secret;
+use crate::watcher::hostname;
+
+/// Checks whose samples are not pushed yet, beyond which new ones are dropped.
+const QUEUE_SIZE: usize = 64;
+
+/// Samples are pushed as each check records them, the periodic exports only find nothing new.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn default_endpoint() -> String {
+    "http://localhost:4318".to_string()
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Encoding of the exported metrics, in the body of the HTTP requests.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    Protobuf,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// Base URL of the OTLP/HTTP receiver of the collector, to which `/v1/metrics` is appended.
+    #[serde(default = "default_endpoint")]
+    endpoint: String,
+    /// Headers sent along with every export, such as an authorization token.
+    #[serde(default)]
+    headers: BTreeMap<String, Secret>,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+    #[serde(default)]
+    encoding: Encoding,
+}
+
+/// Meter of the watchers of a kind and labels, which describe the resource of its metrics.
+struct Watcher {
+    provider: SdkMeterProvider,
+    meter: Meter,
+    gauges: BTreeMap<String, Gauge<f64>>,
+}
+
+struct Exporter {
+    configuration: Configuration,
+    url: String,
+    hostname: String,
+    watchers: BTreeMap<(&'static str, BTreeMap<String, String>), Watcher>,
+}
+
+impl Exporter {
+    fn new(configuration: &Configuration) -> Self {
+        let url = format!(
+            "{}/v1/metrics",
+            configuration.endpoint.trim_end_matches('/')
+        );
+        Exporter {
+            configuration: configuration.clone(),
+            url,
+            hostname: hostname(),
+            watchers: BTreeMap::new(),
+        }
+    }
+
+    /// Starts the meter of the watchers of a batch. Their kind names the instrumentation scope.
+    fn watcher(&self, batch: &Batch) -> Result<Watcher> {
+        let client = Client::builder()
+            .timeout(self.configuration.timeout)
+            .build()
+            .context("Could not create HTTP client.")?;
+        let headers = self
+            .configuration
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.expose().to_string()))
+            .collect::<HashMap<_, _>>();
+        let protocol = match self.configuration.encoding {
+            Encoding::Json => Protocol::HttpJson,
+            Encoding::Protobuf => Protocol::HttpBinary,
+        };
+        // Only the samples of the latest check are exported, not those of every check so far.
+        let exporter = MetricExporter::builder()
+            .with_http()
+            .with_http_client(client)
+            .with_protocol(protocol)
+            .with_endpoint(&self.url)
+            .with_headers(headers)
+            .with_timeout(self.configuration.timeout)
+            .with_temporality(Temporality::Delta)
+            .build()
+            .context("Could not create OTLP exporter.")?;
+        let resource = Resource::builder_empty()
+            .with_attributes(
+                [
+                    KeyValue::new("service.name", "monitrust"),
+                    KeyValue::new("host.name", self.hostname.clone()),
+                    KeyValue::new("monitrust.watcher.kind", batch.kind),
+                ]
+                .into_iter()
+                .chain(
+                    batch
+                        .labels
+                        .iter()
+                        .map(|(k, v)| KeyValue::new(k.clone(), v.clone())),
+                ),
+            )
+            .build();
+        let provider = SdkMeterProvider::builder()
+            .with_resource(resource)
+            .with_reader(
+                PeriodicReader::builder(exporter)
+                    .with_interval(EXPORT_INTERVAL)
+                    .build(),
+            )
+            .build();
+        let meter = provider.meter_with_scope(
+            InstrumentationScope::builder(format!("monitrust.{}", batch.kind)).build(),
+        );
+        Ok(Watcher {
+            provider,
+            meter,
+            gauges: BTreeMap::new(),
+        })
+    }
+
+    /// Exports the samples of a check, one gauge per metric name with a data point per sample.
+    fn export(&mut self, batch: &Batch) -> Result<()> {
+        let key = (batch.kind, batch.labels.clone());
+        if !self.watchers.contains_key(&key) {
+            let watcher = self.watcher(batch)?;
+            self.watchers.insert(key.clone(), watcher);
+        }
+        let Watcher {
+            provider,
+            meter,
+            gauges,
+        } = self.watchers.get_mut(&key).expect("Inserted above");
+        for sample in &batch.samples {
+            let gauge = gauges
+                .entry(sample.name.clone())
+                .or_insert_with(|| meter.f64_gauge(sample.name.clone()).build());
+            let attributes = sample
+                .labels
+                .iter()
+                .map(|(k, v)| KeyValue::new(k.clone(), v.clone()))
+                .collect::<Vec<_>>();
+            gauge.record(sample.value, &attributes);
+        }
+        provider
+            .force_flush()
+            .map_err(|e| anyhow!("Could not export metrics to {}: {}", self.url, e))
+    }
+
+    fn run(mut self, batches: Receiver<Batch>) {
+        for batch in batches {
+            if batch.samples.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.export(&batch) {
+                warn!(otlp_export = ?e);
+            }
+        }
+    }
+}
+
+/// Starts pushing the samples of every check to the collector, from a background thread.
+pub fn start(configuration: &Configuration) -> Result<()> {
+    let exporter = Exporter::new(configuration);
+    info!(exporting_metrics = exporter.url);
+    let (sender, receiver) = sync_channel(QUEUE_SIZE);
+    export_to(sender);
+    std::thread::spawn(move || exporter.run(receiver));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Sample;
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// A request received by the collector.
+    struct Request {
+        line: String,
+        /// Headers, with lowercase names.
+        headers: BTreeMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    /// Answers a single HTTP request, returning it.
+    fn serve_once(listener: TcpListener) -> thread::JoinHandle<Request> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let (mut request_line, mut line) = (String::new(), String::new());
+            reader.read_line(&mut request_line).unwrap();
+            let mut headers = BTreeMap::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+                line.clear();
+            }
+            let mut body = vec![0; headers["content-length"].parse().unwrap()];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                writer,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{{}}"
+            )
+            .unwrap();
+            Request {
+                line: request_line.trim_end().to_string(),
+                headers,
+                body,
+            }
+        })
+    }
+
+    /// Exports the samples of a single check to a collector, returning the request it got.
+    fn export(configuration: serde_json::Value) -> Request {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let collector = serve_once(listener);
+        let mut configuration = configuration;
+        configuration["endpoint"] = format!("http://127.0.0.1:{}/", port).into();
+        let mut exporter = Exporter::new(&serde_json::from_value(configuration).unwrap());
+        exporter
+            .export(&Batch {
+                kind: "disk_space",
+                labels: BTreeMap::from([("team".to_string(), "ops".to_string())]),
+                samples: vec![Sample::new("disk_free_ratio", &[("mount", "/var")], 0.25)],
+            })
+            .unwrap();
+        collector.join().unwrap()
+    }
+
+    fn attribute<'a>(attributes: &'a Value, key: &str) -> &'a Value {
+        let attribute = attributes
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|attribute| attribute["key"] == key)
+            .unwrap_or_else(|| panic!("No {} in {}", key, attributes));
+        &attribute["value"]["stringValue"]
+    }
+
+    #[test]
+    fn exports_the_samples_of_a_check() {
+        let request = export(serde_json::json!({
+            "headers": { "Authorization": "Bearer token" }
+        }));
+        assert_eq!(request.line, "POST /v1/metrics HTTP/1.1");
+        assert_eq!(request.headers["content-type"], "application/json");
+        assert_eq!(request.headers["authorization"], "Bearer token");
+        let body = serde_json::from_slice::<Value>(&request.body).unwrap();
+        let resource_metrics = &body["resourceMetrics"][0];
+        let resource = &resource_metrics["resource"]["attributes"];
+        assert_eq!(attribute(resource, "service.name"), "monitrust");
+        assert_eq!(attribute(resource, "monitrust.watcher.kind"), "disk_space");
+        assert_eq!(attribute(resource, "team"), "ops");
+        let scope_metrics = &resource_metrics["scopeMetrics"][0];
+        assert_eq!(scope_metrics["scope"]["name"], "monitrust.disk_space");
+        let metric = &scope_metrics["metrics"][0];
+        assert_eq!(metric["name"], "disk_free_ratio");
+        let data_point = &metric["gauge"]["dataPoints"][0];
+        assert_eq!(attribute(&data_point["attributes"], "mount"), "/var");
+        assert_eq!(data_point["asDouble"], 0.25);
+    }
+
+    #[test]
+    fn exports_protobuf() {
+        let request = export(serde_json::json!({ "encoding": "Protobuf" }));
+        assert_eq!(request.line, "POST /v1/metrics HTTP/1.1");
+        assert_eq!(request.headers["content-type"], "application/x-protobuf");
+        // Strings are encoded as they are.
+        let name = b"disk_free_ratio";
+        assert!(request
+            .body
+            .windows(name.len())
+            .any(|window| window == name));
+    }
+}
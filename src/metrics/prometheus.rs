@@ -0,0 +1,79 @@
+//! Serves the latest metrics in the Prometheus text exposition format.
+
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::metrics::{snapshot, Sample};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// Address to listen on, e.g. `127.0.0.1:9898`.
+    address: String,
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Formats samples, which must be sorted by name, in the Prometheus text exposition format.
+pub fn render(samples: &[Sample]) -> String {
+    let mut rendered = String::new();
+    let mut previous_name = None;
+    for sample in samples {
+        if previous_name != Some(&sample.name) {
+            let _ = writeln!(rendered, "# TYPE {} gauge", sample.name);
+            previous_name = Some(&sample.name);
+        }
+        let labels = sample
+            .labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+            .collect::<Vec<_>>()
+            .join(",");
+        if labels.is_empty() {
+            let _ = writeln!(rendered, "{} {}", sample.name, sample.value);
+        } else {
+            let _ = writeln!(rendered, "{}{{{}}} {}", sample.name, labels, sample.value);
+        }
+    }
+    rendered
+}
+
+fn respond(mut stream: TcpStream) -> Result<()> {
+    // The request itself does not matter, every path serves the metrics.
+    let mut request = [0; 1024];
+    let _ = stream
+        .read(&mut request)
+        .context("Could not read request")?;
+    let body = render(&snapshot());
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .context("Could not write response")
+}
+
+/// Starts serving the metrics from a background thread.
+pub fn serve(configuration: &Configuration) -> Result<()> {
+    let listener = TcpListener::bind(&configuration.address)
+        .with_context(|| format!("Could not listen on {}", configuration.address))?;
+    info!(serving_metrics = configuration.address);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Err(e) = stream.map_err(Into::into).and_then(respond) {
+                warn!(metrics_endpoint = ?e);
+            }
+        }
+    });
+    Ok(())
+}
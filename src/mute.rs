@@ -0,0 +1,316 @@
+//! Mute rules, which keep known noisy alerts from being reported without disabling their
+//! watchers, until they expire.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use anyhow::Result;
+use chrono::DateTime;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use tracing::{info, warn};
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::status;
+use crate::watcher::ActiveAlert;
+
+#[derive(Deserialize)]
+enum RawMatcher {
+    Message(String),
+    Labels(BTreeMap<String, String>),
+    Kind(String),
+}
+
+/// Which alerts a rule applies to.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "RawMatcher")]
+pub enum Matcher {
+    /// Alerts whose message matches the regular expression, anywhere in it.
+    Message(Regex),
+    /// Alerts with all of the labels.
+    Labels(BTreeMap<String, String>),
+    /// Alerts of a kind of watcher, such as `disk_space`.
+    Kind(String),
+}
+
+impl TryFrom<RawMatcher> for Matcher {
+    type Error = String;
+
+    fn try_from(value: RawMatcher) -> Result<Self, Self::Error> {
+        match value {
+            RawMatcher::Message(regex) => Regex::new(&regex)
+                .map(Matcher::Message)
+                .map_err(|e| format!("invalid message regex '{}': {}", regex, e)),
+            RawMatcher::Labels(labels) => Ok(Matcher::Labels(labels)),
+            RawMatcher::Kind(kind) => Ok(Matcher::Kind(kind)),
+        }
+    }
+}
+
+impl Matcher {
+    pub(crate) fn matches(&self, alert: &ActiveAlert) -> bool {
+        match self {
+            Matcher::Message(regex) => regex.is_match(&alert.message),
+            Matcher::Labels(labels) => labels
+                .iter()
+                .all(|(key, value)| alert.labels.get(key) == Some(value)),
+            Matcher::Kind(kind) => alert.kind == *kind,
+        }
+    }
+}
+
+fn deserialize_until<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<SystemTime>, D::Error> {
+    let Some(until) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    DateTime::parse_from_rfc3339(&until)
+        .map(|until| Some(until.into()))
+        .map_err(|e| serde::de::Error::custom(format!("invalid date '{}': {}", until, e)))
+}
+
+/// Mutes the alerts it matches until `until`, given as an RFC 3339 date such as
+/// `2024-06-01T22:00:00+02:00`, or for good without it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MuteRule {
+    pub r#match: Matcher,
+    #[serde(default, deserialize_with = "deserialize_until")]
+    pub until: Option<SystemTime>,
+}
+
+impl MuteRule {
+    fn mutes(&self, alert: &ActiveAlert, now: SystemTime) -> bool {
+        self.until.is_none_or(|until| now < until) && self.r#match.matches(alert)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Configuration {
+    #[serde(default)]
+    rules: Vec<MuteRule>,
+}
+
+impl Configuration {
+    /// No rule, muting nothing.
+    pub const fn new() -> Self {
+        Configuration { rules: Vec::new() }
+    }
+
+    /// Whether a rule mutes an alert at `now`.
+    pub fn mutes(&self, alert: &ActiveAlert, now: SystemTime) -> bool {
+        self.rules.iter().any(|rule| rule.mutes(alert, now))
+    }
+}
+
+/// Alerts known to a [`Reporter`], by fingerprint.
+#[derive(Default)]
+struct Alerts {
+    /// Alerts that fired while muted and were not reported.
+    held_back: BTreeMap<String, ActiveAlert>,
+    /// Alerts that were reported and not resolved yet.
+    reported: BTreeSet<String>,
+}
+
+/// Holds back the alerts that fire while a rule mutes them, the same as a maintenance window.
+///
+/// An alert that fires and resolves while muted is not reported at all. Alerts still firing once
+/// their rule expires are reported then, as a single batch, by [`Reporter::report_expired`].
+/// Alerts that fired before they were muted are not reported again, but their resolution is.
+/// The status endpoint shows which firing alerts are muted.
+pub struct Reporter<'a, R> {
+    configuration: &'a Configuration,
+    reporter: &'a R,
+    alerts: Mutex<Alerts>,
+}
+
+impl<'a, R: AlertReporter> Reporter<'a, R> {
+    pub fn new(configuration: &'a Configuration, reporter: &'a R) -> Self {
+        status::set_mute_rules(configuration.clone());
+        Reporter {
+            configuration,
+            reporter,
+            alerts: Mutex::new(Alerts::default()),
+        }
+    }
+
+    fn alerts(&self) -> MutexGuard<'_, Alerts> {
+        // Alerts are only ever inserted or removed, a panic can not leave them inconsistent.
+        self.alerts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Holds back the muted alerts, returning the ones to report.
+    fn hold_back(&self, alerts: &[ActiveAlert]) -> Vec<ActiveAlert> {
+        let now = SystemTime::now();
+        let mut known = self.alerts();
+        let mut reported = Vec::new();
+        for alert in alerts {
+            if !self.configuration.mutes(alert, now) {
+                known.held_back.remove(&alert.fingerprint);
+                known.reported.insert(alert.fingerprint.clone());
+                reported.push(alert.clone());
+            } else if known.reported.contains(&alert.fingerprint) {
+                info!(muted_alert = ?alert, "Already reported before it was muted");
+            } else {
+                info!(muted_alert = ?alert);
+                known
+                    .held_back
+                    .insert(alert.fingerprint.clone(), alert.clone());
+            }
+        }
+        reported
+    }
+
+    /// Reports the held back alerts whose rules expired since they fired.
+    pub fn report_expired(&self) {
+        let now = SystemTime::now();
+        let expired = {
+            let mut known = self.alerts();
+            let (muted, expired) = mem::take(&mut known.held_back)
+                .into_iter()
+                .partition::<BTreeMap<_, _>, _>(|(_, a)| self.configuration.mutes(a, now));
+            known.held_back = muted;
+            known.reported.extend(expired.keys().cloned());
+            expired.into_values().collect::<Vec<_>>()
+        };
+        if expired.is_empty() {
+            return;
+        }
+        info!(unmuted_alerts = expired.len());
+        if let Err(e) = self.reporter.report_batch(&expired) {
+            warn!(alert_reporter = ?e);
+        }
+    }
+
+    /// Forgets a resolved alert, telling whether its resolution is to be reported: it is not for
+    /// the alerts that were held back.
+    fn resolve(&self, alert: &ActiveAlert) -> bool {
+        let mut known = self.alerts();
+        if known.held_back.remove(&alert.fingerprint).is_some() {
+            info!(muted_resolved_alert = ?alert);
+            return false;
+        }
+        known.reported.remove(&alert.fingerprint);
+        true
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for Reporter<'_, R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        match self.hold_back(std::slice::from_ref(alert)).first() {
+            Some(alert) => self.reporter.report(alert),
+            None => Ok(()),
+        }
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        let reported = self.hold_back(alerts);
+        if reported.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_batch(&reported)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        if !self.resolve(alert) {
+            return Ok(());
+        }
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.reporter.flush()
+    }
+
+    /// Holds the changes back the same as reports and resolutions. An alert that passes without
+    /// having been reported before, such as one held back until now, is reported as firing.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        let passed = changes
+            .iter()
+            .filter_map(|change| match change.firing() {
+                None => self.resolve(change.alert()).then(|| change.clone()),
+                Some(alert) => {
+                    let was_reported = self.alerts().reported.contains(&alert.fingerprint);
+                    let passed = !self.hold_back(std::slice::from_ref(alert)).is_empty();
+                    passed.then(|| match was_reported {
+                        true => change.clone(),
+                        false => AlertChange::Firing(alert.clone()),
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+        if passed.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_diff(&passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::watcher::Severity;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn alert(fingerprint: &str, labels: &[(&str, &str)]) -> ActiveAlert {
+        ActiveAlert {
+            fingerprint: fingerprint.to_string(),
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..ActiveAlert::new(format!("Disk {} is full", fingerprint), Severity::Warning)
+        }
+    }
+
+    fn fingerprints(alerts: Vec<ActiveAlert>) -> Vec<String> {
+        alerts.into_iter().map(|a| a.fingerprint).collect()
+    }
+
+    #[test]
+    fn alerts_muted_by_label_are_reported_once_the_rule_expires() {
+        let configuration = Configuration {
+            rules: vec![MuteRule {
+                r#match: Matcher::Labels([("noisy".to_string(), "yes".to_string())].into()),
+                until: Some(SystemTime::now() + Duration::from_millis(300)),
+            }],
+        };
+        let recording = RecordingReporter::new();
+        let reporter = Reporter::new(&configuration, &recording);
+
+        reporter
+            .report_batch(&[alert("/var", &[("noisy", "yes")]), alert("/", &[])])
+            .unwrap();
+        reporter.report_expired();
+        assert_eq!(fingerprints(recording.alerts()), ["/"]);
+
+        sleep(Duration::from_millis(400));
+        reporter.report_expired();
+        reporter
+            .report_resolved(&alert("/var", &[("noisy", "yes")]))
+            .unwrap();
+
+        assert_eq!(fingerprints(recording.alerts()), ["/", "/var"]);
+        assert_eq!(fingerprints(recording.resolved()), ["/var"]);
+    }
+
+    #[test]
+    fn alerts_resolving_while_muted_are_not_reported_at_all() {
+        let configuration: Configuration = serde_json::from_value(serde_json::json!({
+            "rules": [{ "match": { "Message": "^Disk /tmp" } }]
+        }))
+        .unwrap();
+        let recording = RecordingReporter::new();
+        let reporter = Reporter::new(&configuration, &recording);
+
+        reporter.report(&alert("/tmp", &[])).unwrap();
+        reporter.report_resolved(&alert("/tmp", &[])).unwrap();
+
+        assert!(recording.alerts().is_empty());
+        assert!(recording.resolved().is_empty());
+    }
+}
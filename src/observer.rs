@@ -0,0 +1,147 @@
+//! Hooks for programs embedding Monitrust, to react to every check and alert in their own code,
+//! such as updating a dashboard, without implementing an
+//! [`AlertReporter`](crate::alert_reporter::AlertReporter).
+//!
+//! Hooks are called from the threads running the watchers, and see the alerts as the watchers
+//! trigger them, before maintenance windows, inhibition rules and reporters apply. Hooks must
+//! neither register nor remove hooks.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard};
+use std::time::SystemTime;
+
+use crate::metrics::Sample;
+use crate::watcher::ActiveAlert;
+
+/// Outcome of a check, whatever the type of its result.
+#[derive(Debug, Clone)]
+pub struct CheckResultSnapshot {
+    /// Kind of the watcher, such as `disk_space`.
+    pub kind: &'static str,
+    /// Identifies the watcher, as in `/status`.
+    pub watcher: String,
+    /// Labels of the watcher, such as `env` or `team`.
+    pub labels: BTreeMap<String, String>,
+    pub checked_at: SystemTime,
+    /// What the check measured, as written in `/status`, or why it failed.
+    pub check: Result<String, String>,
+    /// Values measured by the check, see [`Checker::metrics`]. Empty if the check failed.
+    ///
+    /// [`Checker::metrics`]: crate::watcher::Checker::metrics
+    pub samples: Vec<Sample>,
+}
+
+/// A change in the lifecycle of an alert, for the hooks of [`on_event`].
+#[derive(Debug, Clone)]
+pub enum AlertEvent {
+    /// The alert started firing, or fires again with another severity or once it stopped
+    /// flapping.
+    Firing(Box<ActiveAlert>),
+    /// The alert with this fingerprint stopped firing.
+    Resolved {
+        fingerprint: String,
+        resolved_at: SystemTime,
+    },
+    /// The alert with this fingerprint went through a tier of its escalation, starting at 1.
+    Escalated { fingerprint: String, tier: usize },
+    /// The alert with this fingerprint started flapping. Its transitions are not told until it
+    /// stops, when it is either `Firing` or `Resolved`.
+    Flapping { fingerprint: String },
+}
+
+/// Identifies a registered hook, to remove it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HookId(u64);
+
+type CheckResultHook = Box<dyn Fn(&CheckResultSnapshot) + Send + Sync>;
+type AlertHook = Box<dyn Fn(&ActiveAlert) + Send + Sync>;
+type EventHook = Box<dyn Fn(&AlertEvent) + Send + Sync>;
+
+struct Hooks {
+    check_results: Vec<(HookId, CheckResultHook)>,
+    alerts: Vec<(HookId, AlertHook)>,
+    resolved: Vec<(HookId, AlertHook)>,
+    events: Vec<(HookId, EventHook)>,
+}
+
+static HOOKS: RwLock<Hooks> = RwLock::new(Hooks {
+    check_results: Vec::new(),
+    alerts: Vec::new(),
+    resolved: Vec::new(),
+    events: Vec::new(),
+});
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn hooks() -> RwLockReadGuard<'static, Hooks> {
+    // Hooks are only ever added and removed, a panic can not leave them inconsistent.
+    HOOKS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn register(register: impl FnOnce(&mut Hooks, HookId)) -> HookId {
+    let id = HookId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    register(&mut HOOKS.write().unwrap_or_else(|e| e.into_inner()), id);
+    id
+}
+
+/// Calls `hook` with the outcome of every check of every watcher, failed ones included.
+pub fn on_check_result(hook: impl Fn(&CheckResultSnapshot) + Send + Sync + 'static) -> HookId {
+    register(|hooks, id| hooks.check_results.push((id, Box::new(hook))))
+}
+
+/// Calls `hook` with every alert a watcher reports, including escalations, flapping alerts and
+/// failed checks reported as alerts.
+pub fn on_alert(hook: impl Fn(&ActiveAlert) + Send + Sync + 'static) -> HookId {
+    register(|hooks, id| hooks.alerts.push((id, Box::new(hook))))
+}
+
+/// Calls `hook` with every alert a watcher resolves.
+pub fn on_resolved(hook: impl Fn(&ActiveAlert) + Send + Sync + 'static) -> HookId {
+    register(|hooks, id| hooks.resolved.push((id, Box::new(hook))))
+}
+
+/// Calls `hook` with every change in the lifecycle of an alert.
+pub fn on_event(hook: impl Fn(&AlertEvent) + Send + Sync + 'static) -> HookId {
+    register(|hooks, id| hooks.events.push((id, Box::new(hook))))
+}
+
+/// Stops calling a hook.
+pub fn remove(id: HookId) {
+    let mut hooks = HOOKS.write().unwrap_or_else(|e| e.into_inner());
+    hooks.check_results.retain(|(hook, _)| *hook != id);
+    hooks.alerts.retain(|(hook, _)| *hook != id);
+    hooks.resolved.retain(|(hook, _)| *hook != id);
+    hooks.events.retain(|(hook, _)| *hook != id);
+}
+
+/// Calls the check result hooks, with a snapshot only taken if there is any.
+pub(crate) fn checked(snapshot: impl FnOnce() -> CheckResultSnapshot) {
+    let hooks = hooks();
+    if hooks.check_results.is_empty() {
+        return;
+    }
+    let snapshot = snapshot();
+    hooks
+        .check_results
+        .iter()
+        .for_each(|(_, hook)| hook(&snapshot));
+}
+
+pub(crate) fn reported(alert: &ActiveAlert) {
+    hooks().alerts.iter().for_each(|(_, hook)| hook(alert));
+}
+
+pub(crate) fn resolved(alert: &ActiveAlert) {
+    hooks().resolved.iter().for_each(|(_, hook)| hook(alert));
+}
+
+/// Calls the event hooks, with an event only created if there is any.
+pub(crate) fn emit(event: impl FnOnce() -> AlertEvent) {
+    let hooks = hooks();
+    if hooks.events.is_empty() {
+        return;
+    }
+    let event = event();
+    hooks.events.iter().for_each(|(_, hook)| hook(&event));
+}
@@ -0,0 +1,219 @@
+//! Runs every watcher once and exits with a code telling how bad it went, like Nagios plugins,
+//! e.g. from cron jobs or CI.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::alert_reporter::AlertReporter;
+use crate::alert_store::memory::{self, Memory};
+use crate::watcher::{
+    run_isolated, ActiveAlert, Severity, Watcher, WatcherConfiguration, WatcherEnum,
+};
+use crate::{composite, inhibition, maintenance, mute};
+
+fn default_exit_codes() -> BTreeMap<Severity, u8> {
+    BTreeMap::from([
+        (Severity::Info, 0),
+        (Severity::Warning, 1),
+        (Severity::Critical, 2),
+    ])
+}
+
+fn default_check_failure() -> u8 {
+    3
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// Exit code for each severity of alert, 0 for the ones not listed.
+    #[serde(default = "default_exit_codes")]
+    exit_codes: BTreeMap<Severity, u8>,
+    /// Exit code when a check failed, such as the `UNKNOWN` state of Nagios plugins.
+    #[serde(default = "default_check_failure")]
+    check_failure: u8,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            exit_codes: default_exit_codes(),
+            check_failure: default_check_failure(),
+        }
+    }
+}
+
+/// Passes alerts on, keeping the worst severity reported.
+struct Worst<'a> {
+    reporter: &'a dyn AlertReporter,
+    severity: Cell<Option<Severity>>,
+}
+
+impl Worst<'_> {
+    fn saw(&self, alert: &ActiveAlert) {
+        self.severity
+            .set(self.severity.get().max(Some(alert.severity)));
+    }
+}
+
+impl AlertReporter for Worst<'_> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.saw(alert);
+        self.reporter.report(alert)
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        alerts.iter().for_each(|a| self.saw(a));
+        self.reporter.report_batch(alerts)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.reporter.flush()
+    }
+}
+
+/// Runs every enabled watcher once, reporting the alerts they trigger, and returns the highest
+/// exit code among the severities of these alerts and the failed checks. Each run starts from
+/// scratch: alerts that must trigger for some time before firing never fire, and every alert
+/// triggered is reported, unless a maintenance window covers it, a rule mutes it or another one
+/// inhibits it, in which case it does not count in the exit code either. Composite alerts are
+/// evaluated once every watcher ran.
+pub fn run_once(
+    watchers: Vec<WatcherConfiguration>,
+    alert_reporter: &dyn AlertReporter,
+    maintenance: &maintenance::Configuration,
+    mute: &mute::Configuration,
+    inhibition: &inhibition::Configuration,
+    composite: &composite::Configuration,
+    configuration: &Configuration,
+) -> u8 {
+    let worst = Worst {
+        reporter: alert_reporter,
+        severity: Cell::new(None),
+    };
+    let muted = mute::Reporter::new(mute, &worst);
+    let maintenance = maintenance::Reporter::new(maintenance, &muted);
+    let inhibited = inhibition::Reporter::new(inhibition, &maintenance);
+    let composed = composite::Reporter::new(composite, &inhibited);
+    let alert_store = Memory::new(memory::Configuration::default());
+    let mut exit_code = 0;
+    for watcher in watchers {
+        let mut watcher = match WatcherEnum::try_from(watcher) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                error!(watcher = format!("{:#}", e));
+                exit_code = exit_code.max(configuration.check_failure);
+                continue;
+            }
+        };
+        if !watcher.is_enabled() {
+            continue;
+        }
+        if let Err(e) = run_isolated(&mut watcher, &composed, &alert_store) {
+            error!(watcher = format!("{:#}", e));
+            exit_code = exit_code.max(configuration.check_failure);
+        }
+    }
+    if let Err(e) = composed.flush() {
+        warn!(reporter_error = ?e);
+    }
+    let severity_code = worst
+        .severity
+        .get()
+        .and_then(|s| configuration.exit_codes.get(&s).copied())
+        .unwrap_or(0);
+    exit_code.max(severity_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use serde_json::json;
+
+    /// A memory watcher comparing the available memory, which is never negative, to 0 MB, and
+    /// alerting critical from `critical` MB.
+    fn memory(operator: &str, critical: Option<u64>) -> WatcherConfiguration {
+        serde_json::from_value(json!({ "Memory": {
+            "configuration": { "period": "1m" },
+            "alerts": [{
+                "threshold": { "metric": "Available", "value": 0, "unit": "MB", "operator": operator },
+                "critical": critical,
+            }]
+        } }))
+        .unwrap()
+    }
+
+    fn exit_code(watchers: Vec<WatcherConfiguration>, configuration: &Configuration) -> u8 {
+        run_once(
+            watchers,
+            &RecordingReporter::new(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            configuration,
+        )
+    }
+
+    #[test]
+    fn a_critical_alert_yields_the_critical_exit_code() {
+        let watchers = vec![memory(">=", None), memory(">=", Some(0))];
+
+        assert_eq!(exit_code(watchers, &Configuration::default()), 2);
+    }
+
+    #[test]
+    fn exit_codes_follow_the_table() {
+        let configuration = serde_json::from_value(json!({
+            "exit_codes": { "Warning": 10, "Critical": 20 }
+        }))
+        .unwrap();
+
+        assert_eq!(exit_code(vec![memory(">=", None)], &configuration), 10);
+        assert_eq!(exit_code(vec![memory("<", None)], &configuration), 0);
+    }
+
+    #[test]
+    fn alerts_are_reported_along_the_way() {
+        let reporter = RecordingReporter::new();
+
+        run_once(
+            vec![memory(">=", Some(0))],
+            &reporter,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            &Configuration::default(),
+        );
+
+        let alerts = reporter.alerts();
+        assert_eq!(alerts.len(), 1, "{:?}", alerts);
+        assert_eq!(alerts[0].severity, Severity::Critical);
+    }
+
+    #[test]
+    fn the_null_reporter_of_the_command_line_still_runs_the_checks() {
+        let reporter = crate::alert_reporter::from_flags("null", None).unwrap();
+
+        let exit_code = run_once(
+            vec![memory(">=", Some(0))],
+            reporter.as_ref(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            &Configuration::default(),
+        );
+
+        assert_eq!(exit_code, 2);
+    }
+}
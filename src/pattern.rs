@@ -0,0 +1,43 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Matches names either exactly or against a glob, where `*` matches any sequence of characters
+/// and `?` matches a single character.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Pattern {
+    Exact(String),
+    Glob(String),
+}
+
+impl Pattern {
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => exact == name,
+            Pattern::Glob(glob) => {
+                let glob = glob.chars().collect::<Vec<_>>();
+                let name = name.chars().collect::<Vec<_>>();
+                glob_matches(&glob, &name)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Exact(p) | Pattern::Glob(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+fn glob_matches(glob: &[char], name: &[char]) -> bool {
+    match (glob.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some(('*', rest)), _) => {
+            glob_matches(rest, name) || (!name.is_empty() && glob_matches(glob, &name[1..]))
+        }
+        (Some(('?', rest)), Some((_, name_rest))) => glob_matches(rest, name_rest),
+        (Some((g, rest)), Some((n, name_rest))) => g == n && glob_matches(rest, name_rest),
+        _ => false,
+    }
+}
@@ -0,0 +1,50 @@
+//! What programs embedding Monitrust need to build and run watchers in code, to be imported at
+//! once:
+//!
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! use monitrust::alert_store::memory::{self, Memory};
+//! use monitrust::prelude::*;
+//!
+//! /// Counts the alerts, where a program would show them.
+//! struct Counter(Arc<AtomicUsize>);
+//!
+//! impl AlertReporter for Counter {
+//!     fn report(&self, _alert: &ActiveAlert) -> anyhow::Result<()> {
+//!         self.0.fetch_add(1, Ordering::Relaxed);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let heartbeat: WatcherConfiguration = serde_json::from_str(
+//!     r#"{ "Heartbeat": { "configuration": { "period_hours": 24 }, "alerts": [{}] } }"#,
+//! )?;
+//! let reported = Arc::new(AtomicUsize::new(0));
+//! let set = WatcherSetBuilder::new()
+//!     .watcher(heartbeat)
+//!     .reporter("counter", Counter(Arc::clone(&reported)))
+//!     .build()?;
+//!
+//! // A `Scheduler` runs the watchers at their period, this runs them once.
+//! let alert_store = Memory::new(memory::Configuration::default());
+//! for configuration in set.watchers {
+//!     let mut watcher = WatcherEnum::try_from(configuration)?;
+//!     watcher.run(&set.reporter, &alert_store)?;
+//! }
+//! assert_eq!(reported.load(Ordering::Relaxed), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::alert_reporter::{AlertChange, AlertReporter, CompositeReporter};
+pub use crate::alert_store::AlertStore;
+pub use crate::scheduler::{Scheduler, SchedulerHandle};
+pub use crate::watcher::state::AlertConfiguration;
+pub use crate::watcher::{
+    ActiveAlert, Alert, Checker, MultiWatcher, SerializedMultiWatcher, Severity, Timestamped,
+    Watcher, WatcherConfiguration, WatcherEnum,
+};
+pub use crate::watcher_set::{WatcherSet, WatcherSetBuilder};
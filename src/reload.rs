@@ -0,0 +1,554 @@
+//! Reads the watcher configuration, and reloads it when its files change or when Monitrust
+//! receives SIGHUP. Configurations given as an `http://` or `https://` URL are fetched, see
+//! [`remote_config`](crate::remote_config).
+
+use std::fs;
+#[cfg(feature = "nix")]
+use std::os::unix::thread::JoinHandleExt;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "nix")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "nix")]
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+#[cfg(feature = "nix")]
+use nix::sys::pthread::pthread_kill;
+#[cfg(feature = "nix")]
+use nix::sys::signal::{SigSet, Signal};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::duration::deserialize_duration;
+use crate::scheduler::SchedulerHandle;
+use crate::watcher::WatcherConfiguration;
+
+fn default_interval() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Time for the notifications of a change to arrive, before reading the files again.
+#[cfg(feature = "notify")]
+const SETTLING_TIME: Duration = Duration::from_millis(100);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// How often to check whether the configuration files changed, when the file system can not
+    /// notify their changes.
+    #[serde(
+        default = "default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    interval: Duration,
+}
+
+#[serde_with::serde_as]
+#[derive(Deserialize)]
+struct WatcherConfigurations(#[serde_as(as = "serde_with::EnumMap")] Vec<WatcherConfiguration>);
+
+/// Formats a watcher configuration can be written in. In all of them, watchers are a map from
+/// their kind, such as `DiskSpace`, to their configuration, and other enums are either the name
+/// of a variant or a map with this name as its single key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// A kind can only appear once per file, as tables can not be repeated.
+    #[cfg(feature = "toml")]
+    Toml,
+}
+
+impl Format {
+    /// The format of a file, from its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Format::Yaml),
+            #[cfg(feature = "toml")]
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn deserialize<T: DeserializeOwned>(&self, text: &str) -> Result<T> {
+        Ok(match self {
+            Format::Json => serde_json::from_str(text)?,
+            // YAML has its own syntax for enums, `!Variant value`, which should not be needed to
+            // write a configuration that works in JSON.
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_json::from_value(serde_yaml::from_str(text)?)?,
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::from_str(text)?,
+        })
+    }
+}
+
+/// Parses watcher configurations written in the given format.
+pub fn parse_config(text: &str, format: Format) -> Result<Vec<WatcherConfiguration>> {
+    format
+        .deserialize::<WatcherConfigurations>(text)
+        .map(|configurations| configurations.0)
+}
+
+/// The URL a configuration path stands for, if it is one rather than a file.
+pub fn remote_url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|p| p.starts_with("http://") || p.starts_with("https://"))
+}
+
+/// Reads a configuration file in the format of its extension, JSON by default, or fetches it if
+/// it is a URL.
+pub(crate) fn read_config<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    if let Some(url) = remote_url(path) {
+        #[cfg(feature = "http")]
+        return crate::remote_config::read_config(url);
+        #[cfg(not(feature = "http"))]
+        return Err(anyhow::anyhow!(
+            "Monitrust was built without the 'http' feature, needed to fetch {}",
+            url
+        ));
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Could not open file: {}", path.display()))?;
+    Format::from_path(path)
+        .unwrap_or(Format::Json)
+        .deserialize(&text)
+        .with_context(|| format!("Could not parse file: {}", path.display()))
+}
+
+/// Files a configuration path stands for: the path itself, or the files of a directory in a known
+/// format, sorted by name.
+pub fn config_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = fs::read_dir(path)
+        .with_context(|| format!("Could not read directory: {}", path.display()))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let file = entry
+            .with_context(|| format!("Could not read directory: {}", path.display()))?
+            .path();
+        if file.is_file() && Format::from_path(&file).is_some() {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Drops the configurations equal to an earlier one, keeping the order of the other ones.
+fn deduplicate(configurations: Vec<WatcherConfiguration>) -> Vec<WatcherConfiguration> {
+    let mut unique = Vec::new();
+    for configuration in configurations {
+        if !unique.contains(&configuration) {
+            unique.push(configuration);
+        }
+    }
+    unique
+}
+
+/// Reads and merges the watcher configurations of the files of a directory, in the order of their
+/// names, dropping duplicated ones.
+pub fn load_config_dir(path: &Path) -> Result<Vec<WatcherConfiguration>> {
+    read_watchers(&[path.to_path_buf()])
+}
+
+/// Reads and merges the watcher configurations of several files or directories, dropping
+/// duplicated ones.
+pub fn read_watchers(paths: &[PathBuf]) -> Result<Vec<WatcherConfiguration>> {
+    let mut configurations = Vec::new();
+    for path in paths {
+        for file in config_files(path)? {
+            configurations.extend(read_config::<WatcherConfigurations>(&file)?.0);
+        }
+    }
+    Ok(deduplicate(configurations))
+}
+
+/// Modification times of the configuration files, which also change when a file is added to or
+/// removed from a directory. Remote configurations are only fetched again along with a change of
+/// the files, or on SIGHUP.
+fn modified(paths: &[PathBuf]) -> Result<Vec<(PathBuf, SystemTime)>> {
+    let mut modified = Vec::new();
+    for path in paths.iter().filter(|p| remote_url(p).is_none()) {
+        for file in config_files(path)? {
+            let time = fs::metadata(&file)
+                .and_then(|m| m.modified())
+                .with_context(|| {
+                    format!("Could not get modification time of {}", file.display())
+                })?;
+            modified.push((file, time));
+        }
+    }
+    Ok(modified)
+}
+
+/// Reads the watcher configuration files again and reconfigures the scheduler with them, see
+/// [`SchedulerHandle::reconfigure`]. A configuration that can not be read is logged and ignored,
+/// so that the previous one stays active. Fails once the scheduler is gone.
+pub fn reload(paths: &[PathBuf], scheduler: &SchedulerHandle) -> Result<()> {
+    match read_watchers(paths) {
+        Ok(configurations) => {
+            info!(reloading = ?paths);
+            scheduler.reconfigure(configurations)
+        }
+        Err(e) => {
+            warn!(reload_error = ?e, "Keeping the previous watcher configuration");
+            Ok(())
+        }
+    }
+}
+
+/// Whether the modification times of the configuration files changed since `last_modified`,
+/// which is updated.
+fn changed(paths: &[PathBuf], last_modified: &mut Option<Vec<(PathBuf, SystemTime)>>) -> bool {
+    let modified = match modified(paths) {
+        Ok(modified) => modified,
+        Err(e) => {
+            warn!(reload_error = ?e);
+            return false;
+        }
+    };
+    if last_modified.as_ref() == Some(&modified) {
+        return false;
+    }
+    *last_modified = Some(modified);
+    true
+}
+
+/// Directories to watch for changes of the configuration files: the configuration directories,
+/// and those of the configuration files, as editors often replace a file rather than write it.
+#[cfg(feature = "notify")]
+fn watched_directories(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut directories = Vec::new();
+    for path in paths.iter().filter(|p| remote_url(p).is_none()) {
+        let directory = match path.parent() {
+            _ if path.is_dir() => path.as_path(),
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if !directories.iter().any(|d| d == directory) {
+            directories.push(directory.to_path_buf());
+        }
+    }
+    directories
+}
+
+/// Reconfigures the scheduler whenever the file system notifies a change of the configuration
+/// files, until it is gone. Fails if their directories can not be watched.
+#[cfg(feature = "notify")]
+fn watch_notifications(paths: Vec<PathBuf>, scheduler: SchedulerHandle) -> notify::Result<()> {
+    use notify::{RecursiveMode, Watcher};
+
+    let (sender, notifications) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    for directory in watched_directories(&paths) {
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+    }
+    let mut last_modified = modified(&paths).ok();
+    thread::spawn(move || {
+        // Notifications stop once the watcher is dropped.
+        let _watcher = watcher;
+        while let Ok(notification) = notifications.recv() {
+            if let Err(e) = notification {
+                warn!(reload_error = ?e);
+                continue;
+            }
+            // Saving a file takes several notifications, the last ones are part of this change.
+            thread::sleep(SETTLING_TIME);
+            while notifications.try_recv().is_ok() {}
+            // Other files of the directories change too.
+            if changed(&paths, &mut last_modified) && reload(&paths, &scheduler).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Polls the modification times of the watcher configuration files, and reconfigures the
+/// scheduler whenever one of them changes, until it is gone.
+fn poll(paths: Vec<PathBuf>, interval: Duration, scheduler: SchedulerHandle) {
+    let mut last_modified = modified(&paths).ok();
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        if changed(&paths, &mut last_modified) && reload(&paths, &scheduler).is_err() {
+            break;
+        }
+    });
+}
+
+/// Reconfigures the scheduler whenever one of the watcher configuration files changes, as soon as
+/// the file system notifies it, or else by polling their modification times. A configuration that
+/// can not be read is logged and ignored, so that the previous one stays active. Stops once the
+/// scheduler is gone.
+pub fn watch(paths: Vec<PathBuf>, configuration: &Configuration, scheduler: SchedulerHandle) {
+    #[cfg(feature = "notify")]
+    match watch_notifications(paths.clone(), scheduler.clone()) {
+        Ok(()) => return,
+        Err(e) => warn!(reload_error = ?e, "Polling the watcher configuration for changes"),
+    }
+    poll(paths, configuration.interval, scheduler);
+}
+
+/// Reloads the watcher configuration whenever Monitrust receives SIGHUP, until it is removed.
+#[cfg(feature = "nix")]
+pub struct SighupReload {
+    removed: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+
+#[cfg(feature = "nix")]
+impl SighupReload {
+    /// Starts reloading the configuration on SIGHUP, see [`reload`]. Must be called before any
+    /// other thread is spawned, so that they all inherit the signal mask and the signal gets
+    /// delivered here rather than terminating Monitrust.
+    pub fn install(paths: Vec<PathBuf>, scheduler: SchedulerHandle) -> Result<Self> {
+        let mut signals = SigSet::empty();
+        signals.add(Signal::SIGHUP);
+        signals
+            .thread_block()
+            .context("Could not block reload signal")?;
+        let removed = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&removed);
+        let thread = thread::spawn(move || loop {
+            match signals.wait() {
+                Ok(_) if stop.load(Ordering::SeqCst) => break,
+                Ok(signal) => {
+                    info!(received_signal = ?signal);
+                    if reload(&paths, &scheduler).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    warn!(signal_error = ?e);
+                    break;
+                }
+            }
+        });
+        Ok(SighupReload { removed, thread })
+    }
+
+    /// Stops reloading the configuration on SIGHUP. The signal stays blocked, so that it is
+    /// ignored rather than terminating Monitrust.
+    pub fn remove(self) {
+        self.removed.store(true, Ordering::SeqCst);
+        // The thread already stopped if the signal can not be sent to it.
+        if pthread_kill(self.thread.as_pthread_t(), Signal::SIGHUP).is_ok() {
+            let _ = self.thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::Scheduler;
+    use serde_json::json;
+
+    /// A directory of its own for a test, emptied first.
+    fn directory(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("monitrust-config-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn memory(trigger_threshold: f64) -> serde_json::Value {
+        json!({
+            "configuration": { "period": "1m" },
+            "alerts": [{ "trigger_threshold": trigger_threshold }]
+        })
+    }
+
+    #[test]
+    fn merges_the_files_of_a_directory_without_duplicates() {
+        let directory = directory("merge");
+        let base = json!({ "Memory": memory(0.1), "Swap": {
+            "configuration": { "period": "1m" },
+            "alerts": [{ "metric": "UsedFraction", "threshold": 0.5 }]
+        } });
+        let role = json!({ "Memory": memory(0.1), "Cpu": {
+            "configuration": { "period": "1m", "mode": "Average" },
+            "alerts": [{ "threshold": 0.9 }]
+        } });
+        fs::write(directory.join("20-role.json"), role.to_string()).unwrap();
+        fs::write(directory.join("10-base.json"), base.to_string()).unwrap();
+        fs::write(directory.join("README.md"), "Not a configuration").unwrap();
+
+        let configurations = load_config_dir(&directory).unwrap();
+
+        let kinds = configurations
+            .iter()
+            .map(|c| match c {
+                WatcherConfiguration::Memory(_) => "Memory",
+                WatcherConfiguration::Swap(_) => "Swap",
+                WatcherConfiguration::Cpu(_) => "Cpu",
+                _ => "Other",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, ["Memory", "Swap", "Cpu"]);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn watchers_of_several_paths_are_merged() {
+        let directory = directory("paths");
+        let (base, role) = (directory.join("base.json"), directory.join("role.json"));
+        fs::write(&base, json!({ "Memory": memory(0.1) }).to_string()).unwrap();
+        fs::write(&role, json!({ "Memory": memory(0.2) }).to_string()).unwrap();
+
+        assert_eq!(read_watchers(&[base.clone(), role]).unwrap().len(), 2);
+        assert_eq!(read_watchers(&[base.clone(), base]).unwrap().len(), 1);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn parse_errors_name_the_file() {
+        let directory = directory("invalid");
+        fs::write(directory.join("10-base.json"), json!({}).to_string()).unwrap();
+        fs::write(directory.join("20-role.json"), "{ \"Memory\": ").unwrap();
+
+        let error = load_config_dir(&directory).unwrap_err();
+
+        assert!(error.to_string().contains("20-role.json"), "{:#}", error);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    /// The same memory watcher in JSON, with a variant name as its metric.
+    #[cfg(any(feature = "yaml", feature = "toml"))]
+    fn memory_json() -> &'static str {
+        r#"{ "Memory": {
+            "configuration": { "period": "5m" },
+            "alerts": [{ "threshold": { "metric": "Available", "value": 512, "unit": "MiB" } }]
+        } }"#
+    }
+
+    #[test]
+    fn formats_are_picked_from_the_extension() {
+        assert_eq!(
+            Format::from_path(Path::new("watchers.json")),
+            Some(Format::Json)
+        );
+        assert_eq!(Format::from_path(Path::new("README.md")), None);
+        #[cfg(feature = "yaml")]
+        for file in ["watchers.yaml", "watchers.yml"] {
+            assert_eq!(Format::from_path(Path::new(file)), Some(Format::Yaml));
+        }
+        #[cfg(feature = "toml")]
+        assert_eq!(
+            Format::from_path(Path::new("watchers.toml")),
+            Some(Format::Toml)
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_reads_like_json() {
+        let yaml = "
+Memory:
+  configuration:
+    period: 5m
+  alerts:
+    - threshold: { metric: Available, value: 512, unit: MiB }
+";
+        assert_eq!(
+            parse_config(yaml, Format::Yaml).unwrap(),
+            parse_config(memory_json(), Format::Json).unwrap()
+        );
+    }
+
+    #[cfg(all(feature = "yaml", feature = "nix"))]
+    #[test]
+    fn yaml_writes_variants_with_values_as_maps() {
+        let yaml = "
+DiskSpace:
+  configuration:
+    period: 5m
+    mounts:
+      Discover:
+        exclude: ['/boot*']
+  alerts:
+    - min: 0.0
+      trigger_threshold: 0.05
+";
+        let json = r#"{ "DiskSpace": {
+            "configuration": { "period": "5m", "mounts": { "Discover": { "exclude": ["/boot*"] } } },
+            "alerts": [{ "min": 0.0, "trigger_threshold": 0.05 }]
+        } }"#;
+        assert_eq!(
+            parse_config(yaml, Format::Yaml).unwrap(),
+            parse_config(json, Format::Json).unwrap()
+        );
+        let tagged = yaml.replace(
+            "      Discover:\n        exclude",
+            "      !Discover\n        exclude",
+        );
+        assert!(parse_config(&tagged, Format::Yaml).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_reads_like_json() {
+        let toml = r#"
+[Memory]
+configuration = { period = "5m" }
+alerts = [{ threshold = { metric = "Available", value = 512, unit = "MiB" } }]
+"#;
+        assert_eq!(
+            parse_config(toml, Format::Toml).unwrap(),
+            parse_config(memory_json(), Format::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn an_unreadable_configuration_keeps_the_previous_one() {
+        let directory = directory("unreadable");
+        let paths = vec![directory.join("watchers.json")];
+        fs::write(&paths[0], json!({ "Memory": memory(0.1) }).to_string()).unwrap();
+        let settings = serde_json::from_value(json!({ "self_monitor": false })).unwrap();
+        let (mut scheduler, handle) =
+            Scheduler::new(read_watchers(&paths).unwrap(), &settings).unwrap();
+        let previous = scheduler.scheduled();
+
+        fs::write(&paths[0], "{ \"Memory\": ").unwrap();
+        reload(&paths, &handle).unwrap();
+        scheduler.apply_reconfigurations();
+
+        assert_eq!(scheduler.scheduled(), previous);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[cfg(feature = "nix")]
+    #[test]
+    fn sighup_reloads_the_watchers() {
+        let directory = directory("sighup");
+        let paths = vec![directory.join("watchers.json")];
+        fs::write(&paths[0], json!({ "Memory": memory(0.1) }).to_string()).unwrap();
+        let settings = serde_json::from_value(json!({ "self_monitor": false })).unwrap();
+        let (mut scheduler, handle) =
+            Scheduler::new(read_watchers(&paths).unwrap(), &settings).unwrap();
+        let sighup = SighupReload::install(paths.clone(), handle).unwrap();
+
+        fs::write(&paths[0], json!({ "Memory": memory(0.2) }).to_string()).unwrap();
+        let expected = read_watchers(&paths).unwrap();
+        // Sent to the thread waiting for it only, the other threads of the tests blocking it.
+        pthread_kill(sighup.thread.as_pthread_t(), Signal::SIGHUP).unwrap();
+        let started = std::time::Instant::now();
+        while scheduler.scheduled() != expected && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+            scheduler.apply_reconfigurations();
+        }
+        sighup.remove();
+
+        assert_eq!(scheduler.scheduled(), expected);
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,215 @@
+//! Watcher configurations fetched over HTTP, such as `--config
+//! https://config.example.com/monitrust/web-1.json`, from a central configuration service. The
+//! last copy of each that could be parsed is cached, and used when the service can not be
+//! reached.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::duration::deserialize_duration;
+use crate::reload::Format;
+use crate::secret::Secret;
+use crate::stable_hash;
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_cache_dir() -> PathBuf {
+    PathBuf::from("config-cache")
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// Headers sent along with each request, such as `Authorization`.
+    #[serde(default)]
+    headers: HashMap<String, Secret>,
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+    /// Directory keeping the last copy of each configuration that could be parsed.
+    #[serde(default = "default_cache_dir")]
+    cache_dir: PathBuf,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            headers: HashMap::new(),
+            timeout: default_timeout(),
+            cache_dir: default_cache_dir(),
+        }
+    }
+}
+
+static CONFIGURATION: OnceLock<Configuration> = OnceLock::new();
+
+/// Fetches the remote configurations from now on with these headers and timeout. Only the first
+/// configuration is kept, and reloads keep fetching with it until Monitrust restarts.
+pub fn init(configuration: Configuration) {
+    let _ = CONFIGURATION.set(configuration);
+}
+
+fn configuration() -> &'static Configuration {
+    CONFIGURATION.get_or_init(Configuration::default)
+}
+
+/// The format of a remote configuration, from the extension of its URL, JSON by default.
+fn format(url: &str) -> Format {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    Format::from_path(Path::new(path)).unwrap_or(Format::Json)
+}
+
+/// Name of the cache file of the configuration at `url`: the URL, readable once its other
+/// characters are replaced, followed by a hash of the whole URL so that URLs replaced the same
+/// way, such as `https://a/b?c` and `https://a/b_c`, do not share a file.
+fn cache_name(url: &str) -> String {
+    let readable = url
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    format!("{}-{:016x}", readable, stable_hash::hash(url))
+}
+
+/// Where the last good copy of the configuration at `url` is cached, named after the URL.
+fn cache_path(url: &str) -> PathBuf {
+    configuration().cache_dir.join(cache_name(url))
+}
+
+fn fetch(url: &str) -> Result<String> {
+    let configuration = configuration();
+    let client = Client::builder()
+        .timeout(configuration.timeout)
+        .build()
+        .context("Could not create HTTP client.")?;
+    let mut request = client.get(url);
+    for (name, value) in &configuration.headers {
+        request = request.header(name, value.expose());
+    }
+    let response = request
+        .send()
+        .with_context(|| format!("Could not fetch configuration at {}", url))?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Configuration service answered {} at {}",
+            response.status(),
+            url
+        ));
+    }
+    response
+        .text()
+        .with_context(|| format!("Could not read configuration at {}", url))
+}
+
+/// Writes the cached copy through a temporary file, so that a crash never leaves half of it.
+fn cache(url: &str, text: &str) -> Result<()> {
+    let path = cache_path(url);
+    let temporary = path.with_extension("tmp");
+    fs::create_dir_all(&configuration().cache_dir)
+        .and_then(|()| fs::write(&temporary, text))
+        .and_then(|()| fs::rename(&temporary, &path))
+        .with_context(|| format!("Could not cache configuration at {}", path.display()))
+}
+
+/// Fetches and parses the configuration at `url`, caching it once parsed. When it can not be
+/// fetched or parsed, the cached copy is used instead with a warning, and only fails if there is
+/// none.
+pub fn read_config<T: DeserializeOwned>(url: &str) -> Result<T> {
+    let format = format(url);
+    let fetched = fetch(url).and_then(|text| {
+        let parsed = format
+            .deserialize(&text)
+            .with_context(|| format!("Could not parse configuration at {}", url))?;
+        Ok((text, parsed))
+    });
+    let error = match fetched {
+        Ok((text, parsed)) => {
+            if let Err(e) = cache(url, &text) {
+                warn!(remote_config_error = ?e);
+            }
+            return Ok(parsed);
+        }
+        Err(e) => e,
+    };
+    let path = cache_path(url);
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Err(error.context(format!("No cached copy at {} either", path.display())));
+    };
+    let parsed = format
+        .deserialize(&text)
+        .with_context(|| format!("Could not parse cached configuration {}", path.display()))?;
+    warn!(remote_config_error = ?error, cached = %path.display(), "Using the cached configuration");
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_replaced_the_same_way_keep_their_own_cache() {
+        assert_ne!(cache_name("https://a/b?c"), cache_name("https://a/b_c"));
+        // The same across restarts and upgrades, to find the copy cached by a previous process.
+        assert_eq!(
+            cache_name("https://a/b?c"),
+            "https___a_b_c-092424648774b472"
+        );
+    }
+
+    /// Caches into a directory of this test process, the first configuration being the only one
+    /// kept.
+    fn init_cache() {
+        let cache_dir =
+            std::env::temp_dir().join(format!("monitrust-config-cache-{}", std::process::id()));
+        init(Configuration {
+            cache_dir,
+            ..Configuration::default()
+        });
+    }
+
+    /// URL of a configuration on a port nobody listens on, so that fetching it fails.
+    fn unreachable(name: &str) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        format!("http://{}/{}.json", address, name)
+    }
+
+    #[test]
+    fn the_cached_copy_is_used_when_the_fetch_fails() {
+        init_cache();
+        let url = unreachable("cached");
+        cache(&url, r#"{ "period": "1m" }"#).unwrap();
+
+        let config = read_config::<serde_json::Value>(&url).unwrap();
+
+        assert_eq!(config, serde_json::json!({ "period": "1m" }));
+        let _ = fs::remove_file(cache_path(&url));
+    }
+
+    #[test]
+    fn fails_with_both_errors_without_a_cached_copy() {
+        init_cache();
+        let url = unreachable("uncached");
+
+        let error = format!("{:#}", read_config::<serde_json::Value>(&url).unwrap_err());
+
+        assert!(error.contains("No cached copy at"), "{}", error);
+        assert!(
+            error.contains("Could not fetch configuration at"),
+            "{}",
+            error
+        );
+    }
+}
@@ -1,12 +1,28 @@
 use std::cmp::Ordering;
 use std::time::Instant;
 
-use crate::watcher::WatcherEnum;
+use anyhow::Result;
+
+use crate::watcher::{Watcher, WatcherConfiguration, WatcherEnum};
 
 #[derive(Debug)]
 pub struct ScheduledWatcher {
     pub deadline: Instant,
     pub watcher: WatcherEnum,
+    /// Configuration the watcher was created from, to tell whether a new one changes it.
+    pub configuration: WatcherConfiguration,
+}
+
+impl ScheduledWatcher {
+    /// A watcher first due its initial delay after `scheduled_at`.
+    pub fn try_new(configuration: WatcherConfiguration, scheduled_at: Instant) -> Result<Self> {
+        let watcher: WatcherEnum = configuration.clone().try_into()?;
+        Ok(ScheduledWatcher {
+            deadline: scheduled_at + watcher.initial_delay(),
+            watcher,
+            configuration,
+        })
+    }
 }
 
 impl Eq for ScheduledWatcher {}
@@ -0,0 +1,653 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::collections::BinaryHeap;
+use std::hash::{BuildHasher, Hasher};
+use std::mem;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, Scope};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tracing::{error, info, info_span, warn, Span};
+
+use crate::alert_reporter::AlertReporter;
+use crate::alert_store::AlertStore;
+use crate::clock::{Clock, SystemClock};
+use crate::composite;
+use crate::duration::deserialize_duration;
+use crate::inhibition;
+use crate::maintenance;
+use crate::mute;
+use crate::scheduled_watcher::ScheduledWatcher;
+use crate::startup_grace;
+use crate::status::{self, Lag};
+use crate::watcher::{run_isolated, self_monitor, ActiveAlert, Watcher, WatcherConfiguration};
+
+#[derive(Debug)]
+enum Command {
+    Shutdown,
+    Reconfigure(Vec<WatcherConfiguration>),
+    /// An alert that no watcher triggered, to try the reporters out.
+    Report(Box<ActiveAlert>),
+    /// A watcher completed its run, on a thread of its own.
+    Finished(Box<ScheduledWatcher>),
+}
+
+/// Handle used to control a running [`Scheduler`]. The scheduler also stops once every handle
+/// has been dropped.
+#[derive(Clone, Debug)]
+pub struct SchedulerHandle(Sender<Command>);
+
+impl SchedulerHandle {
+    pub fn shutdown(&self) {
+        // The scheduler may already be gone, in which case there is nothing left to stop.
+        let _ = self.0.send(Command::Shutdown);
+    }
+
+    /// Replaces the running watchers. Watchers whose configuration did not change keep running
+    /// with their state, the other ones are replaced by new watchers running right away, or after
+    /// their `initial_delay`.
+    pub fn reconfigure(&self, configurations: Vec<WatcherConfiguration>) -> Result<()> {
+        self.0
+            .send(Command::Reconfigure(configurations))
+            .map_err(|_| anyhow!("Scheduler is not running anymore"))
+    }
+
+    /// Reports an alert to the reporters of the running scheduler, along their routes but
+    /// bypassing maintenance windows, mute and inhibition rules, e.g. a synthetic alert checking
+    /// that the reporters are configured correctly.
+    pub fn report(&self, alert: ActiveAlert) -> Result<()> {
+        self.0
+            .send(Command::Report(Box::new(alert)))
+            .map_err(|_| anyhow!("Scheduler is not running anymore"))
+    }
+}
+
+fn default_workers() -> usize {
+    4
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// Fraction of its period by which each run of a watcher is randomly brought forward or
+    /// delayed, so that watchers with the same period do not all run at once. Between 0, the
+    /// default, and 1.
+    #[serde(default)]
+    jitter: f64,
+    /// How many watchers can run at once, so that a slow check does not delay the other ones.
+    #[serde(default = "default_workers")]
+    workers: usize,
+    /// Run a `SelfMonitor` watcher alerting when the scheduler falls behind, unless one is
+    /// configured along with the other watchers.
+    #[serde(default = "default_self_monitor")]
+    self_monitor: bool,
+    /// How long after startup alerts are held back, while the checks run, so that transient
+    /// conditions do not page. Alerts still firing once it elapsed are reported then.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    startup_grace: Duration,
+}
+
+fn default_self_monitor() -> bool {
+    true
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            jitter: 0.0,
+            workers: default_workers(),
+            self_monitor: default_self_monitor(),
+            startup_grace: Duration::ZERO,
+        }
+    }
+}
+
+/// Runs each watcher at its own period, until it is asked to shut down.
+#[derive(Debug)]
+pub struct Scheduler {
+    timers: BinaryHeap<Reverse<ScheduledWatcher>>,
+    /// Watchers that are configured but never run, kept so that they show in the status.
+    disabled: Vec<ScheduledWatcher>,
+    /// Configurations of the watchers running right now.
+    running: Vec<WatcherConfiguration>,
+    /// Configurations of running watchers that were removed by a reconfiguration, and are
+    /// dropped once their run completes.
+    retired: Vec<WatcherConfiguration>,
+    commands: Receiver<Command>,
+    jitter: f64,
+    workers: usize,
+    self_monitor: bool,
+    startup_grace: Duration,
+    /// Clock the startup grace is timed with.
+    clock: Arc<dyn Clock>,
+    /// Span of the current cycle, covering the runs of the watchers that were due together.
+    cycle: Option<Span>,
+}
+
+impl Scheduler {
+    pub fn new(
+        configurations: Vec<WatcherConfiguration>,
+        configuration: &Configuration,
+    ) -> Result<(Self, SchedulerHandle)> {
+        let now = Instant::now();
+        let (sender, receiver) = channel();
+        let mut scheduler = Scheduler {
+            timers: BinaryHeap::new(),
+            disabled: Vec::new(),
+            running: Vec::new(),
+            retired: Vec::new(),
+            commands: receiver,
+            jitter: configuration.jitter.clamp(0.0, 1.0),
+            workers: configuration.workers.max(1),
+            self_monitor: configuration.self_monitor,
+            startup_grace: configuration.startup_grace,
+            clock: Arc::new(SystemClock),
+            cycle: None,
+        };
+        for configuration in scheduler.with_self_monitor(configurations) {
+            scheduler.schedule(ScheduledWatcher::try_new(configuration, now)?);
+        }
+        scheduler.warn_if_idle();
+        Ok((scheduler, SchedulerHandle(sender)))
+    }
+
+    /// Times the startup grace with `clock` rather than with the system, e.g. to test it.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Warns when no watcher is left to run besides the self-monitor, as when the configuration
+    /// is empty or every watcher is disabled. The scheduler then waits for a reconfiguration
+    /// without waking up, or only for the self-monitor.
+    fn warn_if_idle(&self) {
+        let is_watching = self
+            .timers
+            .iter()
+            .map(|Reverse(s)| &s.configuration)
+            .chain(self.running.iter().filter(|c| !self.retired.contains(c)))
+            .any(|c| !matches!(c, WatcherConfiguration::SelfMonitor(_)));
+        if !is_watching {
+            warn!(
+                disabled = self.disabled.len(),
+                "No watcher is enabled, waiting for a reconfiguration"
+            );
+        }
+    }
+
+    fn schedule(&mut self, scheduled: ScheduledWatcher) {
+        match scheduled.watcher.is_enabled() {
+            true => self.timers.push(Reverse(scheduled)),
+            false => self.disabled.push(scheduled),
+        }
+    }
+
+    /// Span of the current cycle, starting one with a new correlation id if none is running.
+    fn cycle(&mut self) -> Span {
+        self.cycle
+            .get_or_insert_with(|| {
+                let id = RandomState::new().build_hasher().finish();
+                info_span!("cycle", cycle_id = format!("{:016x}", id))
+            })
+            .clone()
+    }
+
+    /// The configured watchers, along with the built-in self-monitor unless it is disabled or
+    /// one is configured, see [`self_monitor::built_in`].
+    fn with_self_monitor(
+        &self,
+        mut configurations: Vec<WatcherConfiguration>,
+    ) -> Vec<WatcherConfiguration> {
+        let configured = configurations
+            .iter()
+            .any(|c| matches!(c, WatcherConfiguration::SelfMonitor(_)));
+        if self.self_monitor && !configured {
+            configurations.push(self_monitor::built_in());
+        }
+        configurations
+    }
+
+    /// Delay until the next run of a watcher. The jitter is drawn anew for each run, uniformly
+    /// between minus and plus the jitter fraction of the period.
+    fn next_delay(&self, period: Duration) -> Duration {
+        if self.jitter == 0.0 {
+            return period;
+        }
+        let uniform = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        period.mul_f64(1.0 + self.jitter * (2.0 * uniform - 1.0))
+    }
+
+    /// Replaces the watchers whose configuration changed. The new ones are all built first, so
+    /// that if any is invalid the previous configuration keeps running untouched.
+    fn reconfigure(&mut self, configurations: Vec<WatcherConfiguration>) {
+        let configurations = self.with_self_monitor(configurations);
+        let now = Instant::now();
+        let is_scheduled = |c: &WatcherConfiguration| {
+            self.timers.iter().any(|Reverse(s)| s.configuration == *c)
+                || self.disabled.iter().any(|s| s.configuration == *c)
+                || self.running.contains(c)
+        };
+        let added = configurations
+            .iter()
+            .filter(|c| !is_scheduled(c))
+            .map(|c| ScheduledWatcher::try_new(c.clone(), now))
+            .collect::<Result<Vec<_>>>();
+        let added = match added {
+            Ok(added) => added,
+            Err(e) => {
+                warn!(reload_error = ?e, "Keeping the previous watcher configuration");
+                return;
+            }
+        };
+        let (mut kept, mut removed) = (0, 0);
+        let scheduled = mem::take(&mut self.timers)
+            .into_iter()
+            .map(|Reverse(s)| s)
+            .chain(mem::take(&mut self.disabled));
+        for scheduled in scheduled {
+            match configurations.contains(&scheduled.configuration) {
+                true => {
+                    self.schedule(scheduled);
+                    kept += 1;
+                }
+                false => removed += 1,
+            }
+        }
+        for running in &self.running {
+            match configurations.contains(running) {
+                true => {
+                    self.retired.retain(|r| r != running);
+                    kept += 1;
+                }
+                false if !self.retired.contains(running) => {
+                    self.retired.push(running.clone());
+                    removed += 1;
+                }
+                false => {}
+            }
+        }
+        let added_count = added.len();
+        for scheduled in added {
+            self.schedule(scheduled);
+        }
+        info!(
+            kept,
+            added = added_count,
+            removed,
+            disabled = self.disabled.len(),
+            "Reconfigured watchers"
+        );
+        self.warn_if_idle();
+    }
+
+    /// Runs the next watchers that are due, each on a thread of its own, as long as fewer than
+    /// `workers` are running. How late each run starts is recorded for the self-monitor.
+    fn start_due<'scope, R: AlertReporter + Sync>(
+        &mut self,
+        scope: &'scope Scope<'scope, '_>,
+        finished: &Sender<Command>,
+        alert_reporter: &'scope R,
+        alert_store: &'scope dyn AlertStore,
+    ) {
+        let now = Instant::now();
+        while self.running.len() < self.workers
+            && self
+                .timers
+                .peek()
+                .is_some_and(|Reverse(next)| next.deadline <= now)
+        {
+            let Some(Reverse(mut next)) = self.timers.pop() else {
+                return;
+            };
+            status::record_lag(Lag {
+                at: now,
+                kind: next.watcher.kind(),
+                lag: now.saturating_duration_since(next.deadline),
+                period: next.watcher.period(),
+            });
+            self.running.push(next.configuration.clone());
+            let finished = finished.clone();
+            let cycle = self.cycle();
+            scope.spawn(move || {
+                let _cycle = cycle.enter();
+                // A failing or panicking watcher must not stop the other ones.
+                if let Err(e) = run_isolated(&mut next.watcher, alert_reporter, alert_store) {
+                    error!(watcher = format!("{:#}", e));
+                }
+                let _ = finished.send(Command::Finished(Box::new(next)));
+            });
+        }
+    }
+
+    /// Schedules the next run of a watcher that completed, unless it was removed meanwhile.
+    fn finish(&mut self, mut scheduled: ScheduledWatcher) {
+        if let Some(index) = self
+            .running
+            .iter()
+            .position(|c| *c == scheduled.configuration)
+        {
+            self.running.swap_remove(index);
+        }
+        if let Some(index) = self
+            .retired
+            .iter()
+            .position(|c| *c == scheduled.configuration)
+        {
+            self.retired.swap_remove(index);
+            return;
+        }
+        scheduled.deadline = Instant::now() + self.next_delay(scheduled.watcher.period());
+        self.timers.push(Reverse(scheduled));
+    }
+
+    /// Runs the watchers until shutdown, up to `workers` of them at once. A watcher run that
+    /// already started always completes, so that no alert gets interrupted while being reported.
+    /// Alerts are held back during the startup grace, which starts with the run, see
+    /// [`startup_grace::Reporter`], during the maintenance windows, see
+    /// [`maintenance::Reporter`], while a rule mutes them, see [`mute::Reporter`], while another
+    /// alert inhibits them, see [`inhibition::Reporter`], and when they are already active in the
+    /// alert store. Composite alerts are evaluated at the end of each cycle, see
+    /// [`composite::Reporter`]. Each cycle runs within a `cycle` span with its own `cycle_id`, so
+    /// that the logs of the watchers that ran together can be correlated.
+    pub fn run<R: AlertReporter + Sync>(
+        mut self,
+        alert_reporter: &R,
+        maintenance: &maintenance::Configuration,
+        mute: &mute::Configuration,
+        inhibition: &inhibition::Configuration,
+        composite: &composite::Configuration,
+        alert_store: &dyn AlertStore,
+    ) {
+        let unfiltered = alert_reporter;
+        let muted = mute::Reporter::new(mute, alert_reporter);
+        let maintenance = maintenance::Reporter::new(maintenance, &muted);
+        let inhibited = inhibition::Reporter::new(inhibition, &maintenance);
+        let composite = composite::Reporter::new(composite, &inhibited);
+        let alert_reporter =
+            startup_grace::Reporter::new(self.startup_grace, self.clock.clone(), &composite);
+        // Finished watchers are sent back along with the commands of the handles, which are
+        // forwarded so that the scheduler still notices when every handle has been dropped.
+        let (finished, commands) = channel();
+        let handles = mem::replace(&mut self.commands, commands);
+        let forwarded = finished.clone();
+        thread::spawn(move || {
+            for command in handles {
+                if forwarded.send(command).is_err() {
+                    return;
+                }
+            }
+            let _ = forwarded.send(Command::Shutdown);
+        });
+        thread::scope(|scope| {
+            loop {
+                self.start_due(scope, &finished, &alert_reporter, alert_store);
+                status::beat(self.timers.peek().map(|Reverse(next)| next.deadline));
+                // Sleeps until the next watcher is due, or until a command arrives if none is
+                // scheduled or every worker is busy, so that an idle scheduler never polls.
+                let command = match self.timers.peek() {
+                    Some(Reverse(next)) if self.running.len() < self.workers => self
+                        .commands
+                        .recv_timeout(next.deadline.saturating_duration_since(Instant::now())),
+                    _ => self
+                        .commands
+                        .recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match command {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Ok(Command::Reconfigure(configurations)) => {
+                        self.reconfigure(configurations);
+                        continue;
+                    }
+                    Ok(Command::Report(alert)) => {
+                        info!(reporting_synthetic = ?alert);
+                        // Running watchers flush the reporters once their cycle ends.
+                        let reported = unfiltered.report(&alert).and_then(|()| {
+                            if self.running.is_empty() {
+                                unfiltered.flush()
+                            } else {
+                                Ok(())
+                            }
+                        });
+                        if let Err(e) = reported {
+                            warn!(reporter_error = ?e);
+                        }
+                        continue;
+                    }
+                    Ok(Command::Finished(scheduled)) => {
+                        self.finish(*scheduled);
+                        alert_reporter.report_ended();
+                        maintenance.report_closed();
+                        muted.report_expired();
+                    }
+                    Ok(Command::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                        info!("Shutdown requested, draining scheduler");
+                        break;
+                    }
+                }
+                // The watchers that were due together form a cycle, ending once none is due
+                // nor running anymore.
+                if self.running.is_empty()
+                    && self
+                        .timers
+                        .peek()
+                        .is_none_or(|Reverse(next)| next.deadline > Instant::now())
+                {
+                    let _cycle = self.cycle.take().map(|cycle| cycle.entered());
+                    if let Err(e) = alert_reporter.flush() {
+                        warn!(reporter_error = ?e);
+                    }
+                }
+            }
+            while !self.running.is_empty() {
+                match self.commands.recv() {
+                    Ok(Command::Finished(scheduled)) => self.finish(*scheduled),
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+        info!("Scheduler stopped");
+    }
+}
+
+#[cfg(test)]
+impl Scheduler {
+    /// Applies the reconfigurations sent by the handles so far, without running the watchers,
+    /// e.g. to test what sends them.
+    pub(crate) fn apply_reconfigurations(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            if let Command::Reconfigure(configurations) = command {
+                self.reconfigure(configurations);
+            }
+        }
+    }
+
+    /// Configurations of the enabled watchers, waiting for their next run.
+    pub(crate) fn scheduled(&self) -> Vec<WatcherConfiguration> {
+        self.timers
+            .iter()
+            .map(|Reverse(s)| s.configuration.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_store::memory::Memory;
+    use serde_json::json;
+
+    fn memory(enabled: bool) -> WatcherConfiguration {
+        serde_json::from_value(json!({ "Memory": {
+            "configuration": { "period": "5m" },
+            "alerts": [{ "min": 0.0, "trigger_threshold": 0.05 }],
+            "enabled": enabled,
+        } }))
+        .unwrap()
+    }
+
+    fn without_self_monitor() -> Configuration {
+        Configuration {
+            self_monitor: false,
+            ..Configuration::default()
+        }
+    }
+
+    #[test]
+    fn disabled_watchers_are_never_scheduled() {
+        let (mut scheduler, _handle) =
+            Scheduler::new(vec![memory(true), memory(false)], &without_self_monitor()).unwrap();
+
+        assert_eq!(scheduler.scheduled(), vec![memory(true)]);
+        assert_eq!(scheduler.disabled.len(), 1);
+        assert_eq!(scheduler.disabled[0].configuration, memory(false));
+
+        scheduler.reconfigure(vec![memory(false)]);
+        assert!(scheduler.scheduled().is_empty());
+        assert_eq!(scheduler.disabled.len(), 1);
+    }
+
+    /// CPU time the calling thread used so far.
+    #[cfg(target_os = "linux")]
+    fn thread_cpu_time() -> Duration {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // The thread clock always exists, and `time` is a valid timespec to write to.
+        let read = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+        assert_eq!(read, 0);
+        Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn a_scheduler_without_watchers_sleeps_until_reconfigured() {
+        let (scheduler, handle) = Scheduler::new(Vec::new(), &without_self_monitor()).unwrap();
+        let store = Memory::new(Default::default());
+
+        let used = thread::scope(|scope| {
+            let running = scope.spawn(|| {
+                let before = thread_cpu_time();
+                scheduler.run(
+                    &RecordingReporter::new(),
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &store,
+                );
+                thread_cpu_time() - before
+            });
+            thread::sleep(Duration::from_millis(500));
+            handle.shutdown();
+            running.join().unwrap()
+        });
+
+        assert!(used < Duration::from_millis(50), "{:?}", used);
+    }
+
+    /// A memory watcher firing at each run as long as `value` MB are available, first running
+    /// after `initial_delay`.
+    fn always_firing(value: u64, initial_delay: &str) -> WatcherConfiguration {
+        serde_json::from_value(json!({ "Memory": {
+            "configuration": { "period": "5m" },
+            "alerts": [{ "threshold": {
+                "metric": "Available", "value": value, "unit": "MB", "operator": ">=",
+            } }],
+            "initial_delay": initial_delay,
+        } }))
+        .unwrap()
+    }
+
+    #[test]
+    fn watchers_with_an_initial_delay_do_not_run_right_away() {
+        let (scheduler, handle) = Scheduler::new(
+            vec![always_firing(0, "0s"), always_firing(1, "5s")],
+            &without_self_monitor(),
+        )
+        .unwrap();
+        let reporter = RecordingReporter::new();
+        let store = Memory::new(Default::default());
+        let started = Instant::now();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                scheduler.run(
+                    &reporter,
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &store,
+                )
+            });
+            while reporter.alerts().is_empty() && started.elapsed() < Duration::from_secs(5) {
+                thread::sleep(Duration::from_millis(10));
+            }
+            // Leaves the delayed watcher time to run, were it not delayed.
+            thread::sleep(Duration::from_millis(200));
+            handle.shutdown();
+        });
+
+        let alerts = reporter.alerts();
+        assert_eq!(alerts.len(), 1, "{:?}", alerts);
+        assert!(alerts[0].message.contains("0 MB"), "{}", alerts[0].message);
+    }
+
+    /// A command exiting with `exit_code` after sleeping for half a second, critical for that
+    /// code so that the alerts of several commands differ.
+    #[cfg(unix)]
+    fn slow_command(exit_code: i32) -> WatcherConfiguration {
+        serde_json::from_value(json!({ "Command": {
+            "configuration": {
+                "period": "1h",
+                "command": "sh",
+                "args": ["-c", format!("sleep 0.5; exit {}", exit_code)],
+            },
+            "alerts": [{ "ExitCode": { "critical": [exit_code] } }],
+        } }))
+        .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn slow_checks_run_at_the_same_time() {
+        let (scheduler, handle) = Scheduler::new(
+            vec![slow_command(1), slow_command(3)],
+            &without_self_monitor(),
+        )
+        .unwrap();
+        let reporter = RecordingReporter::new();
+        let store = Memory::new(Default::default());
+        let started = Instant::now();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                scheduler.run(
+                    &reporter,
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &store,
+                )
+            });
+            while reporter.alerts().len() < 2 && started.elapsed() < Duration::from_secs(5) {
+                thread::sleep(Duration::from_millis(10));
+            }
+            handle.shutdown();
+        });
+
+        assert_eq!(reporter.alerts().len(), 2);
+        assert!(
+            started.elapsed() < Duration::from_millis(900),
+            "checks took {:?}",
+            started.elapsed()
+        );
+    }
+}
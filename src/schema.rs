@@ -0,0 +1,121 @@
+//! JSON Schema of the watcher configuration files, for editors to validate and complete them.
+
+use schemars::generate::SchemaSettings;
+use schemars::{json_schema, Schema, SchemaGenerator};
+use serde_json::{Map, Value};
+
+use crate::watcher::WatcherConfiguration;
+
+/// Schema of durations, as read by [`crate::duration::deserialize_duration`].
+pub(crate) fn duration(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "description": "A number of seconds, or a duration such as \"90s\", \"2m30s\" or \"1h\", \
+                        in ms, s, m, h and d.",
+        "anyOf": [
+            { "type": "integer", "minimum": 0 },
+            { "type": "string", "pattern": "^\\s*([0-9]+\\s*(ms|s|m|h|d)\\s*)+$" }
+        ]
+    })
+}
+
+/// Schema of sizes, as read by [`crate::size::deserialize_size`].
+pub(crate) fn size(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "description": "A number of bytes, or a size such as \"500MB\" or \"1.5 GiB\".",
+        "anyOf": [
+            { "type": "integer", "minimum": 0 },
+            {
+                "type": "string",
+                "pattern": "^\\s*[0-9.]+\\s*(B|kB|KB|MB|GB|TB|KiB|MiB|GiB|TiB)?\\s*$"
+            }
+        ]
+    })
+}
+
+/// JSON Schema of a watcher configuration file: a map from the kind of each watcher, such as
+/// `DiskSpace`, to its configuration. A kind can appear several times in a file, which the
+/// schema can not tell, an editor may warn about it.
+pub fn config_schema() -> Value {
+    // Every watcher has its own `Configuration` and `Alert` types, which would be told apart by
+    // numbers if they were definitions of their own.
+    let mut schema = SchemaSettings::default()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator()
+        .into_root_schema_for::<WatcherConfiguration>()
+        .to_value();
+    // Watchers are enum variants, each an object with the kind as its single key: their
+    // properties are merged into the properties of the file.
+    let mut properties = Map::new();
+    if let Some(variants) = schema.get_mut("oneOf").and_then(Value::as_array_mut) {
+        for variant in variants {
+            if let Some(Value::Object(kind)) = variant.get_mut("properties").map(Value::take) {
+                properties.extend(kind);
+            }
+        }
+    }
+    let Value::Object(root) = &mut schema else {
+        return schema;
+    };
+    root.remove("oneOf");
+    root.insert("title".into(), "Monitrust watchers".into());
+    root.insert("type".into(), "object".into());
+    root.insert("properties".into(), Value::Object(properties));
+    root.insert("additionalProperties".into(), false.into());
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// What the schema finds wrong with the watchers and their top-level fields in `file`.
+    fn problems(file: &Value) -> Vec<String> {
+        let schema = config_schema();
+        let mut problems = Vec::new();
+        for (kind, watcher) in file.as_object().unwrap() {
+            let Some(kind_schema) = schema["properties"].get(kind) else {
+                problems.push(format!("unknown kind {}", kind));
+                continue;
+            };
+            let required = kind_schema["required"].as_array().unwrap();
+            for field in required.iter().filter_map(Value::as_str) {
+                if watcher.get(field).is_none() {
+                    problems.push(format!("{} lacks {}", kind, field));
+                }
+            }
+            for field in watcher.as_object().unwrap().keys() {
+                if kind_schema["properties"].get(field).is_none() {
+                    problems.push(format!("{} has unknown {}", kind, field));
+                }
+            }
+        }
+        problems
+    }
+
+    #[test]
+    fn the_example_watchers_conform_to_the_schema() {
+        let example = serde_json::from_str(include_str!("../watchers.json.example")).unwrap();
+
+        assert_eq!(problems(&example), Vec::<String>::new());
+        assert_eq!(config_schema()["additionalProperties"], false);
+    }
+
+    #[test]
+    fn malformed_watchers_do_not_conform_to_the_schema() {
+        let file = json!({
+            "Disk_Space": { "configuration": {}, "alerts": [] },
+            "Memory": { "alert": [{}] },
+        });
+
+        assert_eq!(
+            problems(&file),
+            [
+                "unknown kind Disk_Space",
+                "Memory lacks configuration",
+                "Memory lacks alerts",
+                "Memory has unknown alert",
+            ]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Secrets of the configuration, such as tokens and passwords, which can be read from the
+//! environment or from files rather than written in the configuration itself.
+
+use std::fmt;
+
+use anyhow::{anyhow, Context, Result};
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Replaces the `${NAME}` references of a string with the value of the environment variable
+/// `NAME`, `$$` standing for a literal `$`. A variable that is not set is an error.
+pub fn interpolate(s: &str) -> Result<String> {
+    let mut interpolated = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            interpolated.push('$');
+            rest = after;
+        } else if let Some(reference) = rest.strip_prefix('{') {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| anyhow!("Unterminated variable reference in '{}'", s))?;
+            let name = &reference[..end];
+            let value = std::env::var(name)
+                .with_context(|| format!("Environment variable '{}' is not set", name))?;
+            interpolated.push_str(&value);
+            rest = &reference[end + 1..];
+        } else {
+            interpolated.push('$');
+        }
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+/// The value a secret is configured with: the content of a file for `!file <path>`, without its
+/// trailing newline, such as the secrets Docker and Kubernetes mount, or the string itself with
+/// its variables interpolated, see [`interpolate`].
+pub fn resolve(s: &str) -> Result<String> {
+    match s.strip_prefix("!file ") {
+        Some(path) => {
+            let path = interpolate(path.trim())?;
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Could not read secret file '{}'", path))?;
+            Ok(content.trim_end_matches(['\r', '\n']).to_string())
+        }
+        None => interpolate(s),
+    }
+}
+
+/// A secret, resolved when the configuration is read. It is redacted when debugged, so that it
+/// does not show in logs.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Secret(secret.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(\"***\")")
+    }
+}
+
+/// Serializes the resolved value, which configurations are only serialized to be read back.
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+struct SecretVisitor;
+
+impl Visitor<'_> for SecretVisitor {
+    type Value = Secret;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a secret, a string referencing ${VARIABLES} or \"!file <path>\"")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Secret, E> {
+        resolve(v)
+            .map(Secret)
+            .map_err(|e| E::custom(format!("{:#}", e)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(SecretVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_environment_variables() {
+        std::env::set_var("MONITRUST_TEST_TOKEN", "xoxb-123");
+
+        assert_eq!(
+            interpolate("Bearer ${MONITRUST_TEST_TOKEN}, $$5 $x").unwrap(),
+            "Bearer xoxb-123, $5 $x"
+        );
+        assert_eq!(
+            format!("{:#}", interpolate("${MONITRUST_TEST_UNSET}").unwrap_err()),
+            "Environment variable 'MONITRUST_TEST_UNSET' is not set: environment variable not found"
+        );
+        assert!(interpolate("${MONITRUST_TEST_TOKEN").is_err());
+    }
+
+    #[test]
+    fn reads_secrets_from_files() {
+        let path = std::env::temp_dir().join(format!("monitrust-secret-{}", std::process::id()));
+        std::fs::write(&path, "hunter2\n").unwrap();
+
+        let secret: Secret =
+            serde_json::from_value(serde_json::json!(format!("!file {}", path.display()))).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(secret.expose(), "hunter2");
+        assert!(resolve(&format!("!file {}", path.display())).is_err());
+    }
+
+    #[test]
+    fn secrets_are_redacted_when_debugged() {
+        let secret = Secret::new("hunter2");
+
+        let debugged = format!("{:?}", Some(&secret));
+
+        assert_eq!(debugged, "Some(Secret(\"***\"))");
+        assert!(!debugged.contains("hunter2"));
+    }
+}
@@ -0,0 +1,164 @@
+//! Severity overrides, which remap the severity alerts are triggered with, e.g. so that the same
+//! watchers never page in a development environment while some of their alerts are promoted in
+//! production.
+
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::mute::Matcher;
+use crate::watcher::{ActiveAlert, Severity};
+
+/// Sets the severity of the alerts it matches to `set`, then keeps it between `clamp_min` and
+/// `clamp_max`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SeverityRule {
+    pub r#match: Matcher,
+    #[serde(default)]
+    pub set: Option<Severity>,
+    #[serde(default)]
+    pub clamp_min: Option<Severity>,
+    #[serde(default)]
+    pub clamp_max: Option<Severity>,
+}
+
+impl SeverityRule {
+    fn apply(&self, severity: Severity) -> Severity {
+        let severity = self.set.unwrap_or(severity);
+        let severity = self.clamp_min.map_or(severity, |min| severity.max(min));
+        self.clamp_max.map_or(severity, |max| severity.min(max))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Configuration {
+    #[serde(default)]
+    rules: Vec<SeverityRule>,
+}
+
+impl Configuration {
+    /// Rules must change the severity, and their bounds be in order.
+    pub fn validate(&self) -> Result<()> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.set.is_none() && rule.clamp_min.is_none() && rule.clamp_max.is_none() {
+                return Err(anyhow!(
+                    "Severity rule #{} sets neither 'set', 'clamp_min' nor 'clamp_max'",
+                    index + 1
+                ));
+            }
+            if let (Some(min), Some(max)) = (rule.clamp_min, rule.clamp_max) {
+                if min > max {
+                    return Err(anyhow!(
+                        "'clamp_min' of severity rule #{} is above its 'clamp_max'",
+                        index + 1
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Severity of the alert once each rule matching it applied, in order, each to the severity
+    /// left by the previous ones. Rules match the alert as triggered, before any of them applied.
+    pub fn severity_of(&self, alert: &ActiveAlert) -> Severity {
+        self.rules
+            .iter()
+            .filter(|rule| rule.r#match.matches(alert))
+            .fold(alert.severity, |severity, rule| rule.apply(severity))
+    }
+}
+
+static CONFIGURATION: OnceLock<Configuration> = OnceLock::new();
+
+/// Overrides the severity of every alert triggered from now on. Only the first configuration is
+/// kept, until Monitrust restarts: reloads do not change the rules.
+pub fn init(configuration: Configuration) {
+    let _ = CONFIGURATION.set(configuration);
+}
+
+/// The alert with its severity overridden by the rules, if any is configured.
+pub(crate) fn overridden(alert: ActiveAlert) -> ActiveAlert {
+    match CONFIGURATION.get() {
+        Some(configuration) => ActiveAlert {
+            severity: configuration.severity_of(&alert),
+            ..alert
+        },
+        None => alert,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn configuration(rules: serde_json::Value) -> Configuration {
+        serde_json::from_value(json!({ "rules": rules })).expect("Invalid severity overrides")
+    }
+
+    fn alert(kind: &str, env: &str, severity: Severity) -> ActiveAlert {
+        ActiveAlert {
+            kind: kind.to_string(),
+            labels: [("env".to_string(), env.to_string())].into(),
+            ..ActiveAlert::new("Disk is full".to_string(), severity)
+        }
+    }
+
+    #[test]
+    fn matched_alerts_are_clamped_down_and_promoted_up() {
+        let configuration = configuration(json!([
+            { "match": { "Labels": { "env": "dev" } }, "clamp_max": "Warning" },
+            { "match": { "Kind": "disk_space" }, "clamp_min": "Critical" }
+        ]));
+
+        let severity_of =
+            |kind, env, severity| configuration.severity_of(&alert(kind, env, severity));
+        assert_eq!(
+            severity_of("memory", "dev", Severity::Critical),
+            Severity::Warning
+        );
+        assert_eq!(
+            severity_of("disk_space", "prod", Severity::Warning),
+            Severity::Critical
+        );
+        assert_eq!(
+            severity_of("memory", "prod", Severity::Info),
+            Severity::Info
+        );
+        // Rules apply in order, the clamp of the second one wins.
+        assert_eq!(
+            severity_of("disk_space", "dev", Severity::Warning),
+            Severity::Critical
+        );
+    }
+
+    #[test]
+    fn set_remaps_the_severity_before_the_clamps() {
+        let configuration = configuration(json!([
+            { "match": { "Kind": "memory" }, "set": "Critical", "clamp_max": "Warning" }
+        ]));
+
+        assert_eq!(
+            configuration.severity_of(&alert("memory", "prod", Severity::Info)),
+            Severity::Warning
+        );
+    }
+
+    #[test]
+    fn rules_changing_nothing_or_with_bounds_out_of_order_are_rejected() {
+        let error = |rules| configuration(rules).validate().unwrap_err().to_string();
+
+        assert_eq!(
+            error(json!([{ "match": { "Kind": "memory" } }])),
+            "Severity rule #1 sets neither 'set', 'clamp_min' nor 'clamp_max'"
+        );
+        assert_eq!(
+            error(json!([
+                { "match": { "Kind": "memory" }, "set": "Info" },
+                { "match": { "Kind": "memory" }, "clamp_min": "Critical", "clamp_max": "Info" }
+            ])),
+            "'clamp_min' of severity rule #2 is above its 'clamp_max'"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::{self, Deserializer, Visitor};
+
+use crate::watcher::decimal;
+
+/// Parses sizes such as `"500MB"`, `"1.5 GiB"` or `"2GB"`. The supported units are `B`, the
+/// decimal `kB`, `MB`, `GB` and `TB`, and the binary `KiB`, `MiB`, `GiB` and `TiB`; a bare number
+/// is a number of bytes.
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let digits = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (value, unit) = (&s[..digits], s[digits..].trim());
+    let value = value
+        .parse::<f64>()
+        .with_context(|| format!("Invalid size: '{}'", s))?;
+    let unit_bytes: u64 = match unit {
+        "" | "B" => 1,
+        "kB" | "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        unit => return Err(anyhow!("Invalid unit '{}' in size '{}'", unit, s)),
+    };
+    Ok((value * unit_bytes as f64).round() as u64)
+}
+
+/// Formats a number of bytes with the largest decimal unit it holds at least one of, such as
+/// `"1.5 GB"` at the default precision of alerts, see [`crate::watcher::init_precision`].
+pub fn format_size(bytes: u64) -> String {
+    for (unit, unit_bytes) in [("TB", 1e12), ("GB", 1e9), ("MB", 1e6), ("kB", 1e3)] {
+        if bytes as f64 >= unit_bytes {
+            return format!("{} {}", decimal(bytes as f64 / unit_bytes), unit);
+        }
+    }
+    format!("{} B", bytes)
+}
+
+struct SizeVisitor;
+
+impl Visitor<'_> for SizeVisitor {
+    type Value = u64;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a number of bytes or a size such as \"2GB\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<u64, E> {
+        Ok(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<u64, E> {
+        parse_size(v).map_err(E::custom)
+    }
+}
+
+/// Deserializes a number of bytes from a number or from a string parsed by [`parse_size`].
+pub fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    deserializer.deserialize_any(SizeVisitor)
+}
+
+/// Deserializes an optional size, for fields that also need `#[serde(default)]`.
+pub fn deserialize_optional_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    deserialize_size(deserializer).map(Some)
+}
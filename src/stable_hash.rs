@@ -0,0 +1,76 @@
+//! Hashes that stay the same across Rust releases and platforms, for the identifiers that outlive
+//! a process, such as the fingerprints of alerts kept by alert stores and incident tools.
+
+use std::hash::{Hash, Hasher};
+
+use siphasher::sip::SipHasher13;
+
+/// SipHash 1-3 with zero keys, the algorithm of the standard library's `DefaultHasher` when this
+/// was written, which is not guaranteed to stay the same. Sizes are hashed as 64-bit integers and
+/// 128-bit integers as little-endian bytes, as the standard library would hash them with the width
+/// and byte order of the platform.
+#[derive(Debug, Clone)]
+pub(crate) struct StableHasher(SipHasher13);
+
+impl StableHasher {
+    pub(crate) fn new() -> Self {
+        StableHasher(SipHasher13::new_with_keys(0, 0))
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.0.write_u8(i)
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.0.write_u16(i)
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0.write_u32(i)
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0.write_u64(i)
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.0.write(&i.to_le_bytes())
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.0.write_u64(i as u64)
+    }
+}
+
+/// Hash of `value` with a [`StableHasher`].
+pub(crate) fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = StableHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_the_same_as_before() {
+        // Hashes of the `DefaultHasher` of Rust 1.95 on x86_64, that fingerprints were derived
+        // from before.
+        assert_eq!(hash("/var"), 0x59bb_86ee_32c1_e304);
+        assert_eq!(
+            hash(&(42_u32, Some(7_usize), vec!["a", "b"])),
+            0x615f_354e_6697_c0d7
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Alerts held back for a while after startup, as transient conditions such as caches filling or
+//! services starting trip alerts that resolve on their own.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::clock::Clock;
+use crate::status;
+use crate::watcher::ActiveAlert;
+
+/// Holds back the alerts that fire during the startup grace, while the checks keep running.
+///
+/// An alert that fires and resolves within the grace is not reported at all. Alerts still firing
+/// once it elapsed are reported then, as a single batch, by the first report or flush past its
+/// end, or by [`Reporter::report_ended`]. Until then, `/status` tells until when the grace lasts.
+pub struct Reporter<'a, R> {
+    reporter: &'a R,
+    clock: Arc<dyn Clock>,
+    until: Instant,
+    /// Alerts that fired during the grace and were not reported, by fingerprint and escalation
+    /// tier so that an escalated alert does not replace the alert it escalates, or `None` once
+    /// the grace ended.
+    held_back: Mutex<Option<HeldBack>>,
+}
+
+type HeldBack = BTreeMap<(String, Option<usize>), ActiveAlert>;
+
+fn key(alert: &ActiveAlert) -> (String, Option<usize>) {
+    (
+        alert.fingerprint.clone(),
+        alert.escalation.as_ref().map(|e| e.tier),
+    )
+}
+
+/// Removes the alerts held back with `fingerprint`, telling whether there was any.
+fn remove(held_back: &mut HeldBack, fingerprint: &str) -> bool {
+    let before = held_back.len();
+    held_back.retain(|(held, _), _| held != fingerprint);
+    held_back.len() < before
+}
+
+impl<'a, R: AlertReporter> Reporter<'a, R> {
+    /// Holds alerts back for `grace` from now, as read from `clock`. Nothing is held back with a
+    /// zero grace.
+    pub fn new(grace: Duration, clock: Arc<dyn Clock>, reporter: &'a R) -> Self {
+        let held_back = match grace.is_zero() {
+            true => None,
+            false => {
+                info!(startup_grace = ?grace);
+                status::set_startup_grace(Some(clock.system_now() + grace));
+                Some(BTreeMap::new())
+            }
+        };
+        Reporter {
+            reporter,
+            until: clock.now() + grace,
+            clock,
+            held_back: Mutex::new(held_back),
+        }
+    }
+
+    fn held_back(&self) -> MutexGuard<'_, Option<HeldBack>> {
+        // Alerts are only ever inserted or removed, a panic can not leave them inconsistent.
+        self.held_back.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_in_grace(&self) -> bool {
+        self.clock.now() < self.until
+    }
+
+    /// Holds back the alerts while in grace, returning the ones to report.
+    fn hold_back(&self, alerts: &[ActiveAlert]) -> Vec<ActiveAlert> {
+        let in_grace = self.is_in_grace();
+        let mut held_back = self.held_back();
+        let Some(held_back) = held_back.as_mut() else {
+            return alerts.to_vec();
+        };
+        for alert in alerts {
+            if in_grace {
+                info!(startup_grace_alert = ?alert);
+                held_back.insert(key(alert), alert.clone());
+            } else {
+                // Supersedes what was held back during the grace.
+                remove(held_back, &alert.fingerprint);
+            }
+        }
+        match in_grace {
+            true => Vec::new(),
+            false => alerts.to_vec(),
+        }
+    }
+
+    /// Forgets a resolved alert, telling whether its resolution is to be reported: it is not for
+    /// the alerts held back during the grace.
+    fn resolve(&self, alert: &ActiveAlert) -> bool {
+        let held_back = self
+            .held_back()
+            .as_mut()
+            .is_some_and(|held_back| remove(held_back, &alert.fingerprint));
+        if held_back {
+            info!(startup_grace_resolved_alert = ?alert);
+            return false;
+        }
+        true
+    }
+
+    /// Reports the held back alerts once the grace elapsed, and stops holding any back. Called
+    /// before each report and flush, and by the scheduler after each run of a watcher so that
+    /// they are reported even if nothing else is.
+    pub fn report_ended(&self) {
+        if self.is_in_grace() {
+            return;
+        }
+        let Some(ended) = self.held_back().take() else {
+            return;
+        };
+        status::set_startup_grace(None);
+        info!(startup_grace_ended_alerts = ended.len());
+        if ended.is_empty() {
+            return;
+        }
+        let ended = ended.into_values().collect::<Vec<_>>();
+        if let Err(e) = self.reporter.report_batch(&ended) {
+            warn!(alert_reporter = ?e);
+        }
+    }
+}
+
+impl<R: AlertReporter> AlertReporter for Reporter<'_, R> {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.report_ended();
+        match self.hold_back(std::slice::from_ref(alert)).first() {
+            Some(alert) => self.reporter.report(alert),
+            None => Ok(()),
+        }
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        self.report_ended();
+        let reported = self.hold_back(alerts);
+        if reported.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_batch(&reported)
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        self.report_ended();
+        if !self.resolve(alert) {
+            return Ok(());
+        }
+        self.reporter.report_resolved(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.report_ended();
+        self.reporter.flush()
+    }
+
+    /// Holds the changes back the same as reports and resolutions.
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        self.report_ended();
+        let passed = changes
+            .iter()
+            .filter(|change| match change.firing() {
+                None => self.resolve(change.alert()),
+                Some(alert) => !self.hold_back(std::slice::from_ref(alert)).is_empty(),
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if passed.is_empty() {
+            return Ok(());
+        }
+        self.reporter.report_diff(&passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::test_util::FakeClock;
+    use crate::watcher::{Escalation, Severity};
+
+    const GRACE: Duration = Duration::from_secs(60);
+
+    fn alert(escalation: Option<usize>) -> ActiveAlert {
+        let mut alert = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+        alert.fingerprint = "disk_space:/".to_string();
+        alert.escalation = escalation.map(|tier| Escalation {
+            tier,
+            reporter: None,
+        });
+        alert
+    }
+
+    #[test]
+    fn reports_the_held_back_alerts_once_the_grace_elapsed() {
+        let (clock, recorder) = (FakeClock::new(), RecordingReporter::new());
+        let reporter = Reporter::new(GRACE, Arc::new(clock.clone()), &recorder);
+        clock.advance(Duration::from_secs(10));
+        reporter.report(&alert(None)).unwrap();
+        reporter.flush().unwrap();
+        assert!(recorder.alerts().is_empty());
+
+        clock.advance(Duration::from_secs(49));
+        reporter.flush().unwrap();
+        assert!(recorder.alerts().is_empty());
+
+        clock.advance(Duration::from_secs(2));
+        reporter.flush().unwrap();
+        assert_eq!(recorder.alerts().len(), 1);
+        reporter.report(&alert(None)).unwrap();
+        assert_eq!(recorder.alerts().len(), 2);
+    }
+
+    #[test]
+    fn keeps_an_escalated_alert_along_with_the_alert_it_escalates() {
+        let (clock, recorder) = (FakeClock::new(), RecordingReporter::new());
+        let reporter = Reporter::new(GRACE, Arc::new(clock.clone()), &recorder);
+        reporter.report(&alert(None)).unwrap();
+        reporter.report(&alert(Some(1))).unwrap();
+        reporter.report(&alert(Some(1))).unwrap();
+        clock.advance(GRACE);
+        reporter.flush().unwrap();
+        let tiers = recorder
+            .alerts()
+            .iter()
+            .map(|a| a.escalation.as_ref().map(|e| e.tier))
+            .collect::<Vec<_>>();
+        assert_eq!(tiers, [None, Some(1)]);
+    }
+
+    #[test]
+    fn does_not_report_an_alert_resolved_within_the_grace() {
+        let (clock, recorder) = (FakeClock::new(), RecordingReporter::new());
+        let reporter = Reporter::new(GRACE, Arc::new(clock.clone()), &recorder);
+        reporter.report(&alert(None)).unwrap();
+        reporter.report(&alert(Some(1))).unwrap();
+        reporter.report_resolved(&alert(None)).unwrap();
+        clock.advance(GRACE);
+        reporter.flush().unwrap();
+        assert!(recorder.alerts().is_empty());
+        assert!(recorder.resolved().is_empty());
+    }
+}
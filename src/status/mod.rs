@@ -0,0 +1,457 @@
+//! Latest outcome of each watcher, kept so that it can be served as a status page.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Serialize, Serializer};
+
+use crate::mute;
+use crate::watcher::{ActiveAlert, Severity};
+
+pub mod server;
+
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn serialize_rfc3339<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(*time))
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    /// The alert never fired since Monitrust started, or its older transitions were dropped.
+    Inactive,
+    Firing,
+    Resolved,
+}
+
+/// An alert that started firing or resolved.
+#[derive(Serialize, Debug, Clone)]
+pub struct Transition {
+    pub fingerprint: String,
+    pub from: AlertState,
+    pub to: AlertState,
+    #[serde(serialize_with = "serialize_rfc3339")]
+    pub at: SystemTime,
+    /// How long the alert was `from`, unset if its previous transition is unknown.
+    pub duration_seconds: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FiringAlert {
+    pub fingerprint: String,
+    pub severity: Severity,
+    pub message: String,
+    pub detected_at: String,
+    /// Until when the alert is acknowledged, if it is.
+    pub acknowledged_until: Option<String>,
+    /// Whether a mute rule keeps the alert from being reported, as of the last run.
+    pub muted: bool,
+}
+
+impl From<&ActiveAlert> for FiringAlert {
+    fn from(alert: &ActiveAlert) -> Self {
+        FiringAlert {
+            fingerprint: alert.fingerprint.clone(),
+            severity: alert.severity,
+            message: alert.message.clone(),
+            detected_at: rfc3339(alert.detected_at),
+            acknowledged_until: None,
+            muted: MUTE_RULES
+                .lock()
+                .is_ok_and(|rules| rules.mutes(alert, SystemTime::now())),
+        }
+    }
+}
+
+/// How late the scheduler started a run of a watcher, past the time it was due.
+#[derive(Debug, Clone, Copy)]
+pub struct Lag {
+    /// When the run started.
+    pub at: Instant,
+    /// Kind of the watcher, such as `disk_space`.
+    pub kind: &'static str,
+    pub lag: Duration,
+    /// Period of the watcher, which the lag is compared to.
+    pub period: Duration,
+}
+
+impl Lag {
+    /// How many periods of the watcher the run is late by.
+    pub fn periods(&self) -> f64 {
+        self.lag.as_secs_f64() / self.period.as_secs_f64()
+    }
+}
+
+/// How long the recent checks of a watcher took, in seconds.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct CheckDuration {
+    pub last_seconds: f64,
+    pub average_seconds: f64,
+    pub max_seconds: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct WatcherStatus {
+    /// Kind of watcher, such as `disk_space`.
+    pub kind: String,
+    pub enabled: bool,
+    /// Unset for disabled watchers, which never run.
+    pub last_run: Option<String>,
+    pub succeeded: bool,
+    /// What the last check measured, or why it failed.
+    pub check: String,
+    /// Unset until the watcher checked once.
+    pub check_duration: Option<CheckDuration>,
+    pub firing: Vec<FiringAlert>,
+}
+
+impl WatcherStatus {
+    pub fn new(kind: &str, check: Result<String, String>, firing: Vec<FiringAlert>) -> Self {
+        WatcherStatus {
+            kind: kind.to_string(),
+            enabled: true,
+            last_run: Some(rfc3339(SystemTime::now())),
+            succeeded: check.is_ok(),
+            check: check.unwrap_or_else(|e| e),
+            check_duration: None,
+            firing,
+        }
+    }
+
+    pub fn disabled(kind: &str) -> Self {
+        WatcherStatus {
+            kind: kind.to_string(),
+            enabled: false,
+            last_run: None,
+            succeeded: false,
+            check: "disabled".to_string(),
+            check_duration: None,
+            firing: Vec::new(),
+        }
+    }
+}
+
+/// Latest status of each watcher, by identifier.
+static LATEST: Mutex<BTreeMap<String, WatcherStatus>> = Mutex::new(BTreeMap::new());
+
+/// Latest transitions of each alert, by fingerprint, the oldest first.
+static HISTORY: Mutex<BTreeMap<String, VecDeque<Transition>>> = Mutex::new(BTreeMap::new());
+
+/// Transitions kept for each alert, see [`set_history_length`].
+static HISTORY_LENGTH: AtomicUsize = AtomicUsize::new(10);
+
+/// Rules muting alerts, to show which firing alerts are muted.
+static MUTE_RULES: Mutex<mute::Configuration> = Mutex::new(mute::Configuration::new());
+
+/// Until when each acknowledged alert is acknowledged, by fingerprint.
+static ACKNOWLEDGED: Mutex<BTreeMap<String, SystemTime>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy)]
+enum Heartbeat {
+    NotStarted,
+    /// Without any watcher to run, the scheduler only waits for commands.
+    Idle,
+    /// When the scheduler is expected to be back, to run its next watcher.
+    DueBy(Instant),
+}
+
+static HEARTBEAT: Mutex<Heartbeat> = Mutex::new(Heartbeat::NotStarted);
+
+/// Lags of the runs that the scheduler started most recently, the oldest first.
+static LAGS: Mutex<VecDeque<Lag>> = Mutex::new(VecDeque::new());
+
+/// Lags kept, beyond which the oldest ones are dropped.
+const MAX_LAGS: usize = 256;
+
+/// Lengths of the queues of alerts of the reporters, such as rate limited ones. Queues are
+/// dropped along with their reporters.
+static QUEUES: Mutex<Vec<Weak<AtomicUsize>>> = Mutex::new(Vec::new());
+
+/// When each source watched by a `Deadman` watcher last sent a heartbeat, if it did, by name.
+/// Only the sources of configured watchers are kept, so that heartbeats sent for anything else
+/// do not pile up.
+static EXTERNAL_HEARTBEATS: Mutex<BTreeMap<String, Option<SystemTime>>> =
+    Mutex::new(BTreeMap::new());
+
+/// Until when alerts are held back after startup, while they are.
+static STARTUP_GRACE: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+/// Records the status of a watcher after it ran, replacing the previous one.
+pub fn record(id: &str, status: WatcherStatus) {
+    if let Ok(mut latest) = LATEST.lock() {
+        latest.insert(id.to_string(), status);
+    }
+}
+
+/// Forgets a watcher that stopped running, e.g. after the configuration was reloaded.
+pub fn forget(id: &str) {
+    if let Ok(mut latest) = LATEST.lock() {
+        latest.remove(id);
+    }
+}
+
+/// The latest status of every configured watcher, by identifier, along with the current
+/// acknowledgements of their firing alerts.
+pub fn snapshot() -> BTreeMap<String, WatcherStatus> {
+    let mut latest = LATEST
+        .lock()
+        .map(|latest| latest.clone())
+        .unwrap_or_default();
+    let now = SystemTime::now();
+    for alert in latest
+        .values_mut()
+        .flat_map(|status| status.firing.iter_mut())
+    {
+        alert.acknowledged_until = acknowledged_until(&alert.fingerprint, now).map(rfc3339);
+    }
+    latest
+}
+
+/// Sets the rules muting alerts, see [`mute::Reporter`].
+pub fn set_mute_rules(configuration: mute::Configuration) {
+    if let Ok(mut rules) = MUTE_RULES.lock() {
+        *rules = configuration;
+    }
+}
+
+/// Keeps the `length` latest transitions of each alert, none if 0, dropping the older ones.
+pub fn set_history_length(length: usize) {
+    HISTORY_LENGTH.store(length, Ordering::Relaxed);
+    if let Ok(mut history) = HISTORY.lock() {
+        for transitions in history.values_mut() {
+            let extra = transitions.len().saturating_sub(length);
+            transitions.drain(..extra);
+        }
+        history.retain(|_, transitions| !transitions.is_empty());
+    }
+}
+
+/// Records that an alert went `to` a state at `at`, from the state of its previous transition.
+pub fn record_transition(fingerprint: &str, to: AlertState, at: SystemTime) {
+    let length = HISTORY_LENGTH.load(Ordering::Relaxed);
+    if length == 0 {
+        return;
+    }
+    let Ok(mut history) = HISTORY.lock() else {
+        return;
+    };
+    let transitions = history.entry(fingerprint.to_string()).or_default();
+    let previous = transitions.back();
+    let transition = Transition {
+        fingerprint: fingerprint.to_string(),
+        from: previous.map_or(AlertState::Inactive, |p| p.to),
+        to,
+        at,
+        duration_seconds: previous
+            .and_then(|p| at.duration_since(p.at).ok())
+            .map(|d| d.as_secs()),
+    };
+    transitions.push_back(transition);
+    let extra = transitions.len().saturating_sub(length);
+    transitions.drain(..extra);
+}
+
+/// The latest transitions of every alert that fired, by fingerprint, the oldest first.
+pub fn history() -> BTreeMap<String, Vec<Transition>> {
+    HISTORY
+        .lock()
+        .map(|history| {
+            history
+                .iter()
+                .map(|(fingerprint, transitions)| {
+                    (fingerprint.clone(), transitions.iter().cloned().collect())
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Acknowledges an alert until `until`, if one of the watchers has it firing. While
+/// acknowledged, the alert is still evaluated but neither reported again nor escalated.
+pub fn acknowledge(fingerprint: &str, until: SystemTime) -> bool {
+    let firing = snapshot()
+        .values()
+        .any(|status| status.firing.iter().any(|a| a.fingerprint == fingerprint));
+    if firing {
+        if let Ok(mut acknowledged) = ACKNOWLEDGED.lock() {
+            acknowledged.insert(fingerprint.to_string(), until);
+        }
+    }
+    firing
+}
+
+/// Until when an alert is acknowledged, if that is after `now`. Expired acknowledgements are
+/// forgotten.
+pub fn acknowledged_until(fingerprint: &str, now: SystemTime) -> Option<SystemTime> {
+    let mut acknowledged = ACKNOWLEDGED.lock().ok()?;
+    acknowledged.retain(|_, until| *until > now);
+    acknowledged.get(fingerprint).copied()
+}
+
+/// Forgets the acknowledgement of an alert that resolved, so that it is reported if it fires
+/// again.
+pub fn unacknowledge(fingerprint: &str) {
+    if let Ok(mut acknowledged) = ACKNOWLEDGED.lock() {
+        acknowledged.remove(fingerprint);
+    }
+}
+
+/// Tells that the scheduler is alive and will be back by `until`, when its next watcher is due.
+pub fn beat(until: Option<Instant>) {
+    if let Ok(mut heartbeat) = HEARTBEAT.lock() {
+        *heartbeat = until.map_or(Heartbeat::Idle, Heartbeat::DueBy);
+    }
+}
+
+/// Tells until when alerts are held back after startup, or that they are not anymore with
+/// `None`, see [`crate::startup_grace::Reporter`].
+pub fn set_startup_grace(until: Option<SystemTime>) {
+    if let Ok(mut grace) = STARTUP_GRACE.lock() {
+        *grace = until;
+    }
+}
+
+/// Until when alerts are held back after startup, as an RFC 3339 date, unless they are not
+/// anymore.
+pub fn startup_grace_until() -> Option<String> {
+    STARTUP_GRACE
+        .lock()
+        .ok()
+        .and_then(|grace| grace.map(rfc3339))
+}
+
+/// Records how late the scheduler started a run of a watcher, see [`lags_since`].
+pub fn record_lag(lag: Lag) {
+    if let Ok(mut lags) = LAGS.lock() {
+        if lags.len() == MAX_LAGS {
+            lags.pop_front();
+        }
+        lags.push_back(lag);
+    }
+}
+
+/// Lags of the runs started since `since`, among the latest ones.
+pub fn lags_since(since: Instant) -> Vec<Lag> {
+    LAGS.lock()
+        .map(|lags| lags.iter().filter(|l| l.at >= since).copied().collect())
+        .unwrap_or_default()
+}
+
+/// Registers a queue of alerts, whose length its reporter keeps up to date in the returned
+/// counter, see [`queued_alerts`].
+pub fn register_queue() -> Arc<AtomicUsize> {
+    let length = Arc::new(AtomicUsize::new(0));
+    if let Ok(mut queues) = QUEUES.lock() {
+        queues.retain(|queue| queue.strong_count() > 0);
+        queues.push(Arc::downgrade(&length));
+    }
+    length
+}
+
+/// Alerts waiting in the queues of the reporters, altogether.
+pub fn queued_alerts() -> usize {
+    QUEUES
+        .lock()
+        .map(|queues| {
+            queues
+                .iter()
+                .filter_map(Weak::upgrade)
+                .map(|length| length.load(Ordering::Relaxed))
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether the scheduler came back in time, allowing it `grace` to run the watchers that are
+/// due.
+pub fn is_alive(grace: Duration) -> bool {
+    match HEARTBEAT.lock().map(|h| *h) {
+        Ok(Heartbeat::Idle) => true,
+        Ok(Heartbeat::DueBy(until)) => Instant::now() <= until + grace,
+        Ok(Heartbeat::NotStarted) | Err(_) => false,
+    }
+}
+
+/// Starts accepting the heartbeats of a source, see [`receive_heartbeat`].
+pub fn expect_heartbeat(source: &str) {
+    if let Ok(mut heartbeats) = EXTERNAL_HEARTBEATS.lock() {
+        heartbeats.entry(source.to_string()).or_default();
+    }
+}
+
+/// Records a heartbeat sent by a source at `at`, unless no watcher expects it.
+pub fn receive_heartbeat(source: &str, at: SystemTime) -> bool {
+    let Ok(mut heartbeats) = EXTERNAL_HEARTBEATS.lock() else {
+        return false;
+    };
+    match heartbeats.get_mut(source) {
+        Some(last) => {
+            *last = Some(at);
+            true
+        }
+        None => false,
+    }
+}
+
+/// When a source last sent a heartbeat, if it ever did.
+pub fn last_heartbeat(source: &str) -> Option<SystemTime> {
+    EXTERNAL_HEARTBEATS
+        .lock()
+        .ok()
+        .and_then(|heartbeats| heartbeats.get(source).copied().flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn firing_then_resolving_records_two_transitions() {
+        let fired_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let resolved_at = fired_at + Duration::from_secs(90);
+
+        record_transition("status-test/disk", AlertState::Firing, fired_at);
+        record_transition("status-test/disk", AlertState::Resolved, resolved_at);
+
+        let transitions = history().remove("status-test/disk").unwrap();
+        let summary = transitions
+            .iter()
+            .map(|t| (t.from, t.to, t.at, t.duration_seconds))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (AlertState::Inactive, AlertState::Firing, fired_at, None),
+                (
+                    AlertState::Firing,
+                    AlertState::Resolved,
+                    resolved_at,
+                    Some(90)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_latest_transitions_are_kept() {
+        let length = HISTORY_LENGTH.load(Ordering::Relaxed);
+        let at = |i: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(i);
+
+        for i in 0..length as u64 + 3 {
+            let to = match i % 2 {
+                0 => AlertState::Firing,
+                _ => AlertState::Resolved,
+            };
+            record_transition("status-test/memory", to, at(i));
+        }
+
+        let transitions = history().remove("status-test/memory").unwrap();
+        assert_eq!(transitions.len(), length);
+        assert_eq!(transitions[0].at, at(3));
+    }
+}
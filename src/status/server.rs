@@ -0,0 +1,638 @@
+//! Serves the status of the watchers as JSON on `/status`, along with the recent transitions of
+//! their alerts, a dashboard of it on `/`, and whether the scheduler is still running on
+//! `/healthz`. Firing alerts can be
+//! acknowledged with `POST /alerts/{fingerprint}/ack`, synthetic alerts sent to the reporters with
+//! `POST /alerts/test`, and the sources of `Deadman` watchers send their heartbeats with
+//! `POST /heartbeat/{name}`.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::duration::{deserialize_duration, parse_duration};
+use crate::scheduler::SchedulerHandle;
+use crate::secret::Secret;
+use crate::status::{
+    acknowledge, history, is_alive, receive_heartbeat, rfc3339, set_history_length, snapshot,
+    startup_grace_until,
+};
+use crate::watcher::{hostname, ActiveAlert, Severity};
+
+/// Longest request read, headers and body included.
+const MAX_REQUEST_LENGTH: usize = 64 * 1024;
+
+/// How long a client may take to send each part of its request or receive the response, after
+/// which its connection is dropped.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Page showing the watchers and their firing alerts, refreshed from `/status`.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+fn default_stall_after() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_history_length() -> usize {
+    10
+}
+
+fn default_acknowledgement_duration() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Acknowledgement {
+    /// Secret that requests must send as `Authorization: Bearer <token>`.
+    token: Secret,
+    /// How long alerts stay acknowledged, unless the request asks for `?duration=...`.
+    #[serde(
+        default = "default_acknowledgement_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    duration: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Configuration {
+    /// Address to listen on, e.g. `127.0.0.1:9899`.
+    address: String,
+    /// How late the scheduler may be to run its next watcher before it is considered stalled.
+    #[serde(
+        default = "default_stall_after",
+        deserialize_with = "deserialize_duration"
+    )]
+    stall_after: Duration,
+    /// Allows acknowledging alerts and sending synthetic ones, which are refused without it.
+    #[serde(default)]
+    acknowledgement: Option<Acknowledgement>,
+    /// Secret that heartbeats must send as `Authorization: Bearer <token>`. Anyone reaching the
+    /// server can send them without it.
+    #[serde(default)]
+    heartbeat_token: Option<Secret>,
+    /// Transitions of each alert listed in the `history` of the status, the latest ones.
+    #[serde(default = "default_history_length")]
+    history_length: usize,
+}
+
+/// Decodes the `%XX` escapes of a path segment, which fingerprints need for the `/` of paths.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Compares the token in a time that does not depend on where it differs.
+fn is_token(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Acknowledges the alert with the given fingerprint, for the duration of the `query` or the
+/// configured one.
+fn acknowledge_alert(
+    acknowledgement: Option<&Acknowledgement>,
+    token: Option<&str>,
+    fingerprint: &str,
+    query: Option<&str>,
+) -> (&'static str, String) {
+    let Some(acknowledgement) = acknowledgement else {
+        return (
+            "403 Forbidden",
+            json!({ "error": "acknowledgement is not enabled" }).to_string(),
+        );
+    };
+    if !token.is_some_and(|t| is_token(t, acknowledgement.token.expose())) {
+        return (
+            "401 Unauthorized",
+            json!({ "error": "invalid token" }).to_string(),
+        );
+    }
+    let duration = query
+        .and_then(|q| q.split('&').find_map(|p| p.strip_prefix("duration=")))
+        .map(|d| parse_duration(&percent_decode(d)))
+        .unwrap_or(Ok(acknowledgement.duration));
+    let duration = match duration {
+        Ok(duration) => duration,
+        Err(e) => {
+            return (
+                "400 Bad Request",
+                json!({ "error": format!("{:#}", e) }).to_string(),
+            )
+        }
+    };
+    let fingerprint = percent_decode(fingerprint);
+    let until = SystemTime::now() + duration;
+    if !acknowledge(&fingerprint, until) {
+        return (
+            "404 Not Found",
+            json!({ "error": "no such firing alert" }).to_string(),
+        );
+    }
+    info!(acknowledged = fingerprint, until = rfc3339(until));
+    (
+        "200 OK",
+        json!({ "fingerprint": fingerprint, "acknowledged_until": rfc3339(until) }).to_string(),
+    )
+}
+
+/// Alert posted to `/alerts/test`, reported as if a watcher had triggered it.
+#[derive(Deserialize, Debug)]
+struct TestAlert {
+    message: String,
+    #[serde(default)]
+    severity: Severity,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+/// Reports the synthetic alert of the JSON `body` to the reporters of the scheduler, along their
+/// routes, labelled `synthetic` so that it can be told apart from real alerts.
+fn test_alert(
+    acknowledgement: Option<&Acknowledgement>,
+    token: Option<&str>,
+    body: &str,
+    scheduler: &SchedulerHandle,
+    host: &str,
+) -> (&'static str, String) {
+    let Some(acknowledgement) = acknowledgement else {
+        return (
+            "403 Forbidden",
+            json!({ "error": "acknowledgement is not enabled" }).to_string(),
+        );
+    };
+    if !token.is_some_and(|t| is_token(t, acknowledgement.token.expose())) {
+        return (
+            "401 Unauthorized",
+            json!({ "error": "invalid token" }).to_string(),
+        );
+    }
+    let test: TestAlert = match serde_json::from_str(body) {
+        Ok(test) => test,
+        Err(e) => {
+            return (
+                "400 Bad Request",
+                json!({ "error": format!("Invalid test alert: {}", e) }).to_string(),
+            )
+        }
+    };
+    let now = SystemTime::now();
+    let mut labels = test.labels;
+    labels.insert("synthetic".to_string(), "true".to_string());
+    let fingerprint = format!(
+        "synthetic:{}",
+        now.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    );
+    let alert = ActiveAlert::new(test.message, test.severity).detected(
+        host,
+        now,
+        &labels,
+        "synthetic",
+        fingerprint.clone(),
+    );
+    if let Err(e) = scheduler.report(alert) {
+        return (
+            "503 Service Unavailable",
+            json!({ "error": format!("{:#}", e) }).to_string(),
+        );
+    }
+    info!(synthetic_alert = fingerprint);
+    (
+        "202 Accepted",
+        json!({ "fingerprint": fingerprint }).to_string(),
+    )
+}
+
+/// Records a heartbeat of the source with the given name, expected by a `Deadman` watcher.
+fn heartbeat(
+    heartbeat_token: Option<&Secret>,
+    token: Option<&str>,
+    source: &str,
+) -> (&'static str, String) {
+    if heartbeat_token.is_some_and(|h| !token.is_some_and(|t| is_token(t, h.expose()))) {
+        return (
+            "401 Unauthorized",
+            json!({ "error": "invalid token" }).to_string(),
+        );
+    }
+    let source = percent_decode(source);
+    let now = SystemTime::now();
+    if !receive_heartbeat(&source, now) {
+        return (
+            "404 Not Found",
+            json!({ "error": "no such heartbeat source" }).to_string(),
+        );
+    }
+    info!(heartbeat = source);
+    (
+        "200 OK",
+        json!({ "source": source, "received_at": rfc3339(now) }).to_string(),
+    )
+}
+
+/// Reads the headers of a request, along with as much of its body as its `Content-Length` tells.
+fn read_request(stream: &mut TcpStream) -> Result<String> {
+    let mut request = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        let length = stream.read(&mut chunk).context("Could not read request")?;
+        request.extend_from_slice(&chunk[..length]);
+        let text = String::from_utf8_lossy(&request);
+        let complete = text.split_once("\r\n\r\n").is_some_and(|(headers, body)| {
+            let content_length = headers.lines().find_map(|header| {
+                let (name, value) = header.split_once(':')?;
+                name.trim()
+                    .eq_ignore_ascii_case("content-length")
+                    .then(|| value.trim().parse::<usize>().ok())
+                    .flatten()
+            });
+            body.len() >= content_length.unwrap_or(0)
+        });
+        if complete || length == 0 || request.len() >= MAX_REQUEST_LENGTH {
+            return Ok(text.into_owned());
+        }
+    }
+}
+
+fn respond(
+    mut stream: TcpStream,
+    configuration: &Configuration,
+    host: &str,
+    scheduler: &SchedulerHandle,
+) -> Result<()> {
+    // Only the request line matters, e.g. `GET /status HTTP/1.1`, and the token of
+    // acknowledgements and heartbeats, along with the body of synthetic alerts.
+    let request = read_request(&mut stream)?;
+    let mut request_line = request.split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target
+        .split_once('?')
+        .map_or((target, None), |(path, query)| (path, Some(query)));
+    let token = request.lines().skip(1).find_map(|header| {
+        let (name, value) = header.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("authorization")
+            .then(|| value.trim().strip_prefix("Bearer "))
+            .flatten()
+    });
+    let acknowledged = path
+        .strip_prefix("/alerts/")
+        .and_then(|p| p.strip_suffix("/ack"));
+    let source = path
+        .strip_prefix("/heartbeat/")
+        .filter(|s| !s.is_empty() && !s.contains('/'));
+    let stall_after = configuration.stall_after;
+    let content_type = if (method, path) == ("GET", "/") {
+        "text/html; charset=utf-8"
+    } else {
+        "application/json"
+    };
+    let (status, body) = match (method, path, acknowledged, source) {
+        ("GET", "/", _, _) => ("200 OK", DASHBOARD.to_string()),
+        ("POST", "/alerts/test", _, _) => test_alert(
+            configuration.acknowledgement.as_ref(),
+            token,
+            request.split_once("\r\n\r\n").map_or("", |(_, body)| body),
+            scheduler,
+            host,
+        ),
+        ("POST", _, Some(fingerprint), _) => acknowledge_alert(
+            configuration.acknowledgement.as_ref(),
+            token,
+            fingerprint,
+            query,
+        ),
+        ("POST", _, _, Some(source)) => {
+            heartbeat(configuration.heartbeat_token.as_ref(), token, source)
+        }
+        (_, "/status", _, _) => (
+            "200 OK",
+            json!({
+                "hostname": host,
+                "watchers": snapshot(),
+                "history": history(),
+                "startup_grace_until": startup_grace_until(),
+            })
+            .to_string(),
+        ),
+        (_, "/healthz", _, _) if is_alive(stall_after) => {
+            ("200 OK", json!({ "alive": true }).to_string())
+        }
+        (_, "/healthz", _, _) => (
+            "503 Service Unavailable",
+            json!({ "alive": false }).to_string(),
+        ),
+        _ => ("404 Not Found", json!({ "error": "not found" }).to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+    .context("Could not write response")
+}
+
+/// Starts serving the status from a background thread, sending synthetic alerts to `scheduler`.
+pub fn serve(configuration: &Configuration, scheduler: SchedulerHandle) -> Result<()> {
+    let listener = TcpListener::bind(&configuration.address)
+        .with_context(|| format!("Could not listen on {}", configuration.address))?;
+    info!(serving_status = configuration.address);
+    accept(listener, configuration, scheduler);
+    Ok(())
+}
+
+/// Answers the connections to `listener` from a background thread, each on a thread of its own
+/// and within [`CONNECTION_TIMEOUT`], so that a client that never sends its request does not
+/// hold `/healthz` back for the other ones.
+fn accept(listener: TcpListener, configuration: &Configuration, scheduler: SchedulerHandle) {
+    set_history_length(configuration.history_length);
+    let configuration = Arc::new(configuration.clone());
+    let host = Arc::<str>::from(hostname());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!(status_endpoint = ?e);
+                    continue;
+                }
+            };
+            let (configuration, host, scheduler) = (
+                Arc::clone(&configuration),
+                Arc::clone(&host),
+                scheduler.clone(),
+            );
+            thread::spawn(move || {
+                let responded = stream
+                    .set_read_timeout(Some(CONNECTION_TIMEOUT))
+                    .and_then(|()| stream.set_write_timeout(Some(CONNECTION_TIMEOUT)))
+                    .context("Could not set the timeouts of the connection")
+                    .and_then(|()| respond(stream, &configuration, &host, &scheduler));
+                if let Err(e) = responded {
+                    warn!(status_endpoint = ?e);
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::time::Instant;
+
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_store::memory::Memory;
+    use crate::scheduler::{self, Scheduler};
+    use crate::status::{acknowledged_until, beat, forget, record, WatcherStatus};
+
+    /// Serves the status on a port of its own, with the default settings but for the ones of
+    /// `settings`.
+    fn server_with(settings: serde_json::Value) -> (SocketAddr, Scheduler, SchedulerHandle) {
+        let mut configuration = json!({ "address": "127.0.0.1:0" });
+        configuration
+            .as_object_mut()
+            .expect("Not an object")
+            .extend(settings.as_object().cloned().unwrap_or_default());
+        let configuration =
+            serde_json::from_value::<Configuration>(configuration).expect("Invalid configuration");
+        let (scheduler, handle) =
+            Scheduler::new(Vec::new(), &scheduler::Configuration::default()).expect("No scheduler");
+        let listener = TcpListener::bind(&configuration.address).expect("Could not listen");
+        let address = listener.local_addr().expect("No address");
+        accept(listener, &configuration, handle.clone());
+        (address, scheduler, handle)
+    }
+
+    /// Serves the status on a port of its own, with the default settings.
+    fn server() -> (SocketAddr, Scheduler) {
+        let (address, scheduler, _) = server_with(json!({}));
+        (address, scheduler)
+    }
+
+    /// Status line, headers and body of the response to `request`.
+    fn send(address: SocketAddr, request: &str) -> (String, String, String) {
+        let mut stream = TcpStream::connect(address).expect("Could not connect");
+        stream.write_all(request.as_bytes()).expect("Not sent");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).expect("No response");
+        let (headers, body) = response.split_once("\r\n\r\n").expect("No body");
+        let (status, headers) = headers.split_once("\r\n").unwrap_or((headers, ""));
+        (status.to_string(), headers.to_string(), body.to_string())
+    }
+
+    /// Status line and body of the response to `GET path`.
+    fn get(address: SocketAddr, path: &str) -> (String, serde_json::Value) {
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        let (status, _, body) = send(address, &request);
+        (
+            status,
+            serde_json::from_str(&body).expect("Body is not JSON"),
+        )
+    }
+
+    /// Status line and body of the response to `POST path`, sent with `token` if any.
+    fn post(
+        address: SocketAddr,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (String, serde_json::Value) {
+        let authorization = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+            path,
+            authorization,
+            body.len(),
+            body
+        );
+        let (status, _, body) = send(address, &request);
+        (
+            status,
+            serde_json::from_str(&body).expect("Body is not JSON"),
+        )
+    }
+
+    // The heartbeat of the scheduler is global, so that both states are checked by the same
+    // test.
+    #[test]
+    fn serves_status_and_health() {
+        let (address, _scheduler) = server();
+
+        let (status, body) = get(address, "/status");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body["watchers"].is_object(), "{}", body);
+        assert!(body["hostname"].is_string(), "{}", body);
+
+        beat(Some(Instant::now() + Duration::from_secs(10)));
+        let (status, body) = get(address, "/healthz");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, json!({ "alive": true }));
+
+        // Due 2 minutes ago, past the 60s the scheduler may be late by.
+        let stale = Instant::now().checked_sub(Duration::from_secs(120));
+        beat(Some(
+            stale.expect("The clock started less than 2 minutes ago"),
+        ));
+        let (status, body) = get(address, "/healthz");
+        assert_eq!(status, "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(body, json!({ "alive": false }));
+    }
+
+    #[test]
+    fn silent_client_does_not_hold_back_the_other_ones() {
+        let (address, _scheduler) = server();
+        let _silent = TcpStream::connect(address).expect("Could not connect");
+
+        let started = Instant::now();
+        let (status, _) = get(address, "/status");
+
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(started.elapsed() < CONNECTION_TIMEOUT);
+    }
+
+    #[test]
+    fn acknowledges_alerts_only_with_the_token() {
+        let (address, _scheduler, _) =
+            server_with(json!({ "acknowledgement": { "token": "secret" } }));
+        let mut alert = ActiveAlert::new("Disk is full".to_string(), Severity::Critical);
+        alert.fingerprint = "disk_space:0123456789abcdef:/var".to_string();
+        record(
+            "disk_space:acknowledged",
+            WatcherStatus::new("disk_space", Ok(String::new()), vec![(&alert).into()]),
+        );
+        let path = "/alerts/disk_space:0123456789abcdef:%2Fvar/ack?duration=10m";
+
+        let (status, _) = post(address, path, None, "");
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        let (status, _) = post(address, path, Some("guess"), "");
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        assert!(acknowledged_until(&alert.fingerprint, SystemTime::now()).is_none());
+
+        let (status, body) = post(address, path, Some("secret"), "");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["fingerprint"], alert.fingerprint);
+        let until = acknowledged_until(&alert.fingerprint, SystemTime::now());
+        let remaining = until.and_then(|u| u.duration_since(SystemTime::now()).ok());
+        assert!(
+            remaining.is_some_and(|r| r > Duration::from_secs(9 * 60)),
+            "{:?}",
+            remaining
+        );
+        forget("disk_space:acknowledged");
+    }
+
+    #[test]
+    fn test_alerts_reach_the_reporters_only_with_the_token() {
+        let (address, scheduler, handle) =
+            server_with(json!({ "acknowledgement": { "token": "secret" } }));
+        let reporter = RecordingReporter::new();
+        let store = Memory::new(Default::default());
+        let body = r#"{ "message": "Testing the pager", "labels": { "team": "ops" } }"#;
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                scheduler.run(
+                    &reporter,
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &Default::default(),
+                    &store,
+                )
+            });
+            let (status, _) = post(address, "/alerts/test", None, body);
+            assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+            let (status, response) = post(address, "/alerts/test", Some("secret"), body);
+            assert_eq!(status, "HTTP/1.1 202 Accepted");
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while reporter.alerts().is_empty() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            handle.shutdown();
+
+            let alerts = reporter.alerts();
+            assert_eq!(alerts.len(), 1, "{:?}", alerts);
+            assert_eq!(alerts[0].message, "Testing the pager");
+            assert_eq!(alerts[0].fingerprint, response["fingerprint"]);
+            assert_eq!(alerts[0].labels["team"], "ops");
+            assert_eq!(alerts[0].labels["synthetic"], "true");
+        });
+    }
+
+    #[test]
+    fn test_alerts_are_refused_without_acknowledgement() {
+        let (address, _scheduler) = server();
+
+        let (status, _) = post(
+            address,
+            "/alerts/test",
+            Some("secret"),
+            r#"{ "message": "Hi" }"#,
+        );
+
+        assert_eq!(status, "HTTP/1.1 403 Forbidden");
+    }
+
+    #[test]
+    fn serves_a_dashboard_of_the_status() {
+        let (address, _scheduler) = server();
+
+        let (status, headers, body) = send(address, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(
+            headers.contains("Content-Type: text/html; charset=utf-8"),
+            "{}",
+            headers
+        );
+        assert!(body.starts_with("<!DOCTYPE html>"), "{}", body);
+        assert!(body.contains(r#"fetch("/status""#), "{}", body);
+        // A row for each watcher, named by its id, with its last run, check and firing alerts.
+        for field in [
+            "status.watchers",
+            "last_run",
+            "watcher.check",
+            "watcher.firing",
+        ] {
+            assert!(body.contains(field), "No {} in {}", field, body);
+        }
+    }
+}
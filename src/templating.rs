@@ -0,0 +1,69 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use tracing::warn;
+
+/// Placeholders that were already reported as unknown, so that they only get logged once.
+static UNKNOWN_PLACEHOLDERS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Replaces each `{name}` placeholder in `template` by the value of the `name` field. Unknown
+/// placeholders are left as is.
+pub fn render_template(template: &str, fields: &BTreeMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + length];
+        rendered.push_str(&rest[..start]);
+        match fields.get(name) {
+            Some(value) => rendered.push_str(value),
+            None => {
+                let newly_unknown = UNKNOWN_PLACEHOLDERS
+                    .lock()
+                    .map(|mut unknown| unknown.insert(name.to_string()))
+                    .unwrap_or(false);
+                if newly_unknown {
+                    warn!(unknown_placeholder = name, template);
+                }
+                rendered.push_str(&rest[start..=start + length]);
+            }
+        }
+        rest = &rest[start + length + 1..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("mount".to_string(), "/var".to_string()),
+            ("used_pct".to_string(), "91.5".to_string()),
+        ])
+    }
+
+    #[test]
+    fn substitutes_the_fields() {
+        assert_eq!(
+            render_template("Disk {mount} at {used_pct}%, {mount} is full", &fields()),
+            "Disk /var at 91.5%, /var is full"
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_placeholders_literal() {
+        assert_eq!(
+            render_template("Disk {mount} at {used_pct}% of {threshold}%", &fields()),
+            "Disk /var at 91.5% of {threshold}%"
+        );
+        assert_eq!(
+            render_template("Disk {mount} at {used_pct", &fields()),
+            "Disk /var at {used_pct"
+        );
+    }
+}
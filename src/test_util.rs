@@ -0,0 +1,55 @@
+//! Helpers for testing code built on Monitrust, behind the `test-util` feature.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::clock::Clock;
+
+/// A clock that only moves when advanced. Clones share the same time, so that a test can keep
+/// one to advance the clock of the watcher it gave another one to.
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    now: Arc<Mutex<(Instant, SystemTime)>>,
+}
+
+impl FakeClock {
+    /// A clock stopped at the current time.
+    pub fn new() -> Self {
+        FakeClock {
+            now: Arc::new(Mutex::new((Instant::now(), SystemTime::now()))),
+        }
+    }
+
+    /// A clock stopped at `time`, e.g. to test what depends on the time of day.
+    pub fn at(time: SystemTime) -> Self {
+        FakeClock {
+            now: Arc::new(Mutex::new((Instant::now(), time))),
+        }
+    }
+
+    fn time(&self) -> (Instant, SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        now.0 += duration;
+        now.1 += duration;
+    }
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        FakeClock::new()
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        self.time().0
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.time().1
+    }
+}
@@ -0,0 +1,78 @@
+//! Checks a watcher configuration without running it.
+
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+use crate::reload::read_config;
+use crate::watcher::{Watcher, WatcherConfiguration, WatcherEnum};
+
+/// Watcher entries in file order, kept as raw values so that each one can fail on its own.
+struct Entries(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for Entries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = Entries;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of watcher configurations")
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Entries, M::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+/// Whether the entry is enabled. Enabled watchers are probed, while disabled ones are only
+/// parsed, as they may target what is missing from the host.
+fn validate_entry(kind: &str, value: Value) -> Result<bool> {
+    let mut entry = serde_json::Map::new();
+    entry.insert(kind.to_string(), value);
+    let configuration: WatcherConfiguration = serde_json::from_value(Value::Object(entry))?;
+    let watcher = WatcherEnum::try_from(configuration)?;
+    if watcher.is_enabled() {
+        watcher.probe()?;
+    }
+    Ok(watcher.is_enabled())
+}
+
+/// Parses every watcher of a configuration file and builds it, without checking anything nor
+/// sending alerts. All the problems found are listed in the returned error, otherwise the
+/// disabled watchers are listed.
+pub fn validate(path: &Path) -> Result<Vec<String>> {
+    let entries: Entries = read_config(path)?;
+    let (mut problems, mut disabled) = (Vec::new(), Vec::new());
+    for (index, (kind, value)) in entries.0.into_iter().enumerate() {
+        let watcher = format!("  watcher #{} ({})", index + 1, kind);
+        match validate_entry(&kind, value) {
+            Ok(true) => {}
+            Ok(false) => disabled.push(format!("{}: disabled", watcher)),
+            Err(e) => problems.push(format!("{}: {:#}", watcher, e)),
+        }
+    }
+    if problems.is_empty() {
+        Ok(disabled)
+    } else {
+        Err(anyhow!(
+            "{} invalid watcher(s) in {}:\n{}",
+            problems.len(),
+            path.display(),
+            problems.join("\n")
+        ))
+    }
+}
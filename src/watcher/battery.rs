@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::pattern::Pattern;
+use crate::watcher;
+use crate::watcher::{ActiveAlert, Severity};
+
+const POWER_SUPPLY_DIRECTORY: &str = "/sys/class/power_supply";
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    batteries: Vec<Pattern>,
+    /// Whether the absence of batteries was logged already.
+    logged_no_battery: AtomicBool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Batteries to watch, as named in `/sys/class/power_supply`, such as `BAT0`. All of them if
+    /// empty.
+    #[serde(default)]
+    batteries: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatteryState {
+    /// Remaining charge, in percent.
+    pub charge: u8,
+    /// As reported by the kernel, e.g. `Charging`, `Discharging` or `Full`.
+    pub status: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PowerSupply {
+    /// Whether the host is powered by mains, unknown without any mains or UPS power supply.
+    pub ac_online: Option<bool>,
+    /// State of each watched battery, by name. Empty on hosts without batteries.
+    pub batteries: BTreeMap<String, BatteryState>,
+}
+
+impl PowerSupply {
+    /// Whether the host runs on battery, as told by a mains power supply or, lacking one, by a
+    /// discharging battery.
+    pub fn on_battery(&self) -> bool {
+        match self.ac_online {
+            Some(online) => !online,
+            None => self.batteries.values().any(|b| b.status == "Discharging"),
+        }
+    }
+}
+
+fn read_property(supply: &Path, property: &str) -> Result<String> {
+    let path = supply.join(property);
+    fs::read_to_string(&path)
+        .map(|v| v.trim().to_string())
+        .with_context(|| format!("Could not read power supply property '{}'", path.display()))
+}
+
+impl Checker {
+    fn is_watched(&self, battery: &str) -> bool {
+        self.batteries.is_empty() || self.batteries.iter().any(|p| p.matches(battery))
+    }
+
+    fn read_power_supply(&self) -> Result<PowerSupply> {
+        let mut ac_online = None;
+        let mut batteries = BTreeMap::new();
+        let mut others = Vec::new();
+        // Hosts without any power supply simply have no entry in the directory, if any.
+        if let Ok(supplies) = fs::read_dir(POWER_SUPPLY_DIRECTORY) {
+            for supply in supplies.flatten() {
+                let path = supply.path();
+                let name = supply.file_name().to_string_lossy().into_owned();
+                match read_property(&path, "type")?.as_str() {
+                    "Mains" | "UPS" => {
+                        let online = read_property(&path, "online")? == "1";
+                        ac_online = Some(ac_online.unwrap_or(false) || online);
+                    }
+                    "Battery" if self.is_watched(&name) => {
+                        let charge = read_property(&path, "capacity")?;
+                        let charge = charge.parse::<u8>().with_context(|| {
+                            format!("Could not convert battery charge to integer: {}", charge)
+                        })?;
+                        let status = read_property(&path, "status")?;
+                        batteries.insert(name, BatteryState { charge, status });
+                    }
+                    "Battery" => others.push(name),
+                    _ => {}
+                }
+            }
+        }
+        if batteries.is_empty() && !others.is_empty() {
+            return Err(anyhow!(
+                "No battery matches the configured ones (available: {})",
+                others.join(", ")
+            ));
+        }
+        Ok(PowerSupply {
+            ac_online,
+            batteries,
+        })
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = PowerSupply;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "battery");
+        let power_supply = self.read_power_supply()?;
+        if power_supply.batteries.is_empty()
+            && !self.logged_no_battery.swap(true, Ordering::Relaxed)
+        {
+            info!("No battery found, only the AC power will be watched");
+        }
+        for (battery, state) in &power_supply.batteries {
+            info!(battery, charge = state.charge, status = state.status);
+        }
+        info!(ac_online = power_supply.ac_online);
+        Ok(power_supply)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let power = match check_result.ac_online {
+            Some(true) => Some("On AC power".to_string()),
+            Some(false) => Some("On battery".to_string()),
+            None => None,
+        };
+        let summary =
+            power
+                .into_iter()
+                .chain(check_result.batteries.iter().map(|(name, battery)| {
+                    format!("{}: {}% {}", name, battery.charge, battery.status)
+                }))
+                .collect::<Vec<_>>();
+        match summary.is_empty() {
+            true => "No power supply".to_string(),
+            false => summary.join(", "),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            batteries: configuration.batteries,
+            logged_no_battery: AtomicBool::new(false),
+        }
+    }
+
+    fn probe(&self) -> Result<()> {
+        self.read_power_supply().map(|_| ())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let mut samples = check_result
+            .batteries
+            .iter()
+            .map(|(battery, state)| {
+                Sample::new(
+                    "monitrust_battery_charge_percent",
+                    &[("battery", battery.as_str())],
+                    state.charge as f64,
+                )
+            })
+            .collect::<Vec<_>>();
+        if let Some(online) = check_result.ac_online {
+            samples.push(Sample::new(
+                "monitrust_ac_online",
+                &[],
+                if online { 1.0 } else { 0.0 },
+            ));
+        }
+        samples
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Alert when a battery is charged less than this percentage while the host runs on it.
+    #[serde(default)]
+    min_charge: Option<u8>,
+    /// Alert as soon as the host runs on battery, e.g. for servers behind a UPS.
+    #[serde(default)]
+    on_battery: bool,
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self.min_charge {
+            Some(min_charge) if min_charge > 100 => {
+                Err(anyhow!("'min_charge' is a percentage, not {}", min_charge))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        if !check_result.on_battery() {
+            return None;
+        }
+        let lowest = check_result.batteries.iter().min_by_key(|(_, b)| b.charge);
+        match (lowest, self.min_charge) {
+            (Some((battery, state)), Some(min_charge)) if state.charge < min_charge => {
+                Some(ActiveAlert::new(
+                    format!(
+                        "🪫 Battery {} is at {}% while running on battery (threshold: {}%).",
+                        battery, state.charge, min_charge
+                    ),
+                    Severity::Critical,
+                ))
+            }
+            _ if self.on_battery => Some(ActiveAlert::new(
+                match lowest {
+                    Some((battery, state)) => format!(
+                        "🔌 AC power is lost, running on battery {} at {}%.",
+                        battery, state.charge
+                    ),
+                    None => "🔌 AC power is lost, running on battery.".to_string(),
+                },
+                Severity::default(),
+            )),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::hash::{Hash, Hasher};
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{deserialize_duration, Period};
+use crate::metrics::Sample;
+use crate::watcher;
+use crate::watcher::{threshold_bits, ActiveAlert, Severity};
+
+/// Characters of the output that alerts quote.
+const SNIPPET_LENGTH: usize = 200;
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_critical_codes() -> Vec<i32> {
+    vec![2]
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    command: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Program to run, looked for in the `PATH` unless it is a path.
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// The command is killed if it runs for longer than that, failing the check.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    /// Unset if the command was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    /// The first number printed by the command, such as `42` in `OK - 42 jobs queued`.
+    pub fn value(&self) -> Option<f64> {
+        self.stdout
+            .split(|c: char| c.is_whitespace() || c == ';' || c == '=' || c == '|')
+            .find_map(|word| word.parse::<f64>().ok())
+    }
+
+    /// The beginning of what the command printed, on its standard error if it printed nothing
+    /// else.
+    pub fn snippet(&self) -> String {
+        let output = match self.stdout.trim() {
+            "" => self.stderr.trim(),
+            stdout => stdout,
+        };
+        let mut snippet = output.chars().take(SNIPPET_LENGTH).collect::<String>();
+        if snippet.len() < output.len() {
+            snippet.push('…');
+        }
+        snippet
+    }
+}
+
+/// Reads a pipe of the command from its own thread, as the command may fill both before it exits.
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let Some(mut pipe) = pipe else { return };
+        let mut buffer = [0; 4096];
+        // What was read before the pipe failed is all there is.
+        while let Ok(read @ 1..) = pipe.read(&mut buffer) {
+            if sender.send(buffer[..read].to_vec()).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+/// What the command wrote to a pipe, up to the deadline if processes it started in the
+/// background still hold the pipe open.
+fn read_until(receiver: Receiver<Vec<u8>>, deadline: Instant) -> String {
+    let mut content = Vec::new();
+    while let Ok(chunk) = receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    {
+        content.extend(chunk);
+    }
+    String::from_utf8_lossy(&content).into_owned()
+}
+
+/// Kills the command, along with the processes it started on Unix, which share its process
+/// group.
+fn kill(child: &mut Child) -> Result<()> {
+    #[cfg(all(unix, feature = "nix"))]
+    {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+        killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL)
+            .context("Could not kill command")?;
+    }
+    #[cfg(not(all(unix, feature = "nix")))]
+    child.kill().context("Could not kill command")?;
+    let _ = child.wait();
+    Ok(())
+}
+
+/// Waits for the command to exit, killing it once the deadline is past.
+fn wait(child: &mut Child, deadline: Instant, timeout: Duration) -> Result<Option<i32>> {
+    loop {
+        if let Some(status) = child.try_wait().context("Could not wait for command")? {
+            return Ok(status.code());
+        }
+        if Instant::now() >= deadline {
+            kill(child)?;
+            return Err(anyhow!(
+                "Command timed out after {:?} and was killed",
+                timeout
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Whether the program can be found, as a path or in one of the directories of the `PATH`.
+fn is_found(command: &str) -> bool {
+    if command.contains(std::path::MAIN_SEPARATOR) || command.contains('/') {
+        return Path::new(command).is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|directory| {
+            directory.join(command).is_file()
+                || (cfg!(windows) && directory.join(format!("{}.exe", command)).is_file())
+        })
+    })
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = CommandOutput;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "command", command = self.command);
+        let mut command = Command::new(&self.command);
+        command
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let deadline = Instant::now() + self.timeout;
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(anyhow!("Command '{}' was not found", self.command))
+            }
+            Err(e) => return Err(e).context(format!("Could not run '{}'", self.command)),
+        };
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+        let exit_code = wait(&mut child, deadline, self.timeout)?;
+        let output = CommandOutput {
+            exit_code,
+            stdout: read_until(stdout, deadline),
+            stderr: read_until(stderr, deadline),
+        };
+        info!(exit_code = output.exit_code, output = output.snippet());
+        Ok(output)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let exit = match check_result.exit_code {
+            Some(exit_code) => format!("Exited with {}", exit_code),
+            None => "Killed by a signal".to_string(),
+        };
+        match check_result.snippet() {
+            snippet if snippet.is_empty() => exit,
+            snippet => format!("{}: {}", exit, snippet),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            command: configuration.command,
+            args: configuration.args,
+            timeout: configuration.timeout,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.timeout >= self.period {
+            return Err(anyhow!(
+                "'timeout' ({:?}) must be shorter than the period ({:?})",
+                self.timeout,
+                self.period
+            ));
+        }
+        Ok(())
+    }
+
+    fn probe(&self) -> Result<()> {
+        if !is_found(&self.command) {
+            return Err(anyhow!("Command '{}' was not found", self.command));
+        }
+        Ok(())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let labels = [("command", self.command.as_str())];
+        let mut samples = Vec::new();
+        if let Some(exit_code) = check_result.exit_code {
+            samples.push(Sample::new(
+                "monitrust_command_exit_code",
+                &labels,
+                exit_code as f64,
+            ));
+        }
+        if let Some(value) = check_result.value() {
+            samples.push(Sample::new("monitrust_command_value", &labels, value));
+        }
+        samples
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// Fires when the command does not exit with 0, as Nagios plugins do: the alert is critical
+    /// for the `critical` exit codes (2 by default), and a warning otherwise.
+    ExitCode {
+        #[serde(default = "default_critical_codes")]
+        critical: Vec<i32>,
+    },
+    /// Fires when the first number the command prints is above the threshold, or when it prints
+    /// none.
+    Value {
+        threshold: f64,
+        /// Above this value, the alert is critical rather than a warning.
+        #[serde(default)]
+        critical: Option<f64>,
+    },
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Alert::ExitCode { critical } => {
+                0.hash(state);
+                critical.hash(state);
+            }
+            Alert::Value {
+                threshold,
+                critical,
+            } => {
+                1.hash(state);
+                threshold_bits(*threshold).hash(state);
+                critical.map(threshold_bits).hash(state);
+            }
+        }
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Alert::ExitCode { .. } => Ok(()),
+            Alert::Value {
+                threshold,
+                critical,
+            } => watcher::validate_critical(*threshold, *critical),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        match self {
+            Alert::ExitCode { critical } => {
+                let exit = match check_result.exit_code {
+                    Some(0) => return None,
+                    Some(code) => format!("exited with {}", code),
+                    None => "was killed by a signal".to_string(),
+                };
+                let severity = match check_result.exit_code {
+                    Some(code) if critical.contains(&code) => Severity::Critical,
+                    _ => Severity::default(),
+                };
+                Some(ActiveAlert::new(
+                    format!("📟 Command {}: {}", exit, check_result.snippet()),
+                    severity,
+                ))
+            }
+            Alert::Value {
+                threshold,
+                critical,
+            } => {
+                let Some(value) = check_result.value() else {
+                    return Some(ActiveAlert::new(
+                        format!(
+                            "📟 Command printed no value (exit code: {}): {}",
+                            check_result
+                                .exit_code
+                                .map_or("none".to_string(), |c| c.to_string()),
+                            check_result.snippet()
+                        ),
+                        Severity::default(),
+                    ));
+                };
+                if value <= *threshold {
+                    return None;
+                }
+                let severity = match critical {
+                    Some(critical) if value > *critical => Severity::Critical,
+                    _ => Severity::default(),
+                };
+                Some(ActiveAlert::new(
+                    format!(
+                        "📟 Command printed {} (threshold: {}, exit code: {}): {}",
+                        value,
+                        threshold,
+                        check_result
+                            .exit_code
+                            .map_or("none".to_string(), |c| c.to_string()),
+                        check_result.snippet()
+                    ),
+                    severity,
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::{Alert as _, Checker as _};
+    use serde_json::json;
+
+    fn checker(script: &str, timeout: &str) -> Checker {
+        Checker::new(
+            serde_json::from_value(json!({
+                "period": "1m",
+                "command": "sh",
+                "args": ["-c", script],
+                "timeout": timeout,
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn alert(alert: serde_json::Value) -> Alert {
+        serde_json::from_value(alert).unwrap()
+    }
+
+    #[test]
+    fn values_are_the_first_number_printed() {
+        let output = |stdout: &str| CommandOutput {
+            exit_code: Some(0),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        };
+
+        assert_eq!(output("OK - 42 jobs queued").value(), Some(42.0));
+        assert_eq!(output("load=0.75;1;2").value(), Some(0.75));
+        assert_eq!(output("nothing to see").value(), None);
+        assert_eq!(output(&"x".repeat(300)).snippet().chars().count(), 201);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_command_exiting_with_an_error_alerts() {
+        let output = checker("echo 'CRITICAL - queue is stuck'; exit 2", "5s")
+            .check()
+            .unwrap();
+        assert_eq!(output.exit_code, Some(2));
+
+        let active = alert(json!({ "ExitCode": {} }))
+            .is_triggered(&output)
+            .unwrap();
+        assert_eq!(active.severity, Severity::Critical);
+        assert_eq!(
+            active.message,
+            "📟 Command exited with 2: CRITICAL - queue is stuck"
+        );
+
+        let output = checker("echo fine", "5s").check().unwrap();
+        assert!(alert(json!({ "ExitCode": {} }))
+            .is_triggered(&output)
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_value_over_the_threshold_alerts() {
+        let output = checker("echo 'OK - 42 jobs queued'", "5s").check().unwrap();
+
+        let active = alert(json!({ "Value": { "threshold": 40.0 } }))
+            .is_triggered(&output)
+            .unwrap();
+        assert_eq!(active.severity, Severity::Warning);
+        assert_eq!(
+            active.message,
+            "📟 Command printed 42 (threshold: 40, exit code: 0): OK - 42 jobs queued"
+        );
+        assert!(alert(json!({ "Value": { "threshold": 50.0 } }))
+            .is_triggered(&output)
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_command_running_too_long_is_killed() {
+        let started = Instant::now();
+
+        let error = checker("sleep 5", "100ms").check().unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Command timed out after 100ms and was killed"
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,170 @@
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::watcher;
+use crate::watcher::{decimal, percent, threshold_bits, ActiveAlert, Severity};
+
+const NETFILTER_DIR: &str = "/proc/sys/net/netfilter";
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+}
+
+/// Entries of the connection tracking table of netfilter.
+#[derive(Debug, Clone, Copy)]
+pub struct Conntrack {
+    pub count: u64,
+    pub max: u64,
+}
+
+impl Conntrack {
+    /// Fraction of the table in use, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        if self.max == 0 {
+            return 0.0;
+        }
+        self.count as f64 / self.max as f64
+    }
+}
+
+fn read_value(name: &str) -> Result<u64> {
+    let path = Path::new(NETFILTER_DIR).join(name);
+    let content =
+        std::fs::read_to_string(&path).context(format!("Could not read '{}'", path.display()))?;
+    content.trim().parse::<u64>().context(format!(
+        "Could not convert '{}' to integer: {}",
+        path.display(),
+        content.trim()
+    ))
+}
+
+fn read_conntrack() -> Result<Conntrack> {
+    Ok(Conntrack {
+        count: read_value("nf_conntrack_count")?,
+        max: read_value("nf_conntrack_max")?,
+    })
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Conntrack;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "conntrack");
+        let conntrack = read_conntrack()?;
+        info!(
+            conntrack_count = conntrack.count,
+            conntrack_max = conntrack.max
+        );
+        Ok(conntrack)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let used = match check_result.max {
+            0 => String::new(),
+            max => format!(" ({})", percent(check_result.count as f64 / max as f64)),
+        };
+        format!(
+            "{} of {} entries{}",
+            check_result.count, check_result.max, used
+        )
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+        }
+    }
+
+    /// Fails on hosts where connections are not tracked, which only have the counters once the
+    /// `nf_conntrack` module is loaded.
+    fn probe(&self) -> Result<()> {
+        if !Path::new(NETFILTER_DIR).join("nf_conntrack_count").exists() {
+            return Err(anyhow!(
+                "Connection tracking is not available, is the 'nf_conntrack' module loaded?"
+            ));
+        }
+        read_conntrack().map(|_| ())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        vec![
+            Sample::new(
+                "monitrust_conntrack_entries",
+                &[],
+                check_result.count as f64,
+            ),
+            Sample::new("monitrust_conntrack_max", &[], check_result.max as f64),
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// Fraction of the table above which to alert, between 0 and 1.
+    threshold: f64,
+    /// Above this fraction, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        watcher::validate_fraction("threshold", self.threshold)?;
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let ratio = check_result.ratio();
+        if ratio <= self.threshold {
+            return None;
+        }
+        let severity = match self.critical {
+            Some(critical) if ratio > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        Some(ActiveAlert::new(
+            format!(
+                "🔌 Connection tracking table is at {} of {} entries ({}%, threshold: {}%).",
+                check_result.count,
+                check_result.max,
+                decimal(100.0 * ratio),
+                decimal(100.0 * self.threshold)
+            ),
+            severity,
+        ))
+    }
+}
@@ -0,0 +1,385 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::duration::Period;
+use crate::watcher;
+use crate::watcher::sampling::{Aggregate, Aggregation, SamplingChecker};
+use crate::watcher::{decimal, percent, threshold_bits, ActiveAlert, Severity};
+
+/// Window used to sample CPU usage when there is no previous sample to compare against.
+const INITIAL_SAMPLE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Alert on the usage averaged across all cores.
+    Average,
+    /// Alert as soon as a single core goes over the threshold.
+    AnyCore,
+}
+
+/// Cumulative jiffies spent busy and in total, as read from one `cpu` line of `/proc/stat`, or
+/// ticks of one core on macOS.
+#[derive(Debug, Clone, Copy)]
+struct Times {
+    busy: u64,
+    total: u64,
+}
+
+impl Times {
+    fn usage_since(&self, previous: &Times) -> f64 {
+        let total = self.total.saturating_sub(previous.total);
+        if total == 0 {
+            return 0.0;
+        }
+        self.busy.saturating_sub(previous.busy) as f64 / total as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    aggregate: Times,
+    per_core: Vec<Times>,
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    mode: Mode,
+    previous: Mutex<Option<Snapshot>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    mode: Mode,
+}
+
+#[derive(Debug, Clone)]
+pub struct CpuUsage {
+    pub mode: Mode,
+    pub aggregate: f64,
+    /// Usage per core, indexed by core number. Empty if per-core stats are not available.
+    pub per_core: Vec<f64>,
+}
+
+impl Aggregate for CpuUsage {
+    /// Aggregates the usage of each core on its own. Per-core usage is dropped if the number of
+    /// cores changed between samples.
+    fn combine(samples: Vec<Self>, aggregation: Aggregation) -> Self {
+        let cores = samples[0].per_core.len();
+        let per_core = match samples.iter().all(|s| s.per_core.len() == cores) {
+            true => (0..cores)
+                .map(|core| aggregation.apply(samples.iter().map(|s| s.per_core[core])))
+                .collect(),
+            false => Vec::new(),
+        };
+        CpuUsage {
+            mode: samples[0].mode,
+            aggregate: aggregation.apply(samples.iter().map(|s| s.aggregate)),
+            per_core,
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn read_snapshot() -> Result<Snapshot> {
+    use anyhow::Context;
+    use std::io::{BufRead, BufReader};
+
+    let stat_file = "/proc/stat";
+    let file = std::fs::File::open(stat_file)
+        .context(format!("Could not open stat file at '{}'", stat_file))?;
+    let buf_read = BufReader::new(file);
+    let mut aggregate = None;
+    let mut per_core = Vec::new();
+    for l in buf_read.lines() {
+        let l = l.context("Could not read line from stat file.")?;
+        let mut split = l.split_whitespace();
+        let name = match split.next() {
+            Some(name) if name.starts_with("cpu") => name,
+            _ => continue,
+        };
+        let values = split
+            .map(|v| {
+                v.parse::<u64>()
+                    .context(format!("Could not convert CPU time to integer: {}", v))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // user, nice, system, idle, iowait, irq, softirq, steal; guest times are already
+        // accounted for in user and nice.
+        let total = values.iter().take(8).sum();
+        let idle = values.get(3).copied().unwrap_or(0) + values.get(4).copied().unwrap_or(0);
+        let times = Times {
+            busy: total - idle,
+            total,
+        };
+        if name == "cpu" {
+            aggregate = Some(times);
+        } else {
+            per_core.push(times);
+        }
+    }
+    let aggregate =
+        aggregate.ok_or_else(|| anyhow!("Could not find aggregate 'cpu' line in stat file"))?;
+    if per_core.is_empty() {
+        warn!("Per-core CPU stats are not available, falling back to aggregate usage only");
+    }
+    Ok(Snapshot {
+        aggregate,
+        per_core,
+    })
+}
+
+/// Ticks of each core from `host_processor_info`, the aggregate being their sum. Busy ticks are
+/// the user, system and nice ones.
+#[cfg(target_os = "macos")]
+fn read_snapshot() -> Result<Snapshot> {
+    let mut cores: libc::natural_t = 0;
+    let mut info: libc::processor_info_array_t = std::ptr::null_mut();
+    let mut info_count: libc::mach_msg_type_number_t = 0;
+    // SAFETY: the out pointers are valid for writes.
+    #[allow(deprecated)]
+    let result = unsafe {
+        libc::host_processor_info(
+            libc::mach_host_self(),
+            libc::PROCESSOR_CPU_LOAD_INFO,
+            &mut cores,
+            &mut info,
+            &mut info_count,
+        )
+    };
+    if result != libc::KERN_SUCCESS {
+        return Err(anyhow!(
+            "Could not execute 'host_processor_info': error {}",
+            result
+        ));
+    }
+    // SAFETY: the kernel allocated `info_count` integers, `CPU_STATE_MAX` per core.
+    let ticks = unsafe { std::slice::from_raw_parts(info, info_count as usize) };
+    let per_core = ticks
+        .chunks_exact(libc::CPU_STATE_MAX as usize)
+        .map(|core| {
+            let tick = |state: libc::c_int| core[state as usize] as u32 as u64;
+            let busy = tick(libc::CPU_STATE_USER)
+                + tick(libc::CPU_STATE_SYSTEM)
+                + tick(libc::CPU_STATE_NICE);
+            Times {
+                busy,
+                total: busy + tick(libc::CPU_STATE_IDLE),
+            }
+        })
+        .collect::<Vec<_>>();
+    // SAFETY: the array was allocated in this task by the kernel, and is not read anymore.
+    #[allow(deprecated)]
+    unsafe {
+        libc::vm_deallocate(
+            libc::mach_task_self(),
+            info as libc::vm_address_t,
+            info_count as libc::vm_size_t * std::mem::size_of::<libc::integer_t>(),
+        );
+    }
+    let aggregate = Times {
+        busy: per_core.iter().map(|t| t.busy).sum(),
+        total: per_core.iter().map(|t| t.total).sum(),
+    };
+    Ok(Snapshot {
+        aggregate,
+        per_core,
+    })
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = CpuUsage;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "cpu");
+        let mut previous = self
+            .previous
+            .lock()
+            .map_err(|_| anyhow!("CPU sample lock is poisoned"))?;
+        let before = match previous.take() {
+            Some(snapshot) => snapshot,
+            None => {
+                let snapshot = read_snapshot()?;
+                sleep(INITIAL_SAMPLE_WINDOW);
+                snapshot
+            }
+        };
+        let now = read_snapshot()?;
+        let aggregate = now.aggregate.usage_since(&before.aggregate);
+        let per_core = if now.per_core.len() == before.per_core.len() {
+            now.per_core
+                .iter()
+                .zip(before.per_core.iter())
+                .map(|(n, b)| n.usage_since(b))
+                .collect()
+        } else {
+            warn!("Number of CPU cores changed between samples, ignoring per-core usage");
+            Vec::new()
+        };
+        *previous = Some(now);
+        info!(cpu_usage = aggregate);
+        Ok(CpuUsage {
+            mode: self.mode,
+            aggregate,
+            per_core,
+        })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let busiest = check_result.per_core.iter().copied().reduce(f64::max);
+        match (check_result.mode, busiest) {
+            (Mode::AnyCore, Some(busiest)) => format!(
+                "{} used, {} on the busiest core",
+                percent(check_result.aggregate),
+                percent(busiest)
+            ),
+            _ => format!("{} used", percent(check_result.aggregate)),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            mode: configuration.mode,
+            previous: Mutex::new(None),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    threshold: f64,
+    /// Above this value, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Alert {
+    fn severity(&self, usage: f64) -> Severity {
+        match self.critical {
+            Some(critical) if usage > critical => Severity::Critical,
+            _ => Severity::default(),
+        }
+    }
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = SamplingChecker<Checker>;
+
+    fn validate(&self) -> Result<()> {
+        watcher::validate_fraction("threshold", self.threshold)?;
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    fn with_threshold(&self, threshold: f64) -> Option<Self> {
+        Some(Alert {
+            threshold,
+            ..self.clone()
+        })
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let offending_core = check_result
+            .per_core
+            .iter()
+            .enumerate()
+            .filter(|(_, usage)| **usage > self.threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        match (check_result.mode, offending_core) {
+            (Mode::AnyCore, Some((core, usage))) => Some(
+                ActiveAlert::new(
+                    format!(
+                        "🔥 CPU usage of core {} is at {}% (threshold: {}%).",
+                        core,
+                        decimal(100.0 * usage),
+                        decimal(100.0 * self.threshold)
+                    ),
+                    self.severity(*usage),
+                )
+                .annotated([
+                    ("core", core.to_string()),
+                    ("usage_pct", decimal(100.0 * usage)),
+                    ("threshold", decimal(100.0 * self.threshold)),
+                ]),
+            ),
+            // Without per-core stats, the aggregate is all we can alert on.
+            (Mode::AnyCore, None) if !check_result.per_core.is_empty() => None,
+            _ if check_result.aggregate > self.threshold => Some(
+                ActiveAlert::new(
+                    format!(
+                        "🔥 Average CPU usage is at {}% (threshold: {}%).",
+                        decimal(100.0 * check_result.aggregate),
+                        decimal(100.0 * self.threshold)
+                    ),
+                    self.severity(check_result.aggregate),
+                )
+                .annotated([
+                    ("usage_pct", decimal(100.0 * check_result.aggregate)),
+                    ("threshold", decimal(100.0 * self.threshold)),
+                ]),
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_cpu_times_of_this_machine() {
+        let snapshot = read_snapshot().unwrap();
+
+        assert!(snapshot.aggregate.total > 0, "{:?}", snapshot);
+        assert!(
+            snapshot.aggregate.busy <= snapshot.aggregate.total,
+            "{:?}",
+            snapshot
+        );
+        for core in &snapshot.per_core {
+            assert!(core.busy <= core.total, "{:?}", snapshot);
+        }
+    }
+
+    #[test]
+    fn usage_is_the_busy_share_of_the_elapsed_times() {
+        let previous = Times {
+            busy: 100,
+            total: 400,
+        };
+        let current = Times {
+            busy: 130,
+            total: 500,
+        };
+
+        assert_eq!(current.usage_since(&previous), 0.3);
+        assert_eq!(previous.usage_since(&previous), 0.0);
+    }
+}
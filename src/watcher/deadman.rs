@@ -0,0 +1,257 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{
+    deserialize_duration, deserialize_optional_duration, format_duration, Period,
+};
+use crate::metrics::Sample;
+use crate::status::{expect_heartbeat, last_heartbeat};
+use crate::watcher;
+use crate::watcher::{ActiveAlert, Evaluation, Severity};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    sources: Vec<String>,
+    /// Sources that never sent a heartbeat are silent since then.
+    started: SystemTime,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Names of the sources sending heartbeats, with `POST /heartbeat/{name}` on the status
+    /// server.
+    sources: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceSilence {
+    pub source: String,
+    /// When the source last sent a heartbeat, unset if it never did since Monitrust started.
+    pub last: Option<SystemTime>,
+    /// Time since the last heartbeat, or since Monitrust started.
+    pub silent_for: Duration,
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Vec<SourceSilence>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "deadman");
+        let now = SystemTime::now();
+        let silences = self
+            .sources
+            .iter()
+            .map(|source| {
+                let last = last_heartbeat(source);
+                let silent_for = now
+                    .duration_since(last.unwrap_or(self.started))
+                    .unwrap_or_default();
+                info!(source, silent_for = ?silent_for);
+                SourceSilence {
+                    source: source.clone(),
+                    last,
+                    silent_for,
+                }
+            })
+            .collect();
+        Ok(silences)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .iter()
+            .map(|silence| {
+                format!(
+                    "{}: silent for {}",
+                    silence.source,
+                    format_duration(silence.silent_for)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        for source in &configuration.sources {
+            expect_heartbeat(source);
+        }
+        Checker {
+            period: configuration.period.0,
+            sources: configuration.sources,
+            started: SystemTime::now(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.sources.is_empty() {
+            return Err(anyhow!("At least one source must be watched"));
+        }
+        if self.sources.iter().any(String::is_empty) {
+            return Err(anyhow!("Source names can not be empty"));
+        }
+        Ok(())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        check_result
+            .iter()
+            .map(|silence| {
+                Sample::new(
+                    "monitrust_heartbeat_age_seconds",
+                    &[("source", silence.source.as_str())],
+                    silence.silent_for.as_secs_f64(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Sources silent for longer than this trigger the alert.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(deserialize_with = "deserialize_duration")]
+    max_silence: Duration,
+    /// Sources silent for longer than this are critical rather than a warning.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    critical_silence: Option<Duration>,
+}
+
+impl Alert {
+    fn triggered_for(&self, silence: &SourceSilence) -> Option<ActiveAlert> {
+        if silence.silent_for <= self.max_silence {
+            return None;
+        }
+        let severity = match self.critical_silence {
+            Some(critical_silence) if silence.silent_for > critical_silence => Severity::Critical,
+            _ => Severity::default(),
+        };
+        let message = match silence.last {
+            Some(_) => format!(
+                "🪦 {} has not sent a heartbeat for {} (max silence: {}).",
+                silence.source,
+                format_duration(silence.silent_for),
+                format_duration(self.max_silence)
+            ),
+            None => format!(
+                "🪦 {} has not sent any heartbeat since Monitrust started {} ago (max silence: {}).",
+                silence.source,
+                format_duration(silence.silent_for),
+                format_duration(self.max_silence)
+            ),
+        };
+        Some(ActiveAlert::new(message, severity))
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self.critical_silence {
+            Some(critical_silence) if critical_silence < self.max_silence => Err(anyhow!(
+                "'critical_silence' ({}) is shorter than 'max_silence' ({})",
+                format_duration(critical_silence),
+                format_duration(self.max_silence)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result.iter().find_map(|s| self.triggered_for(s))
+    }
+
+    /// Evaluates each source on its own, so that they fire and resolve independently.
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|silence| {
+                let triggered = self.triggered_for(silence);
+                Evaluation {
+                    subject: silence.source.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::receive_heartbeat;
+    use crate::watcher::{Alert as _, Checker as _};
+    use serde_json::json;
+
+    fn checker(source: &str) -> Checker {
+        Checker::new(
+            serde_json::from_value(json!({ "period": "1m", "sources": [source] })).unwrap(),
+        )
+    }
+
+    fn alert() -> Alert {
+        serde_json::from_value(json!({ "max_silence": "1h", "critical_silence": "1d" })).unwrap()
+    }
+
+    #[test]
+    fn a_source_silent_past_the_deadline_fires_until_it_pings() {
+        let source = "deadman-backup";
+        assert!(!receive_heartbeat("deadman-unknown", SystemTime::now()));
+        let checker = checker(source);
+        assert!(receive_heartbeat(
+            source,
+            SystemTime::now() - Duration::from_secs(2 * 60 * 60)
+        ));
+
+        let silences = checker.check().unwrap();
+        let evaluations = alert().evaluate(&silences);
+        assert_eq!(evaluations.len(), 1);
+        assert_eq!(evaluations[0].subject, source);
+        let fired = evaluations[0].triggered.as_ref().unwrap();
+        assert_eq!(fired.severity, Severity::Warning);
+        assert_eq!(
+            fired.message,
+            "🪦 deadman-backup has not sent a heartbeat for 2h (max silence: 1h)."
+        );
+
+        assert!(receive_heartbeat(source, SystemTime::now()));
+        let evaluations = alert().evaluate(&checker.check().unwrap());
+        assert!(evaluations[0].cleared);
+        assert!(evaluations[0].triggered.is_none());
+    }
+
+    #[test]
+    fn a_source_that_never_pinged_is_silent_since_the_start() {
+        let mut checker = checker("deadman-edge");
+        checker.started = SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+
+        let fired = alert().is_triggered(&checker.check().unwrap()).unwrap();
+
+        assert_eq!(fired.severity, Severity::Critical);
+        assert_eq!(
+            fired.message,
+            "🪦 deadman-edge has not sent any heartbeat since Monitrust started 2d ago (max silence: 1h)."
+        );
+    }
+}
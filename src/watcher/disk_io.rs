@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{deserialize_duration, Period};
+use crate::metrics::Sample;
+use crate::pattern::Pattern;
+use crate::size::format_size;
+use crate::watcher;
+use crate::watcher::{decimal, percent, threshold_bits, ActiveAlert, Evaluation, Severity};
+
+/// Size of the sectors counted in `/proc/diskstats`, whatever the actual sector size of the
+/// device.
+const SECTOR_SIZE: u64 = 512;
+
+fn default_sample_window() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_exclude() -> Vec<Pattern> {
+    ["loop*", "ram*", "zram*"]
+        .map(|p| Pattern::Glob(p.to_string()))
+        .to_vec()
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    devices: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    sample_window: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Devices to watch, as named in `/proc/diskstats`. All of them if empty.
+    #[serde(default)]
+    devices: Vec<Pattern>,
+    /// Devices not to watch, even if selected. Defaults to loop and RAM devices.
+    #[serde(default = "default_exclude")]
+    exclude: Vec<Pattern>,
+    /// How long to measure the I/O of devices for at each check.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(
+        default = "default_sample_window",
+        deserialize_with = "deserialize_duration"
+    )]
+    sample_window: Duration,
+}
+
+/// Cumulative counters of a device, as read from `/proc/diskstats`.
+#[derive(Debug, Clone, Copy)]
+struct Counters {
+    reads: u64,
+    sectors_read: u64,
+    milliseconds_reading: u64,
+    writes: u64,
+    sectors_written: u64,
+    milliseconds_writing: u64,
+    milliseconds_busy: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceIo {
+    pub device: String,
+    /// Read and write requests completed per second.
+    pub iops: f64,
+    /// Bytes read and written per second.
+    pub bytes_per_second: f64,
+    /// Fraction of the sample window during which the device was busy.
+    pub utilization: f64,
+    /// Average time to complete a request, 0 if none completed.
+    pub latency: Duration,
+}
+
+impl DeviceIo {
+    fn between(device: String, before: Counters, after: Counters, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let requests = (after.reads + after.writes).saturating_sub(before.reads + before.writes);
+        let sectors = (after.sectors_read + after.sectors_written)
+            .saturating_sub(before.sectors_read + before.sectors_written);
+        let milliseconds_waiting = (after.milliseconds_reading + after.milliseconds_writing)
+            .saturating_sub(before.milliseconds_reading + before.milliseconds_writing);
+        let milliseconds_busy = after
+            .milliseconds_busy
+            .saturating_sub(before.milliseconds_busy);
+        DeviceIo {
+            device,
+            iops: requests as f64 / seconds,
+            bytes_per_second: (sectors * SECTOR_SIZE) as f64 / seconds,
+            // The busy time is counted in whole milliseconds, so it can slightly exceed the
+            // window.
+            utilization: (milliseconds_busy as f64 / 1000.0 / seconds).min(1.0),
+            latency: match requests {
+                0 => Duration::ZERO,
+                _ => {
+                    Duration::from_secs_f64(milliseconds_waiting as f64 / 1000.0 / requests as f64)
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiskIoUsage {
+    pub sample_window: Duration,
+    pub devices: Vec<DeviceIo>,
+}
+
+impl Checker {
+    fn is_watched(&self, device: &str) -> bool {
+        (self.devices.is_empty() || self.devices.iter().any(|p| p.matches(device)))
+            && !self.exclude.iter().any(|p| p.matches(device))
+    }
+
+    fn read_counters(&self) -> Result<BTreeMap<String, Counters>> {
+        let stats_file = "/proc/diskstats";
+        let content = std::fs::read_to_string(stats_file)
+            .context(format!("Could not read disk stats at '{}'", stats_file))?;
+        let mut counters = BTreeMap::new();
+        let mut others = Vec::new();
+        for l in content.lines() {
+            // Major and minor numbers come first, then the device name and its counters.
+            let mut split = l.split_whitespace().skip(2);
+            let Some(device) = split.next() else {
+                continue;
+            };
+            if !self.is_watched(device) {
+                others.push(device);
+                continue;
+            }
+            let values = split
+                .take(10)
+                .map(|v| {
+                    v.parse::<u64>()
+                        .context(format!("Could not convert disk counter to integer: {}", v))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if values.len() < 10 {
+                return Err(anyhow!(
+                    "Expected at least 10 counters for device '{}', found {}",
+                    device,
+                    values.len()
+                ));
+            }
+            counters.insert(
+                device.to_string(),
+                Counters {
+                    reads: values[0],
+                    sectors_read: values[2],
+                    milliseconds_reading: values[3],
+                    writes: values[4],
+                    sectors_written: values[6],
+                    milliseconds_writing: values[7],
+                    milliseconds_busy: values[9],
+                },
+            );
+        }
+        if counters.is_empty() {
+            return Err(anyhow!(
+                "No device of '{}' matches the configured ones (available: {})",
+                stats_file,
+                others.join(", ")
+            ));
+        }
+        Ok(counters)
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = DiskIoUsage;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "disk_io");
+        let start = Instant::now();
+        let before = self.read_counters()?;
+        sleep(self.sample_window);
+        let after = self.read_counters()?;
+        let elapsed = start.elapsed();
+        // Devices that appeared during the sample window have nothing to compare against.
+        let devices = after
+            .into_iter()
+            .filter_map(|(device, after)| {
+                let before = *before.get(&device)?;
+                Some(DeviceIo::between(device, before, after, elapsed))
+            })
+            .collect::<Vec<_>>();
+        for io in &devices {
+            info!(
+                device = io.device,
+                iops = io.iops,
+                bytes_per_second = io.bytes_per_second,
+                utilization = io.utilization,
+                latency = ?io.latency
+            );
+        }
+        Ok(DiskIoUsage {
+            sample_window: self.sample_window,
+            devices,
+        })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .devices
+            .iter()
+            .map(|device| {
+                format!(
+                    "{}: {} IOPS, {}/s, {} busy",
+                    device.device,
+                    decimal(device.iops),
+                    format_size(device.bytes_per_second as u64),
+                    percent(device.utilization)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            devices: configuration.devices,
+            exclude: configuration.exclude,
+            sample_window: configuration.sample_window,
+        }
+    }
+
+    fn probe(&self) -> Result<()> {
+        self.read_counters().map(|_| ())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for io in &check_result.devices {
+            let labels = [("device", io.device.as_str())];
+            samples.push(Sample::new("monitrust_disk_io_iops", &labels, io.iops));
+            samples.push(Sample::new(
+                "monitrust_disk_io_bytes_per_second",
+                &labels,
+                io.bytes_per_second,
+            ));
+            samples.push(Sample::new(
+                "monitrust_disk_io_utilization_ratio",
+                &labels,
+                io.utilization,
+            ));
+            samples.push(Sample::new(
+                "monitrust_disk_io_latency_seconds",
+                &labels,
+                io.latency.as_secs_f64(),
+            ));
+        }
+        samples
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// The threshold is a fraction of the time the device was busy, between 0 and 1.
+    Utilization,
+    /// The threshold is an average request latency in milliseconds.
+    Latency,
+    /// The threshold is a number of requests per second.
+    Iops,
+    /// The threshold is a number of bytes per second.
+    Throughput,
+}
+
+impl Metric {
+    fn value(&self, io: &DeviceIo) -> f64 {
+        match self {
+            Metric::Utilization => io.utilization,
+            Metric::Latency => io.latency.as_secs_f64() * 1000.0,
+            Metric::Iops => io.iops,
+            Metric::Throughput => io.bytes_per_second,
+        }
+    }
+
+    fn describe(&self, value: f64) -> String {
+        match self {
+            Metric::Utilization => format!("utilization is at {}%", decimal(100.0 * value)),
+            Metric::Latency => format!("average latency is at {} ms", decimal(value)),
+            Metric::Iops => format!("IOPS are at {:.0}", value),
+            Metric::Throughput => format!("throughput is at {:.0} bytes/s", value),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    metric: Metric,
+    threshold: f64,
+    /// Above this value, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Alert {
+    fn triggered_for(&self, io: &DeviceIo, sample_window: Duration) -> Option<ActiveAlert> {
+        let value = self.metric.value(io);
+        if value <= self.threshold {
+            return None;
+        }
+        let severity = match self.critical {
+            Some(critical) if value > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        let threshold = match self.metric {
+            Metric::Utilization => format!("{}%", decimal(100.0 * self.threshold)),
+            _ => self.threshold.to_string(),
+        };
+        Some(ActiveAlert::new(
+            format!(
+                "🐢 Disk {} {} over the last {:?} (threshold: {}).",
+                io.device,
+                self.metric.describe(value),
+                sample_window,
+                threshold
+            ),
+            severity,
+        ))
+    }
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.metric.hash(state);
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    /// Alert about the device that triggers it the most. Each device is reported on its own by
+    /// [`watcher::Alert::evaluate`].
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result
+            .devices
+            .iter()
+            .max_by(|a, b| self.metric.value(a).total_cmp(&self.metric.value(b)))
+            .and_then(|io| self.triggered_for(io, check_result.sample_window))
+    }
+
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .devices
+            .iter()
+            .map(|io| {
+                let triggered = self.triggered_for(io, check_result.sample_window);
+                Evaluation {
+                    subject: io.device.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
@@ -1,34 +1,532 @@
-use std::time::Duration;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+#[cfg(unix)]
 use nix::sys::statvfs::statvfs;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::duration::{deserialize_optional_duration, format_duration, Period};
+use crate::metrics::Sample;
+use crate::pattern::Pattern;
+use crate::size::{deserialize_optional_size, format_size};
+use crate::templating::render_template;
 use crate::watcher;
-use crate::watcher::ActiveAlert;
+use crate::watcher::native_usage::{self, Backend};
+#[cfg(unix)]
+use crate::watcher::read_only::unescape;
+use crate::watcher::{
+    decimal, percent, threshold_bits, ActiveAlert, Comparison, Evaluation, Severity, SeverityTier,
+};
+
+/// Number of recent readings of each mount point its fill rate is computed from.
+const HISTORY_LENGTH: usize = 12;
+
+/// Filesystems that are not backed by a disk, or are read-only images that are always full.
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "ramfs",
+    "securityfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+#[cfg(unix)]
+fn default_mounts() -> Mounts {
+    Mounts::Paths(vec!["/".to_string()])
+}
+
+#[cfg(windows)]
+fn default_mounts() -> Mounts {
+    Mounts::Paths(vec!["C:\\".to_string()])
+}
+
+/// Which mount points to watch.
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub enum Mounts {
+    Paths(Vec<String>),
+    /// Every mount point listed in `/proc/mounts`, or every drive on Windows, as of each check.
+    Discover(Discovery),
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Discovery {
+    /// Globs of the mount points to watch, all of them if empty.
+    #[serde(default)]
+    include: Vec<String>,
+    /// Globs of the mount points not to watch, even if included.
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// Also watch pseudo filesystems such as `tmpfs`. Does not apply to Windows drives.
+    #[serde(default)]
+    pseudo_filesystems: bool,
+    /// Also watch filesystems mounted read-only, which can not fill up. Does not apply to
+    /// Windows drives.
+    #[serde(default)]
+    include_readonly: bool,
+    /// Also watch mount points on the same device as one already watched, such as bind mounts,
+    /// which would report the same disk twice. Does not apply to Windows drives.
+    #[serde(default)]
+    include_binds: bool,
+}
+
+impl Discovery {
+    fn is_watched(&self, mount: &str, filesystem: &str) -> bool {
+        let matches = |globs: &[String]| {
+            globs
+                .iter()
+                .any(|glob| Pattern::Glob(glob.clone()).matches(mount))
+        };
+        (self.pseudo_filesystems || !PSEUDO_FILESYSTEMS.contains(&filesystem))
+            && (self.include.is_empty() || matches(&self.include))
+            && !matches(&self.exclude)
+    }
+
+    #[cfg(unix)]
+    fn discover(&self) -> Result<Vec<String>> {
+        let mounts_file = "/proc/mounts";
+        let content = std::fs::read_to_string(mounts_file)
+            .context(format!("Could not read mounts at '{}'", mounts_file))?;
+        Ok(self.discover_in(&content))
+    }
+
+    /// The mount points to watch among those listed in `content`, in the format of
+    /// `/proc/mounts`.
+    #[cfg(unix)]
+    fn discover_in(&self, content: &str) -> Vec<String> {
+        let mut mounts = Vec::new();
+        let mut devices = Vec::new();
+        for l in content.lines() {
+            let mut split = l.split_whitespace();
+            let (Some(_device), Some(mount), Some(filesystem), Some(options)) =
+                (split.next(), split.next(), split.next(), split.next())
+            else {
+                continue;
+            };
+            let mount = unescape(mount);
+            // A mount point mounted over several times is only checked once.
+            if !self.is_watched(&mount, filesystem) || mounts.contains(&mount) {
+                continue;
+            }
+            if !self.include_readonly && options.split(',').any(|o| o == "ro") {
+                info!(mount, "Skipping read-only mount");
+                continue;
+            }
+            if !self.include_binds {
+                use std::os::unix::fs::MetadataExt;
+                // Mount points that can not be read are kept, for their check to fail.
+                if let Ok(device) = std::fs::metadata(&mount).map(|m| m.dev()) {
+                    if devices.contains(&device) {
+                        info!(mount, "Skipping mount of a device already watched");
+                        continue;
+                    }
+                    devices.push(device);
+                }
+            }
+            mounts.push(mount);
+        }
+        mounts
+    }
+
+    #[cfg(windows)]
+    fn discover(&self) -> Result<Vec<String>> {
+        Ok(logical_drives()?
+            .into_iter()
+            .filter(|d| self.is_watched(d, ""))
+            .collect())
+    }
+}
+
+/// Root of every drive, such as `C:\`.
+#[cfg(windows)]
+fn logical_drives() -> Result<Vec<String>> {
+    use windows_sys::Win32::Storage::FileSystem::GetLogicalDriveStringsW;
+
+    // Enough for the 26 drive letters, each as a NUL-terminated `C:\`.
+    let mut buffer = vec![0u16; 128];
+    // SAFETY: `buffer` can hold the length passed.
+    let length =
+        unsafe { GetLogicalDriveStringsW(buffer.len() as u32, buffer.as_mut_ptr()) } as usize;
+    if length == 0 || length > buffer.len() {
+        return Err(anyhow!(
+            "Could not execute 'GetLogicalDriveStringsW': {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(buffer[..length]
+        .split(|c| *c == 0)
+        .filter(|d| !d.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect())
+}
+
+/// Device and filesystem type of a mount point, as listed in `/proc/mounts`.
+#[derive(Debug)]
+struct Mounted {
+    device: String,
+    filesystem: String,
+}
+
+/// What each mount point in `/proc/mounts` is, the last one mounted over it if several are.
+/// Empty if it can not be read.
+#[cfg(unix)]
+fn mount_table() -> BTreeMap<String, Mounted> {
+    let content = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    content
+        .lines()
+        .filter_map(|l| {
+            let mut split = l.split_whitespace();
+            let (device, mount, filesystem) = (split.next()?, split.next()?, split.next()?);
+            let mounted = Mounted {
+                device: unescape(device),
+                filesystem: filesystem.to_string(),
+            };
+            Some((unescape(mount), mounted))
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn mount_table() -> BTreeMap<String, Mounted> {
+    BTreeMap::new()
+}
+
+/// What a path is on: the longest mount point it is under.
+fn mounted_of<'a>(path: &str, table: &'a BTreeMap<String, Mounted>) -> Option<&'a Mounted> {
+    table
+        .iter()
+        .filter(|(mount, _)| std::path::Path::new(path).starts_with(mount))
+        .max_by_key(|(mount, _)| mount.len())
+        .map(|(_, mounted)| mounted)
+}
 
-// TODO: why does the compiler complain about that?
 #[derive(Debug)]
 pub struct Checker {
     period: Duration,
+    mounts: Mounts,
+    native_usage: bool,
+    /// Recent readings of the free fraction of each mount point, oldest first.
+    history: Mutex<BTreeMap<String, VecDeque<(Instant, f64)>>>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
 pub struct Configuration {
-    period_minutes: u64,
+    #[serde(flatten)]
+    period: Period,
+    /// Only `/` is watched if omitted.
+    #[serde(default = "default_mounts")]
+    mounts: Mounts,
+    /// Reads the usage of ZFS and Btrfs mount points with `zfs list` and `btrfs filesystem
+    /// usage`, which count their snapshots, quotas, reservations and RAID profiles, rather than
+    /// with `statvfs`. Needs the `zfs` or `btrfs` feature.
+    #[serde(default)]
+    native_usage: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Inodes {
+    pub total: u64,
+    pub used: u64,
+    pub free: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiskUsage {
+    pub mount: String,
+    /// Fraction of the blocks that are available.
+    pub free_space: f64,
+    /// Size of the available blocks.
+    pub free_bytes: u64,
+    /// Type of the filesystem the mount point is on, such as `ext4`. Unknown on Windows.
+    pub filesystem: Option<String>,
+    /// What the free space was read with.
+    pub backend: Backend,
+    /// Unknown on filesystems that do not report inodes, such as some network mounts, and on
+    /// Windows.
+    pub inodes: Option<Inodes>,
+    /// Fraction of the blocks filled per second over the recent checks, negative while space is
+    /// freed. Unknown until the mount point has been checked twice.
+    pub fill_rate: Option<f64>,
+}
+
+/// Outcome of the check of a mount point.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MountCheck {
+    pub mount: String,
+    /// Why the mount point could not be checked otherwise, such as a network mount that does
+    /// not answer, so that the other mount points are still checked.
+    pub usage: Result<DiskUsage, String>,
+}
+
+impl DiskUsage {
+    /// Time until no block is available anymore, if the disk keeps filling at its fill rate.
+    /// Zero once the disk is full, whatever its fill rate.
+    pub fn time_to_full(&self) -> Option<Duration> {
+        if self.free_space <= 0.0 {
+            return Some(Duration::ZERO);
+        }
+        match self.fill_rate {
+            Some(rate) if rate > 0.0 => Duration::try_from_secs_f64(self.free_space / rate).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Rate at which the free fraction decreases, per second, as the slope of the least squares
+/// line through the readings.
+pub fn fill_rate(readings: &VecDeque<(Instant, f64)>) -> Option<f64> {
+    let (first, _) = readings.front()?;
+    let points = readings
+        .iter()
+        .map(|(at, free)| (at.duration_since(*first).as_secs_f64(), *free))
+        .collect::<Vec<_>>();
+    let count = points.len() as f64;
+    let mean_time = points.iter().map(|(t, _)| t).sum::<f64>() / count;
+    let mean_free = points.iter().map(|(_, f)| f).sum::<f64>() / count;
+    let variance = points
+        .iter()
+        .map(|(t, _)| (t - mean_time).powi(2))
+        .sum::<f64>();
+    if variance == 0.0 {
+        return None;
+    }
+    let covariance = points
+        .iter()
+        .map(|(t, f)| (t - mean_time) * (f - mean_free))
+        .sum::<f64>();
+    Some(-covariance / variance)
+}
+
+#[cfg(unix)]
+fn disk_usage(mount: String) -> Result<DiskUsage> {
+    let stats =
+        statvfs(mount.as_str()).context(format!("Could not execute 'statvfs' on {}", mount))?;
+    let free_space = stats.blocks_available() as f64 / stats.blocks() as f64;
+    let free_bytes = stats.blocks_available() as u64 * stats.fragment_size() as u64;
+    let inodes = if stats.files() == 0 {
+        warn!(mount, "Filesystem reports no inodes, skipping inode checks");
+        None
+    } else {
+        Some(Inodes {
+            total: stats.files() as u64,
+            used: (stats.files() - stats.files_free()) as u64,
+            free: stats.files_free() as u64,
+        })
+    };
+    info!(mount, free_space, free_bytes, inodes = ?inodes);
+    Ok(DiskUsage {
+        mount,
+        free_space,
+        free_bytes,
+        inodes,
+        filesystem: None,
+        backend: Backend::Statvfs,
+        fill_rate: None,
+    })
+}
+
+#[cfg(windows)]
+fn disk_usage(mount: String) -> Result<DiskUsage> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = std::ffi::OsStr::new(&mount)
+        .encode_wide()
+        .chain([0])
+        .collect::<Vec<_>>();
+    let (mut available, mut total, mut free) = (0, 0, 0);
+    // SAFETY: `path` is NUL-terminated and the sizes are written to valid `u64`s.
+    let succeeded =
+        unsafe { GetDiskFreeSpaceExW(path.as_ptr(), &mut available, &mut total, &mut free) };
+    if succeeded == 0 {
+        return Err(anyhow!(
+            "Could not execute 'GetDiskFreeSpaceExW' on {}: {}",
+            mount,
+            std::io::Error::last_os_error()
+        ));
+    }
+    // Like `statvfs`, counts the space available to Monitrust rather than all the free space.
+    let free_space = available as f64 / total as f64;
+    info!(mount, free_space, free_bytes = available);
+    Ok(DiskUsage {
+        mount,
+        free_space,
+        free_bytes: available,
+        inodes: None,
+        filesystem: None,
+        backend: Backend::Statvfs,
+        fill_rate: None,
+    })
+}
+
+impl Checker {
+    /// Replaces the figures of `statvfs` with the ones of the tools of the filesystem, when
+    /// `native_usage` is set and Monitrust was built with a backend for it. They are kept if the
+    /// tools fail.
+    fn native(&self, usage: DiskUsage, mounted: Option<&Mounted>) -> DiskUsage {
+        let usage = DiskUsage {
+            filesystem: mounted.map(|m| m.filesystem.clone()),
+            ..usage
+        };
+        let Some(mounted) = mounted.filter(|_| self.native_usage) else {
+            return usage;
+        };
+        match native_usage::read(&mounted.filesystem, &mounted.device, &usage.mount) {
+            Some(Ok((backend, native))) => {
+                info!(
+                    mount = usage.mount,
+                    ?backend,
+                    used = native.used,
+                    available = native.available
+                );
+                DiskUsage {
+                    free_space: native.free_fraction(),
+                    free_bytes: native.available,
+                    backend,
+                    ..usage
+                }
+            }
+            Some(Err(e)) => {
+                warn!(mount = usage.mount, native_usage_error = ?e, "Falling back to statvfs");
+                usage
+            }
+            None => usage,
+        }
+    }
+
+    /// Checks each mount point with `disk_usage`, which failing on one of them leaves the others
+    /// checked.
+    fn check_mounts(
+        &self,
+        mounts: Vec<String>,
+        disk_usage: impl Fn(String) -> Result<DiskUsage>,
+    ) -> Result<Vec<MountCheck>> {
+        let table = mount_table();
+        let mut checks = mounts
+            .into_iter()
+            .map(|mount| MountCheck {
+                usage: disk_usage(mount.clone())
+                    .map(|usage| self.native(usage, mounted_of(&mount, &table)))
+                    .map_err(|e| {
+                        warn!(disk_space_error = ?e);
+                        format!("{:#}", e)
+                    }),
+                mount,
+            })
+            .collect::<Vec<_>>();
+        self.record(&mut checks)?;
+        Ok(checks)
+    }
+
+    /// Adds the usages to the history of their mount point, to compute their fill rate. Mount
+    /// points that were not checked are forgotten, the ones that failed to be are kept.
+    fn record(&self, checks: &mut [MountCheck]) -> Result<()> {
+        let mut history = self
+            .history
+            .lock()
+            .map_err(|_| anyhow!("Disk space history lock is poisoned"))?;
+        history.retain(|mount, _| checks.iter().any(|c| c.mount == *mount));
+        let now = Instant::now();
+        for usage in checks.iter_mut().filter_map(|c| c.usage.as_mut().ok()) {
+            let readings = history.entry(usage.mount.clone()).or_default();
+            readings.push_back((now, usage.free_space));
+            if readings.len() > HISTORY_LENGTH {
+                readings.pop_front();
+            }
+            usage.fill_rate = fill_rate(readings);
+            info!(mount = usage.mount, fill_rate = ?usage.fill_rate);
+        }
+        Ok(())
+    }
 }
 
 impl watcher::Checker for Checker {
-    type CheckResult = f64;
+    /// Usage of each mount point, or why it could not be checked.
+    type CheckResult = Vec<MountCheck>;
     type Configuration = Configuration;
 
     fn check(&self) -> Result<Self::CheckResult> {
         info!(checking = "disk_space");
-        let stats = statvfs("/").context("Could not execute 'statvfs'")?;
-        let free_space = stats.blocks_available() as f64 / stats.blocks() as f64;
-        info!(free_space);
-        Ok(free_space)
+        let mounts = match &self.mounts {
+            Mounts::Paths(paths) => paths.clone(),
+            Mounts::Discover(discovery) => discovery.discover()?,
+        };
+        self.check_mounts(mounts, disk_usage)
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for usage in check_result.iter().filter_map(|c| c.usage.as_ref().ok()) {
+            let labels = [("mount", usage.mount.as_str())];
+            samples.push(Sample::new(
+                "monitrust_disk_free_ratio",
+                &labels,
+                usage.free_space,
+            ));
+            samples.push(Sample::new(
+                "monitrust_disk_free_bytes",
+                &labels,
+                usage.free_bytes as f64,
+            ));
+            if let Some(fill_rate) = usage.fill_rate {
+                samples.push(Sample::new(
+                    "monitrust_disk_fill_rate_per_second",
+                    &labels,
+                    fill_rate,
+                ));
+            }
+            if let Some(inodes) = &usage.inodes {
+                samples.push(Sample::new(
+                    "monitrust_disk_inodes_free",
+                    &labels,
+                    inodes.free as f64,
+                ));
+                samples.push(Sample::new(
+                    "monitrust_disk_inodes_total",
+                    &labels,
+                    inodes.total as f64,
+                ));
+            }
+        }
+        samples
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .iter()
+            .map(|check| match &check.usage {
+                Ok(usage) => format!(
+                    "{}: {} free ({})",
+                    check.mount,
+                    percent(usage.free_space),
+                    format_size(usage.free_bytes)
+                ),
+                Err(e) => format!("{}: {}", check.mount, e),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
     }
 
     fn period(&self) -> Duration {
@@ -37,34 +535,915 @@ impl watcher::Checker for Checker {
 
     fn new(configuration: Self::Configuration) -> Self {
         Checker {
-            period: Duration::from_secs(60 * configuration.period_minutes),
+            period: configuration.period.0,
+            mounts: configuration.mounts,
+            native_usage: configuration.native_usage,
+            history: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.native_usage && !cfg!(any(feature = "zfs", feature = "btrfs")) {
+            return Err(anyhow!(
+                "'native_usage' needs Monitrust to be built with the 'zfs' or 'btrfs' feature"
+            ));
+        }
+        Ok(())
+    }
+
+    fn probe(&self) -> Result<()> {
+        match &self.mounts {
+            Mounts::Paths(paths) => {
+                for path in paths {
+                    disk_usage(path.clone())?;
+                }
+                Ok(())
+            }
+            Mounts::Discover(discovery) => discovery.discover().map(|_| ()),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// What an alert watches. To watch both bytes and inodes of a mount, configure one alert for
+/// each.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Resource {
+    #[default]
+    Bytes,
+    Inodes,
+}
+
+/// Which mount points an override applies to.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// A mount point, exactly as checked, such as `/var`.
+    Mount(String),
+    /// Every mount point of a filesystem type, such as `xfs`, as listed in `/proc/mounts`.
+    /// Filesystem types are unknown on Windows.
+    Filesystem(String),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Mount(mount) => write!(f, "mount point '{}'", mount),
+            Target::Filesystem(filesystem) => write!(f, "filesystem '{}'", filesystem),
+        }
+    }
+}
+
+/// Thresholds of an alert on some mount points, instead of its own, e.g. a stricter threshold
+/// for `/` than for scratch space.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Override {
+    #[serde(flatten)]
+    target: Target,
+    trigger_threshold: f64,
+    /// Defaults to `trigger_threshold`, like the one of the alert.
+    #[serde(default)]
+    clear_threshold: Option<f64>,
+    /// Unset unless given, as the one of the alert may be above `trigger_threshold`.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Hash for Override {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.target.hash(state);
+        threshold_bits(self.trigger_threshold).hash(state);
+        self.clear_threshold.map(threshold_bits).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+/// Alert as configured. `min` and `trigger_threshold` are required, unless `min_free_bytes` or
+/// `full_within` is set, in which case a full disk triggers the alert too.
+#[derive(Deserialize, JsonSchema)]
+struct RawAlert {
+    #[serde(default)]
+    resource: Resource,
+    #[serde(default)]
+    min: Option<f64>,
+    #[serde(default, alias = "max")]
+    trigger_threshold: Option<f64>,
+    #[schemars(schema_with = "crate::schema::size")]
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    min_free_bytes: Option<u64>,
+    #[serde(default)]
+    clear_threshold: Option<f64>,
+    #[serde(default)]
+    critical: Option<f64>,
+    #[serde(default)]
+    severities: Vec<SeverityTier>,
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    full_within: Option<Duration>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    overrides: Vec<Override>,
+    #[serde(default)]
+    operator: Option<Comparison>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawAlert")]
+#[schemars(with = "RawAlert")]
 pub struct Alert {
-    min: f64,
-    max: f64,
+    resource: Resource,
+    /// Free fractions at or below this one never trigger the alert, e.g. for images that are
+    /// always full. Unset, even a full disk triggers it.
+    min: Option<f64>,
+    /// Free fraction below which the alert triggers.
+    trigger_threshold: Option<f64>,
+    /// Available bytes below which the alert triggers, parsed by [`parse_size`], such as
+    /// `"2GB"`. When both thresholds are set, the alert triggers as soon as either is crossed.
+    ///
+    /// [`parse_size`]: crate::size::parse_size
+    min_free_bytes: Option<u64>,
+    /// Once firing, the alert is only cleared when the value goes back above this threshold.
+    /// Defaults to `trigger_threshold`.
+    clear_threshold: Option<f64>,
+    /// Below this value, the alert is critical rather than a warning.
+    critical: Option<f64>,
+    /// Severities the alert takes below each free fraction, instead of `critical`.
+    severities: Vec<SeverityTier>,
+    /// Only triggers the alert when the disk is predicted to be full within this duration, at
+    /// its current fill rate. The free fraction must still be between `min` and
+    /// `trigger_threshold`, which then default to the whole disk.
+    full_within: Option<Duration>,
+    /// Template of the alert message, with `{mount}`, `{free_pct}`, `{used_pct}`, `{free}` and
+    /// `{threshold}` placeholders, as well as `{time_to_full}` along with `full_within`.
+    message: Option<String>,
+    /// Thresholds of some mount points, instead of the ones above. The most specific override
+    /// matching a mount point applies: one on the exact mount point, then one on its
+    /// filesystem type, the first one listed of each.
+    overrides: Vec<Override>,
+    /// How the free fraction and bytes are compared to the thresholds, `<` by default.
+    operator: Option<Comparison>,
+}
+
+impl TryFrom<RawAlert> for Alert {
+    type Error = String;
+
+    fn try_from(raw: RawAlert) -> Result<Self, Self::Error> {
+        let trigger_threshold = match (raw.trigger_threshold, raw.min_free_bytes) {
+            (None, None) if raw.full_within.is_some() => Some(1.0),
+            (None, None) => return Err("missing field 'trigger_threshold'".into()),
+            (trigger_threshold, _) => trigger_threshold,
+        };
+        if raw.min.is_none() && raw.min_free_bytes.is_none() && raw.full_within.is_none() {
+            return Err("missing field 'min'".into());
+        }
+        Ok(Alert {
+            resource: raw.resource,
+            min: raw.min,
+            trigger_threshold,
+            min_free_bytes: raw.min_free_bytes,
+            clear_threshold: raw.clear_threshold,
+            critical: raw.critical,
+            severities: raw.severities,
+            full_within: raw.full_within,
+            message: raw.message,
+            overrides: raw.overrides,
+            operator: raw.operator,
+        })
+    }
+}
+
+/// Threshold an alert crossed.
+enum Crossed {
+    /// Free fraction.
+    Fraction(f64),
+    /// Available bytes.
+    Bytes(u64),
+}
+
+impl Alert {
+    /// The alert with the thresholds of the most specific override matching the usage, if any.
+    fn for_usage(&self, usage: &DiskUsage) -> Cow<'_, Alert> {
+        let on = |target: Target| self.overrides.iter().find(|o| o.target == target);
+        let found = on(Target::Mount(usage.mount.clone())).or_else(|| {
+            usage
+                .filesystem
+                .clone()
+                .and_then(|filesystem| on(Target::Filesystem(filesystem)))
+        });
+        match found {
+            Some(o) => Cow::Owned(self.overridden(o)),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    fn overridden(&self, o: &Override) -> Alert {
+        Alert {
+            trigger_threshold: Some(o.trigger_threshold),
+            clear_threshold: o.clear_threshold,
+            critical: o.critical,
+            overrides: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    fn comparison(&self) -> Comparison {
+        self.operator.unwrap_or(Comparison::Below)
+    }
+
+    /// Free fraction of the watched resource, if known.
+    fn free(&self, usage: &DiskUsage) -> Option<f64> {
+        match self.resource {
+            Resource::Bytes => Some(usage.free_space),
+            Resource::Inodes => usage
+                .inodes
+                .as_ref()
+                .map(|i| i.free as f64 / i.total as f64),
+        }
+    }
+
+    /// The threshold crossed by the usage, comparing the free fraction to `limit` rather than to
+    /// the trigger threshold, if the disk is filling fast enough when `full_within` is set.
+    fn crossed(&self, usage: &DiskUsage, free: f64, limit: Option<f64>) -> Option<Crossed> {
+        if self.min.is_some_and(|min| free <= min) {
+            return None;
+        }
+        if let Some(horizon) = self.full_within {
+            if usage
+                .time_to_full()
+                .is_none_or(|time_to_full| time_to_full >= horizon)
+            {
+                return None;
+            }
+        }
+        let comparison = self.comparison();
+        match (limit, self.min_free_bytes) {
+            (Some(limit), _) if watcher::compare(free, comparison, limit) => {
+                Some(Crossed::Fraction(limit))
+            }
+            (_, Some(min_free_bytes))
+                if watcher::compare(usage.free_bytes as f64, comparison, min_free_bytes as f64) =>
+            {
+                Some(Crossed::Bytes(min_free_bytes))
+            }
+            _ => None,
+        }
+    }
+
+    fn triggered_for(&self, usage: &DiskUsage) -> Option<ActiveAlert> {
+        let free = self.free(usage)?;
+        let crossed = self.crossed(usage, free, self.trigger_threshold)?;
+        let comparison = self.comparison();
+        let severity = match self.critical {
+            Some(critical) if comparison.is_beyond(free, critical) => Severity::Critical,
+            _ => watcher::tier_severity(&self.severities, free, |a, b| comparison.is_beyond(a, b)),
+        };
+        let (threshold, threshold_value) = match crossed {
+            Crossed::Fraction(fraction) => (
+                format!("{}%", decimal(100.0 * fraction)),
+                decimal(100.0 * fraction),
+            ),
+            Crossed::Bytes(bytes) => (format_size(bytes), bytes.to_string()),
+        };
+        // An operator set explicitly shows in the messages, e.g. `(threshold: > 90%)`.
+        let shown = match self.operator {
+            Some(operator) => format!("{} {}", operator, threshold),
+            None => threshold.clone(),
+        };
+        let time_to_full = usage.time_to_full().filter(|_| self.full_within.is_some());
+        let mut annotations = vec![
+            ("mount", usage.mount.clone()),
+            ("free_pct", decimal(100.0 * free)),
+            ("used_pct", decimal(100.0 * (1.0 - free))),
+            ("free_bytes", usage.free_bytes.to_string()),
+            ("threshold", threshold_value),
+        ];
+        if let Some(time_to_full) = time_to_full {
+            annotations.push(("time_to_full_seconds", time_to_full.as_secs().to_string()));
+        }
+        let message = match (&self.message, self.resource, time_to_full) {
+            (Some(template), _, _) => {
+                let mut fields = BTreeMap::from(
+                    [
+                        ("mount", usage.mount.clone()),
+                        ("free_pct", decimal(100.0 * free)),
+                        ("used_pct", decimal(100.0 * (1.0 - free))),
+                        ("free", format_size(usage.free_bytes)),
+                        ("threshold", threshold.trim_end_matches('%').to_string()),
+                    ]
+                    .map(|(name, value)| (name.to_string(), value)),
+                );
+                if let Some(time_to_full) = time_to_full {
+                    fields.insert("time_to_full".to_string(), format_duration(time_to_full));
+                }
+                render_template(template, &fields)
+            }
+            (None, Resource::Bytes, Some(time_to_full)) if time_to_full.is_zero() => {
+                format!("📈 {} is full.", usage.mount)
+            }
+            (None, Resource::Bytes, Some(time_to_full)) => format!(
+                "📈 {} is {}% full and filling at {}%/h, it will be full in {} \
+                 (horizon: {}).",
+                usage.mount,
+                decimal(100.0 * (1.0 - free)),
+                decimal(100.0 * 3600.0 * usage.fill_rate.unwrap_or_default()),
+                format_duration(time_to_full),
+                format_duration(self.full_within.unwrap_or_default())
+            ),
+            (None, Resource::Bytes, _) => match crossed {
+                Crossed::Fraction(_) => format!(
+                    "💾 Free disk space on {} is {}% (threshold: {}).",
+                    usage.mount,
+                    decimal(100.0 * free),
+                    shown
+                ),
+                Crossed::Bytes(_) => format!(
+                    "💾 Free disk space on {} is {} (threshold: {}).",
+                    usage.mount,
+                    format_size(usage.free_bytes),
+                    shown
+                ),
+            },
+            (None, Resource::Inodes, _) => format!(
+                "🗂️ Free inodes on {} are at {}% (threshold: {}).",
+                usage.mount,
+                decimal(100.0 * free),
+                shown
+            ),
+        };
+        Some(ActiveAlert::new(message, severity).annotated(annotations))
+    }
+
+    fn cleared_for(&self, usage: &DiskUsage) -> bool {
+        let clear_threshold = self.clear_threshold.or(self.trigger_threshold);
+        match self.free(usage) {
+            Some(free) => self.crossed(usage, free, clear_threshold).is_none(),
+            None => true,
+        }
+    }
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.resource.hash(state);
+        // Hashed as when it defaulted to 0, for alerts to keep their fingerprint.
+        threshold_bits(self.min.unwrap_or(0.0)).hash(state);
+        // Hashed as when it was required, for alerts to keep their fingerprint.
+        if let Some(trigger_threshold) = self.trigger_threshold {
+            threshold_bits(trigger_threshold).hash(state);
+        }
+        self.clear_threshold.map(threshold_bits).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+        self.message.hash(state);
+        // Alerts keep the fingerprint they had before each of these options existed, as long as
+        // they do not set it.
+        if let Some(min_free_bytes) = self.min_free_bytes {
+            min_free_bytes.hash(state);
+        }
+        if !self.severities.is_empty() {
+            self.severities.hash(state);
+        }
+        if let Some(full_within) = self.full_within {
+            full_within.hash(state);
+        }
+        if !self.overrides.is_empty() {
+            self.overrides.hash(state);
+        }
+        if let Some(operator) = self.operator {
+            operator.hash(state);
+        }
+    }
 }
 
 impl watcher::Alert for Alert {
     type Checker = Checker;
 
+    fn validate(&self) -> Result<()> {
+        for o in &self.overrides {
+            self.overridden(o)
+                .validate()
+                .with_context(|| format!("Override on {}", o.target))?;
+        }
+        if self.resource != Resource::Bytes {
+            if self.full_within.is_some() {
+                return Err(anyhow!(
+                    "'full_within' only applies to the 'Bytes' resource"
+                ));
+            }
+            if self.min_free_bytes.is_some() {
+                return Err(anyhow!(
+                    "'min_free_bytes' only applies to the 'Bytes' resource"
+                ));
+            }
+        }
+        if let Some(min) = self.min {
+            watcher::validate_fraction("min", min)?;
+        }
+        let comparison = self.comparison();
+        watcher::validate_comparison(comparison, self.critical, &self.severities)?;
+        let Some(trigger_threshold) = self.trigger_threshold else {
+            return Ok(());
+        };
+        watcher::validate_fraction("trigger_threshold", trigger_threshold)?;
+        if let Some(min) = self.min.filter(|min| *min >= trigger_threshold) {
+            return Err(anyhow!(
+                "'min' ({}) is not below 'trigger_threshold' ({})",
+                min,
+                trigger_threshold
+            ));
+        }
+        if let Some(clear_threshold) = self.clear_threshold {
+            watcher::validate_fraction("clear_threshold", clear_threshold)?;
+            if comparison.is_beyond(clear_threshold, trigger_threshold) {
+                return Err(anyhow!(
+                    "'clear_threshold' ({}) is past 'trigger_threshold' ({})",
+                    clear_threshold,
+                    trigger_threshold
+                ));
+            }
+        }
+        match self.critical {
+            Some(critical) if comparison.is_beyond(trigger_threshold, critical) => Err(anyhow!(
+                "'critical' ({}) is not past 'trigger_threshold' ({})",
+                critical,
+                trigger_threshold
+            )),
+            _ => watcher::validate_tiers(
+                trigger_threshold,
+                &self.severities,
+                self.critical,
+                |a, b| comparison.is_beyond(a, b),
+            ),
+        }
+    }
+
+    /// Replaces the free fraction below which the alert triggers.
+    fn with_threshold(&self, threshold: f64) -> Option<Self> {
+        Some(Alert {
+            trigger_threshold: Some(threshold),
+            ..self.clone()
+        })
+    }
+
+    /// Alert about the first mount point that triggers it. Each mount point is reported on its
+    /// own by [`watcher::Alert::evaluate`].
     fn is_triggered(
         &self,
         check_result: &<Self::Checker as watcher::Checker>::CheckResult,
     ) -> Option<ActiveAlert> {
-        if self.min < *check_result && *check_result < self.max {
-            Some(ActiveAlert {
-                message: format!(
-                    "💾 Free disk space is {:.2}% (threshold: {:.2}%).",
-                    100.0 * *check_result,
-                    100.0 * self.max
-                ),
+        self.evaluate(check_result)
+            .into_iter()
+            .find_map(|e| e.triggered)
+    }
+
+    fn is_cleared(&self, check_result: &<Self::Checker as watcher::Checker>::CheckResult) -> bool {
+        self.evaluate(check_result).iter().all(|e| e.cleared)
+    }
+
+    /// Mount points that could not be checked trigger the alert with their own message.
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|check| match &check.usage {
+                Ok(usage) => {
+                    let alert = self.for_usage(usage);
+                    Evaluation {
+                        subject: check.mount.clone(),
+                        triggered: alert.triggered_for(usage),
+                        cleared: alert.cleared_for(usage),
+                    }
+                }
+                Err(error) => Evaluation {
+                    subject: check.mount.clone(),
+                    triggered: Some(ActiveAlert::new(
+                        format!("💾 Could not read disk space on {}: {}", check.mount, error),
+                        Severity::default(),
+                    )),
+                    cleared: false,
+                },
             })
-        } else {
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_store::memory::Memory;
+    use crate::stable_hash;
+    use crate::watcher::replay::{ReplayChecker, Snapshot};
+    use serde_json::json;
+
+    fn alert(configuration: serde_json::Value) -> Alert {
+        serde_json::from_value(configuration).expect("Invalid alert")
+    }
+
+    #[test]
+    fn options_left_unset_keep_the_fingerprint() {
+        let alert = alert(json!({ "min": 0.0, "trigger_threshold": 0.1, "critical": 0.05 }));
+        let before_the_options = (
+            Resource::Bytes,
+            threshold_bits(0.0),
+            threshold_bits(0.1),
+            None::<u64>,
+            Some(threshold_bits(0.05)),
+            None::<String>,
+        );
+        assert_eq!(
+            stable_hash::hash(&alert),
+            stable_hash::hash(&before_the_options)
+        );
+    }
+
+    #[test]
+    fn inconsistent_thresholds_are_explained() {
+        use crate::watcher::Alert as _;
+
+        for (configuration, problem) in [
+            (
+                json!({ "min": 0.0, "trigger_threshold": 10.0 }),
+                "'trigger_threshold' is a fraction between 0 and 1, not 10",
+            ),
+            (
+                json!({ "min": 0.2, "trigger_threshold": 0.1 }),
+                "'min' (0.2) is not below 'trigger_threshold' (0.1)",
+            ),
+            (
+                json!({ "min": 0.0, "trigger_threshold": 0.1, "clear_threshold": 0.05 }),
+                "'clear_threshold' (0.05) is past 'trigger_threshold' (0.1)",
+            ),
+            (
+                json!({ "min": 0.0, "trigger_threshold": 0.1, "critical": 0.2 }),
+                "'critical' (0.2) is not past 'trigger_threshold' (0.1)",
+            ),
+            (
+                json!({
+                    "resource": "Inodes",
+                    "min": 0.0,
+                    "trigger_threshold": 0.1,
+                    "full_within": "1d",
+                }),
+                "'full_within' only applies to the 'Bytes' resource",
+            ),
+        ] {
+            let error = alert(configuration).validate().unwrap_err();
+            assert_eq!(format!("{:#}", error), problem);
+        }
+        assert!(
+            alert(json!({ "min": 0.0, "trigger_threshold": 0.1, "critical": 0.05 }))
+                .validate()
+                .is_ok()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_and_bind_mounts_are_only_discovered_when_included() {
+        // `/.` is on the device of `/`, as a bind mount of it would be.
+        let content = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                       /dev/sda1 /. ext4 rw,relatime 0 0\n\
+                       /dev/loop0 /unreadable ext4 ro,relatime 0 0\n";
+        let discover = |discovery: serde_json::Value| {
+            serde_json::from_value::<Discovery>(discovery)
+                .expect("Invalid discovery")
+                .discover_in(content)
+        };
+
+        assert_eq!(discover(json!({})), ["/"]);
+        assert_eq!(
+            discover(json!({ "include_readonly": true })),
+            ["/", "/unreadable"]
+        );
+        assert_eq!(discover(json!({ "include_binds": true })), ["/", "/."]);
+    }
+
+    #[test]
+    fn reads_the_default_mount_of_this_machine() {
+        let checker = <Checker as watcher::Checker>::new(
+            serde_json::from_value(json!({ "period": "1m" })).unwrap(),
+        );
+
+        let checks = watcher::Checker::check(&checker).unwrap();
+
+        assert_eq!(checks.len(), 1);
+        let usage = checks[0].usage.as_ref().unwrap();
+        assert_eq!(usage.mount, if cfg!(windows) { "C:\\" } else { "/" });
+        assert!((0.0..=1.0).contains(&usage.free_space), "{:?}", usage);
+    }
+
+    #[test]
+    fn summarizes_each_mount() {
+        let checker = <Checker as watcher::Checker>::new(
+            serde_json::from_value(json!({ "period": "1m" })).unwrap(),
+        );
+        let usage = DiskUsage {
+            mount: "/".to_string(),
+            free_space: 0.125,
+            free_bytes: 1_500_000_000,
+            filesystem: None,
+            backend: Backend::Statvfs,
+            inodes: None,
+            fill_rate: None,
+        };
+        let checks = vec![
+            MountCheck {
+                mount: "/".to_string(),
+                usage: Ok(usage),
+            },
+            MountCheck {
+                mount: "/mnt".to_string(),
+                usage: Err("Could not execute 'statvfs' on /mnt".to_string()),
+            },
+        ];
+        assert_eq!(
+            watcher::Checker::summary(&checker, &checks),
+            "/: 12.5% free (1.5 GB), /mnt: Could not execute 'statvfs' on /mnt"
+        );
+    }
+
+    /// Usage of `/`, with no inode and no fill rate.
+    fn usage(free_space: f64, free_bytes: u64) -> DiskUsage {
+        DiskUsage {
+            mount: "/".to_string(),
+            free_space,
+            free_bytes,
+            filesystem: None,
+            backend: Backend::Statvfs,
+            inodes: None,
+            fill_rate: None,
+        }
+    }
+
+    #[test]
+    fn alerts_are_annotated_with_the_values_of_the_mount() {
+        let alert = alert(json!({ "min": 0.0, "trigger_threshold": 0.1 }));
+
+        let fired = alert.triggered_for(&usage(0.05, 500_000_000)).unwrap();
+
+        let annotations = fired
+            .annotations
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            annotations,
+            [
+                ("free_bytes", "500000000"),
+                ("free_pct", "5.0"),
+                ("mount", "/"),
+                ("threshold", "10.0"),
+                ("used_pct", "95.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_override_of_the_mount_beats_the_one_of_its_filesystem() {
+        let alert = alert(json!({
+            "min": 0.0,
+            "trigger_threshold": 0.1,
+            "overrides": [
+                { "Filesystem": "ext4", "trigger_threshold": 0.03 },
+                { "Mount": "/", "trigger_threshold": 0.2 },
+            ],
+        }));
+        let check = |mount: &str, filesystem: &str, free_space| MountCheck {
+            mount: mount.to_string(),
+            usage: Ok(DiskUsage {
+                mount: mount.to_string(),
+                filesystem: Some(filesystem.to_string()),
+                ..usage(free_space, 1_000_000_000)
+            }),
+        };
+
+        let evaluations = watcher::Alert::evaluate(
+            &alert,
+            &vec![
+                check("/", "ext4", 0.15),
+                check("/data", "ext4", 0.05),
+                check("/scratch", "xfs", 0.05),
+            ],
+        );
+
+        let fired = evaluations
+            .iter()
+            .filter(|e| e.triggered.is_some())
+            .map(|e| e.subject.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fired, ["/", "/scratch"]);
+        let threshold = &evaluations[0].triggered.as_ref().unwrap().annotations["threshold"];
+        assert_eq!(threshold, "20.0");
+    }
+
+    #[test]
+    fn min_free_bytes_fires_on_a_full_disk() {
+        let alert = alert(json!({ "min_free_bytes": "1GB" }));
+        let full = usage(0.0, 0);
+        assert!(alert.triggered_for(&full).is_some());
+        assert!(!alert.cleared_for(&full));
+
+        // Unless told to ignore full disks.
+        let with_min = super::Alert {
+            min: Some(0.0),
+            ..alert.clone()
+        };
+        assert!(with_min.triggered_for(&full).is_none());
+    }
+
+    #[test]
+    fn each_operator_compares_the_free_space() {
+        // The disk has exactly 10% free.
+        let triggers = |operator: &str| {
+            alert(json!({ "min": 0.0, "trigger_threshold": 0.1, "operator": operator }))
+                .triggered_for(&usage(0.1, 100_000_000))
+                .is_some()
+        };
+        assert!(!triggers(">"));
+        assert!(triggers(">="));
+        assert!(!triggers("<"));
+        assert!(triggers("<="));
+        assert!(triggers("=="));
+
+        // Without an operator, the threshold is a low-water mark.
+        let low_water = alert(json!({ "min": 0.0, "trigger_threshold": 0.1 }));
+        assert!(low_water.triggered_for(&usage(0.09, 90_000_000)).is_some());
+        assert!(low_water.triggered_for(&usage(0.1, 100_000_000)).is_none());
+        let message = alert(json!({ "min": 0.0, "trigger_threshold": 0.9, "operator": ">" }))
+            .triggered_for(&usage(0.95, 950_000_000))
+            .unwrap()
+            .message;
+        assert_eq!(
+            message,
+            "💾 Free disk space on / is 95.0% (threshold: > 90.0%)."
+        );
+    }
+
+    #[test]
+    fn fill_rate_is_the_slope_of_the_readings() {
+        let start = Instant::now();
+        let readings = [(0, 0.5), (60, 0.45), (120, 0.4)]
+            .map(|(seconds, free)| (start + Duration::from_secs(seconds), free))
+            .into_iter()
+            .collect::<VecDeque<_>>();
+        let rate = fill_rate(&readings).unwrap();
+        assert!((rate - 0.05 / 60.0).abs() < 1e-12, "{}", rate);
+        // Readings all taken at once tell nothing.
+        assert_eq!(
+            fill_rate(&VecDeque::from([(start, 0.5), (start, 0.4)])),
             None
+        );
+
+        let filling = DiskUsage {
+            fill_rate: Some(rate),
+            ..usage(0.4, 4_000_000_000)
+        };
+        assert_eq!(filling.time_to_full(), Some(Duration::from_secs(480)));
+        let freeing = DiskUsage {
+            fill_rate: Some(-rate),
+            ..usage(0.4, 4_000_000_000)
+        };
+        assert_eq!(freeing.time_to_full(), None);
+    }
+
+    #[test]
+    fn full_within_keeps_firing_once_the_disk_is_full() {
+        let alert = alert(json!({ "full_within": "1h" }));
+        // Filling 10% an hour, full in 5 hours then in 30 minutes.
+        let filling = DiskUsage {
+            fill_rate: Some(0.1 / 3600.0),
+            ..usage(0.5, 5_000_000_000)
+        };
+        assert!(alert.triggered_for(&filling).is_none());
+        let almost_full = DiskUsage {
+            fill_rate: Some(0.1 / 3600.0),
+            ..usage(0.05, 500_000_000)
+        };
+        assert!(alert.triggered_for(&almost_full).is_some());
+        // Nothing fills a full disk anymore.
+        let full = DiskUsage {
+            fill_rate: Some(0.0),
+            ..usage(0.0, 0)
+        };
+        assert_eq!(alert.triggered_for(&full).unwrap().message, "📈 / is full.");
+        assert!(!alert.cleared_for(&full));
+    }
+
+    #[test]
+    fn alerts_at_other_percentages_share_a_fingerprint() {
+        let mut watcher = watcher::MultiWatcher::new(
+            serde_json::from_value::<watcher::SerializedMultiWatcher<Alert>>(json!({
+                "configuration": { "period": "1m" },
+                "alerts": [{ "min": 0.0, "trigger_threshold": 0.1 }]
+            }))
+            .unwrap(),
+        );
+        let start = std::time::SystemTime::now();
+        let checks = [(0.08, 800_000_000), (0.05, 500_000_000)]
+            .into_iter()
+            .enumerate()
+            .map(|(index, (free_space, free_bytes))| Snapshot {
+                sampled_at: start + Duration::from_secs(60 * index as u64),
+                result: vec![MountCheck {
+                    mount: "/".to_string(),
+                    usage: Ok(usage(free_space, free_bytes)),
+                }],
+            })
+            .collect();
+        let reporter = RecordingReporter::new();
+        watcher
+            .replay(
+                &ReplayChecker::<Checker>::new(checks),
+                &reporter,
+                &Memory::new(Default::default()),
+            )
+            .unwrap();
+
+        let alerts = reporter.alerts();
+        assert_eq!(alerts.len(), 2);
+        assert_ne!(alerts[0].message, alerts[1].message);
+        assert_eq!(alerts[0].fingerprint, alerts[1].fingerprint);
+    }
+
+    #[test]
+    fn only_probing_reads_the_mount_points() {
+        let configuration = serde_json::from_value::<watcher::WatcherConfiguration>(json!({
+            "DiskSpace": {
+                "configuration": { "mounts": { "Paths": ["/no/such/mount"] }, "period": "1m" },
+                "alerts": [{ "min": 0.0, "trigger_threshold": 0.1 }]
+            }
+        }))
+        .unwrap();
+        let watcher = watcher::WatcherEnum::try_from(configuration).unwrap();
+        let error = watcher::Watcher::probe(&watcher).unwrap_err();
+        assert!(error.to_string().contains("/no/such/mount"), "{}", error);
+    }
+
+    #[test]
+    fn a_failing_mount_leaves_the_others_checked() {
+        let checker = <Checker as watcher::Checker>::new(
+            serde_json::from_value(json!({ "period": "1m" })).unwrap(),
+        );
+        let mounts = ["/", "/mnt/nfs", "/data"].map(String::from).to_vec();
+        let checks = checker
+            .check_mounts(mounts, |mount| match mount.as_str() {
+                "/" => Ok(usage(0.05, 500_000_000)),
+                "/mnt/nfs" => Err(anyhow!("Could not execute 'statvfs' on /mnt/nfs")),
+                _ => Ok(DiskUsage {
+                    mount,
+                    ..usage(0.5, 5_000_000_000)
+                }),
+            })
+            .unwrap();
+
+        let evaluations = watcher::Alert::evaluate(
+            &alert(json!({ "min": 0.0, "trigger_threshold": 0.1 })),
+            &checks,
+        );
+        let triggered = evaluations
+            .iter()
+            .map(|e| {
+                (
+                    e.subject.as_str(),
+                    e.triggered.as_ref().map(|a| a.message.as_str()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            triggered,
+            [
+                ("/", Some("💾 Free disk space on / is 5.0% (threshold: 10.0%).")),
+                (
+                    "/mnt/nfs",
+                    Some("💾 Could not read disk space on /mnt/nfs: Could not execute 'statvfs' on /mnt/nfs")
+                ),
+                ("/data", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn options_set_change_the_fingerprint() {
+        let unset = stable_hash::hash(&alert(json!({ "min": 0.0, "trigger_threshold": 0.1 })));
+        for (option, value) in [
+            ("min_free_bytes", json!("1GB")),
+            (
+                "severities",
+                json!([{ "at": 0.05, "severity": "Critical" }]),
+            ),
+            ("full_within", json!("1d")),
+        ] {
+            let mut configuration = json!({ "min": 0.0, "trigger_threshold": 0.1 });
+            configuration[option] = value;
+            assert_ne!(
+                stable_hash::hash(&alert(configuration)),
+                unset,
+                "{}",
+                option
+            );
         }
     }
 }
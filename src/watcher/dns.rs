@@ -0,0 +1,402 @@
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeSet;
+use std::hash::{BuildHasher, Hasher};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{deserialize_duration, Period};
+use crate::metrics::Sample;
+use crate::watcher;
+use crate::watcher::target::{self, TargetCheck};
+use crate::watcher::{ActiveAlert, Evaluation, Severity};
+
+const DNS_PORT: u16 = 53;
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    hostname: String,
+    /// The system's resolver if empty.
+    resolvers: Vec<String>,
+    timeout: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    hostname: String,
+    /// Address of the DNS server to query, such as `1.1.1.1` or `[::1]:5353`, instead of the
+    /// system's resolver.
+    #[serde(default)]
+    resolver: Option<String>,
+    /// DNS servers to query along with `resolver`, one after the other, each alerting on its
+    /// own.
+    #[serde(default)]
+    resolvers: Vec<String>,
+    /// How long to wait for the answers of the resolver. The system's resolver has its own
+    /// timeouts.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    pub hostname: String,
+    /// The resolver queried, the system's one if `None`.
+    pub resolver: Option<SocketAddr>,
+    /// Addresses the hostname resolved to, never empty.
+    pub addresses: BTreeSet<IpAddr>,
+    pub elapsed: Duration,
+}
+
+/// Resolution of the hostname by one resolver, named like `example.com with 1.1.1.1:53`.
+pub type ResolverCheck = TargetCheck<Resolution>;
+
+fn parse_resolver(resolver: &str) -> Result<SocketAddr> {
+    resolver
+        .parse::<SocketAddr>()
+        .or_else(|_| {
+            resolver
+                .parse::<IpAddr>()
+                .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        })
+        .with_context(|| format!("Invalid resolver address: '{}'", resolver))
+}
+
+/// Builds a recursive query for the records of a type, see RFC 1035.
+fn query(id: u16, hostname: &str, record_type: u16) -> Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(12 + hostname.len() + 6);
+    packet.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question and no other record.
+    packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in hostname.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow!("Invalid hostname: '{}'", hostname));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&record_type.to_be_bytes());
+    // Internet class.
+    packet.extend_from_slice(&[0, 1]);
+    Ok(packet)
+}
+
+/// Skips a possibly compressed domain name, returning the offset right after it.
+fn skip_name(packet: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let length = *packet.get(offset)?;
+        match length {
+            0 => return Some(offset + 1),
+            l if l & 0xc0 == 0xc0 => return Some(offset + 2),
+            l => offset += 1 + usize::from(l),
+        }
+    }
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(offset)?,
+        *packet.get(offset + 1)?,
+    ]))
+}
+
+/// Extracts the addresses of a response, which must have a single question.
+fn parse_response(packet: &[u8]) -> Result<Vec<IpAddr>> {
+    let malformed = || anyhow!("Malformed DNS response");
+    let flags = read_u16(packet, 2).ok_or_else(malformed)?;
+    match flags & 0x000f {
+        0 => {}
+        3 => return Err(anyhow!("no such domain")),
+        2 => return Err(anyhow!("server failure")),
+        5 => return Err(anyhow!("query refused")),
+        code => return Err(anyhow!("error code {}", code)),
+    }
+    let answers = read_u16(packet, 6).ok_or_else(malformed)?;
+    // Skips the question, made of its name, type and class.
+    let mut offset = skip_name(packet, 12).ok_or_else(malformed)? + 4;
+    let mut addresses = Vec::new();
+    for _ in 0..answers {
+        offset = skip_name(packet, offset).ok_or_else(malformed)?;
+        let record_type = read_u16(packet, offset).ok_or_else(malformed)?;
+        let length = usize::from(read_u16(packet, offset + 8).ok_or_else(malformed)?);
+        let data = packet
+            .get(offset + 10..offset + 10 + length)
+            .ok_or_else(malformed)?;
+        // Other records, such as CNAME ones, lead to the addresses that follow them.
+        match (record_type, data.len()) {
+            (TYPE_A, 4) => addresses.push(IpAddr::from(<[u8; 4]>::try_from(data)?)),
+            (TYPE_AAAA, 16) => addresses.push(IpAddr::from(<[u8; 16]>::try_from(data)?)),
+            _ => {}
+        }
+        offset += 10 + length;
+    }
+    Ok(addresses)
+}
+
+impl Checker {
+    /// Queries the A and AAAA records of the hostname at once from the configured resolver.
+    fn resolve_with(&self, resolver: SocketAddr) -> Result<BTreeSet<IpAddr>> {
+        let local: SocketAddr = match resolver {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).context("Could not open UDP socket")?;
+        socket
+            .connect(resolver)
+            .with_context(|| format!("Could not reach resolver {}", resolver))?;
+        let id = RandomState::new().build_hasher().finish() as u16;
+        let mut pending = BTreeSet::new();
+        for (i, record_type) in [TYPE_A, TYPE_AAAA].into_iter().enumerate() {
+            let id = id.wrapping_add(i as u16);
+            socket
+                .send(&query(id, &self.hostname, record_type)?)
+                .with_context(|| format!("Could not send DNS query to {}", resolver))?;
+            pending.insert(id);
+        }
+        let deadline = Instant::now() + self.timeout;
+        let mut addresses = BTreeSet::new();
+        let mut buffer = [0; 4096];
+        while !pending.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(anyhow!("timed out after {:?}", self.timeout));
+            }
+            socket
+                .set_read_timeout(Some(deadline - now))
+                .context("Could not set UDP socket timeout")?;
+            let length = match socket.recv(&mut buffer) {
+                Ok(length) => length,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue
+                }
+                Err(e) => return Err(e).context("Could not receive DNS response"),
+            };
+            let response = &buffer[..length];
+            // Answers to other queries, e.g. late ones from a previous check, are ignored.
+            if !read_u16(response, 0).is_some_and(|id| pending.remove(&id)) {
+                continue;
+            }
+            addresses.extend(parse_response(response)?);
+        }
+        Ok(addresses)
+    }
+
+    fn resolve(&self, resolver: Option<SocketAddr>) -> Result<BTreeSet<IpAddr>> {
+        match resolver {
+            Some(resolver) => self.resolve_with(resolver),
+            None => Ok((self.hostname.as_str(), 0)
+                .to_socket_addrs()?
+                .map(|a| a.ip())
+                .collect()),
+        }
+    }
+
+    fn check_resolver(&self, resolver: Option<&str>) -> ResolverCheck {
+        let target = format!(
+            "{} with {}",
+            self.hostname,
+            resolver.unwrap_or("the system's resolver")
+        );
+        let resolver = match resolver.map(parse_resolver).transpose() {
+            Ok(resolver) => resolver,
+            Err(e) => {
+                return ResolverCheck {
+                    target,
+                    outcome: Err(format!("{:#}", e)),
+                }
+            }
+        };
+        let start = Instant::now();
+        let outcome = self
+            .resolve(resolver)
+            .map_err(|e| format!("{:#}", e))
+            .and_then(|addresses| match addresses.is_empty() {
+                true => Err("no address".to_string()),
+                false => Ok(Resolution {
+                    hostname: self.hostname.clone(),
+                    resolver,
+                    addresses,
+                    elapsed: start.elapsed(),
+                }),
+            });
+        info!(target, outcome = ?outcome);
+        ResolverCheck { target, outcome }
+    }
+}
+
+impl watcher::Checker for Checker {
+    /// Resolution of the hostname by each resolver, or why it failed.
+    type CheckResult = Vec<ResolverCheck>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "dns");
+        if self.resolvers.is_empty() {
+            return Ok(vec![self.check_resolver(None)]);
+        }
+        Ok(self
+            .resolvers
+            .iter()
+            .map(|resolver| self.check_resolver(Some(resolver)))
+            .collect())
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        target::summary(check_result, |resolution| {
+            format!(
+                "{} addresses in {} ms",
+                resolution.addresses.len(),
+                resolution.elapsed.as_millis()
+            )
+        })
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            hostname: configuration.hostname,
+            resolvers: target::hosts(configuration.resolver, configuration.resolvers),
+            timeout: configuration.timeout,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        for resolver in &self.resolvers {
+            parse_resolver(resolver)?;
+        }
+        Ok(())
+    }
+
+    fn probe(&self) -> Result<()> {
+        query(0, &self.hostname, TYPE_A).map(|_| ())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        check_result
+            .iter()
+            .filter_map(|check| check.outcome.as_ref().ok())
+            .map(|resolution| {
+                let resolver = resolution
+                    .resolver
+                    .map_or("system".to_string(), |r| r.to_string());
+                Sample::new(
+                    "monitrust_dns_resolution_seconds",
+                    &[
+                        ("hostname", resolution.hostname.as_str()),
+                        ("resolver", resolver.as_str()),
+                    ],
+                    resolution.elapsed.as_secs_f64(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Also alert when resolving takes longer than this.
+    #[serde(default)]
+    max_resolution_ms: Option<u64>,
+    /// Addresses the hostname must resolve to, among others.
+    #[serde(default)]
+    expected: Vec<IpAddr>,
+}
+
+impl Alert {
+    fn triggered_for(&self, check: &ResolverCheck) -> Option<ActiveAlert> {
+        let target = &check.target;
+        let resolution = match &check.outcome {
+            Ok(resolution) => resolution,
+            Err(e) => {
+                return Some(ActiveAlert::new(
+                    format!("🌐 Could not resolve {}: {}.", target, e),
+                    Severity::Critical,
+                ))
+            }
+        };
+        let missing = self
+            .expected
+            .iter()
+            .filter(|a| !resolution.addresses.contains(a))
+            .map(IpAddr::to_string)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Some(ActiveAlert::new(
+                format!(
+                    "🌐 {} resolved to {}, without the expected {}.",
+                    target,
+                    resolution
+                        .addresses
+                        .iter()
+                        .map(IpAddr::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    missing.join(", ")
+                ),
+                Severity::Critical,
+            ));
+        }
+        let max_resolution = Duration::from_millis(self.max_resolution_ms?);
+        if resolution.elapsed <= max_resolution {
+            return None;
+        }
+        Some(ActiveAlert::new(
+            format!(
+                "🐢 Resolving {} took {} ms (max: {} ms).",
+                target,
+                resolution.elapsed.as_millis(),
+                max_resolution.as_millis()
+            ),
+            Severity::default(),
+        ))
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result.iter().find_map(|c| self.triggered_for(c))
+    }
+
+    /// Evaluates each resolver on its own, so that they fire and resolve independently.
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|check| {
+                let triggered = self.triggered_for(check);
+                Evaluation {
+                    subject: check.target.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{
+    deserialize_duration, deserialize_optional_duration, format_duration, Period,
+};
+use crate::metrics::Sample;
+use crate::pattern::Pattern;
+use crate::watcher;
+use crate::watcher::{ActiveAlert, Evaluation, Severity};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    files: Vec<Target>,
+}
+
+/// A file whose modification time is watched.
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub enum Target {
+    File(PathBuf),
+    /// The most recently modified file of a directory, among the ones whose name matches if a
+    /// pattern is set.
+    Newest {
+        directory: PathBuf,
+        #[serde(default)]
+        name: Option<Pattern>,
+    },
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::File(path) => write!(f, "{}", path.display()),
+            Target::Newest {
+                directory,
+                name: Some(name),
+            } => write!(f, "{}/{}", directory.display(), name),
+            Target::Newest {
+                directory,
+                name: None,
+            } => write!(f, "{}/*", directory.display()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    files: Vec<Target>,
+}
+
+#[derive(Debug, Clone)]
+pub enum FileState {
+    /// Time since the file was last modified, zero if it was modified in the future.
+    Modified {
+        path: PathBuf,
+        age: Duration,
+    },
+    /// The file, or any file of the directory, does not exist.
+    Missing,
+    PermissionDenied,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileAge {
+    /// The watched target, as configured.
+    pub target: String,
+    pub state: FileState,
+}
+
+fn modified(path: &Path) -> std::io::Result<SystemTime> {
+    std::fs::metadata(path)?.modified()
+}
+
+/// Most recently modified file of a directory, among the ones matching `name`.
+fn newest(
+    directory: &Path,
+    name: Option<&Pattern>,
+) -> std::io::Result<Option<(PathBuf, SystemTime)>> {
+    let mut newest: Option<(PathBuf, SystemTime)> = None;
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        if name.is_some_and(|n| !n.matches(&entry.file_name().to_string_lossy())) {
+            continue;
+        }
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            // The file was removed while the directory was being read.
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified()?;
+        if newest.as_ref().is_none_or(|(_, newest)| modified > *newest) {
+            newest = Some((entry.path(), modified));
+        }
+    }
+    Ok(newest)
+}
+
+fn check_target(target: &Target, now: SystemTime) -> Result<FileState> {
+    let found = match target {
+        Target::File(path) => modified(path).map(|m| Some((path.clone(), m))),
+        Target::Newest { directory, name } => newest(directory, name.as_ref()),
+    };
+    match found {
+        Ok(Some((path, modified))) => Ok(FileState::Modified {
+            path,
+            age: now.duration_since(modified).unwrap_or_default(),
+        }),
+        Ok(None) => Ok(FileState::Missing),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(FileState::Missing),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => Ok(FileState::PermissionDenied),
+        Err(e) => Err(e).context(format!("Could not read modification time of '{}'", target)),
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Vec<FileAge>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "file_age");
+        let now = SystemTime::now();
+        let mut ages = Vec::new();
+        for target in &self.files {
+            let state = check_target(target, now)?;
+            info!(file = %target, state = ?state);
+            ages.push(FileAge {
+                target: target.to_string(),
+                state,
+            });
+        }
+        Ok(ages)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .iter()
+            .map(|file| match &file.state {
+                FileState::Modified { age, .. } => {
+                    format!("{}: modified {} ago", file.target, format_duration(*age))
+                }
+                FileState::Missing => format!("{}: missing", file.target),
+                FileState::PermissionDenied => format!("{}: permission denied", file.target),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            files: configuration.files,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.files.is_empty() {
+            return Err(anyhow!("At least one file must be watched"));
+        }
+        Ok(())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        check_result
+            .iter()
+            .filter_map(|file| match &file.state {
+                FileState::Modified { age, .. } => Some(Sample::new(
+                    "monitrust_file_age_seconds",
+                    &[("file", file.target.as_str())],
+                    age.as_secs_f64(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Files last modified longer ago than this trigger the alert, as well as missing ones.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(deserialize_with = "deserialize_duration")]
+    max_age: Duration,
+    /// Files last modified longer ago than this are critical rather than a warning.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    critical_age: Option<Duration>,
+}
+
+impl Alert {
+    fn triggered_for(&self, file: &FileAge) -> Option<ActiveAlert> {
+        match &file.state {
+            FileState::Modified { age, .. } if *age <= self.max_age => None,
+            FileState::Modified { path, age } => {
+                let severity = match self.critical_age {
+                    Some(critical_age) if *age > critical_age => Severity::Critical,
+                    _ => Severity::default(),
+                };
+                Some(ActiveAlert::new(
+                    format!(
+                        "⏳ {} was last modified {} ago (max age: {}).",
+                        path.display(),
+                        format_duration(*age),
+                        format_duration(self.max_age)
+                    ),
+                    severity,
+                ))
+            }
+            FileState::Missing => Some(ActiveAlert::new(
+                format!("⏳ {} is missing.", file.target),
+                Severity::default(),
+            )),
+            FileState::PermissionDenied => Some(ActiveAlert::new(
+                format!(
+                    "🔒 Permission denied to read the modification time of {}.",
+                    file.target
+                ),
+                Severity::default(),
+            )),
+        }
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self.critical_age {
+            Some(critical_age) if critical_age < self.max_age => Err(anyhow!(
+                "'critical_age' ({}) is shorter than 'max_age' ({})",
+                format_duration(critical_age),
+                format_duration(self.max_age)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result.iter().find_map(|f| self.triggered_for(f))
+    }
+
+    /// Evaluates each watched file on its own, so that they fire and resolve independently.
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|file| {
+                let triggered = self.triggered_for(file);
+                Evaluation {
+                    subject: file.target.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,341 @@
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::watcher;
+use crate::watcher::{decimal, percent, threshold_bits, ActiveAlert, Evaluation, Severity};
+
+/// Fields queried from `nvidia-smi`, in the order of [`GpuState`].
+const QUERY: &str = "index,name,utilization.gpu,memory.used,memory.total,temperature.gpu";
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    gpus: Vec<u32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Indexes of the GPUs to watch, as listed by `nvidia-smi`. All of them if empty.
+    #[serde(default)]
+    gpus: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GpuState {
+    pub index: u32,
+    pub name: String,
+    /// Fraction of the last sample period during which a kernel was running, in percent.
+    /// Unknown for GPUs that do not report it.
+    pub utilization: Option<f64>,
+    /// Memory in MiB.
+    pub memory_used: Option<f64>,
+    pub memory_total: Option<f64>,
+    /// Temperature in degrees Celsius.
+    pub temperature: Option<f64>,
+}
+
+impl GpuState {
+    /// Fraction of the memory used, between 0 and 1.
+    pub fn memory_usage(&self) -> Option<f64> {
+        match (self.memory_used, self.memory_total) {
+            (Some(used), Some(total)) if total > 0.0 => Some(used / total),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a value of `nvidia-smi`, which reports values it does not support as `[N/A]`.
+fn parse_value(value: &str) -> Result<Option<f64>> {
+    if value.starts_with('[') {
+        return Ok(None);
+    }
+    value
+        .parse::<f64>()
+        .map(Some)
+        .with_context(|| format!("Could not convert GPU value to number: {}", value))
+}
+
+/// Parses the CSV output of `nvidia-smi --query-gpu`, without header nor units.
+fn parse_query(output: &str) -> Result<Vec<GpuState>> {
+    output
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let fields = l.split(',').map(str::trim).collect::<Vec<_>>();
+            let [index, name, utilization, memory_used, memory_total, temperature] = fields[..]
+            else {
+                return Err(anyhow!("Unexpected nvidia-smi output: {}", l));
+            };
+            Ok(GpuState {
+                index: index.parse().with_context(|| {
+                    format!("Could not convert GPU index to integer: {}", index)
+                })?,
+                name: name.to_string(),
+                utilization: parse_value(utilization)?,
+                memory_used: parse_value(memory_used)?,
+                memory_total: parse_value(memory_total)?,
+                temperature: parse_value(temperature)?,
+            })
+        })
+        .collect()
+}
+
+impl Checker {
+    fn query(&self) -> Result<Vec<GpuState>> {
+        let output = match Command::new("nvidia-smi")
+            .arg(format!("--query-gpu={}", QUERY))
+            .arg("--format=csv,noheader,nounits")
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(anyhow!(
+                    "nvidia-smi was not found, is there an NVIDIA GPU with its driver installed?"
+                ))
+            }
+            Err(e) => return Err(e).context("Could not run nvidia-smi"),
+        };
+        if !output.status.success() {
+            // Also fails this way when the driver finds no GPU.
+            return Err(anyhow!(
+                "nvidia-smi failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stdout).trim()
+            ));
+        }
+        let all = parse_query(&String::from_utf8_lossy(&output.stdout))?;
+        if all.is_empty() {
+            return Err(anyhow!("nvidia-smi did not list any GPU"));
+        }
+        let missing = self
+            .gpus
+            .iter()
+            .filter(|i| !all.iter().any(|g| g.index == **i))
+            .map(u32::to_string)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "No GPU with index {} (available: {})",
+                missing.join(", "),
+                all.iter()
+                    .map(|g| g.index.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        Ok(all
+            .into_iter()
+            .filter(|g| self.gpus.is_empty() || self.gpus.contains(&g.index))
+            .collect())
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Vec<GpuState>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "gpu");
+        let gpus = self.query()?;
+        for gpu in &gpus {
+            info!(
+                gpu = gpu.index,
+                name = gpu.name,
+                utilization = gpu.utilization,
+                memory_used = gpu.memory_used,
+                memory_total = gpu.memory_total,
+                temperature = gpu.temperature
+            );
+        }
+        Ok(gpus)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .iter()
+            .map(|gpu| {
+                let mut summary = format!("GPU {}", gpu.index);
+                if let Some(utilization) = gpu.utilization {
+                    summary.push_str(&format!(": {:.0}% used", utilization));
+                }
+                if let Some(memory_usage) = gpu.memory_usage() {
+                    summary.push_str(&format!(", {} of its memory", percent(memory_usage)));
+                }
+                if let Some(temperature) = gpu.temperature {
+                    summary.push_str(&format!(", {} °C", decimal(temperature)));
+                }
+                summary
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            gpus: configuration.gpus,
+        }
+    }
+
+    /// Fails on hosts without NVIDIA GPUs, or without the selected ones.
+    fn probe(&self) -> Result<()> {
+        self.query().map(|_| ())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for gpu in check_result {
+            let index = gpu.index.to_string();
+            let labels = [("gpu", index.as_str()), ("name", gpu.name.as_str())];
+            let values = [
+                ("monitrust_gpu_utilization_percent", gpu.utilization),
+                (
+                    "monitrust_gpu_memory_used_bytes",
+                    gpu.memory_used.map(|m| m * 1024.0 * 1024.0),
+                ),
+                (
+                    "monitrust_gpu_memory_total_bytes",
+                    gpu.memory_total.map(|m| m * 1024.0 * 1024.0),
+                ),
+                ("monitrust_gpu_temperature_celsius", gpu.temperature),
+            ];
+            for (name, value) in values {
+                if let Some(value) = value {
+                    samples.push(Sample::new(name, &labels, value));
+                }
+            }
+        }
+        samples
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// The threshold is a utilization percentage.
+    Utilization,
+    /// The threshold is a fraction of the memory used, between 0 and 1.
+    Memory,
+    /// The threshold is a temperature in degrees Celsius.
+    Temperature,
+}
+
+impl Metric {
+    fn value(&self, gpu: &GpuState) -> Option<f64> {
+        match self {
+            Metric::Utilization => gpu.utilization,
+            Metric::Memory => gpu.memory_usage(),
+            Metric::Temperature => gpu.temperature,
+        }
+    }
+
+    fn describe(&self, value: f64) -> String {
+        match self {
+            Metric::Utilization => format!("utilization is at {:.0}%", value),
+            Metric::Memory => format!("memory usage is at {}%", decimal(100.0 * value)),
+            Metric::Temperature => format!("temperature is at {:.0}°C", value),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    metric: Metric,
+    threshold: f64,
+    /// Above this value, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Alert {
+    fn triggered_for(&self, gpu: &GpuState) -> Option<ActiveAlert> {
+        let value = self.metric.value(gpu)?;
+        if value <= self.threshold {
+            return None;
+        }
+        let severity = match self.critical {
+            Some(critical) if value > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        let threshold = match self.metric {
+            Metric::Utilization => format!("{}%", self.threshold),
+            Metric::Memory => format!("{}%", decimal(100.0 * self.threshold)),
+            Metric::Temperature => format!("{}°C", self.threshold),
+        };
+        Some(ActiveAlert::new(
+            format!(
+                "🎮 GPU {} ({}) {} (threshold: {}).",
+                gpu.index,
+                gpu.name,
+                self.metric.describe(value),
+                threshold
+            ),
+            severity,
+        ))
+    }
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.metric.hash(state);
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        if self.metric == Metric::Memory {
+            watcher::validate_fraction("threshold", self.threshold)?;
+        }
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    /// Alert about the GPU that triggers it the most. Each GPU is reported on its own by
+    /// [`watcher::Alert::evaluate`].
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result
+            .iter()
+            .filter_map(|gpu| Some((gpu, self.metric.value(gpu)?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .and_then(|(gpu, _)| self.triggered_for(gpu))
+    }
+
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|gpu| {
+                let triggered = self.triggered_for(gpu);
+                Evaluation {
+                    subject: gpu.index.to_string(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
@@ -1,17 +1,18 @@
 use std::time::Duration;
 
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::watcher;
-use crate::watcher::ActiveAlert;
+use crate::watcher::{ActiveAlert, Severity};
 
 #[derive(Debug)]
 pub struct Checker {
     period: Duration,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
 pub struct Configuration {
     period_hours: u64,
 }
@@ -24,6 +25,10 @@ impl watcher::Checker for Checker {
         Ok(())
     }
 
+    fn summary(&self, _check_result: &Self::CheckResult) -> String {
+        "Alive".to_string()
+    }
+
     fn period(&self) -> Duration {
         self.period
     }
@@ -35,7 +40,7 @@ impl watcher::Checker for Checker {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Alert {}
 
 impl watcher::Alert for Alert {
@@ -45,8 +50,9 @@ impl watcher::Alert for Alert {
         &self,
         _: &<Self::Checker as watcher::Checker>::CheckResult,
     ) -> Option<ActiveAlert> {
-        Some(ActiveAlert {
-            message: "MonitRust is still running 💓".to_string(),
-        })
+        Some(ActiveAlert::new(
+            "MonitRust is still running 💓".to_string(),
+            Severity::Info,
+        ))
     }
 }
@@ -0,0 +1,225 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use reqwest::Method;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::duration::{deserialize_duration, Period};
+use crate::watcher;
+use crate::watcher::{ActiveAlert, Severity};
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    timeout: Duration,
+    client: Client,
+    method: Method,
+    url: String,
+    body_contains: Option<String>,
+    /// Configured method, if it is invalid and GET is used instead.
+    invalid_method: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+    /// Substring the response body is expected to contain.
+    #[serde(default)]
+    body_contains: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Response {
+        status: u16,
+        /// Whether the body contains the expected substring, if one is configured.
+        body_matches: Option<bool>,
+    },
+    TimedOut,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpCheck {
+    pub url: String,
+    pub elapsed: Duration,
+    pub outcome: Outcome,
+}
+
+impl Checker {
+    fn request(&self) -> Outcome {
+        let response = match self.client.request(self.method.clone(), &self.url).send() {
+            Ok(response) => response,
+            Err(e) if e.is_timeout() => return Outcome::TimedOut,
+            Err(e) => return Outcome::Failed(e.to_string()),
+        };
+        let status = response.status().as_u16();
+        let body_matches = match &self.body_contains {
+            None => None,
+            Some(expected) => match response.text() {
+                Ok(body) => Some(body.contains(expected.as_str())),
+                Err(e) if e.is_timeout() => return Outcome::TimedOut,
+                Err(e) => return Outcome::Failed(e.to_string()),
+            },
+        };
+        Outcome::Response {
+            status,
+            body_matches,
+        }
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = HttpCheck;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "http");
+        let start = Instant::now();
+        let outcome = self.request();
+        let check = HttpCheck {
+            url: self.url.clone(),
+            elapsed: start.elapsed(),
+            outcome,
+        };
+        info!(url = check.url, elapsed = ?check.elapsed, outcome = ?check.outcome);
+        Ok(check)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let elapsed = check_result.elapsed.as_millis();
+        match &check_result.outcome {
+            Outcome::Response {
+                status,
+                body_matches: Some(false),
+            } => format!(
+                "Status {} in {} ms, without the expected content",
+                status, elapsed
+            ),
+            Outcome::Response { status, .. } => format!("Status {} in {} ms", status, elapsed),
+            Outcome::TimedOut => format!("Timed out after {} ms", elapsed),
+            Outcome::Failed(e) => e.clone(),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        let (method, invalid_method) =
+            match Method::from_bytes(configuration.method.to_uppercase().as_bytes()) {
+                Ok(method) => (method, None),
+                Err(e) => {
+                    warn!(http_method = ?e, "Falling back to GET");
+                    (Method::GET, Some(configuration.method))
+                }
+            };
+        let client = Client::builder()
+            .timeout(configuration.timeout)
+            .build()
+            .unwrap_or_else(|e| {
+                warn!(http_client = ?e, "Falling back to a client without timeout");
+                Client::new()
+            });
+        Checker {
+            period: configuration.period.0,
+            timeout: configuration.timeout,
+            client,
+            method,
+            url: configuration.url,
+            body_contains: configuration.body_contains,
+            invalid_method,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(method) = &self.invalid_method {
+            return Err(anyhow!("Invalid HTTP method: {}", method));
+        }
+        reqwest::Url::parse(&self.url).with_context(|| format!("Invalid URL: {}", self.url))?;
+        if self.timeout >= self.period {
+            return Err(anyhow!(
+                "'timeout' ({:?}) is not shorter than the period ({:?})",
+                self.timeout,
+                self.period
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Status codes considered healthy, any 2xx code if omitted.
+    #[serde(default)]
+    expected_status: Option<Vec<u16>>,
+}
+
+impl Alert {
+    fn is_expected(&self, status: u16) -> bool {
+        match &self.expected_status {
+            Some(expected) => expected.contains(&status),
+            None => (200..300).contains(&status),
+        }
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self
+            .expected_status
+            .iter()
+            .flatten()
+            .find(|status| !(100..=599).contains(*status))
+        {
+            Some(status) => Err(anyhow!("Invalid HTTP status code: {}", status)),
+            None => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let url = &check_result.url;
+        let elapsed = check_result.elapsed.as_millis();
+        let message = match &check_result.outcome {
+            Outcome::Response { status, .. } if !self.is_expected(*status) => format!(
+                "🌐 {} answered with status {} in {} ms.",
+                url, status, elapsed
+            ),
+            Outcome::Response {
+                status,
+                body_matches: Some(false),
+            } => format!(
+                "🌐 {} answered with status {} in {} ms, without the expected content.",
+                url, status, elapsed
+            ),
+            Outcome::Response { .. } => return None,
+            Outcome::TimedOut => format!("🌐 {} timed out after {} ms.", url, elapsed),
+            Outcome::Failed(e) => format!("🌐 Could not reach {}: {}.", url, e),
+        };
+        Some(ActiveAlert::new(message, Severity::Critical))
+    }
+}
@@ -0,0 +1,224 @@
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::watcher;
+use crate::watcher::{decimal, threshold_bits, ActiveAlert, Severity};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Window {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+}
+
+impl Window {
+    fn minutes(&self) -> u32 {
+        match self {
+            Window::OneMinute => 1,
+            Window::FiveMinutes => 5,
+            Window::FifteenMinutes => 15,
+        }
+    }
+}
+
+fn all_windows() -> Vec<Window> {
+    vec![
+        Window::OneMinute,
+        Window::FiveMinutes,
+        Window::FifteenMinutes,
+    ]
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    windows: Vec<Window>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Load average windows to watch, all of them if omitted.
+    #[serde(default = "all_windows")]
+    windows: Vec<Window>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+    pub cpu_count: usize,
+    pub windows: Vec<Window>,
+}
+
+impl LoadAverage {
+    fn get(&self, window: Window) -> f64 {
+        match window {
+            Window::OneMinute => self.one,
+            Window::FiveMinutes => self.five,
+            Window::FifteenMinutes => self.fifteen,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_load_averages() -> Result<[f64; 3]> {
+    let loadavg_file = "/proc/loadavg";
+    let content = std::fs::read_to_string(loadavg_file)
+        .context(format!("Could not read loadavg file at '{}'", loadavg_file))?;
+    let values = content
+        .split_whitespace()
+        .take(3)
+        .map(|v| {
+            v.parse::<f64>()
+                .context(format!("Could not convert load average to float: {}", v))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    values
+        .try_into()
+        .map_err(|_| anyhow!("Could not parse loadavg file: {}", content))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_load_averages() -> Result<[f64; 3]> {
+    let mut values = [0.0; 3];
+    // SAFETY: `values` can hold the 3 samples requested.
+    let read = unsafe { libc::getloadavg(values.as_mut_ptr(), 3) };
+    if read == 3 {
+        Ok(values)
+    } else {
+        Err(anyhow!("Could not execute 'getloadavg'"))
+    }
+}
+
+#[cfg(windows)]
+fn read_load_averages() -> Result<[f64; 3]> {
+    Err(anyhow!("Load averages are not available on Windows"))
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = LoadAverage;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "load_avg");
+        let [one, five, fifteen] = read_load_averages()?;
+        let cpu_count = std::thread::available_parallelism()
+            .context("Could not retrieve the number of CPUs")?
+            .get();
+        info!(one, five, fifteen, cpu_count);
+        Ok(LoadAverage {
+            one,
+            five,
+            fifteen,
+            cpu_count,
+            windows: self.windows.clone(),
+        })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let cpus = match check_result.cpu_count {
+            1 => "1 CPU".to_string(),
+            count => format!("{} CPUs", count),
+        };
+        format!(
+            "{}, {}, {} over 1, 5 and 15 minutes, on {}",
+            decimal(check_result.one),
+            decimal(check_result.five),
+            decimal(check_result.fifteen),
+            cpus
+        )
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            windows: configuration.windows,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    threshold: f64,
+    /// Whether the load averages are divided by the number of CPUs before being compared.
+    #[serde(default)]
+    per_cpu: bool,
+    /// Above this value, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        threshold_bits(self.threshold).hash(state);
+        self.per_cpu.hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    fn with_threshold(&self, threshold: f64) -> Option<Self> {
+        Some(Alert {
+            threshold,
+            ..self.clone()
+        })
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let divisor = if self.per_cpu {
+            check_result.cpu_count as f64
+        } else {
+            1.0
+        };
+        check_result
+            .windows
+            .iter()
+            .map(|w| (w, check_result.get(*w) / divisor))
+            .find(|(_, load)| *load > self.threshold)
+            .map(|(window, load)| {
+                let severity = match self.critical {
+                    Some(critical) if load > critical => Severity::Critical,
+                    _ => Severity::default(),
+                };
+                ActiveAlert::new(
+                    format!(
+                        "⚖️ {}-minute load average{} is {} (threshold: {}).",
+                        window.minutes(),
+                        if self.per_cpu { " per CPU" } else { "" },
+                        decimal(load),
+                        decimal(self.threshold)
+                    ),
+                    severity,
+                )
+                .annotated([
+                    ("window_minutes", window.minutes().to_string()),
+                    ("load", decimal(load)),
+                    ("threshold", decimal(self.threshold)),
+                ])
+            })
+    }
+}
@@ -0,0 +1,330 @@
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::duration::{format_duration, Period};
+use crate::pattern::Pattern;
+use crate::watcher;
+use crate::watcher::presence::{Absence, ExpectPresence};
+use crate::watcher::{ActiveAlert, Severity};
+
+/// Longest sample line included in alerts.
+const SAMPLE_LENGTH: usize = 200;
+
+/// Where the previous check stopped reading.
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    /// Identity of the file, to notice when it is rotated.
+    file_id: Option<(u64, u64)>,
+    offset: u64,
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    path: PathBuf,
+    patterns: Vec<Pattern>,
+    position: Mutex<Option<Position>>,
+    absence: Absence,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    path: PathBuf,
+    /// Patterns matched against whole lines, such as `{ "Glob": "*ERROR*" }`. A line matches if
+    /// any of them does.
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogMatches {
+    pub path: PathBuf,
+    pub patterns: Vec<Pattern>,
+    /// Lines that matched since the previous check.
+    pub count: usize,
+    /// Last matching line, if any.
+    pub sample: Option<String>,
+    /// How long no line matched, unset if one did since the previous check.
+    pub absent_for: Option<Duration>,
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+impl Checker {
+    /// Opens the log file, along with the position of its end.
+    fn open(&self) -> Result<(File, Position)> {
+        let file = File::open(&self.path)
+            .with_context(|| format!("Could not open log file: {}", self.path.display()))?;
+        let metadata = file
+            .metadata()
+            .with_context(|| format!("Could not stat log file: {}", self.path.display()))?;
+        let end = Position {
+            file_id: file_id(&metadata),
+            offset: metadata.len(),
+        };
+        Ok((file, end))
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = LogMatches;
+    type Configuration = Configuration;
+
+    /// Scans the lines appended since the previous check. The first check only finds where the
+    /// file ends, so that existing lines are not reported. A file that was replaced or truncated
+    /// is scanned from its start.
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "log_scan");
+        let mut position = self
+            .position
+            .lock()
+            .map_err(|_| anyhow!("Log position lock is poisoned"))?;
+        let (file, end) = self.open()?;
+        let offset = match *position {
+            None => end.offset,
+            Some(previous) if previous.file_id != end.file_id || previous.offset > end.offset => {
+                warn!(log_rotated = %self.path.display());
+                0
+            }
+            Some(previous) => previous.offset,
+        };
+        let mut reader = BufReader::new(file);
+        reader
+            .seek(SeekFrom::Start(offset))
+            .with_context(|| format!("Could not seek in log file: {}", self.path.display()))?;
+        let read_at = Instant::now();
+        let mut read = offset;
+        let mut count = 0;
+        let mut sample = None;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let length = reader
+                .read_until(b'\n', &mut line)
+                .with_context(|| format!("Could not read log file: {}", self.path.display()))?;
+            // A line being written is scanned once it is complete.
+            if length == 0 || line.last() != Some(&b'\n') {
+                break;
+            }
+            read += length as u64;
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            if self.patterns.iter().any(|p| p.matches(text)) {
+                count += 1;
+                sample = Some(text.chars().take(SAMPLE_LENGTH).collect());
+            }
+        }
+        *position = Some(Position {
+            file_id: end.file_id,
+            offset: read,
+        });
+        info!(log_file = %self.path.display(), matching_lines = count);
+        Ok(LogMatches {
+            path: self.path.clone(),
+            patterns: self.patterns.clone(),
+            count,
+            sample,
+            absent_for: self.absence.observe(count > 0, read_at),
+        })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        format!(
+            "{} matches in {}",
+            check_result.count,
+            check_result.path.display()
+        )
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            path: configuration.path,
+            patterns: configuration.patterns,
+            position: Mutex::new(None),
+            absence: Absence::default(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.patterns.is_empty() {
+            return Err(anyhow!("No pattern to match lines against"));
+        }
+        Ok(())
+    }
+
+    fn probe(&self) -> Result<()> {
+        self.open().map(|_| ())
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct RawAlert {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    critical: Option<usize>,
+    #[serde(
+        rename = "ExpectPresence",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    expect_presence: Option<ExpectPresence>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "RawAlert", into = "RawAlert")]
+pub enum Alert {
+    /// Fires when more lines than `threshold` matched since the previous check, 0 by default so
+    /// that any matching line does. Above `critical` matching lines, the alert is critical
+    /// rather than a warning.
+    Matches {
+        threshold: usize,
+        critical: Option<usize>,
+    },
+    /// Written `{ "ExpectPresence": { "for": "1h" } }`, fires when no line matched for that
+    /// long, e.g. because the application stopped logging.
+    ExpectPresence(ExpectPresence),
+}
+
+impl TryFrom<RawAlert> for Alert {
+    type Error = String;
+
+    fn try_from(raw: RawAlert) -> Result<Self, Self::Error> {
+        match raw.expect_presence {
+            Some(_) if raw.threshold.is_some() || raw.critical.is_some() => {
+                Err("'ExpectPresence' can not be combined with 'threshold' or 'critical'".into())
+            }
+            Some(expect_presence) => Ok(Alert::ExpectPresence(expect_presence)),
+            None => Ok(Alert::Matches {
+                threshold: raw.threshold.unwrap_or_default(),
+                critical: raw.critical,
+            }),
+        }
+    }
+}
+
+impl From<Alert> for RawAlert {
+    fn from(alert: Alert) -> Self {
+        match alert {
+            Alert::Matches {
+                threshold,
+                critical,
+            } => RawAlert {
+                threshold: Some(threshold),
+                critical,
+                expect_presence: None,
+            },
+            Alert::ExpectPresence(expect_presence) => RawAlert {
+                threshold: None,
+                critical: None,
+                expect_presence: Some(expect_presence),
+            },
+        }
+    }
+}
+
+/// Hashes the thresholds alone, as before alerts could expect lines, so that the fingerprints of
+/// existing alerts do not change.
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Alert::Matches {
+                threshold,
+                critical,
+            } => {
+                threshold.hash(state);
+                critical.hash(state);
+            }
+            Alert::ExpectPresence(expect_presence) => expect_presence.hash(state),
+        }
+    }
+}
+
+fn describe_patterns(patterns: &[Pattern]) -> String {
+    patterns
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Alert::Matches {
+                threshold,
+                critical,
+            } => watcher::validate_critical(*threshold, *critical),
+            Alert::ExpectPresence(_) => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let (threshold, critical) = match self {
+            Alert::Matches {
+                threshold,
+                critical,
+            } => (*threshold, *critical),
+            Alert::ExpectPresence(expect_presence) => {
+                let absent_for = expect_presence.missing_for(check_result.absent_for)?;
+                return Some(ActiveAlert::new(
+                    format!(
+                        "📜 No line of {} matched '{}' for {} (expected within: {}).",
+                        check_result.path.display(),
+                        describe_patterns(&check_result.patterns),
+                        format_duration(absent_for),
+                        format_duration(expect_presence.for_duration())
+                    ),
+                    Severity::default(),
+                ));
+            }
+        };
+        if check_result.count <= threshold {
+            return None;
+        }
+        let severity = match critical {
+            Some(critical) if check_result.count > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        let mut message = format!(
+            "📜 {} lines of {} matched '{}' since the last check (threshold: {}).",
+            check_result.count,
+            check_result.path.display(),
+            describe_patterns(&check_result.patterns),
+            threshold
+        );
+        if let Some(sample) = &check_result.sample {
+            message.push_str(&format!("\nLast one: {}", sample));
+        }
+        Some(ActiveAlert::new(message, severity))
+    }
+}
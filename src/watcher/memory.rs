@@ -1,81 +1,448 @@
-use std::io::{BufRead, BufReader};
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
 use std::time::Duration;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
+use tracing::info;
 
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::size::format_size;
+use crate::templating::render_template;
 use crate::watcher;
-use crate::watcher::ActiveAlert;
+use crate::watcher::{
+    decimal, percent, threshold_bits, ActiveAlert, Comparison, Severity, SeverityTier,
+};
 
 // TODO: why does the compiler complain about that?
 #[derive(Debug)]
 pub struct Checker {
     period: Duration,
+    cgroup_aware: Option<bool>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
 pub struct Configuration {
-    period_minutes: u64,
+    #[serde(flatten)]
+    period: Period,
+    /// Whether to measure the memory of the cgroup Monitrust runs in, such as the one of its
+    /// container, against its limit rather than the memory of the host. By default, the cgroup
+    /// is measured when it has a limit below the memory of the host, and `true` fails otherwise.
+    /// Only on Linux.
+    #[serde(default)]
+    cgroup_aware: Option<bool>,
 }
 
-impl watcher::Checker for Checker {
-    type CheckResult = f64;
-    type Configuration = Configuration;
+/// What the memory figures are about.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryView {
+    Host,
+    /// The cgroup of Monitrust, its limit as the total memory.
+    Cgroup,
+}
 
-    fn check(&self) -> Result<Self::CheckResult> {
-        info!(checking = "memory");
-        let meminfo_file = "/proc/meminfo";
-        let file = std::fs::File::open(meminfo_file)
-            .context(format!("Could not open meminfo file at '{}'", meminfo_file))?;
-        let buf_read = BufReader::new(file);
-        const MEM_TOTAL_STR: &str = "MemTotal:";
-        const MEM_AVAIL_STR: &str = "MemAvailable:";
-        let mut mem_total = None;
-        let mut mem_avail = None;
-        for l in buf_read.lines() {
-            let l = l.context("Could not read line from meminfo file.")?;
-            let split = l.split_whitespace().collect::<Vec<_>>();
-            match &split[..] {
-                [name, value, _unit] => {
-                    let target = if *name == MEM_TOTAL_STR {
-                        &mut mem_total
-                    } else if *name == MEM_AVAIL_STR {
-                        &mut mem_avail
-                    } else {
-                        continue;
-                    };
-                    *target = Some(
-                        value
-                            .parse::<u64>()
-                            .context(format!("Could not convert memory to integer: {}", value))?,
-                    );
-                }
-                // Some values are just numbers e.g. page numbers
-                [_, _alue] => {}
-                _ => warn!("Could not parse line in meminfo file: {}", l),
-            }
+/// Memory figures of `/proc/meminfo`, in bytes, or their closest equivalents on Windows and macOS.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryInfo {
+    pub total: u64,
+    pub free: u64,
+    /// Estimate of the memory available to new processes without swapping, by the kernel.
+    pub available: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub view: MemoryView,
+}
+
+impl MemoryInfo {
+    /// Fraction of the memory available, between 0 and 1.
+    pub fn available_ratio(&self) -> f64 {
+        self.available as f64 / self.total as f64
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+const MEMINFO_FILE: &str = "/proc/meminfo";
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_memory() -> Result<MemoryInfo> {
+    read_memory_from(std::path::Path::new(MEMINFO_FILE))
+}
+
+/// Reads the memory figures of `meminfo`, normally `/proc/meminfo`, or the ones of `sysinfo(2)`
+/// when it can not be read, such as where `/proc` is restricted. `sysinfo` tells neither the
+/// memory available nor the caches, so the available memory is only the free memory and the
+/// buffers then, lower than the one of the kernel.
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn read_memory_from(meminfo: &std::path::Path) -> Result<MemoryInfo> {
+    use tracing::warn;
+
+    let error = match read_meminfo(meminfo) {
+        Ok(memory) => return Ok(memory),
+        Err(e) => e,
+    };
+    match read_sysinfo() {
+        Ok(memory) => {
+            warn!(meminfo_error = ?error, "Degraded to the memory figures of sysinfo");
+            Ok(memory)
         }
-        match (mem_total, mem_avail) {
-            (Some(t), Some(a)) => {
-                let free_memory = (a as f64) / (t as f64);
-                info!(free_memory);
-                Ok(free_memory)
+        Err(e) => Err(error.context(format!("{:#}", e))),
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_meminfo(meminfo_file: &std::path::Path) -> Result<MemoryInfo> {
+    use anyhow::Context;
+    use std::io::{BufRead, BufReader};
+    use tracing::warn;
+
+    let file = std::fs::File::open(meminfo_file).context(format!(
+        "Could not open meminfo file at '{}'",
+        meminfo_file.display()
+    ))?;
+    let buf_read = BufReader::new(file);
+    let mut values = BTreeMap::new();
+    for l in buf_read.lines() {
+        let l = l.context("Could not read line from meminfo file.")?;
+        let split = l.split_whitespace().collect::<Vec<_>>();
+        match &split[..] {
+            [name, value, _unit] => {
+                let value = value
+                    .parse::<u64>()
+                    .context(format!("Could not convert memory to integer: {}", value))?;
+                // Values are in kibibytes, despite the `kB` unit.
+                values.insert(name.trim_end_matches(':').to_string(), 1024 * value);
             }
-            (Some(_), None) => Err(anyhow!(
-                "Could not retrieve available memory from meminfo file (key: {})",
-                MEM_AVAIL_STR
-            )),
-            (None, Some(_)) => Err(anyhow!(
-                "Could not retrieve total memory from meminfo file (key: {})",
-                MEM_TOTAL_STR
-            )),
-            (None, None) => Err(anyhow!(
-                "Could retrieve neither total nor available memory from meminfo file (keys: {} and {})",
-                MEM_TOTAL_STR, MEM_AVAIL_STR
+            // Some values are just numbers e.g. page numbers
+            [_, _alue] => {}
+            _ => warn!("Could not parse line in meminfo file: {}", l),
+        }
+    }
+    let value = |key: &str| {
+        values
+            .get(key)
+            .copied()
+            .ok_or_else(|| anyhow!("Could not retrieve {} from meminfo file", key))
+    };
+    Ok(MemoryInfo {
+        total: value("MemTotal")?,
+        free: value("MemFree")?,
+        available: value("MemAvailable")?,
+        buffers: value("Buffers")?,
+        cached: value("Cached")?,
+        view: MemoryView::Host,
+    })
+}
+
+/// Memory figures of the `sysinfo` system call, which counts them in units of `mem_unit` bytes.
+#[cfg(target_os = "linux")]
+fn read_sysinfo() -> Result<MemoryInfo> {
+    // SAFETY: `sysinfo` only holds integers, for which zero is valid.
+    let mut info: libc::sysinfo = unsafe { std::mem::zeroed() };
+    // SAFETY: `info` is valid for writes.
+    if unsafe { libc::sysinfo(&mut info) } != 0 {
+        return Err(anyhow!(
+            "Could not execute 'sysinfo': {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // `c_ulong` is only 32 bits wide on 32-bit targets.
+    #[allow(clippy::useless_conversion)]
+    let bytes = |count: libc::c_ulong| u64::from(count) * u64::from(info.mem_unit.max(1));
+    Ok(MemoryInfo {
+        total: bytes(info.totalram),
+        free: bytes(info.freeram),
+        available: bytes(info.freeram) + bytes(info.bufferram),
+        buffers: bytes(info.bufferram),
+        cached: 0,
+        view: MemoryView::Host,
+    })
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn read_sysinfo() -> Result<MemoryInfo> {
+    Err(anyhow!("'sysinfo' is only available on Linux"))
+}
+
+/// Reads a file of a cgroup holding a single value, `None` if it does not exist. `max` stands for
+/// no limit, as `None` too.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_cgroup_value(path: &std::path::Path) -> Result<Option<u64>> {
+    use anyhow::Context;
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(format!("Could not read '{}'", path.display())),
+    };
+    match content.trim() {
+        "max" => Ok(None),
+        value => value.parse::<u64>().map(Some).context(format!(
+            "Could not convert '{}' of '{}' to integer",
+            value,
+            path.display()
+        )),
+    }
+}
+
+/// Reads `key value` lines of the `memory.stat` file of a cgroup.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_cgroup_stat(path: &std::path::Path) -> Result<BTreeMap<String, u64>> {
+    use anyhow::Context;
+
+    let content =
+        std::fs::read_to_string(path).context(format!("Could not read '{}'", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|l| {
+            let (key, value) = l.split_once(' ')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect())
+}
+
+/// Memory of a cgroup from the files of its directory, `None` without a limit. The memory whose
+/// inactive pages the kernel can reclaim counts as available, like `MemAvailable` does.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_cgroup(
+    dir: &std::path::Path,
+    [limit_file, usage_file]: [&str; 2],
+    [cache_key, inactive_key]: [&str; 2],
+) -> Result<Option<MemoryInfo>> {
+    let Some(limit) = read_cgroup_value(&dir.join(limit_file))? else {
+        return Ok(None);
+    };
+    let usage = read_cgroup_value(&dir.join(usage_file))?
+        .ok_or_else(|| anyhow!("Could not find '{}' in '{}'", usage_file, dir.display()))?;
+    let stat = read_cgroup_stat(&dir.join("memory.stat"))?;
+    let stat_value = |key: &str| stat.get(key).copied().unwrap_or(0);
+    let free = limit.saturating_sub(usage);
+    Ok(Some(MemoryInfo {
+        total: limit,
+        free,
+        available: (free + stat_value(inactive_key)).min(limit),
+        buffers: 0,
+        cached: stat_value(cache_key),
+        view: MemoryView::Cgroup,
+    }))
+}
+
+/// Memory of a cgroup v2, from `memory.max`, `memory.current` and `memory.stat`.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_cgroup_v2(dir: &std::path::Path) -> Result<Option<MemoryInfo>> {
+    read_cgroup(
+        dir,
+        ["memory.max", "memory.current"],
+        ["file", "inactive_file"],
+    )
+}
+
+/// Memory of a cgroup v1, from `memory.limit_in_bytes`, `memory.usage_in_bytes` and
+/// `memory.stat`. Without a limit, the limit is a huge number rather than `max`.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_cgroup_v1(dir: &std::path::Path) -> Result<Option<MemoryInfo>> {
+    read_cgroup(
+        dir,
+        ["memory.limit_in_bytes", "memory.usage_in_bytes"],
+        ["total_cache", "total_inactive_file"],
+    )
+}
+
+/// Memory of the cgroup Monitrust runs in, as listed by `/proc/self/cgroup`, `None` if it has no
+/// limit. Within a container, the cgroup directory is usually the root of the hierarchy, where the
+/// listed path does not exist.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn read_cgroup_memory() -> Result<Option<MemoryInfo>> {
+    use std::path::Path;
+
+    let Ok(cgroups) = std::fs::read_to_string("/proc/self/cgroup") else {
+        return Ok(None);
+    };
+    for l in cgroups.lines() {
+        let mut fields = l.splitn(3, ':');
+        let (Some(_), Some(controllers), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        // The v2 hierarchy lists no controllers.
+        let is_v2 = controllers.is_empty();
+        if !is_v2 && !controllers.split(',').any(|c| c == "memory") {
+            continue;
+        }
+        let root = Path::new(match is_v2 {
+            true => "/sys/fs/cgroup",
+            false => "/sys/fs/cgroup/memory",
+        });
+        let own = root.join(path.trim_start_matches('/'));
+        let dir = if own.is_dir() { own.as_path() } else { root };
+        return match is_v2 {
+            true => read_cgroup_v2(dir),
+            false => read_cgroup_v1(dir),
+        };
+    }
+    Ok(None)
+}
+
+/// `GlobalMemoryStatusEx` only tells the memory available, so it is also counted as free, and
+/// there are neither buffers nor caches.
+#[cfg(windows)]
+fn read_memory() -> Result<MemoryInfo> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    // SAFETY: `MEMORYSTATUSEX` only holds integers, for which zero is valid.
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    // SAFETY: `status` is valid for writes and its length is set, as required.
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return Err(anyhow!(
+            "Could not execute 'GlobalMemoryStatusEx': {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(MemoryInfo {
+        total: status.ullTotalPhys,
+        free: status.ullAvailPhys,
+        available: status.ullAvailPhys,
+        buffers: 0,
+        cached: 0,
+        view: MemoryView::Host,
+    })
+}
+
+/// Mach counts pages as free, wired, active, inactive, compressed or speculative, its free pages
+/// being only the ones not even used for caches. They are mapped so that the used memory is the
+/// one of Activity Monitor:
+/// - `free` is the free pages;
+/// - `available` is all the memory but the wired, compressed and anonymous (app) pages that are
+///   not purgeable;
+/// - `cached` is the file-backed and purgeable pages, and there are no `buffers`.
+#[cfg(target_os = "macos")]
+fn read_memory() -> Result<MemoryInfo> {
+    let mut total: u64 = 0;
+    let mut size = std::mem::size_of::<u64>();
+    // SAFETY: the name is NUL-terminated and `hw.memsize` is a 64-bit integer.
+    let result = unsafe {
+        libc::sysctlbyname(
+            c"hw.memsize".as_ptr(),
+            &mut total as *mut u64 as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if result != 0 {
+        return Err(anyhow!(
+            "Could not execute 'sysctlbyname' for hw.memsize: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // SAFETY: `vm_statistics64` only holds integers, for which zero is valid.
+    let mut stats: libc::vm_statistics64 = unsafe { std::mem::zeroed() };
+    let mut count = libc::HOST_VM_INFO64_COUNT;
+    // SAFETY: `stats` is valid for writes of `count` integers.
+    #[allow(deprecated)]
+    let result = unsafe {
+        libc::host_statistics64(
+            libc::mach_host_self(),
+            libc::HOST_VM_INFO64,
+            &mut stats as *mut libc::vm_statistics64 as libc::host_info64_t,
+            &mut count,
+        )
+    };
+    if result != libc::KERN_SUCCESS {
+        return Err(anyhow!(
+            "Could not execute 'host_statistics64': error {}",
+            result
+        ));
+    }
+    // SAFETY: `sysconf` has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    let pages = |count: libc::natural_t| count as u64 * page_size;
+    let used = pages(stats.internal_page_count).saturating_sub(pages(stats.purgeable_count))
+        + pages(stats.wire_count)
+        + pages(stats.compressor_page_count);
+    Ok(MemoryInfo {
+        total,
+        free: pages(stats.free_count),
+        available: total.saturating_sub(used),
+        buffers: 0,
+        cached: pages(stats.external_page_count) + pages(stats.purgeable_count),
+        view: MemoryView::Host,
+    })
+}
+
+impl Checker {
+    /// The memory of the cgroup instead of the host one, when measuring it.
+    #[cfg(not(any(windows, target_os = "macos")))]
+    fn view(&self, host: MemoryInfo) -> Result<MemoryInfo> {
+        if self.cgroup_aware == Some(false) {
+            return Ok(host);
+        }
+        match read_cgroup_memory()? {
+            Some(cgroup) if cgroup.total < host.total => Ok(cgroup),
+            _ if self.cgroup_aware == Some(true) => Err(anyhow!(
+                "Could not find a memory limit for the cgroup of Monitrust"
             )),
+            _ => Ok(host),
+        }
+    }
+
+    #[cfg(any(windows, target_os = "macos"))]
+    fn view(&self, host: MemoryInfo) -> Result<MemoryInfo> {
+        match self.cgroup_aware {
+            Some(true) => Err(anyhow!("'cgroup_aware' is only supported on Linux")),
+            _ => Ok(host),
         }
-        //let sysinfo = sysinfo().context("Could not execute 'sysinfo'")?;
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = MemoryInfo;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "memory");
+        let memory = self.view(read_memory()?)?;
+        info!(
+            view = ?memory.view,
+            total = memory.total,
+            free = memory.free,
+            available = memory.available,
+            buffers = memory.buffers,
+            cached = memory.cached
+        );
+        Ok(memory)
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        vec![
+            Sample::new(
+                "monitrust_memory_available_ratio",
+                &[],
+                check_result.available_ratio(),
+            ),
+            Sample::new(
+                "monitrust_memory_available_bytes",
+                &[],
+                check_result.available as f64,
+            ),
+            Sample::new(
+                "monitrust_memory_total_bytes",
+                &[],
+                check_result.total as f64,
+            ),
+        ]
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        format!(
+            "{} available ({} of {})",
+            percent(check_result.available_ratio()),
+            format_size(check_result.available),
+            format_size(check_result.total)
+        )
     }
 
     fn period(&self) -> Duration {
@@ -84,34 +451,703 @@ impl watcher::Checker for Checker {
 
     fn new(configuration: Self::Configuration) -> Self {
         Checker {
-            period: Duration::from_secs(60 * configuration.period_minutes),
+            period: configuration.period.0,
+            cgroup_aware: configuration.cgroup_aware,
+        }
+    }
+
+    fn probe(&self) -> Result<()> {
+        match self.cgroup_aware {
+            Some(true) => self.view(read_memory()?).map(|_| ()),
+            _ => Ok(()),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryMetric {
+    /// Memory that is not available, alerting above the threshold.
+    Used,
+    /// `MemAvailable`, the memory the kernel estimates new processes can get without swapping,
+    /// alerting below the threshold.
+    Available,
+    /// `MemFree`, the memory that is not used at all, even by caches. Usually low on a healthy
+    /// system, as the kernel uses free memory for caches.
+    Free,
+    /// Free memory along with buffers and caches, as counted before `MemAvailable` existed.
+    /// Overestimates what is available, as some caches can not be dropped.
+    RealAvailable,
+}
+
+impl MemoryMetric {
+    fn bytes(&self, memory: &MemoryInfo) -> u64 {
+        match self {
+            MemoryMetric::Used => memory.total.saturating_sub(memory.available),
+            MemoryMetric::Available => memory.available,
+            MemoryMetric::Free => memory.free,
+            MemoryMetric::RealAvailable => memory.free + memory.buffers + memory.cached,
+        }
+    }
+
+    /// Whether the alert triggers above the threshold, rather than below it.
+    fn alerts_above(&self) -> bool {
+        matches!(self, MemoryMetric::Used)
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            MemoryMetric::Used => "Used memory",
+            MemoryMetric::Available => "Available memory",
+            MemoryMetric::Free => "Free memory",
+            MemoryMetric::RealAvailable => "Free memory with buffers and caches",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    /// Percent of the total memory.
+    Percent,
+    MB,
+    GB,
+    MiB,
+    GiB,
+}
+
+impl Unit {
+    /// Converts a number of bytes out of the total memory to this unit.
+    fn convert(&self, bytes: u64, total: u64) -> f64 {
+        let bytes = bytes as f64;
+        match self {
+            Unit::Percent => 100.0 * bytes / total as f64,
+            Unit::MB => bytes / 1e6,
+            Unit::GB => bytes / 1e9,
+            Unit::MiB => bytes / (1u64 << 20) as f64,
+            Unit::GiB => bytes / (1u64 << 30) as f64,
+        }
+    }
+
+    fn format(&self, value: f64) -> String {
+        match self {
+            Unit::Percent => format!("{}%", decimal(value)),
+            Unit::MB => format!("{:.0} MB", value),
+            Unit::GB => format!("{} GB", decimal(value)),
+            Unit::MiB => format!("{:.0} MiB", value),
+            Unit::GiB => format!("{} GiB", decimal(value)),
+        }
+    }
+}
+
+/// A memory metric, compared to a value in the given unit.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    value: f64,
+    unit: Unit,
+    metric: MemoryMetric,
+    /// How the metric is compared to the value, `>` for `Used` and `<` for the other metrics by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operator: Option<Comparison>,
+}
+
+impl Threshold {
+    /// The metric in the unit of the threshold.
+    fn measure(&self, memory: &MemoryInfo) -> f64 {
+        self.unit.convert(self.metric.bytes(memory), memory.total)
+    }
+
+    fn comparison(&self) -> Comparison {
+        self.operator.unwrap_or(if self.metric.alerts_above() {
+            Comparison::Above
+        } else {
+            Comparison::Below
+        })
+    }
+
+    /// Whether `value` is beyond `limit`, on the side the threshold alerts on.
+    fn is_beyond(&self, value: f64, limit: f64) -> bool {
+        self.comparison().is_beyond(value, limit)
+    }
+
+    /// Whether `value` compared to `limit` triggers the alert.
+    fn is_crossed(&self, value: f64, limit: f64) -> bool {
+        watcher::compare(value, self.comparison(), limit)
+    }
+}
+
+impl Hash for Threshold {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        threshold_bits(self.value).hash(state);
+        self.unit.hash(state);
+        self.metric.hash(state);
+        // Thresholds without an operator keep the fingerprint they had before operators existed.
+        if let Some(operator) = self.operator {
+            operator.hash(state);
+        }
+    }
+}
+
+/// Alerts configured either with a [`Threshold`], or with the available fraction of the memory
+/// below `trigger_threshold`, which `clear_threshold`, `critical` and `min` are then fractions
+/// of as well.
+#[derive(Deserialize, JsonSchema)]
+struct RawAlert {
+    #[serde(default)]
+    threshold: Option<Threshold>,
+    #[serde(default, alias = "max")]
+    trigger_threshold: Option<f64>,
+    #[serde(default)]
+    min: Option<f64>,
+    #[serde(default)]
+    clear_threshold: Option<f64>,
+    #[serde(default)]
+    critical: Option<f64>,
+    #[serde(default)]
+    severities: Vec<SeverityTier>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawAlert")]
+#[schemars(with = "RawAlert")]
 pub struct Alert {
-    min: f64,
-    max: f64,
+    threshold: Threshold,
+    /// Values at or below this one never trigger the alert, in the unit of the threshold.
+    min: Option<f64>,
+    /// Once firing, the alert is only cleared when the value goes back past this threshold.
+    /// Defaults to the threshold.
+    clear_threshold: Option<f64>,
+    /// Past this value, the alert is critical rather than a warning.
+    critical: Option<f64>,
+    /// Severities the alert takes past each value, in the unit of the threshold, instead of
+    /// `critical`.
+    severities: Vec<SeverityTier>,
+    /// Template of the alert message, with `{free_pct}`, `{used_pct}`, `{value}` and
+    /// `{threshold}` placeholders.
+    message: Option<String>,
+    /// What the configured values were multiplied by, 100 for the fractions of a
+    /// `trigger_threshold`, so that scheduled thresholds are too.
+    #[serde(skip)]
+    scale: f64,
+}
+
+impl TryFrom<RawAlert> for Alert {
+    type Error = String;
+
+    fn try_from(raw: RawAlert) -> Result<Self, Self::Error> {
+        let (threshold, scale) = match (raw.threshold, raw.trigger_threshold) {
+            (Some(threshold), None) => (threshold, 1.0),
+            (None, Some(trigger_threshold)) => (
+                Threshold {
+                    value: 100.0 * trigger_threshold,
+                    unit: Unit::Percent,
+                    metric: MemoryMetric::Available,
+                    operator: None,
+                },
+                100.0,
+            ),
+            (Some(_), Some(_)) => {
+                return Err("only one of 'threshold' and 'trigger_threshold' can be set".into())
+            }
+            (None, None) => return Err("missing field 'threshold'".into()),
+        };
+        Ok(Alert {
+            threshold,
+            min: raw.min.map(|m| scale * m),
+            clear_threshold: raw.clear_threshold.map(|c| scale * c),
+            critical: raw.critical.map(|c| scale * c),
+            severities: raw
+                .severities
+                .into_iter()
+                .map(|tier| SeverityTier {
+                    at: scale * tier.at,
+                    ..tier
+                })
+                .collect(),
+            message: raw.message,
+            scale,
+        })
+    }
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.threshold.hash(state);
+        self.min.map(threshold_bits).hash(state);
+        self.clear_threshold.map(threshold_bits).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+        self.message.hash(state);
+        threshold_bits(self.scale).hash(state);
+        // Alerts without severities keep the fingerprint they had before severities existed.
+        if !self.severities.is_empty() {
+            self.severities.hash(state);
+        }
+    }
+}
+
+impl Alert {
+    /// Whether the value is past `limit`, but not at or below `min`.
+    fn is_past(&self, value: f64, limit: f64) -> bool {
+        self.threshold.is_crossed(value, limit) && self.min.is_none_or(|min| value > min)
+    }
 }
 
 impl watcher::Alert for Alert {
     type Checker = Checker;
 
+    fn validate(&self) -> Result<()> {
+        let threshold = self.threshold.value;
+        watcher::validate_comparison(self.threshold.comparison(), self.critical, &self.severities)?;
+        if let Some(clear_threshold) = self.clear_threshold {
+            if self.threshold.is_beyond(clear_threshold, threshold) {
+                return Err(anyhow!(
+                    "'clear_threshold' ({}) is past the threshold ({})",
+                    clear_threshold,
+                    threshold
+                ));
+            }
+        }
+        match self.critical {
+            Some(critical) if self.threshold.is_beyond(threshold, critical) => Err(anyhow!(
+                "'critical' ({}) is not past the threshold ({})",
+                critical,
+                threshold
+            )),
+            _ => watcher::validate_tiers(threshold, &self.severities, self.critical, |a, b| {
+                self.threshold.is_beyond(a, b)
+            }),
+        }
+    }
+
+    /// Replaces the value of the threshold, a fraction along with `trigger_threshold`.
+    fn with_threshold(&self, threshold: f64) -> Option<Self> {
+        Some(Alert {
+            threshold: Threshold {
+                value: self.scale * threshold,
+                ..self.threshold
+            },
+            ..self.clone()
+        })
+    }
+
     fn is_triggered(
         &self,
         check_result: &<Self::Checker as watcher::Checker>::CheckResult,
     ) -> Option<ActiveAlert> {
-        if self.min < *check_result && *check_result < self.max {
-            Some(ActiveAlert {
-                message: format!(
-                    "📝 Free memory is at {:.2}% (threshold: {:.2}%).",
-                    100.0 * *check_result,
-                    100.0 * self.max
+        let value = self.threshold.measure(check_result);
+        if !self.is_past(value, self.threshold.value) {
+            return None;
+        }
+        let severity = match self.critical {
+            Some(critical) if self.threshold.is_beyond(value, critical) => Severity::Critical,
+            _ => watcher::tier_severity(&self.severities, value, |a, b| {
+                self.threshold.is_beyond(a, b)
+            }),
+        };
+        let unit = self.threshold.unit;
+        let available = check_result.available_ratio();
+        let free_pct = decimal(100.0 * available);
+        let used_pct = decimal(100.0 * (1.0 - available));
+        let threshold = decimal(self.threshold.value);
+        let annotations = [
+            ("metric", format!("{:?}", self.threshold.metric)),
+            ("unit", format!("{:?}", unit)),
+            ("value", decimal(value)),
+            ("threshold", threshold.clone()),
+            ("free_pct", free_pct.clone()),
+            ("used_pct", used_pct.clone()),
+        ];
+        let message = match &self.message {
+            Some(template) => {
+                let fields = [
+                    ("free_pct", free_pct),
+                    ("used_pct", used_pct),
+                    ("value", unit.format(value)),
+                    ("threshold", threshold),
+                ]
+                .map(|(name, value)| (name.to_string(), value));
+                render_template(template, &BTreeMap::from(fields))
+            }
+            None => format!(
+                "📝 {} is at {} (threshold: {}).",
+                self.threshold.metric.describe(),
+                unit.format(value),
+                match self.threshold.operator {
+                    Some(operator) => format!("{} {}", operator, unit.format(self.threshold.value)),
+                    None => unit.format(self.threshold.value),
+                }
+            ),
+        };
+        Some(ActiveAlert::new(message, severity).annotated(annotations))
+    }
+
+    fn is_cleared(&self, check_result: &<Self::Checker as watcher::Checker>::CheckResult) -> bool {
+        let clear_threshold = self.clear_threshold.unwrap_or(self.threshold.value);
+        !self.is_past(self.threshold.measure(check_result), clear_threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_store::memory::Memory;
+    use crate::stable_hash;
+    use crate::watcher::replay::{ReplayChecker, Snapshot};
+    use crate::watcher::{Alert as _, MultiWatcher, SerializedMultiWatcher};
+    use serde_json::json;
+    use std::time::SystemTime;
+
+    fn alert(configuration: serde_json::Value) -> Alert {
+        serde_json::from_value(configuration).expect("Invalid alert")
+    }
+
+    /// Memory of a host with `used_pct` of its 1000 bytes used.
+    fn memory(used_pct: u64) -> MemoryInfo {
+        MemoryInfo {
+            total: 1000,
+            free: 1000 - 10 * used_pct,
+            available: 1000 - 10 * used_pct,
+            buffers: 0,
+            cached: 0,
+            view: MemoryView::Host,
+        }
+    }
+
+    /// Alerts reported by a watcher with a single `alert`, on checks that measured each usage in
+    /// turn, a minute apart.
+    fn reported(alert: serde_json::Value, used_pcts: &[u64]) -> Vec<ActiveAlert> {
+        let mut watcher = MultiWatcher::new(
+            serde_json::from_value::<SerializedMultiWatcher<Alert>>(json!({
+                "configuration": { "period": "1m" },
+                "alerts": [alert]
+            }))
+            .unwrap(),
+        );
+        let start = SystemTime::now();
+        let replay = ReplayChecker::<Checker>::new(
+            used_pcts
+                .iter()
+                .enumerate()
+                .map(|(index, used_pct)| Snapshot {
+                    sampled_at: start + Duration::from_secs(60 * index as u64),
+                    result: memory(*used_pct),
+                })
+                .collect(),
+        );
+        let reporter = RecordingReporter::new();
+        watcher
+            .replay(&replay, &reporter, &Memory::new(Default::default()))
+            .unwrap();
+        reporter.alerts()
+    }
+
+    #[test]
+    fn reads_the_memory_of_this_machine() {
+        let checker = <Checker as watcher::Checker>::new(
+            serde_json::from_value(json!({ "period": "1m" })).unwrap(),
+        );
+
+        let memory = watcher::Checker::check(&checker).unwrap();
+
+        assert!(memory.total > 0, "{:?}", memory);
+        assert!(memory.available <= memory.total, "{:?}", memory);
+        assert!(memory.free <= memory.total, "{:?}", memory);
+    }
+
+    /// A cgroup directory holding the `files`, with their content.
+    #[cfg(not(any(windows, target_os = "macos")))]
+    fn cgroup(test: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("monitrust-cgroup-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    fn figures(memory: MemoryInfo) -> (MemoryView, u64, u64, u64, u64) {
+        (
+            memory.view,
+            memory.total,
+            memory.free,
+            memory.available,
+            memory.cached,
+        )
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn reads_the_memory_of_a_cgroup_v2() {
+        let dir = cgroup(
+            "v2",
+            &[
+                ("memory.max", "1073741824\n"),
+                ("memory.current", "805306368\n"),
+                (
+                    "memory.stat",
+                    "anon 536870912\nfile 268435456\ninactive_file 134217728\n",
                 ),
-            })
-        } else {
-            None
+            ],
+        );
+        let unlimited = cgroup("v2-unlimited", &[("memory.max", "max\n")]);
+
+        let memory = read_cgroup_v2(&dir).unwrap().unwrap();
+        let without_limit = read_cgroup_v2(&unlimited).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&unlimited).unwrap();
+
+        assert_eq!(
+            figures(memory),
+            (
+                MemoryView::Cgroup,
+                1073741824,
+                268435456,
+                268435456 + 134217728,
+                268435456
+            )
+        );
+        assert!(without_limit.is_none());
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn reads_the_memory_of_a_cgroup_v1() {
+        let dir = cgroup(
+            "v1",
+            &[
+                ("memory.limit_in_bytes", "536870912\n"),
+                ("memory.usage_in_bytes", "268435456\n"),
+                (
+                    "memory.stat",
+                    "cache 7\ntotal_cache 100\ntotal_inactive_file 50\n",
+                ),
+            ],
+        );
+
+        let memory = read_cgroup_v1(&dir).unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            figures(memory),
+            (MemoryView::Cgroup, 536870912, 268435456, 268435506, 100)
+        );
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[test]
+    fn reads_the_figures_of_meminfo_when_it_can() {
+        let dir = cgroup(
+            "meminfo",
+            &[(
+                "meminfo",
+                "MemTotal: 1000 kB\nMemFree: 100 kB\nMemAvailable: 400 kB\nBuffers: 50 kB\n\
+                 Cached: 200 kB\nHugePages_Total: 0\n",
+            )],
+        );
+
+        let memory = read_memory_from(&dir.join("meminfo")).unwrap();
+
+        assert_eq!(
+            figures(memory),
+            (MemoryView::Host, 1_024_000, 102_400, 409_600, 204_800)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn an_unreadable_meminfo_degrades_to_sysinfo() {
+        let memory = read_memory_from(std::path::Path::new("/nonexistent/meminfo")).unwrap();
+
+        // sysinfo does not tell the caches.
+        assert_eq!(memory.cached, 0, "{:?}", memory);
+        assert_eq!(memory.view, MemoryView::Host);
+        assert!(memory.total > 0, "{:?}", memory);
+        assert_eq!(memory.available, memory.free + memory.buffers);
+        assert!(memory.available <= memory.total, "{:?}", memory);
+    }
+
+    #[test]
+    fn values_are_written_at_the_default_precision() {
+        assert_eq!(Unit::Percent.format(90.38271604938272), "90.4%");
+        assert_eq!(Unit::GiB.format(1.96), "2.0 GiB");
+        assert_eq!(Unit::MB.format(511.6), "512 MB");
+    }
+
+    #[test]
+    fn options_left_unset_keep_the_fingerprint() {
+        let alert = alert(json!({ "trigger_threshold": 0.1, "critical": 0.05 }));
+        let before_severities = (
+            Threshold {
+                value: 10.0,
+                unit: Unit::Percent,
+                metric: MemoryMetric::Available,
+                operator: None,
+            },
+            None::<u64>,
+            None::<u64>,
+            Some(threshold_bits(5.0)),
+            None::<String>,
+            threshold_bits(100.0),
+        );
+        assert_eq!(
+            stable_hash::hash(&alert),
+            stable_hash::hash(&before_severities)
+        );
+    }
+
+    #[test]
+    fn alerts_at_other_percentages_share_a_fingerprint() {
+        let alerts = reported(
+            json!({ "threshold": { "metric": "Used", "value": 80, "unit": "Percent" } }),
+            &[85, 88],
+        );
+        assert_ne!(alerts[0].message, alerts[1].message);
+        assert_eq!(alerts[0].fingerprint, alerts[1].fingerprint);
+    }
+
+    #[test]
+    fn crossing_a_severity_tier_keeps_the_fingerprint() {
+        let alerts = reported(
+            json!({
+                "threshold": { "metric": "Used", "value": 80, "unit": "Percent" },
+                "severities": [{ "at": 90, "severity": "Critical" }]
+            }),
+            &[85, 92],
+        );
+        let severities = alerts.iter().map(|a| a.severity).collect::<Vec<_>>();
+        assert_eq!(severities, [Severity::Warning, Severity::Critical]);
+        assert_eq!(alerts[0].fingerprint, alerts[1].fingerprint);
+    }
+
+    const MIB: u64 = 1 << 20;
+
+    /// 8 GiB of memory, 1 GiB of which is available, 256 MiB free, with 128 MiB of buffers and
+    /// 512 MiB of caches.
+    fn host() -> MemoryInfo {
+        MemoryInfo {
+            total: 8192 * MIB,
+            free: 256 * MIB,
+            available: 1024 * MIB,
+            buffers: 128 * MIB,
+            cached: 512 * MIB,
+            view: MemoryView::Host,
         }
     }
+
+    #[test]
+    fn measures_each_metric_in_each_unit() {
+        let measure = |metric, unit| {
+            Threshold {
+                value: 0.0,
+                unit,
+                metric,
+                operator: None,
+            }
+            .measure(&host())
+        };
+        let gib = (MIB * 1024) as f64;
+        for (metric, bytes) in [
+            (MemoryMetric::Used, 7168 * MIB),
+            (MemoryMetric::Available, 1024 * MIB),
+            (MemoryMetric::Free, 256 * MIB),
+            (MemoryMetric::RealAvailable, 896 * MIB),
+        ] {
+            let bytes = bytes as f64;
+            assert_eq!(measure(metric, Unit::Percent), 100.0 * bytes / (8.0 * gib));
+            assert_eq!(measure(metric, Unit::MB), bytes / 1e6);
+            assert_eq!(measure(metric, Unit::GB), bytes / 1e9);
+            assert_eq!(measure(metric, Unit::MiB), bytes / MIB as f64);
+            assert_eq!(measure(metric, Unit::GiB), bytes / gib);
+        }
+    }
+
+    #[test]
+    fn used_alerts_above_and_other_metrics_below_the_threshold() {
+        let triggers = |threshold: serde_json::Value| {
+            alert(json!({ "threshold": threshold }))
+                .is_triggered(&host())
+                .is_some()
+        };
+        assert!(triggers(
+            json!({ "metric": "Used", "value": 80, "unit": "Percent" })
+        ));
+        assert!(!triggers(
+            json!({ "metric": "Used", "value": 90, "unit": "Percent" })
+        ));
+        assert!(triggers(
+            json!({ "metric": "Available", "value": 2, "unit": "GiB" })
+        ));
+        assert!(!triggers(
+            json!({ "metric": "Available", "value": 512, "unit": "MiB" })
+        ));
+        assert!(triggers(
+            json!({ "metric": "Free", "value": 300, "unit": "MB" })
+        ));
+        assert!(!triggers(
+            json!({ "metric": "Free", "value": 0.2, "unit": "GB" })
+        ));
+        assert!(triggers(
+            json!({ "metric": "RealAvailable", "value": 1, "unit": "GiB" })
+        ));
+        assert!(!triggers(
+            json!({ "metric": "RealAvailable", "value": 10, "unit": "Percent" })
+        ));
+        assert!(triggers(
+            json!({ "metric": "Available", "value": 512, "unit": "MiB", "operator": ">=" })
+        ));
+    }
+
+    #[test]
+    fn each_operator_compares_the_available_memory() {
+        // The host has exactly 1 GiB available.
+        let triggers = |operator: &str| {
+            alert(json!({
+                "threshold": { "metric": "Available", "value": 1, "unit": "GiB", "operator": operator }
+            }))
+            .is_triggered(&host())
+            .is_some()
+        };
+        assert!(!triggers(">"));
+        assert!(triggers(">="));
+        assert!(!triggers("<"));
+        assert!(triggers("<="));
+        assert!(triggers("=="));
+    }
+
+    #[test]
+    fn low_water_marks_get_critical_further_down() {
+        let severity = |critical: f64| {
+            alert(json!({
+                "threshold": { "metric": "Available", "value": 2, "unit": "GiB", "operator": "<" },
+                "critical": critical
+            }))
+            .is_triggered(&host())
+            .unwrap()
+            .severity
+        };
+        assert_eq!(severity(1.5), Severity::Critical);
+        assert_eq!(severity(0.5), Severity::Warning);
+    }
+
+    #[test]
+    fn messages_show_the_metric_in_its_unit() {
+        let alert = alert(json!({
+            "threshold": { "metric": "Available", "value": 2, "unit": "GiB" }
+        }));
+
+        let triggered = alert.is_triggered(&host()).unwrap();
+
+        assert_eq!(
+            triggered.message,
+            "📝 Available memory is at 1.0 GiB (threshold: 2.0 GiB)."
+        );
+        assert_eq!(triggered.annotations["metric"], "Available");
+        assert_eq!(triggered.annotations["unit"], "GiB");
+    }
 }
@@ -1,32 +1,454 @@
-use std::fmt::Debug;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
 use std::hash::{Hash, Hasher};
-use std::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{sync_channel, RecvTimeoutError};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use enum_dispatch::enum_dispatch;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
-use serde::Deserialize;
-use tracing::{info, warn};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn, Span};
 
 use crate::alert_reporter::AlertReporter;
+use crate::alert_store::AlertStore;
+use crate::clock::Clock;
+use crate::metrics::Sample;
+use crate::watcher::replay::ReplayChecker;
+use crate::watcher::state::{AlertConfiguration, WatcherOptions, WatcherState};
 
-#[cfg(feature = "nix")]
+pub mod battery;
+pub mod command;
+pub mod conntrack;
+pub mod cpu;
+pub mod deadman;
+pub mod disk_io;
+#[cfg(any(feature = "nix", windows))]
 pub mod disk_space;
+pub mod dns;
+pub mod file_age;
+pub mod gpu;
 pub mod heartbeat;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod load_avg;
+pub mod log_scan;
 pub mod memory;
+#[cfg(any(feature = "nix", windows))]
+pub mod native_usage;
+pub mod net;
+pub mod open_files;
+pub mod ping;
+pub mod presence;
+pub mod pressure;
+pub mod process;
+pub mod read_only;
+pub mod replay;
+pub mod sampling;
+pub mod self_monitor;
+pub mod state;
+pub mod swap;
+pub mod systemd;
+pub mod target;
+pub mod tcp_port;
+pub mod thermal;
+#[cfg(feature = "tls")]
+pub mod tls_cert;
+pub mod uptime;
 
-#[derive(Debug, Clone)]
+/// Severities are ordered from the least to the most severe.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Default,
+)]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ActiveAlert {
     pub message: String,
+    pub severity: Severity,
+    pub detected_at: SystemTime,
+    pub hostname: String,
+    /// Metadata from the watcher and alert configurations, for reporters to route alerts with.
+    pub labels: BTreeMap<String, String>,
+    /// Kind of watcher the alert comes from, such as `disk_space`, whatever its fingerprint is.
+    pub kind: String,
+    /// Identifies what the alert is about: the kind of watcher, the alert rule and its subject,
+    /// such as a mount point. Unlike the message, it does not change with the measured values,
+    /// so reports of the same alert can be correlated.
+    pub fingerprint: String,
+    /// Set when the alert is reported again because it kept firing for too long.
+    pub escalation: Option<Escalation>,
+    /// What the alert means, from its configuration.
+    pub description: Option<String>,
+    /// Where to find what to do about the alert, from its configuration.
+    pub runbook_url: Option<String>,
+    /// Values the alert was triggered by, such as `used_pct` and `threshold`, for reporters
+    /// sending structured data. Numbers are written without their unit.
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// Marks an alert reported again because it kept firing past a tier of its escalation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Escalation {
+    /// Tier reached, starting at 1.
+    pub tier: usize,
+    /// Name of the only reporter to report the escalated alert to, or all of them if `None`.
+    pub reporter: Option<String>,
+}
+
+impl ActiveAlert {
+    /// Creates an alert, to be stamped with its detection time and host by the watcher.
+    pub fn new(message: String, severity: Severity) -> Self {
+        ActiveAlert {
+            message,
+            severity,
+            detected_at: SystemTime::UNIX_EPOCH,
+            hostname: String::new(),
+            labels: BTreeMap::new(),
+            kind: String::new(),
+            fingerprint: String::new(),
+            escalation: None,
+            description: None,
+            runbook_url: None,
+            annotations: BTreeMap::new(),
+        }
+    }
+
+    /// Adds annotations to the alert, see [`ActiveAlert::annotations`].
+    pub fn annotated<'a>(
+        mut self,
+        annotations: impl IntoIterator<Item = (&'a str, String)>,
+    ) -> Self {
+        self.annotations
+            .extend(annotations.into_iter().map(|(k, v)| (k.to_string(), v)));
+        self
+    }
+
+    pub(crate) fn detected(
+        self,
+        hostname: &str,
+        detected_at: SystemTime,
+        labels: &BTreeMap<String, String>,
+        kind: &str,
+        fingerprint: String,
+    ) -> Self {
+        ActiveAlert {
+            detected_at,
+            hostname: hostname.to_string(),
+            labels: labels.clone(),
+            kind: kind.to_string(),
+            fingerprint,
+            ..self
+        }
+    }
+}
+
+/// Bits of a threshold, for alerts to implement `Hash` consistently with `==`. Thresholds are
+/// read from JSON, which can not express NaN, so alerts comparing them can also be `Eq`.
+pub(crate) fn threshold_bits(threshold: f64) -> u64 {
+    // Adding zero turns `-0.0`, which is equal to `0.0`, into `0.0`.
+    (threshold + 0.0).to_bits()
+}
+
+static PRECISION: OnceLock<usize> = OnceLock::new();
+
+/// Sets the decimal places of the measured values and thresholds written in the messages and
+/// annotations of alerts from now on, 1 by default. Only the first precision is kept, even
+/// across reloads.
+pub fn init_precision(precision: usize) {
+    let _ = PRECISION.set(precision);
+}
+
+/// A value as written in alert messages and annotations, rounded to the configured precision,
+/// see [`init_precision`].
+pub(crate) fn decimal(value: f64) -> String {
+    format!("{:.*}", PRECISION.get().copied().unwrap_or(1), value)
+}
+
+/// A fraction as a percentage, such as `"12.5%"` for `0.125`, see [`decimal`].
+pub(crate) fn percent(fraction: f64) -> String {
+    format!("{}%", decimal(100.0 * fraction))
+}
+
+/// Fails unless a threshold given as a fraction, such as `0.9` for 90%, lies between 0 and 1.
+pub(crate) fn validate_fraction(name: &str, value: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(anyhow!(
+            "'{}' is a fraction between 0 and 1, not {}",
+            name,
+            value
+        ));
+    }
+    Ok(())
+}
+
+/// Fails if the `critical` threshold of an alert firing above `threshold` is below it, as the
+/// alert would always be critical.
+pub(crate) fn validate_critical<T: PartialOrd + Display>(
+    threshold: T,
+    critical: Option<T>,
+) -> Result<()> {
+    match critical {
+        Some(critical) if critical < threshold => Err(anyhow!(
+            "'critical' ({}) is below the threshold ({})",
+            critical,
+            threshold
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Operator comparing a measured value to the threshold of an alert, such as `"<"` for values
+/// that are bad when too low, like available memory.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Above,
+    #[serde(rename = ">=")]
+    AtLeast,
+    #[serde(rename = "<")]
+    Below,
+    #[serde(rename = "<=")]
+    AtMost,
+    /// Exactly equal, mostly useful for values that are whole numbers.
+    #[serde(rename = "==")]
+    Equal,
+}
+
+impl Comparison {
+    /// Whether `a` is worse than `b`: above it for `>` and `>=`, below it for `<` and `<=`, and
+    /// never for `==`. This orders the critical threshold and the tiers of an alert after its
+    /// threshold.
+    pub fn is_beyond(&self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Above | Comparison::AtLeast => a > b,
+            Comparison::Below | Comparison::AtMost => a < b,
+            Comparison::Equal => false,
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparison::Above => ">",
+            Comparison::AtLeast => ">=",
+            Comparison::Below => "<",
+            Comparison::AtMost => "<=",
+            Comparison::Equal => "==",
+        })
+    }
+}
+
+/// Whether `value` is on the alerting side of `threshold` according to `comparison`, e.g.
+/// whether available memory is `<` 500 MB.
+pub fn compare(value: f64, comparison: Comparison, threshold: f64) -> bool {
+    match comparison {
+        Comparison::Above => value > threshold,
+        Comparison::AtLeast => value >= threshold,
+        Comparison::Below => value < threshold,
+        Comparison::AtMost => value <= threshold,
+        Comparison::Equal => value == threshold,
+    }
+}
+
+/// Fails if an alert comparing with `==` has severities past its threshold, which no value can
+/// be.
+pub(crate) fn validate_comparison(
+    comparison: Comparison,
+    critical: Option<f64>,
+    tiers: &[SeverityTier],
+) -> Result<()> {
+    if comparison == Comparison::Equal && (critical.is_some() || !tiers.is_empty()) {
+        return Err(anyhow!(
+            "'critical' and 'severities' can not be set along with '=='"
+        ));
+    }
+    Ok(())
+}
+
+/// Severity an alert takes once its value is past `at`, in the unit of its threshold. A single
+/// alert with several tiers goes from a warning to critical while keeping its fingerprint, where
+/// two alerts would fire separately.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+pub struct SeverityTier {
+    pub at: f64,
+    pub severity: Severity,
+}
+
+impl Eq for SeverityTier {}
+
+impl Hash for SeverityTier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        threshold_bits(self.at).hash(state);
+        self.severity.hash(state);
+    }
+}
+
+/// Severity of the most severe tier the value is past, according to `is_beyond`, or the default
+/// one below every tier.
+pub(crate) fn tier_severity(
+    tiers: &[SeverityTier],
+    value: f64,
+    is_beyond: impl Fn(f64, f64) -> bool,
+) -> Severity {
+    tiers
+        .iter()
+        .filter(|tier| is_beyond(value, tier.at))
+        .map(|tier| tier.severity)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Fails unless each tier is at or past the threshold, and past the previous tier with a higher
+/// severity, `is_beyond(a, b)` telling whether `a` is past `b`. Tiers replace `critical`, which
+/// can not be set along with them.
+pub(crate) fn validate_tiers(
+    threshold: f64,
+    tiers: &[SeverityTier],
+    critical: Option<f64>,
+    is_beyond: impl Fn(f64, f64) -> bool,
+) -> Result<()> {
+    if critical.is_some() && !tiers.is_empty() {
+        return Err(anyhow!("'critical' can not be set along with 'severities'"));
+    }
+    let mut previous: Option<&SeverityTier> = None;
+    for tier in tiers {
+        if is_beyond(threshold, tier.at) {
+            return Err(anyhow!(
+                "Severity tier at {} is not past the threshold ({})",
+                tier.at,
+                threshold
+            ));
+        }
+        if let Some(previous) = previous {
+            if !is_beyond(tier.at, previous.at) || tier.severity <= previous.severity {
+                return Err(anyhow!(
+                    "Severity tier at {} ({:?}) must be past the previous one at {} ({:?}), \
+                     with a higher severity",
+                    tier.at,
+                    tier.severity,
+                    previous.at,
+                    previous.severity
+                ));
+            }
+        }
+        previous = Some(tier);
+    }
+    Ok(())
+}
+
+/// Name of the host Monitrust runs on, or `unknown` if it cannot be retrieved.
+pub(crate) fn hostname() -> String {
+    #[cfg(feature = "nix")]
+    let hostname = nix::unistd::gethostname()
+        .ok()
+        .and_then(|h| h.into_string().ok());
+    #[cfg(not(feature = "nix"))]
+    let hostname = std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|h| h.trim().to_string());
+    hostname.unwrap_or_else(|| {
+        warn!("Could not retrieve hostname");
+        "unknown".to_string()
+    })
+}
+
+/// Result of a checker measuring a rate since its previous check, such as the traffic of an
+/// interface. The first check has no previous sample to compute a delta from, and only primes
+/// the baseline: by convention, alerts neither fire nor resolve on a `Priming` result.
+#[derive(Debug, Clone)]
+pub enum Warmup<T> {
+    Priming,
+    Ready(T),
+}
+
+impl<T> Warmup<T> {
+    /// The measure, unless the check only primed the baseline.
+    pub fn ready(&self) -> Option<&T> {
+        match self {
+            Warmup::Priming => None,
+            Warmup::Ready(measure) => Some(measure),
+        }
+    }
+}
+
+/// Result of a check along with when its data was captured, so that alerts are dated and timed
+/// from the capture rather than from when a slow check completed.
+#[derive(Debug, Clone)]
+pub struct Timestamped<T> {
+    pub result: T,
+    /// Monotonic time of the capture, which `for_duration`, cooldowns and escalations are
+    /// measured with.
+    pub sampled: Instant,
+    /// Wall-clock time of the capture, which alerts are dated with.
+    pub sampled_at: SystemTime,
 }
 
 pub trait Checker {
-    type CheckResult;
-    type Configuration: DeserializeOwned + Debug;
+    type CheckResult: Debug;
+    type Configuration: DeserializeOwned + Debug + Clone + Eq + Hash;
     fn check(&self) -> Result<Self::CheckResult>;
     fn period(&self) -> Duration;
 
+    /// Checks, stamping the result with the time `clock` tells when the data was captured. By
+    /// default, that is when the check starts: checkers that wait before reading their data can
+    /// stamp it at the read instead.
+    fn timestamped_check(&self, clock: &dyn Clock) -> Result<Timestamped<Self::CheckResult>> {
+        let (sampled, sampled_at) = (clock.now(), clock.system_now());
+        self.check().map(|result| Timestamped {
+            result,
+            sampled,
+            sampled_at,
+        })
+    }
+
     fn new(configuration: Self::Configuration) -> Self;
+
+    /// Verifies the configuration up front, for problems that would otherwise only show once
+    /// checking. Reads nothing from the system, as watchers are validated each time they are
+    /// loaded.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Tries what the checker reads, such as its files, devices or hosts, to tell whether it can
+    /// check on this system. Only `--check` probes, as probing may block, or fail until the
+    /// system is ready, such as a network mount that does not answer yet.
+    fn probe(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Metrics measured by a check, to be exposed besides alerting.
+    fn metrics(&self, _check_result: &Self::CheckResult) -> Vec<Sample> {
+        Vec::new()
+    }
+
+    /// What a check measured in a few words, such as `"12.5% used"`, as shown in `/status`. The
+    /// whole result, debug-formatted, by default.
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        format!("{:?}", check_result)
+    }
 }
 
 pub trait Alert: Debug {
@@ -35,95 +457,945 @@ pub trait Alert: Debug {
         &self,
         check_result: &<Self::Checker as Checker>::CheckResult,
     ) -> Option<ActiveAlert>;
+
+    /// Whether a firing alert should be considered resolved. Alerts with hysteresis can keep
+    /// firing after they stopped being triggered.
+    fn is_cleared(&self, check_result: &<Self::Checker as Checker>::CheckResult) -> bool {
+        self.is_triggered(check_result).is_none()
+    }
+
+    /// Verifies the thresholds of the alert up front, such as their ranges and whether they are
+    /// consistent with each other.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The alert with its threshold replaced by one of its `threshold_schedule`, given in the
+    /// same unit. Alerts without a single numeric threshold can not be scheduled, and return
+    /// `None`.
+    fn with_threshold(&self, _threshold: f64) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Evaluates the alert for each subject of a check, such as each mount point of a disk space
+    /// check, so that each subject fires and resolves on its own. Alerts about a single subject
+    /// can rely on the default implementation.
+    fn evaluate(&self, check_result: &<Self::Checker as Checker>::CheckResult) -> Vec<Evaluation> {
+        vec![Evaluation::single(
+            self.is_triggered(check_result),
+            self.is_cleared(check_result),
+        )]
+    }
+}
+
+/// Outcome of an alert for one of the subjects of a check.
+#[derive(Debug)]
+pub struct Evaluation {
+    /// Identifies the subject among the ones of the check.
+    pub subject: String,
+    pub triggered: Option<ActiveAlert>,
+    pub cleared: bool,
+}
+
+impl Evaluation {
+    /// Outcome of an alert about the check as a whole.
+    pub fn single(triggered: Option<ActiveAlert>, cleared: bool) -> Self {
+        Evaluation {
+            subject: String::new(),
+            triggered,
+            cleared,
+        }
+    }
 }
 
 #[enum_dispatch(WatcherEnum)]
 pub trait Watcher {
-    fn run<A: AlertReporter>(&self, alert_reporter: &A) -> Result<()>;
+    /// Checks once and reports the alerts that changed. Reporters are taken as trait objects,
+    /// so that they can be picked at runtime, such as from a `Vec<Box<dyn AlertReporter>>`.
+    fn run(
+        &mut self,
+        alert_reporter: &dyn AlertReporter,
+        alert_store: &dyn AlertStore,
+    ) -> Result<()>;
     fn period(&self) -> Duration;
+    /// Delay of the first run, from when the watcher is scheduled.
+    fn initial_delay(&self) -> Duration;
+    /// See [`Checker::validate`] and [`Alert::validate`]. Every problem found is listed in the
+    /// error.
+    fn validate(&self) -> Result<()>;
+    /// See [`Checker::probe`].
+    fn probe(&self) -> Result<()>;
+    /// Disabled watchers are kept in the configuration, but never run.
+    fn is_enabled(&self) -> bool;
+    /// Kind of the watcher, such as `disk_space`, as used in its status and metrics.
+    fn kind(&self) -> &'static str;
+}
+
+/// Runs the watcher, turning a panic into an error so that a panicking watcher does not stop the
+/// other ones.
+pub(crate) fn run_isolated(
+    watcher: &mut dyn Watcher,
+    alert_reporter: &dyn AlertReporter,
+    alert_store: &dyn AlertStore,
+) -> Result<()> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        watcher.run(alert_reporter, alert_store)
+    }))
+    .unwrap_or_else(|_| Err(anyhow!("Watcher panicked")))
 }
 
 #[derive(Debug)]
 pub struct MultiWatcher<A: Alert + Debug> {
-    checker: A::Checker,
+    checker: Arc<A::Checker>,
     alerts: Vec<A>,
+    state: WatcherState,
+    /// Thread of a check that timed out, so that checks that hang do not pile up.
+    timed_out_check: Option<JoinHandle<()>>,
+    enabled: bool,
 }
 
-impl<A: Alert + DeserializeOwned + Clone> MultiWatcher<A> {
+impl<A: Alert + DeserializeOwned + Clone + Hash> MultiWatcher<A> {
     pub fn new(serialized_configuration: SerializedMultiWatcher<A>) -> Self {
+        let (alerts, state) = WatcherState::new(
+            &serialized_configuration.configuration,
+            serialized_configuration.alerts,
+            serialized_configuration.options,
+        );
+        if !serialized_configuration.enabled {
+            state.publish_disabled();
+        }
         MultiWatcher {
-            checker: A::Checker::new(serialized_configuration.configuration),
-            alerts: serialized_configuration.alerts,
+            checker: Arc::new(A::Checker::new(serialized_configuration.configuration)),
+            alerts,
+            state,
+            timed_out_check: None,
+            enabled: serialized_configuration.enabled,
         }
     }
 }
 
-impl<A: Alert> Watcher for MultiWatcher<A> {
-    fn run<R: AlertReporter>(&self, alert_reporter: &R) -> Result<()> {
-        let check_result = self.checker.check()?;
-        self.alerts
-            .iter()
-            .filter_map(|a| a.is_triggered(&check_result))
-            .inspect(|a| info!(firing_alert = ?a))
-            .filter_map(|alert| match alert_reporter.report(&alert) {
-                Ok(_) => None,
-                Err(e) => Some(e),
-            })
-            .for_each(|e| {
-                warn!(alert_reporter = ?e);
-            });
+impl<A: Alert> MultiWatcher<A> {
+    /// Reads the time from `clock` rather than from the system, e.g. to test how alerts fire
+    /// over time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.state.set_clock(clock);
+        self
+    }
+
+    pub fn checker(&self) -> &A::Checker {
+        &self.checker
+    }
+
+    /// Alerts of the watcher, in the order they are configured.
+    pub fn alerts(&self) -> &[A] {
+        &self.alerts
+    }
+
+    pub fn alert_count(&self) -> usize {
+        self.alerts.len()
+    }
+
+    /// Evaluates the alerts on the result of a check, reporting the ones that changed. Returns
+    /// the number of alerts reported.
+    fn report(
+        &mut self,
+        check: &Timestamped<<A::Checker as Checker>::CheckResult>,
+        alert_reporter: &dyn AlertReporter,
+        alert_store: &dyn AlertStore,
+    ) -> usize {
+        self.state.record_result(
+            self.checker.metrics(&check.result),
+            self.checker.summary(&check.result),
+        );
+        let thresholds = self.state.scheduled_thresholds();
+        self.state.report_changes(
+            check,
+            self.alerts
+                .iter()
+                .zip(thresholds)
+                .map(
+                    |(alert, threshold)| match threshold.and_then(|t| alert.with_threshold(t)) {
+                        Some(scheduled) => scheduled.evaluate(&check.result),
+                        None => alert.evaluate(&check.result),
+                    },
+                ),
+            alert_reporter,
+            alert_store,
+        )
+    }
+
+    /// Evaluates and reports the alerts on each result `replay` yields in turn, as runs would on
+    /// the results of the checker, e.g. to tell why an alert fired from the snapshots of the
+    /// checks. Returns the number of alerts reported.
+    pub fn replay(
+        &mut self,
+        replay: &ReplayChecker<A::Checker>,
+        alert_reporter: &dyn AlertReporter,
+        alert_store: &dyn AlertStore,
+    ) -> Result<usize> {
+        let span = self.state.span();
+        let _run = span.enter();
+        let mut fired = 0;
+        while let Some(check) = replay.next_check() {
+            if let Some(check) = self.state.checked(Ok(check), alert_reporter)? {
+                fired += self.report(&check, alert_reporter, alert_store);
+            }
+        }
+        Ok(fired)
+    }
+}
+
+impl<A: Alert> MultiWatcher<A>
+where
+    A::Checker: Send + Sync + 'static,
+    <A::Checker as Checker>::CheckResult: Send + 'static,
+{
+    /// Runs a check on its own thread, abandoning it if it does not complete within the timeout.
+    /// No check is started while an abandoned one is still running.
+    fn check(&mut self) -> Result<Timestamped<<A::Checker as Checker>::CheckResult>> {
+        if let Some(timed_out) = self.timed_out_check.take() {
+            if !timed_out.is_finished() {
+                self.timed_out_check = Some(timed_out);
+                return Err(anyhow!("A previous check that timed out is still running"));
+            }
+        }
+        let timeout = self.state.check_timeout(self.checker.period());
+        let checker = Arc::clone(&self.checker);
+        let clock = self.state.clock();
+        let (sender, receiver) = sync_channel(1);
+        let span = Span::current();
+        let check = thread::spawn(move || {
+            let _run = span.enter();
+            // The watcher stopped waiting if the check timed out.
+            let _ = sender.send(checker.timestamped_check(&*clock));
+        });
+        match receiver.recv_timeout(timeout) {
+            Ok(check_result) => check_result,
+            Err(RecvTimeoutError::Timeout) => {
+                warn!(check_timeout = ?timeout, "Abandoning check");
+                self.timed_out_check = Some(check);
+                Err(anyhow!("Check timed out after {:?}", timeout))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("Check panicked")),
+        }
+    }
+}
+
+impl<A: Alert> Watcher for MultiWatcher<A>
+where
+    A::Checker: Send + Sync + 'static,
+    <A::Checker as Checker>::CheckResult: Send + 'static,
+{
+    fn run(
+        &mut self,
+        alert_reporter: &dyn AlertReporter,
+        alert_store: &dyn AlertStore,
+    ) -> Result<()> {
+        let span = self.state.span();
+        let _run = span.enter();
+        let mut attempt = 0;
+        let check_result = loop {
+            let started = Instant::now();
+            let check_result = self.check();
+            self.state
+                .record_check(started.elapsed(), check_result.is_ok());
+            match check_result {
+                Ok(check_result) => {
+                    if attempt > 0 {
+                        info!(check_retries = attempt, "Check succeeded after retrying");
+                    }
+                    break Ok(check_result);
+                }
+                Err(e) => match self.state.retry_check(attempt, e) {
+                    Ok(delay) => {
+                        thread::sleep(delay);
+                        attempt += 1;
+                    }
+                    Err(e) => break Err(e),
+                },
+            }
+        };
+        let Some(check) = self.state.checked(check_result, alert_reporter)? else {
+            return Ok(());
+        };
+        let fired = self.report(&check, alert_reporter, alert_store);
+        span.record("alerts_fired", fired);
         Ok(())
     }
 
     fn period(&self) -> Duration {
-        self.checker.period()
+        self.state.period(self.checker.period())
+    }
+
+    fn initial_delay(&self) -> Duration {
+        self.state.initial_delay()
+    }
+
+    fn validate(&self) -> Result<()> {
+        let problems = self
+            .checker
+            .validate()
+            .err()
+            .into_iter()
+            .chain(self.alerts.iter().enumerate().filter_map(|(index, alert)| {
+                alert
+                    .validate()
+                    .and_then(|()| validate_schedule(alert, self.state.threshold_schedule(index)))
+                    .and_then(|()| self.state.validate_fingerprint_template(index))
+                    .err()
+                    .map(|e| e.context(format!("Alert #{}", index + 1)))
+            }))
+            .map(|e| format!("{:#}", e))
+            .collect::<Vec<_>>();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(problems.join("; ")))
+        }
+    }
+
+    fn probe(&self) -> Result<()> {
+        self.checker.probe()
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn kind(&self) -> &'static str {
+        self.state.kind()
     }
 }
 
-#[derive(Deserialize, Debug)]
+/// Fails if the alert can not be scheduled, or if it is invalid with one of its scheduled
+/// thresholds.
+fn validate_schedule<A: Alert>(alert: &A, thresholds: impl Iterator<Item = f64>) -> Result<()> {
+    for threshold in thresholds {
+        alert
+            .with_threshold(threshold)
+            .ok_or_else(|| anyhow!("'threshold_schedule' is not supported by this alert"))?
+            .validate()
+            .with_context(|| format!("Scheduled threshold {}", threshold))?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[schemars(bound = "A: JsonSchema, <A::Checker as Checker>::Configuration: JsonSchema")]
 pub struct SerializedMultiWatcher<A: Clone + Debug + Alert> {
     configuration: <A::Checker as Checker>::Configuration,
-    alerts: Vec<A>,
+    alerts: Vec<AlertConfiguration<A>>,
+    #[serde(flatten)]
+    options: WatcherOptions,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl<A: Clone + Debug + Alert> SerializedMultiWatcher<A> {
+    /// A watcher checking with `configuration`, enabled and with the default options.
+    pub fn new(
+        configuration: <A::Checker as Checker>::Configuration,
+        alerts: Vec<AlertConfiguration<A>>,
+    ) -> Self {
+        SerializedMultiWatcher {
+            configuration,
+            alerts,
+            options: WatcherOptions::default(),
+            enabled: default_enabled(),
+        }
+    }
+
+    pub fn with_options(mut self, options: WatcherOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+}
+
+// Derives would require the checkers themselves to be `Clone` and `PartialEq`.
+impl<A: Clone + Debug + Alert> Clone for SerializedMultiWatcher<A> {
+    fn clone(&self) -> Self {
+        SerializedMultiWatcher {
+            configuration: self.configuration.clone(),
+            alerts: self.alerts.clone(),
+            options: self.options.clone(),
+            enabled: self.enabled,
+        }
+    }
+}
+
+impl<A: Clone + Debug + Alert + PartialEq> PartialEq for SerializedMultiWatcher<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.configuration == other.configuration
+            && self.alerts == other.alerts
+            && self.options == other.options
+            && self.enabled == other.enabled
+    }
+}
+
+impl<A: Clone + Debug + Alert + Eq> Eq for SerializedMultiWatcher<A> {}
+
+impl<A: Clone + Debug + Alert + Hash> Hash for SerializedMultiWatcher<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.configuration.hash(state);
+        self.alerts.hash(state);
+        self.options.hash(state);
+        self.enabled.hash(state);
+    }
 }
 
 #[enum_dispatch]
 #[derive(Debug)]
 pub enum WatcherEnum {
-    #[cfg(feature = "nix")]
+    #[cfg(any(feature = "nix", windows))]
     DiskSpace(MultiWatcher<disk_space::Alert>),
     Memory(MultiWatcher<memory::Alert>),
     Heartbeat(MultiWatcher<heartbeat::Alert>),
+    Cpu(MultiWatcher<cpu::Alert>),
+    LoadAvg(MultiWatcher<load_avg::Alert>),
+    Process(MultiWatcher<process::Alert>),
+    Swap(MultiWatcher<swap::Alert>),
+    TcpPort(MultiWatcher<tcp_port::Alert>),
+    Net(MultiWatcher<net::Alert>),
+    Uptime(MultiWatcher<uptime::Alert>),
+    Thermal(MultiWatcher<thermal::Alert>),
+    DiskIo(MultiWatcher<disk_io::Alert>),
+    Systemd(MultiWatcher<systemd::Alert>),
+    LogScan(MultiWatcher<log_scan::Alert>),
+    Battery(MultiWatcher<battery::Alert>),
+    Gpu(MultiWatcher<gpu::Alert>),
+    Ping(MultiWatcher<ping::Alert>),
+    Dns(MultiWatcher<dns::Alert>),
+    OpenFiles(MultiWatcher<open_files::Alert>),
+    FileAge(MultiWatcher<file_age::Alert>),
+    Conntrack(MultiWatcher<conntrack::Alert>),
+    Command(MultiWatcher<command::Alert>),
+    Deadman(MultiWatcher<deadman::Alert>),
+    SelfMonitor(MultiWatcher<self_monitor::Alert>),
+    Pressure(MultiWatcher<pressure::Alert>),
+    ReadOnly(MultiWatcher<read_only::Alert>),
+    #[cfg(feature = "http")]
+    Http(MultiWatcher<http::Alert>),
+    #[cfg(feature = "tls")]
+    TlsCert(MultiWatcher<tls_cert::Alert>),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum WatcherConfiguration {
-    #[cfg(feature = "nix")]
+    #[cfg(any(feature = "nix", windows))]
     DiskSpace(SerializedMultiWatcher<disk_space::Alert>),
     Memory(SerializedMultiWatcher<memory::Alert>),
     Heartbeat(SerializedMultiWatcher<heartbeat::Alert>),
+    Cpu(SerializedMultiWatcher<cpu::Alert>),
+    LoadAvg(SerializedMultiWatcher<load_avg::Alert>),
+    Process(SerializedMultiWatcher<process::Alert>),
+    Swap(SerializedMultiWatcher<swap::Alert>),
+    TcpPort(SerializedMultiWatcher<tcp_port::Alert>),
+    Net(SerializedMultiWatcher<net::Alert>),
+    Uptime(SerializedMultiWatcher<uptime::Alert>),
+    Thermal(SerializedMultiWatcher<thermal::Alert>),
+    DiskIo(SerializedMultiWatcher<disk_io::Alert>),
+    Systemd(SerializedMultiWatcher<systemd::Alert>),
+    LogScan(SerializedMultiWatcher<log_scan::Alert>),
+    Battery(SerializedMultiWatcher<battery::Alert>),
+    Gpu(SerializedMultiWatcher<gpu::Alert>),
+    Ping(SerializedMultiWatcher<ping::Alert>),
+    Dns(SerializedMultiWatcher<dns::Alert>),
+    OpenFiles(SerializedMultiWatcher<open_files::Alert>),
+    FileAge(SerializedMultiWatcher<file_age::Alert>),
+    Conntrack(SerializedMultiWatcher<conntrack::Alert>),
+    Command(SerializedMultiWatcher<command::Alert>),
+    Deadman(SerializedMultiWatcher<deadman::Alert>),
+    SelfMonitor(SerializedMultiWatcher<self_monitor::Alert>),
+    Pressure(SerializedMultiWatcher<pressure::Alert>),
+    ReadOnly(SerializedMultiWatcher<read_only::Alert>),
+    #[cfg(feature = "http")]
+    Http(SerializedMultiWatcher<http::Alert>),
+    #[cfg(feature = "tls")]
+    TlsCert(SerializedMultiWatcher<tls_cert::Alert>),
 }
 
-impl From<WatcherConfiguration> for WatcherEnum {
-    fn from(value: WatcherConfiguration) -> Self {
-        match value {
-            #[cfg(feature = "nix")]
+/// Builds the watcher of a configuration, failing if it is enabled but cannot work as configured,
+/// see [`Watcher::validate`]. Disabled watchers are only built, as they may target what is
+/// missing from the host.
+impl TryFrom<WatcherConfiguration> for WatcherEnum {
+    type Error = anyhow::Error;
+
+    fn try_from(value: WatcherConfiguration) -> Result<Self> {
+        let watcher = match value {
+            #[cfg(any(feature = "nix", windows))]
             WatcherConfiguration::DiskSpace(d) => WatcherEnum::DiskSpace(MultiWatcher::new(d)),
             WatcherConfiguration::Memory(m) => WatcherEnum::Memory(MultiWatcher::new(m)),
             WatcherConfiguration::Heartbeat(h) => WatcherEnum::Heartbeat(MultiWatcher::new(h)),
+            WatcherConfiguration::Cpu(c) => WatcherEnum::Cpu(MultiWatcher::new(c)),
+            WatcherConfiguration::LoadAvg(l) => WatcherEnum::LoadAvg(MultiWatcher::new(l)),
+            WatcherConfiguration::Process(p) => WatcherEnum::Process(MultiWatcher::new(p)),
+            WatcherConfiguration::Swap(s) => WatcherEnum::Swap(MultiWatcher::new(s)),
+            WatcherConfiguration::TcpPort(t) => WatcherEnum::TcpPort(MultiWatcher::new(t)),
+            WatcherConfiguration::Net(n) => WatcherEnum::Net(MultiWatcher::new(n)),
+            WatcherConfiguration::Uptime(u) => WatcherEnum::Uptime(MultiWatcher::new(u)),
+            WatcherConfiguration::Thermal(t) => WatcherEnum::Thermal(MultiWatcher::new(t)),
+            WatcherConfiguration::DiskIo(d) => WatcherEnum::DiskIo(MultiWatcher::new(d)),
+            WatcherConfiguration::Systemd(s) => WatcherEnum::Systemd(MultiWatcher::new(s)),
+            WatcherConfiguration::LogScan(l) => WatcherEnum::LogScan(MultiWatcher::new(l)),
+            WatcherConfiguration::Battery(b) => WatcherEnum::Battery(MultiWatcher::new(b)),
+            WatcherConfiguration::Gpu(g) => WatcherEnum::Gpu(MultiWatcher::new(g)),
+            WatcherConfiguration::Ping(p) => WatcherEnum::Ping(MultiWatcher::new(p)),
+            WatcherConfiguration::Dns(d) => WatcherEnum::Dns(MultiWatcher::new(d)),
+            WatcherConfiguration::OpenFiles(o) => WatcherEnum::OpenFiles(MultiWatcher::new(o)),
+            WatcherConfiguration::FileAge(f) => WatcherEnum::FileAge(MultiWatcher::new(f)),
+            WatcherConfiguration::Conntrack(c) => WatcherEnum::Conntrack(MultiWatcher::new(c)),
+            WatcherConfiguration::Command(c) => WatcherEnum::Command(MultiWatcher::new(c)),
+            WatcherConfiguration::Deadman(d) => WatcherEnum::Deadman(MultiWatcher::new(d)),
+            WatcherConfiguration::SelfMonitor(s) => WatcherEnum::SelfMonitor(MultiWatcher::new(s)),
+            WatcherConfiguration::Pressure(p) => WatcherEnum::Pressure(MultiWatcher::new(p)),
+            WatcherConfiguration::ReadOnly(r) => WatcherEnum::ReadOnly(MultiWatcher::new(r)),
+            #[cfg(feature = "http")]
+            WatcherConfiguration::Http(h) => WatcherEnum::Http(MultiWatcher::new(h)),
+            #[cfg(feature = "tls")]
+            WatcherConfiguration::TlsCert(t) => WatcherEnum::TlsCert(MultiWatcher::new(t)),
+        };
+        if watcher.is_enabled() {
+            watcher.validate()?;
         }
+        Ok(watcher)
     }
 }
 
-impl PartialEq<Self> for WatcherConfiguration {
-    fn eq(&self, other: &Self) -> bool {
-        std::mem::discriminant(self) == std::mem::discriminant(other)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_store::memory::Memory;
+    use serde_json::json;
+
+    /// Panics on every check, or only when evaluating its alerts with `in_alert`.
+    #[derive(Debug)]
+    struct PanickingChecker {
+        in_alert: bool,
     }
-}
 
-impl Hash for WatcherConfiguration {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        std::mem::discriminant(self).hash(state)
+    impl Checker for PanickingChecker {
+        type CheckResult = ();
+        type Configuration = bool;
+
+        fn check(&self) -> Result<()> {
+            assert!(self.in_alert, "Checker panicked");
+            Ok(())
+        }
+
+        fn period(&self) -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn new(in_alert: bool) -> Self {
+            PanickingChecker { in_alert }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+    struct PanickingAlert {}
+
+    impl Alert for PanickingAlert {
+        type Checker = PanickingChecker;
+
+        fn is_triggered(&self, _check_result: &()) -> Option<ActiveAlert> {
+            panic!("Alert panicked")
+        }
+    }
+
+    fn panicking(in_alert: bool) -> MultiWatcher<PanickingAlert> {
+        MultiWatcher::new(
+            serde_json::from_value(json!({ "configuration": in_alert, "alerts": [{}] })).unwrap(),
+        )
+    }
+
+    #[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+    struct FlakyConfiguration {
+        #[serde(default)]
+        sleep_ms: u64,
+        /// Number of checks failing before the first one that succeeds.
+        #[serde(default)]
+        failures: u32,
+    }
+
+    /// Sleeps during each check, and fails the first ones.
+    #[derive(Debug)]
+    struct FlakyChecker {
+        configuration: FlakyConfiguration,
+        checks: std::sync::atomic::AtomicU32,
+    }
+
+    impl Checker for FlakyChecker {
+        type CheckResult = ();
+        type Configuration = FlakyConfiguration;
+
+        fn check(&self) -> Result<()> {
+            thread::sleep(Duration::from_millis(self.configuration.sleep_ms));
+            let check = self
+                .checks
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if check < self.configuration.failures {
+                return Err(anyhow!("Check {} failed", check + 1));
+            }
+            Ok(())
+        }
+
+        fn period(&self) -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn new(configuration: FlakyConfiguration) -> Self {
+            FlakyChecker {
+                configuration,
+                checks: Default::default(),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+    struct FlakyAlert {
+        /// Whether the alert triggers on each successful check.
+        #[serde(default)]
+        fires: bool,
+    }
+
+    impl Alert for FlakyAlert {
+        type Checker = FlakyChecker;
+
+        fn is_triggered(&self, _check_result: &()) -> Option<ActiveAlert> {
+            self.fires
+                .then(|| ActiveAlert::new("Flaky".to_string(), Severity::Warning))
+        }
+    }
+
+    /// A flaky watcher checking with `configuration`, with the watcher `options` and an alert
+    /// that never triggers.
+    fn flaky(
+        configuration: serde_json::Value,
+        options: serde_json::Value,
+    ) -> MultiWatcher<FlakyAlert> {
+        let mut watcher = json!({ "configuration": configuration, "alerts": [{}] });
+        if let (Some(watcher), Some(options)) = (watcher.as_object_mut(), options.as_object()) {
+            watcher.extend(options.clone());
+        }
+        MultiWatcher::new(serde_json::from_value(watcher).unwrap())
+    }
+
+    #[test]
+    fn compare_applies_each_operator() {
+        let operators = [
+            (">", [false, false, true]),
+            (">=", [false, true, true]),
+            ("<", [true, false, false]),
+            ("<=", [true, true, false]),
+            ("==", [false, true, false]),
+        ];
+        for (operator, expected) in operators {
+            let comparison: Comparison = serde_json::from_value(json!(operator)).unwrap();
+            assert_eq!(comparison.to_string(), operator);
+            let compared = [4.0, 5.0, 6.0].map(|value| compare(value, comparison, 5.0));
+            assert_eq!(compared, expected, "{}", operator);
+        }
+
+        // Lower values are the worse ones for a low-water mark.
+        assert!(Comparison::Below.is_beyond(1.0, 2.0));
+        assert!(!Comparison::AtMost.is_beyond(2.0, 1.0));
+        assert!(Comparison::AtLeast.is_beyond(2.0, 1.0));
+        assert!(!Comparison::Equal.is_beyond(2.0, 1.0));
+    }
+
+    #[test]
+    fn a_hanging_check_times_out() {
+        let mut watcher = flaky(
+            json!({ "sleep_ms": 500 }),
+            json!({ "check_timeout": "50ms" }),
+        );
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+
+        let error = watcher.run(&reporter, &store).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("Check timed out after 50ms"),
+            "{:#}",
+            error
+        );
+        let error = watcher.run(&reporter, &store).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("still running"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[test]
+    fn a_hanging_check_alerts_when_configured_to() {
+        let mut watcher = flaky(
+            json!({ "sleep_ms": 500 }),
+            json!({ "check_timeout": "50ms", "alert_on_check_failure": true }),
+        );
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+
+        watcher.run(&reporter, &store).unwrap();
+
+        let alerts = reporter.alerts();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Severity::Critical);
+        assert!(
+            alerts[0].message.contains("Check timed out"),
+            "{}",
+            alerts[0].message
+        );
+    }
+
+    #[test]
+    fn thresholds_out_of_range_are_named() {
+        assert!(validate_fraction("threshold", 0.9).is_ok());
+        assert_eq!(
+            validate_fraction("threshold", 90.0)
+                .unwrap_err()
+                .to_string(),
+            "'threshold' is a fraction between 0 and 1, not 90"
+        );
+        assert!(validate_critical(2.0, Some(4.0)).is_ok());
+        assert!(validate_critical(2.0, None).is_ok());
+        assert_eq!(
+            validate_critical(2.0, Some(1.5)).unwrap_err().to_string(),
+            "'critical' (1.5) is below the threshold (2)"
+        );
+    }
+
+    #[test]
+    fn validation_lists_every_problem_of_the_watcher() {
+        let threshold =
+            json!({ "metric": "Available", "value": 100, "unit": "MB", "operator": "<=" });
+        let configuration = serde_json::from_value::<WatcherConfiguration>(json!({ "Memory": {
+            "configuration": { "period": "1m" },
+            "alerts": [
+                { "threshold": threshold, "critical": 200 },
+                { "threshold": threshold },
+                { "threshold": threshold, "clear_threshold": 50 },
+            ],
+        } }))
+        .unwrap();
+
+        let error = WatcherEnum::try_from(configuration).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Alert #1: 'critical' (200) is not past the threshold (100); \
+             Alert #3: 'clear_threshold' (50) is past the threshold (100)"
+        );
+    }
+
+    #[test]
+    fn a_check_succeeding_on_retry_does_not_alert() {
+        let mut watcher = flaky(
+            json!({ "failures": 1 }),
+            json!({
+                "check_retries": 2,
+                "check_retry_delay": "10ms",
+                "alert_on_check_failure": true,
+            }),
+        );
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+
+        watcher.run(&reporter, &store).unwrap();
+
+        assert!(reporter.alerts().is_empty());
+        let checks = watcher
+            .checker
+            .checks
+            .load(std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(checks, 2);
+    }
+
+    #[test]
+    fn a_check_failing_every_retry_fails() {
+        let mut watcher = flaky(
+            json!({ "failures": 3 }),
+            json!({ "check_retries": 2, "check_retry_delay": "10ms" }),
+        );
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+
+        let error = watcher.run(&reporter, &store).unwrap_err();
+
+        assert!(
+            format!("{:#}", error).ends_with("Failed 3 attempts: Check 3 failed"),
+            "{:#}",
+            error
+        );
+        let checks = watcher
+            .checker
+            .checks
+            .load(std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(checks, 3);
     }
-}
 
-impl Eq for WatcherConfiguration {}
+    /// Logs written by a test subscriber.
+    #[cfg(feature = "logging")]
+    #[derive(Clone, Default)]
+    struct Logs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    #[cfg(feature = "logging")]
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn runs_are_traced_in_a_span_of_their_own() {
+        let configuration = serde_json::from_value::<WatcherConfiguration>(json!({ "Memory": {
+            "configuration": { "period": "1m" },
+            "alerts": [{
+                "threshold": { "metric": "Available", "value": 0, "unit": "MB", "operator": ">=" },
+            }],
+            "labels": { "team": "ops" },
+        } }))
+        .unwrap();
+        let mut watcher = WatcherEnum::try_from(configuration).unwrap();
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::EXIT)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            watcher.run(&reporter, &store).unwrap();
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        // The check also enters the span, on a thread of its own, before any alert fired.
+        let exited = logs
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|event| {
+                event["fields"]["message"] == "exit" && !event["span"]["alerts_fired"].is_null()
+            })
+            .unwrap_or_else(|| panic!("The run did not trace the alerts it fired: {}", logs));
+        let span = &exited["span"];
+        assert_eq!(span["name"], "watcher_run");
+        assert_eq!(span["kind"], "memory");
+        assert_eq!(span["labels"], r#"{"team": "ops"}"#);
+        assert_eq!(span["alerts_fired"], 1);
+        assert!(span["check_duration"].as_f64().is_some(), "{}", span);
+    }
+
+    #[test]
+    fn alerts_triggered_twice_in_a_run_are_reported_once() {
+        let reported = |deduplicate: &str| {
+            let at_least = |value: u64| {
+                json!({
+                    "threshold": { "metric": "Available", "value": value, "unit": "MB", "operator": ">=" },
+                    "message": "Memory is available",
+                })
+            };
+            let configuration = serde_json::from_value::<WatcherConfiguration>(json!({ "Memory": {
+                "configuration": { "period": "1m" },
+                "alerts": [at_least(0), at_least(0), at_least(1)],
+                "deduplicate": deduplicate,
+            } }))
+            .unwrap();
+            let mut watcher = WatcherEnum::try_from(configuration).unwrap();
+            let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+            watcher.run(&reporter, &store).unwrap();
+            reporter.alerts().len()
+        };
+
+        assert_eq!(reported("Fingerprint"), 2);
+        assert_eq!(reported("Message"), 1);
+    }
+
+    #[test]
+    fn alerts_of_a_slow_check_are_dated_from_its_start() {
+        let mut watcher = MultiWatcher::<FlakyAlert>::new(
+            serde_json::from_value(json!({
+                "configuration": { "sleep_ms": 300 },
+                "alerts": [{ "fires": true }],
+            }))
+            .unwrap(),
+        );
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+
+        let started_at = SystemTime::now();
+        watcher.run(&reporter, &store).unwrap();
+        let reported_at = SystemTime::now();
+
+        let detected_at = reporter.alerts()[0].detected_at;
+        assert!(detected_at >= started_at, "{:?}", detected_at);
+        assert!(
+            reported_at.duration_since(detected_at).unwrap() >= Duration::from_millis(300),
+            "{:?}",
+            detected_at
+        );
+    }
+
+    #[test]
+    fn reporters_picked_at_runtime_drive_the_runs() {
+        use crate::alert_reporter::null::NullReporter;
+
+        let recording = RecordingReporter::new();
+        let reporters: Vec<Box<dyn AlertReporter + '_>> =
+            vec![Box::new(NullReporter::new()), Box::new(&recording)];
+
+        for reporter in &reporters {
+            let mut watcher = MultiWatcher::<FlakyAlert>::new(
+                serde_json::from_value(
+                    json!({ "configuration": {}, "alerts": [{ "fires": true }] }),
+                )
+                .unwrap(),
+            );
+            watcher
+                .run(reporter.as_ref(), &Memory::new(Default::default()))
+                .unwrap();
+        }
+
+        assert_eq!(recording.alerts().len(), 1);
+    }
+
+    #[test]
+    fn a_panicking_check_fails_the_run() {
+        let mut watcher = panicking(false);
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+
+        for _ in 0..2 {
+            let error = run_isolated(&mut watcher, &reporter, &store).unwrap_err();
+            assert!(
+                format!("{:#}", error).contains("Check panicked"),
+                "{:#}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn a_panicking_alert_fails_the_run() {
+        let mut watcher = panicking(true);
+        let (reporter, store) = (RecordingReporter::new(), Memory::new(Default::default()));
+
+        let error = run_isolated(&mut watcher, &reporter, &store).unwrap_err();
+
+        assert_eq!(error.to_string(), "Watcher panicked");
+        assert!(reporter.alerts().is_empty());
+    }
+}
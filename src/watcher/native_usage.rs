@@ -0,0 +1,237 @@
+//! Usage of ZFS and Btrfs mount points as told by their own tools: `statvfs` counts neither
+//! their snapshots, quotas, reservations nor RAID profiles the way their users do.
+
+#[cfg(any(feature = "zfs", feature = "btrfs"))]
+use std::io::ErrorKind;
+#[cfg(any(feature = "zfs", feature = "btrfs"))]
+use std::process::Command;
+
+#[cfg(any(feature = "zfs", feature = "btrfs"))]
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// What the usage of a mount point was read with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// `statvfs`, or `GetDiskFreeSpaceExW` on Windows.
+    #[default]
+    Statvfs,
+    /// `zfs list`, whose available space accounts for the quotas and reservations of the
+    /// dataset and its parents, and whose used space includes its snapshots.
+    Zfs,
+    /// `btrfs filesystem usage`, with the data used and the free space estimated for the data
+    /// profile, such as half of the unallocated space on RAID1.
+    Btrfs,
+}
+
+/// Space of a filesystem, as told by its tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativeUsage {
+    pub used: u64,
+    pub available: u64,
+}
+
+impl NativeUsage {
+    /// Fraction of the space that is available, out of the used and the available space.
+    pub fn free_fraction(&self) -> f64 {
+        let total = self.used.saturating_add(self.available);
+        if total == 0 {
+            return 0.0;
+        }
+        self.available as f64 / total as f64
+    }
+}
+
+/// Runs a filesystem tool, returning what it wrote.
+#[cfg(any(feature = "zfs", feature = "btrfs"))]
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = match Command::new(program).args(args).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(anyhow!("{} was not found", program))
+        }
+        Err(e) => return Err(e).with_context(|| format!("Could not run {}", program)),
+    };
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads the output of `zfs list -Hp -o used,available`: both in bytes, separated by a tab.
+#[cfg(feature = "zfs")]
+pub fn parse_zfs_list(output: &str) -> Result<NativeUsage> {
+    let line = output
+        .lines()
+        .next()
+        .ok_or_else(|| anyhow!("zfs list did not list the dataset"))?;
+    let mut fields = line.split('\t');
+    let mut next = |name: &str| -> Result<u64> {
+        let field = fields
+            .next()
+            .ok_or_else(|| anyhow!("Missing {} in zfs list output: {}", name, line))?;
+        field
+            .trim()
+            .parse()
+            .with_context(|| format!("Could not parse {} of zfs list: {}", name, field))
+    };
+    Ok(NativeUsage {
+        used: next("used")?,
+        available: next("available")?,
+    })
+}
+
+/// Reads the output of `btrfs filesystem usage -b`: the `Used` of its `Data` profile line, such
+/// as `Data,RAID1: Size:1073741824, Used:536870912 (50.00%)`, which is counted once whatever the
+/// profile, and its `Free (estimated)` overall.
+#[cfg(feature = "btrfs")]
+pub fn parse_btrfs_usage(output: &str) -> Result<NativeUsage> {
+    let mut used = None;
+    let mut available = None;
+    for line in output.lines().map(str::trim) {
+        if let Some(free) = line.strip_prefix("Free (estimated):") {
+            let free = free.split_whitespace().next().unwrap_or_default();
+            available = Some(
+                free.parse::<u64>()
+                    .with_context(|| format!("Could not parse free space of btrfs: {}", free))?,
+            );
+        } else if line.starts_with("Data,") {
+            let data = line
+                .split_once("Used:")
+                .map(|(_, used)| used.trim_start())
+                .and_then(|used| used.split(|c: char| !c.is_ascii_digit()).next())
+                .ok_or_else(|| anyhow!("Missing used space in btrfs data line: {}", line))?;
+            let data = data
+                .parse::<u64>()
+                .with_context(|| format!("Could not parse used space of btrfs: {}", line))?;
+            used = Some(used.unwrap_or(0) + data);
+        }
+    }
+    match (used, available) {
+        (Some(used), Some(available)) => Ok(NativeUsage { used, available }),
+        (None, _) => Err(anyhow!(
+            "btrfs filesystem usage did not list any data profile"
+        )),
+        (_, None) => Err(anyhow!(
+            "btrfs filesystem usage did not estimate the free space"
+        )),
+    }
+}
+
+/// Usage of a mount point from the tools of its filesystem, given its device as listed in
+/// `/proc/mounts`, if Monitrust was built with a backend for it.
+// Without both backends, the device or the mount point goes unused.
+#[cfg_attr(not(all(feature = "zfs", feature = "btrfs")), allow(unused_variables))]
+pub(crate) fn read(
+    filesystem: &str,
+    device: &str,
+    mount: &str,
+) -> Option<anyhow::Result<(Backend, NativeUsage)>> {
+    match filesystem {
+        // The device of a ZFS mount point is its dataset, such as `rpool/home`.
+        #[cfg(feature = "zfs")]
+        "zfs" => Some(
+            run("zfs", &["list", "-Hp", "-o", "used,available", device])
+                .and_then(|output| parse_zfs_list(&output))
+                .map(|usage| (Backend::Zfs, usage)),
+        ),
+        #[cfg(feature = "btrfs")]
+        "btrfs" => Some(
+            run("btrfs", &["filesystem", "usage", "-b", mount])
+                .and_then(|output| parse_btrfs_usage(&output))
+                .map(|usage| (Backend::Btrfs, usage)),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_fraction_is_out_of_the_used_and_available_space() {
+        let usage = NativeUsage {
+            used: 3,
+            available: 1,
+        };
+        assert_eq!(usage.free_fraction(), 0.25);
+        let empty = NativeUsage {
+            used: 0,
+            available: 0,
+        };
+        assert_eq!(empty.free_fraction(), 0.0);
+    }
+
+    #[cfg(feature = "zfs")]
+    #[test]
+    fn parses_zfs_list() {
+        let usage = parse_zfs_list(include_str!("../../tests/fixtures/zfs-list.txt")).unwrap();
+        assert_eq!(
+            usage,
+            NativeUsage {
+                used: 1286520832,
+                available: 52563457024,
+            }
+        );
+    }
+
+    #[cfg(feature = "zfs")]
+    #[test]
+    fn fails_on_incomplete_zfs_list() {
+        assert!(parse_zfs_list("").is_err());
+        assert!(parse_zfs_list("1286520832\n").is_err());
+        assert!(parse_zfs_list("1.2G\t49G\n").is_err());
+    }
+
+    #[cfg(feature = "btrfs")]
+    #[test]
+    fn parses_btrfs_usage_once_whatever_the_profile() {
+        let usage =
+            parse_btrfs_usage(include_str!("../../tests/fixtures/btrfs-usage-raid1.txt")).unwrap();
+        assert_eq!(
+            usage,
+            NativeUsage {
+                used: 2143289344,
+                available: 18248695808,
+            }
+        );
+    }
+
+    #[cfg(feature = "btrfs")]
+    #[test]
+    fn sums_the_data_profiles_of_btrfs_usage() {
+        let usage =
+            parse_btrfs_usage(include_str!("../../tests/fixtures/btrfs-usage-mixed.txt")).unwrap();
+        assert_eq!(
+            usage,
+            NativeUsage {
+                used: 536870912 + 1073741824,
+                available: 19321694208,
+            }
+        );
+    }
+
+    #[cfg(feature = "btrfs")]
+    #[test]
+    fn fails_on_incomplete_btrfs_usage() {
+        let output = include_str!("../../tests/fixtures/btrfs-usage-raid1.txt");
+        let without_data: String = output
+            .lines()
+            .filter(|line| !line.starts_with("Data,"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(parse_btrfs_usage(&without_data).is_err());
+        let without_free: String = output
+            .lines()
+            .filter(|line| !line.trim().starts_with("Free (estimated)"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(parse_btrfs_usage(&without_free).is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{deserialize_optional_duration, format_duration, Period};
+use crate::size::format_size;
+use crate::watcher;
+use crate::watcher::presence::{Absence, ExpectPresence};
+use crate::watcher::sampling::{Aggregate, Aggregation, SamplingChecker};
+use crate::watcher::{threshold_bits, ActiveAlert, Severity, Warmup};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    interface: String,
+    sample_window: Option<Duration>,
+    /// Counters of the interface, when they were last read.
+    previous: Mutex<Option<(Instant, Counters, Counters)>>,
+    absence: Absence,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Name of the interface, as listed in `/proc/net/dev`.
+    interface: String,
+    /// How long to measure the interface traffic for at each check. The traffic is measured
+    /// since the previous check by default, the first check only reading the counters.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    sample_window: Option<Duration>,
+}
+
+/// Counters of one direction of an interface, as read from `/proc/net/dev`.
+#[derive(Debug, Clone, Copy)]
+struct Counters {
+    bytes: u64,
+    packets: u64,
+    errors: u64,
+    drops: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Traffic {
+    pub bytes_per_second: f64,
+    pub packets_per_second: f64,
+    /// Errors and drops during the sample window.
+    pub errors: u64,
+    pub drops: u64,
+}
+
+impl Traffic {
+    fn between(before: Counters, after: Counters, seconds: f64) -> Self {
+        Traffic {
+            bytes_per_second: after.bytes.saturating_sub(before.bytes) as f64 / seconds,
+            packets_per_second: after.packets.saturating_sub(before.packets) as f64 / seconds,
+            errors: after.errors.saturating_sub(before.errors),
+            drops: after.drops.saturating_sub(before.drops),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NetUsage {
+    pub interface: String,
+    /// How long the traffic was measured for.
+    pub sample_window: Duration,
+    pub receive: Traffic,
+    pub transmit: Traffic,
+    /// How long no byte was received nor transmitted, e.g. because the counters of the interface
+    /// froze, unset if some were during the sample window.
+    pub idle_for: Option<Duration>,
+}
+
+impl Traffic {
+    fn combine(samples: &[Traffic], aggregation: Aggregation) -> Self {
+        let apply = |value: fn(&Traffic) -> f64| aggregation.apply(samples.iter().map(value));
+        Traffic {
+            bytes_per_second: apply(|t| t.bytes_per_second),
+            packets_per_second: apply(|t| t.packets_per_second),
+            errors: apply(|t| t.errors as f64).round() as u64,
+            drops: apply(|t| t.drops as f64).round() as u64,
+        }
+    }
+}
+
+impl Aggregate for NetUsage {
+    /// Errors and drops are aggregated as the other values, e.g. their most during a sample
+    /// window rather than their total. How long the interface has been idle is the one of the
+    /// last sample.
+    fn combine(samples: Vec<Self>, aggregation: Aggregation) -> Self {
+        let receive = samples.iter().map(|s| s.receive).collect::<Vec<_>>();
+        let transmit = samples.iter().map(|s| s.transmit).collect::<Vec<_>>();
+        NetUsage {
+            receive: Traffic::combine(&receive, aggregation),
+            transmit: Traffic::combine(&transmit, aggregation),
+            idle_for: samples.last().and_then(|s| s.idle_for),
+            ..samples[0].clone()
+        }
+    }
+}
+
+fn read_counters(interface: &str) -> Result<(Counters, Counters)> {
+    let dev_file = "/proc/net/dev";
+    let content = std::fs::read_to_string(dev_file)
+        .context(format!("Could not read network stats at '{}'", dev_file))?;
+    let mut interfaces = Vec::new();
+    // The first two lines are headers.
+    for l in content.lines().skip(2) {
+        let Some((name, values)) = l.split_once(':') else {
+            continue;
+        };
+        let name = name.trim();
+        if name != interface {
+            interfaces.push(name);
+            continue;
+        }
+        let values = values
+            .split_whitespace()
+            .map(|v| {
+                v.parse::<u64>().context(format!(
+                    "Could not convert network counter to integer: {}",
+                    v
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if values.len() < 12 {
+            return Err(anyhow!(
+                "Expected at least 12 counters for interface '{}', found {}",
+                interface,
+                values.len()
+            ));
+        }
+        // Receive counters come first, then transmit counters starting at the ninth column.
+        let counters = |offset: usize| Counters {
+            bytes: values[offset],
+            packets: values[offset + 1],
+            errors: values[offset + 2],
+            drops: values[offset + 3],
+        };
+        return Ok((counters(0), counters(8)));
+    }
+    Err(anyhow!(
+        "Could not find interface '{}' in '{}' (available: {})",
+        interface,
+        dev_file,
+        interfaces.join(", ")
+    ))
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Warmup<NetUsage>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "net");
+        let (receive, transmit) = read_counters(&self.interface)?;
+        let read_at = Instant::now();
+        let (before, after) = match self.sample_window {
+            Some(sample_window) => {
+                sleep(sample_window);
+                let (receive_after, transmit_after) = read_counters(&self.interface)?;
+                (
+                    (read_at, receive, transmit),
+                    (Instant::now(), receive_after, transmit_after),
+                )
+            }
+            None => {
+                let mut previous = self
+                    .previous
+                    .lock()
+                    .map_err(|_| anyhow!("Net sample lock is poisoned"))?;
+                let Some(before) = previous.replace((read_at, receive, transmit)) else {
+                    info!(interface = self.interface, "Priming traffic baseline");
+                    return Ok(Warmup::Priming);
+                };
+                (before, (read_at, receive, transmit))
+            }
+        };
+        let elapsed = after.0.duration_since(before.0);
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let active = after.1.bytes != before.1.bytes || after.2.bytes != before.2.bytes;
+        let usage = NetUsage {
+            interface: self.interface.clone(),
+            sample_window: self
+                .sample_window
+                .unwrap_or(Duration::from_millis(elapsed.as_millis() as u64)),
+            receive: Traffic::between(before.1, after.1, seconds),
+            transmit: Traffic::between(before.2, after.2, seconds),
+            idle_for: self.absence.observe(active, after.0),
+        };
+        info!(
+            interface = usage.interface,
+            receive_bytes_per_second = usage.receive.bytes_per_second,
+            transmit_bytes_per_second = usage.transmit.bytes_per_second,
+            receive_errors = usage.receive.errors,
+            transmit_errors = usage.transmit.errors,
+            receive_drops = usage.receive.drops,
+            transmit_drops = usage.transmit.drops
+        );
+        Ok(Warmup::Ready(usage))
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        match check_result {
+            Warmup::Priming => "Priming the traffic baseline".to_string(),
+            Warmup::Ready(usage) => format!(
+                "{}: {}/s in, {}/s out",
+                usage.interface,
+                format_size(usage.receive.bytes_per_second as u64),
+                format_size(usage.transmit.bytes_per_second as u64)
+            ),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            interface: configuration.interface,
+            sample_window: configuration.sample_window,
+            previous: Mutex::new(None),
+            absence: Absence::default(),
+        }
+    }
+
+    fn probe(&self) -> Result<()> {
+        read_counters(&self.interface).map(|_| ())
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// The threshold is a number of bytes per second.
+    Throughput,
+    /// The threshold is a number of packets per second.
+    PacketRate,
+    /// The threshold is a number of errors during the sample window.
+    Errors,
+    /// The threshold is a number of dropped packets during the sample window.
+    Drops,
+}
+
+impl Metric {
+    fn value(&self, traffic: &Traffic) -> f64 {
+        match self {
+            Metric::Throughput => traffic.bytes_per_second,
+            Metric::PacketRate => traffic.packets_per_second,
+            Metric::Errors => traffic.errors as f64,
+            Metric::Drops => traffic.drops as f64,
+        }
+    }
+
+    fn describe(&self, value: f64) -> String {
+        match self {
+            Metric::Throughput => format!("throughput is at {:.0} bytes/s", value),
+            Metric::PacketRate => format!("packet rate is at {:.0} packets/s", value),
+            Metric::Errors => format!("errors are at {}", value),
+            Metric::Drops => format!("drops are at {}", value),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct RawAlert {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metric: Option<Metric>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    critical: Option<f64>,
+    #[serde(
+        rename = "ExpectPresence",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    expect_presence: Option<ExpectPresence>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawAlert", into = "RawAlert")]
+pub enum Alert {
+    /// Fires when the `metric` of either direction is above `threshold`, 0 by default so that
+    /// any error or drop does. Above `critical`, the alert is critical rather than a warning.
+    Threshold {
+        metric: Metric,
+        threshold: f64,
+        critical: Option<f64>,
+    },
+    /// Written `{ "ExpectPresence": { "for": "10m" } }`, fires when no byte was received nor
+    /// transmitted for that long, e.g. because the counters of the interface froze.
+    ExpectPresence(ExpectPresence),
+}
+
+impl TryFrom<RawAlert> for Alert {
+    type Error = String;
+
+    fn try_from(raw: RawAlert) -> Result<Self, Self::Error> {
+        match raw.expect_presence {
+            Some(_)
+                if raw.metric.is_some() || raw.threshold.is_some() || raw.critical.is_some() =>
+            {
+                Err(
+                    "'ExpectPresence' can not be combined with 'metric', 'threshold' or \
+                     'critical'"
+                        .into(),
+                )
+            }
+            Some(expect_presence) => Ok(Alert::ExpectPresence(expect_presence)),
+            None => Ok(Alert::Threshold {
+                metric: raw.metric.ok_or("missing field 'metric'")?,
+                threshold: raw.threshold.unwrap_or_default(),
+                critical: raw.critical,
+            }),
+        }
+    }
+}
+
+impl From<Alert> for RawAlert {
+    fn from(alert: Alert) -> Self {
+        match alert {
+            Alert::Threshold {
+                metric,
+                threshold,
+                critical,
+            } => RawAlert {
+                metric: Some(metric),
+                threshold: Some(threshold),
+                critical,
+                expect_presence: None,
+            },
+            Alert::ExpectPresence(expect_presence) => RawAlert {
+                metric: None,
+                threshold: None,
+                critical: None,
+                expect_presence: Some(expect_presence),
+            },
+        }
+    }
+}
+
+impl Eq for Alert {}
+
+/// Hashes the thresholds alone, as before alerts could expect traffic, so that the fingerprints
+/// of existing alerts do not change.
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Alert::Threshold {
+                metric,
+                threshold,
+                critical,
+            } => {
+                metric.hash(state);
+                threshold_bits(*threshold).hash(state);
+                critical.map(threshold_bits).hash(state);
+            }
+            Alert::ExpectPresence(expect_presence) => expect_presence.hash(state),
+        }
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = SamplingChecker<Checker>;
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Alert::Threshold {
+                threshold,
+                critical,
+                ..
+            } => watcher::validate_critical(*threshold, *critical),
+            Alert::ExpectPresence(_) => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let usage = check_result.ready()?;
+        let (metric, threshold, critical) = match self {
+            Alert::Threshold {
+                metric,
+                threshold,
+                critical,
+            } => (metric, *threshold, *critical),
+            Alert::ExpectPresence(expect_presence) => {
+                let idle_for = expect_presence.missing_for(usage.idle_for)?;
+                return Some(ActiveAlert::new(
+                    format!(
+                        "🌐 No traffic on {} for {} (expected within: {}).",
+                        usage.interface,
+                        format_duration(idle_for),
+                        format_duration(expect_presence.for_duration())
+                    ),
+                    Severity::default(),
+                ));
+            }
+        };
+        let (direction, value) = [
+            ("Receive", metric.value(&usage.receive)),
+            ("Transmit", metric.value(&usage.transmit)),
+        ]
+        .into_iter()
+        .filter(|(_, value)| *value > threshold)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        let severity = match critical {
+            Some(critical) if value > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        Some(ActiveAlert::new(
+            format!(
+                "🌐 {} {} on {} over the last {:?} (threshold: {}).",
+                direction,
+                metric.describe(value),
+                usage.interface,
+                usage.sample_window,
+                threshold
+            ),
+            severity,
+        ))
+    }
+
+    /// Alerts firing before the baseline was primed keep firing until traffic is measured.
+    fn is_cleared(&self, check_result: &<Self::Checker as watcher::Checker>::CheckResult) -> bool {
+        check_result.ready().is_some() && self.is_triggered(check_result).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::{Alert as _, Checker as _};
+    use serde_json::json;
+
+    #[test]
+    fn the_first_check_only_primes_the_baseline() {
+        let checker = Checker::new(
+            serde_json::from_value(json!({ "period": "1m", "interface": "lo" })).unwrap(),
+        );
+        // Fires on any traffic, none included.
+        let alert: Alert =
+            serde_json::from_value(json!({ "metric": "Throughput", "threshold": -1.0 })).unwrap();
+
+        let first = checker.check().unwrap();
+        assert!(matches!(first, Warmup::Priming));
+        assert!(alert.is_triggered(&first).is_none());
+        assert!(!alert.is_cleared(&first));
+
+        let second = checker.check().unwrap();
+        assert!(matches!(second, Warmup::Ready(_)));
+        assert!(alert.is_triggered(&second).is_some());
+    }
+
+    #[test]
+    fn frozen_counters_alert_once_absent_for_long_enough() {
+        let alert: Alert =
+            serde_json::from_value(json!({ "ExpectPresence": { "for": "10m" } })).unwrap();
+        let absence = Absence::default();
+        let idle = Traffic {
+            bytes_per_second: 0.0,
+            packets_per_second: 0.0,
+            errors: 0,
+            drops: 0,
+        };
+        let start = Instant::now();
+        // Counters that stopped changing after the first check.
+        let check = |active: bool, minutes: u64| {
+            Warmup::Ready(NetUsage {
+                interface: "eth0".to_string(),
+                sample_window: Duration::from_secs(60),
+                receive: idle,
+                transmit: idle,
+                idle_for: absence.observe(active, start + Duration::from_secs(60 * minutes)),
+            })
+        };
+
+        assert!(alert.is_triggered(&check(true, 0)).is_none());
+        assert!(alert.is_triggered(&check(false, 5)).is_none());
+        let triggered = alert.is_triggered(&check(false, 11)).unwrap();
+
+        assert_eq!(
+            triggered.message,
+            "🌐 No traffic on eth0 for 11m (expected within: 10m)."
+        );
+        assert!(alert.is_triggered(&check(true, 12)).is_none());
+    }
+}
@@ -0,0 +1,341 @@
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::pattern::Pattern;
+use crate::watcher;
+use crate::watcher::{decimal, threshold_bits, ActiveAlert, Evaluation, Severity};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    process: Option<Pattern>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Also watch the processes whose name matches, as found in `/proc/<pid>/comm`. Reading the
+    /// descriptors of processes of other users requires root.
+    #[serde(default)]
+    process: Option<Pattern>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    pub count: u64,
+    /// Unlimited if `None`.
+    pub limit: Option<u64>,
+}
+
+impl Usage {
+    /// Fraction of the limit in use, between 0 and 1.
+    pub fn ratio(&self) -> Option<f64> {
+        match self.limit {
+            Some(limit) if limit > 0 => Some(self.count as f64 / limit as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessFiles {
+    pub pid: u32,
+    pub name: String,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenFiles {
+    pub system: Usage,
+    pub processes: Vec<ProcessFiles>,
+}
+
+/// File handles allocated by the whole system, from `/proc/sys/fs/file-nr`: allocated, free
+/// among the allocated ones (always 0 since Linux 2.6), and maximum.
+fn read_system() -> Result<Usage> {
+    let file_nr = "/proc/sys/fs/file-nr";
+    let content = std::fs::read_to_string(file_nr)
+        .context(format!("Could not read file handles at '{}'", file_nr))?;
+    let values = content
+        .split_whitespace()
+        .map(|v| {
+            v.parse::<u64>()
+                .context(format!("Could not convert file handles to integer: {}", v))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    match values[..] {
+        [allocated, free, max] => Ok(Usage {
+            count: allocated.saturating_sub(free),
+            limit: Some(max),
+        }),
+        _ => Err(anyhow!("Unexpected content of '{}': {}", file_nr, content)),
+    }
+}
+
+/// Soft limit of open files of a process, from the `Max open files` line of its limits file.
+fn read_limit(limits: &str) -> Result<Option<u64>> {
+    let line = limits
+        .lines()
+        .find_map(|l| l.strip_prefix("Max open files"))
+        .ok_or_else(|| anyhow!("Could not find 'Max open files' in process limits"))?;
+    match line.split_whitespace().next() {
+        Some("unlimited") => Ok(None),
+        Some(soft) => soft.parse::<u64>().map(Some).context(format!(
+            "Could not convert open files limit to integer: {}",
+            soft
+        )),
+        None => Err(anyhow!("Missing open files limit in process limits")),
+    }
+}
+
+/// Open descriptors of a process and the content of its limits file.
+fn read_process(path: &Path) -> std::io::Result<(u64, String)> {
+    let count = std::fs::read_dir(path.join("fd"))?.count() as u64;
+    Ok((count, std::fs::read_to_string(path.join("limits"))?))
+}
+
+/// Open files of the process at `path` if its name matches the pattern, or `None` if it does
+/// not or if it exited meanwhile.
+fn read_process_files(path: &Path, pid: u32, pattern: &Pattern) -> Result<Option<ProcessFiles>> {
+    let name = match std::fs::read_to_string(path.join("comm")) {
+        Ok(comm) => comm.trim_end().to_string(),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).context(format!(
+                "Could not read process name at '{}'",
+                path.display()
+            ))
+        }
+    };
+    if !pattern.matches(&name) {
+        return Ok(None);
+    }
+    let (count, limits) = match read_process(path) {
+        Ok(process) => process,
+        // The process exited while the table was being scanned.
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).context(format!(
+                "Could not read open files of process at '{}'",
+                path.display()
+            ))
+        }
+    };
+    let usage = Usage {
+        count,
+        limit: read_limit(&limits)?,
+    };
+    Ok(Some(ProcessFiles { pid, name, usage }))
+}
+
+/// Open files of the processes matching the pattern. Processes that can not be read, such as
+/// the ones of other users without the permission to, are skipped so that the other ones are
+/// still checked.
+fn read_processes(pattern: &Pattern) -> Result<Vec<ProcessFiles>> {
+    let proc_dir = "/proc";
+    let entries = std::fs::read_dir(proc_dir)
+        .context(format!("Could not read process table at '{}'", proc_dir))?;
+    let mut processes = Vec::new();
+    for entry in entries {
+        let entry = entry.context("Could not read entry from process table.")?;
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        match read_process_files(&entry.path(), pid, pattern) {
+            Ok(Some(process)) => processes.push(process),
+            Ok(None) => {}
+            Err(e) => warn!(open_files_error = format!("{:#}", e)),
+        }
+    }
+    processes.sort_by_key(|p| p.pid);
+    Ok(processes)
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = OpenFiles;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "open_files");
+        let system = read_system()?;
+        info!(open_files = system.count, limit = system.limit);
+        let processes = match &self.process {
+            Some(pattern) => read_processes(pattern)?,
+            None => Vec::new(),
+        };
+        for process in &processes {
+            info!(
+                pid = process.pid,
+                process = process.name,
+                open_files = process.usage.count,
+                limit = process.usage.limit
+            );
+        }
+        Ok(OpenFiles { system, processes })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let usage = |usage: &Usage| match usage.limit {
+            Some(limit) => format!("{} of {}", usage.count, limit),
+            None => usage.count.to_string(),
+        };
+        std::iter::once(format!("{} open files", usage(&check_result.system)))
+            .chain(check_result.processes.iter().map(|process| {
+                format!(
+                    "{} ({}): {}",
+                    process.name,
+                    process.pid,
+                    usage(&process.usage)
+                )
+            }))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            process: configuration.process,
+        }
+    }
+
+    fn probe(&self) -> Result<()> {
+        read_system().map(|_| ())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let mut samples = vec![Sample::new(
+            "monitrust_open_files",
+            &[("scope", "system")],
+            check_result.system.count as f64,
+        )];
+        if let Some(limit) = check_result.system.limit {
+            samples.push(Sample::new(
+                "monitrust_open_files_limit",
+                &[("scope", "system")],
+                limit as f64,
+            ));
+        }
+        for process in &check_result.processes {
+            let pid = process.pid.to_string();
+            let labels = [
+                ("scope", "process"),
+                ("process", process.name.as_str()),
+                ("pid", pid.as_str()),
+            ];
+            samples.push(Sample::new(
+                "monitrust_open_files",
+                &labels,
+                process.usage.count as f64,
+            ));
+            if let Some(limit) = process.usage.limit {
+                samples.push(Sample::new(
+                    "monitrust_open_files_limit",
+                    &labels,
+                    limit as f64,
+                ));
+            }
+        }
+        samples
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// Fraction of the limit above which to alert, between 0 and 1.
+    threshold: f64,
+    /// Above this fraction, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl Alert {
+    fn triggered_for(&self, owner: &str, usage: &Usage) -> Option<ActiveAlert> {
+        let ratio = usage.ratio()?;
+        if ratio <= self.threshold {
+            return None;
+        }
+        let severity = match self.critical {
+            Some(critical) if ratio > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        Some(ActiveAlert::new(
+            format!(
+                "📂 Open files of {} are at {} of {} ({}%, threshold: {}%).",
+                owner,
+                usage.count,
+                usage.limit.unwrap_or_default(),
+                decimal(100.0 * ratio),
+                decimal(100.0 * self.threshold)
+            ),
+            severity,
+        ))
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        watcher::validate_fraction("threshold", self.threshold)?;
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    /// Alerts about the system first, then about the first process over the threshold. Each
+    /// one is reported on its own by [`watcher::Alert::evaluate`].
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        self.evaluate(check_result)
+            .into_iter()
+            .find_map(|e| e.triggered)
+    }
+
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        let system = self.triggered_for("the system", &check_result.system);
+        let mut evaluations = vec![Evaluation {
+            subject: "system".to_string(),
+            cleared: system.is_none(),
+            triggered: system,
+        }];
+        for process in &check_result.processes {
+            let triggered = self.triggered_for(
+                &format!("process {} ({})", process.name, process.pid),
+                &process.usage,
+            );
+            evaluations.push(Evaluation {
+                subject: process.pid.to_string(),
+                cleared: triggered.is_none(),
+                triggered,
+            });
+        }
+        evaluations
+    }
+}
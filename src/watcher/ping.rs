@@ -0,0 +1,457 @@
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use tracing::{info, warn};
+
+use crate::duration::{deserialize_duration, Period};
+use crate::metrics::Sample;
+use crate::watcher;
+use crate::watcher::target::{self, TargetCheck};
+use crate::watcher::{decimal, ActiveAlert, Evaluation, Severity};
+
+fn default_count() -> u16 {
+    5
+}
+
+fn default_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(1)
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    hosts: Vec<String>,
+    count: u16,
+    interval: Duration,
+    timeout: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Host name or IP address to ping.
+    #[serde(default)]
+    host: Option<String>,
+    /// Hosts to ping along with `host`, one after the other, each alerting on its own.
+    /// Hostnames are pinged over both IPv4 and IPv6, if they resolve to addresses of both.
+    #[serde(default)]
+    hosts: Vec<String>,
+    /// Echo requests sent at each check.
+    #[serde(default = "default_count")]
+    count: u16,
+    /// Time between two echo requests.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(
+        default = "default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    interval: Duration,
+    /// How long to wait for each reply before counting it as lost.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct PingResult {
+    pub address: IpAddr,
+    pub sent: u16,
+    pub received: u16,
+    /// Average round-trip time of the replies, if any.
+    pub average_rtt: Option<Duration>,
+}
+
+/// Pings of a host, over one address family for hostnames.
+pub type Ping = TargetCheck<PingResult>;
+
+impl PingResult {
+    /// Percentage of requests without a reply.
+    pub fn loss(&self) -> f64 {
+        match self.sent {
+            0 => 0.0,
+            sent => 100.0 * f64::from(sent - self.received) / f64::from(sent),
+        }
+    }
+}
+
+/// ICMP or ICMPv6 socket, along with how its packets are framed.
+struct IcmpSocket {
+    socket: UdpSocket,
+    v6: bool,
+    /// Raw IPv4 sockets receive the IP header along with the ICMP message.
+    with_ip_header: bool,
+    /// Datagram sockets get their identifier assigned by the kernel.
+    identifier: Option<u16>,
+}
+
+/// Computes the Internet checksum of RFC 1071.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|c| u32::from(u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)])))
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+impl IcmpSocket {
+    /// Opens an unprivileged ICMP datagram socket if the system allows it (see the
+    /// `net.ipv4.ping_group_range` sysctl), and a raw socket otherwise, which requires the
+    /// `CAP_NET_RAW` capability.
+    fn open(address: IpAddr) -> Result<Self> {
+        let (domain, protocol, v6) = match address {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, false),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, true),
+        };
+        let (socket, raw) = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+            Ok(socket) => (socket, false),
+            Err(datagram_error) => match Socket::new(domain, Type::RAW, Some(protocol)) {
+                Ok(socket) => (socket, true),
+                Err(e) => {
+                    return Err(anyhow!(
+                        "Could not open an ICMP socket, which requires either an allowed group in \
+                        the net.ipv4.ping_group_range sysctl ({}) or the CAP_NET_RAW capability \
+                        ({})",
+                        datagram_error,
+                        e
+                    ))
+                }
+            },
+        };
+        Ok(IcmpSocket {
+            socket: socket.into(),
+            v6,
+            with_ip_header: raw && !v6,
+            identifier: raw.then(|| std::process::id() as u16),
+        })
+    }
+
+    fn send_echo(&self, address: IpAddr, sequence: u16) -> Result<()> {
+        let request_type = if self.v6 { 128 } else { 8 };
+        let identifier = self.identifier.unwrap_or_default().to_be_bytes();
+        let sequence = sequence.to_be_bytes();
+        let mut packet = [
+            request_type,
+            0,
+            0,
+            0,
+            identifier[0],
+            identifier[1],
+            sequence[0],
+            sequence[1],
+        ];
+        // The kernel computes ICMPv6 checksums itself, as they cover the IP addresses.
+        if !self.v6 {
+            let checksum = checksum(&packet).to_be_bytes();
+            packet[2..4].copy_from_slice(&checksum);
+        }
+        self.socket
+            .send_to(&packet, SocketAddr::new(address, 0))
+            .with_context(|| format!("Could not send echo request to {}", address))?;
+        Ok(())
+    }
+
+    /// Waits for the reply to an echo request until `deadline`, ignoring other ICMP messages.
+    fn receive_reply(&self, address: IpAddr, sequence: u16, deadline: Instant) -> Result<bool> {
+        let reply_type = if self.v6 { 129 } else { 0 };
+        let mut buffer = [0; 1500];
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            self.socket
+                .set_read_timeout(Some(deadline - now))
+                .context("Could not set ICMP socket timeout")?;
+            let (length, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(false)
+                }
+                Err(e) => return Err(e).context("Could not receive ICMP reply"),
+            };
+            let offset = match self.with_ip_header {
+                true => usize::from(buffer[0] & 0x0f) * 4,
+                false => 0,
+            };
+            let Some(message) = buffer[..length].get(offset..offset + 8) else {
+                continue;
+            };
+            let matches_identifier = self
+                .identifier
+                .is_none_or(|i| message[4..6] == i.to_be_bytes());
+            if from.ip() == address
+                && message[0] == reply_type
+                && matches_identifier
+                && message[6..8] == sequence.to_be_bytes()
+            {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Addresses to ping for a host, the first one of each family it resolves to.
+fn resolve(host: &str) -> Result<Vec<(String, IpAddr)>, String> {
+    Ok(target::resolve(host, host, 0)?
+        .into_iter()
+        .filter_map(|(target, addresses)| Some((target, addresses.first()?.ip())))
+        .collect())
+}
+
+impl Checker {
+    fn ping(&self, address: IpAddr) -> Result<PingResult> {
+        let socket = IcmpSocket::open(address)?;
+        let mut round_trips = Vec::new();
+        for sequence in 0..self.count {
+            let start = Instant::now();
+            socket.send_echo(address, sequence)?;
+            if socket.receive_reply(address, sequence, start + self.timeout)? {
+                round_trips.push(start.elapsed());
+            }
+            if sequence + 1 < self.count {
+                sleep(self.interval.saturating_sub(start.elapsed()));
+            }
+        }
+        let received = round_trips.len() as u16;
+        Ok(PingResult {
+            address,
+            sent: self.count,
+            received,
+            average_rtt: (received > 0)
+                .then(|| round_trips.iter().sum::<Duration>() / u32::from(received)),
+        })
+    }
+
+    fn ping_host(&self, host: &str) -> Vec<Ping> {
+        match resolve(host) {
+            Ok(addresses) => addresses
+                .into_iter()
+                .map(|(target, address)| Ping {
+                    outcome: self.ping(address).map_err(|e| format!("{:#}", e)),
+                    target,
+                })
+                .collect(),
+            Err(e) => vec![Ping {
+                target: host.to_string(),
+                outcome: Err(e),
+            }],
+        }
+    }
+}
+
+impl watcher::Checker for Checker {
+    /// Pings of each target, or why they could not be sent.
+    type CheckResult = Vec<Ping>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "ping");
+        let pings = self
+            .hosts
+            .iter()
+            .flat_map(|host| self.ping_host(host))
+            .collect::<Vec<_>>();
+        for ping in &pings {
+            match &ping.outcome {
+                Ok(result) => info!(
+                    target = ping.target,
+                    address = %result.address,
+                    sent = result.sent,
+                    received = result.received,
+                    average_rtt = ?result.average_rtt
+                ),
+                Err(e) => info!(target = ping.target, ping_error = e),
+            }
+        }
+        Ok(pings)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        target::summary(check_result, |ping| {
+            let mut summary = format!("{} of {} replies", ping.received, ping.sent);
+            if let Some(average_rtt) = ping.average_rtt {
+                summary.push_str(&format!(" in {} ms", average_rtt.as_millis()));
+            }
+            summary
+        })
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        let period = configuration.period.0;
+        let count = configuration.count.max(1);
+        let hosts = target::hosts(configuration.host, configuration.hosts);
+        let duration = (configuration.interval * u32::from(count - 1) + configuration.timeout)
+            * hosts.len().max(1) as u32;
+        if duration >= period {
+            warn!(
+                ?duration,
+                ?period,
+                "Pinging takes longer than the period, checks will time out"
+            );
+        }
+        Checker {
+            period,
+            hosts,
+            count,
+            interval: configuration.interval,
+            timeout: configuration.timeout,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.hosts.is_empty() {
+            return Err(anyhow!("Either 'host' or 'hosts' must be set"));
+        }
+        Ok(())
+    }
+
+    /// Fails if a host can not be resolved, or if ICMP sockets can not be opened.
+    fn probe(&self) -> Result<()> {
+        for host in &self.hosts {
+            let addresses = resolve(host).map_err(|e| anyhow!("Host {}: {}", host, e))?;
+            for (_, address) in addresses {
+                IcmpSocket::open(address)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for ping in check_result {
+            let Ok(result) = &ping.outcome else {
+                continue;
+            };
+            let labels = [("target", ping.target.as_str())];
+            samples.push(Sample::new(
+                "monitrust_ping_loss_ratio",
+                &labels,
+                result.loss() / 100.0,
+            ));
+            if let Some(rtt) = result.average_rtt {
+                samples.push(Sample::new(
+                    "monitrust_ping_rtt_seconds",
+                    &labels,
+                    rtt.as_secs_f64(),
+                ));
+            }
+        }
+        samples
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Percentage of lost requests above which to alert. Losing every request always alerts.
+    #[serde(default)]
+    max_loss_percent: Option<u8>,
+    /// Alert when the average round-trip time is longer than this.
+    #[serde(default)]
+    max_rtt_ms: Option<u64>,
+}
+
+impl Alert {
+    fn triggered_for(&self, ping: &Ping) -> Option<ActiveAlert> {
+        let target = &ping.target;
+        let result = match &ping.outcome {
+            Ok(result) => result,
+            Err(e) => {
+                return Some(ActiveAlert::new(
+                    format!("📡 Could not ping {}: {}.", target, e),
+                    Severity::Critical,
+                ))
+            }
+        };
+        let Some(rtt) = result.average_rtt else {
+            return Some(ActiveAlert::new(
+                format!(
+                    "📡 Host {} ({}) is unreachable, none of {} echo requests got a reply.",
+                    target, result.address, result.sent
+                ),
+                Severity::Critical,
+            ));
+        };
+        let loss = result.loss();
+        let lossy = self
+            .max_loss_percent
+            .is_some_and(|max| loss > f64::from(max));
+        let slow = self
+            .max_rtt_ms
+            .is_some_and(|max| rtt > Duration::from_millis(max));
+        if !lossy && !slow {
+            return None;
+        }
+        Some(ActiveAlert::new(
+            format!(
+                "📡 Pinging {} lost {:.0}% of {} requests, with an average round trip of {} ms \
+                (max loss: {}, max round trip: {}).",
+                target,
+                loss,
+                result.sent,
+                decimal(rtt.as_secs_f64() * 1000.0),
+                self.max_loss_percent
+                    .map_or("none".to_string(), |m| format!("{}%", m)),
+                self.max_rtt_ms
+                    .map_or("none".to_string(), |m| format!("{} ms", m)),
+            ),
+            Severity::default(),
+        ))
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self.max_loss_percent {
+            Some(max_loss_percent) if max_loss_percent > 100 => Err(anyhow!(
+                "'max_loss_percent' is a percentage, not {}",
+                max_loss_percent
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result.iter().find_map(|p| self.triggered_for(p))
+    }
+
+    /// Evaluates each target on its own, so that they fire and resolve independently.
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|ping| {
+                let triggered = self.triggered_for(ping);
+                Evaluation {
+                    subject: ping.target.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
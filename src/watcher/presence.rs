@@ -0,0 +1,53 @@
+//! Alerts on values that are expected to keep showing up, such as lines written to a log file,
+//! firing once they have been absent or zero for long enough.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::duration::deserialize_duration;
+
+/// Triggers once the value of the watcher has been absent or zero for `for`, such as `"10m"`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExpectPresence {
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(rename = "for", deserialize_with = "deserialize_duration")]
+    for_duration: Duration,
+}
+
+impl ExpectPresence {
+    /// How long the value has been absent, if that is long enough to trigger the alert.
+    pub(crate) fn missing_for(&self, absent_for: Option<Duration>) -> Option<Duration> {
+        absent_for.filter(|absent_for| *absent_for >= self.for_duration)
+    }
+
+    pub(crate) fn for_duration(&self) -> Duration {
+        self.for_duration
+    }
+}
+
+/// When a checker last saw its value present, across its checks: since it first checked if it
+/// never did.
+#[derive(Debug, Default)]
+pub(crate) struct Absence(Mutex<Option<Instant>>);
+
+impl Absence {
+    fn last_seen(&self) -> MutexGuard<'_, Option<Instant>> {
+        // The instant is only ever replaced, a panic can not leave it inconsistent.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records whether the value is present at `now`, returning for how long it has been absent
+    /// if it is not.
+    pub(crate) fn observe(&self, present: bool, now: Instant) -> Option<Duration> {
+        let mut last_seen = self.last_seen();
+        let since = *last_seen.get_or_insert(now);
+        if present {
+            *last_seen = Some(now);
+            return None;
+        }
+        Some(now.saturating_duration_since(since))
+    }
+}
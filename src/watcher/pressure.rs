@@ -0,0 +1,404 @@
+//! Pressure stall information of Linux: the share of time tasks were stalled waiting for the
+//! CPU, memory or IO, which rises as soon as a resource is contended, well before an OOM kill.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::watcher;
+use crate::watcher::{decimal, threshold_bits, ActiveAlert, Severity};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Cpu,
+    Memory,
+    Io,
+}
+
+impl Resource {
+    const ALL: [Resource; 3] = [Resource::Cpu, Resource::Memory, Resource::Io];
+
+    fn path(&self) -> String {
+        format!("/proc/pressure/{}", self.name())
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::Io => "io",
+        }
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resource::Cpu => write!(f, "CPU"),
+            Resource::Memory => write!(f, "Memory"),
+            Resource::Io => write!(f, "IO"),
+        }
+    }
+}
+
+/// Which tasks a stall counts.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Stall {
+    /// Time at least one task was stalled.
+    #[default]
+    Some,
+    /// Time every task that could run was stalled at once, which is lost time for the whole
+    /// system. Not reported for the CPU of the whole system.
+    Full,
+}
+
+impl Stall {
+    fn name(&self) -> &'static str {
+        match self {
+            Stall::Some => "some",
+            Stall::Full => "full",
+        }
+    }
+}
+
+/// Window the share of stalled time is averaged over.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Window {
+    #[default]
+    TenSeconds,
+    OneMinute,
+    FiveMinutes,
+}
+
+impl Window {
+    const ALL: [Window; 3] = [Window::TenSeconds, Window::OneMinute, Window::FiveMinutes];
+
+    fn seconds(&self) -> u32 {
+        match self {
+            Window::TenSeconds => 10,
+            Window::OneMinute => 60,
+            Window::FiveMinutes => 300,
+        }
+    }
+}
+
+/// Shares of time tasks were stalled, as fractions, over each window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Averages {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+}
+
+impl Averages {
+    pub fn get(&self, window: Window) -> f64 {
+        match window {
+            Window::TenSeconds => self.avg10,
+            Window::OneMinute => self.avg60,
+            Window::FiveMinutes => self.avg300,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pressure {
+    pub resource: Resource,
+    pub some: Averages,
+    /// Unknown for the CPU on kernels before 5.13.
+    pub full: Option<Averages>,
+}
+
+impl Pressure {
+    pub fn averages(&self, stall: Stall) -> Option<&Averages> {
+        match stall {
+            Stall::Some => Some(&self.some),
+            Stall::Full => self.full.as_ref(),
+        }
+    }
+}
+
+/// Reads a line of a pressure file, such as `some avg10=1.53 avg60=0.87 avg300=0.25 total=1234`,
+/// whose averages are percentages.
+fn parse_averages(line: &str) -> Result<Averages> {
+    let average = |name: &str| -> Result<f64> {
+        let value = line
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+            .ok_or_else(|| anyhow!("Missing {} in pressure line: {}", name, line))?;
+        let percent = value
+            .parse::<f64>()
+            .with_context(|| format!("Could not convert {} to float: {}", name, value))?;
+        Ok(percent / 100.0)
+    };
+    Ok(Averages {
+        avg10: average("avg10")?,
+        avg60: average("avg60")?,
+        avg300: average("avg300")?,
+    })
+}
+
+pub fn parse_pressure(resource: Resource, content: &str) -> Result<Pressure> {
+    let line = |stall: Stall| {
+        content
+            .lines()
+            .find(|l| l.split_whitespace().next() == Some(stall.name()))
+    };
+    let some = line(Stall::Some)
+        .ok_or_else(|| anyhow!("Missing 'some' line in {}: {}", resource.path(), content))?;
+    Ok(Pressure {
+        resource,
+        some: parse_averages(some)?,
+        full: line(Stall::Full).map(parse_averages).transpose()?,
+    })
+}
+
+fn read_pressure(resource: Resource) -> Result<Pressure> {
+    let path = resource.path();
+    let content = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Could not read pressure stall information at '{}', which needs Linux 4.20 or \
+             later built with CONFIG_PSI, and not booted with psi=0",
+            path
+        )
+    })?;
+    parse_pressure(resource, &content)
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+}
+
+impl watcher::Checker for Checker {
+    /// Pressure of the CPU, the memory and IO.
+    type CheckResult = Vec<Pressure>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "pressure");
+        Resource::ALL
+            .into_iter()
+            .map(|resource| {
+                let pressure = read_pressure(resource)?;
+                info!(resource = resource.name(), some = ?pressure.some, full = ?pressure.full);
+                Ok(pressure)
+            })
+            .collect()
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for pressure in check_result {
+            for stall in [Stall::Some, Stall::Full] {
+                let Some(averages) = pressure.averages(stall) else {
+                    continue;
+                };
+                for window in Window::ALL {
+                    let window_seconds = window.seconds().to_string();
+                    samples.push(Sample::new(
+                        "monitrust_pressure_stall_ratio",
+                        &[
+                            ("resource", pressure.resource.name()),
+                            ("stall", stall.name()),
+                            ("window_seconds", window_seconds.as_str()),
+                        ],
+                        averages.get(window),
+                    ));
+                }
+            }
+        }
+        samples
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .iter()
+            .map(|pressure| {
+                format!(
+                    "{}: {}% stalled over 10s",
+                    pressure.resource,
+                    decimal(pressure.some.avg10)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+        }
+    }
+
+    /// Fails on kernels without pressure stall information.
+    fn probe(&self) -> Result<()> {
+        Resource::ALL
+            .into_iter()
+            .try_for_each(|resource| read_pressure(resource).map(|_| ()))
+    }
+}
+
+/// Fires when tasks were stalled waiting for `resource` more than `threshold` of the `window`,
+/// as a fraction, e.g. 0.1 for 10% of the last 10 seconds.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    resource: Resource,
+    #[serde(default)]
+    stall: Stall,
+    #[serde(default)]
+    window: Window,
+    threshold: f64,
+    /// Above this value, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.resource.hash(state);
+        self.stall.hash(state);
+        self.window.hash(state);
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        if self.resource == Resource::Cpu && self.stall == Stall::Full {
+            return Err(anyhow!(
+                "'Full' pressure of the CPU is not reported for the whole system"
+            ));
+        }
+        watcher::validate_fraction("threshold", self.threshold)?;
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    fn with_threshold(&self, threshold: f64) -> Option<Self> {
+        Some(Alert {
+            threshold,
+            ..self.clone()
+        })
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let stalled = check_result
+            .iter()
+            .find(|p| p.resource == self.resource)?
+            .averages(self.stall)?
+            .get(self.window);
+        if stalled <= self.threshold {
+            return None;
+        }
+        let severity = match self.critical {
+            Some(critical) if stalled > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        let tasks = match self.stall {
+            Stall::Some => "some tasks",
+            Stall::Full => "all tasks",
+        };
+        Some(
+            ActiveAlert::new(
+                format!(
+                    "🫨 {} pressure: {} were stalled {}% of the last {}s (threshold: {}%).",
+                    self.resource,
+                    tasks,
+                    decimal(100.0 * stalled),
+                    self.window.seconds(),
+                    decimal(100.0 * self.threshold)
+                ),
+                severity,
+            )
+            .annotated([
+                ("resource", self.resource.name().to_string()),
+                ("stall", self.stall.name().to_string()),
+                ("window_seconds", self.window.seconds().to_string()),
+                ("stall_pct", decimal(100.0 * stalled)),
+                ("threshold", decimal(100.0 * self.threshold)),
+            ]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::Alert as _;
+    use serde_json::json;
+
+    const MEMORY: &str = "some avg10=1.53 avg60=12.50 avg300=0.25 total=1234\n\
+                          full avg10=0.00 avg60=8.25 avg300=0.10 total=567\n";
+
+    #[test]
+    fn pressure_files_are_read_as_fractions() {
+        let pressure = parse_pressure(Resource::Memory, MEMORY).unwrap();
+
+        assert_eq!(pressure.some.get(Window::OneMinute), 0.125);
+        assert_eq!(pressure.averages(Stall::Full).unwrap().avg300, 0.001);
+        let cpu = parse_pressure(
+            Resource::Cpu,
+            "some avg10=0.00 avg60=0.00 avg300=0.00 total=0",
+        )
+        .unwrap();
+        assert!(cpu.full.is_none());
+        assert!(parse_pressure(Resource::Io, "").is_err());
+    }
+
+    #[test]
+    fn alerts_name_the_resource_window_and_stalled_share() {
+        let alert: Alert = serde_json::from_value(json!({
+            "resource": "Memory",
+            "stall": "Full",
+            "window": "OneMinute",
+            "threshold": 0.05,
+            "critical": 0.2,
+        }))
+        .unwrap();
+        let pressure = vec![parse_pressure(Resource::Memory, MEMORY).unwrap()];
+
+        let triggered = alert.is_triggered(&pressure).unwrap();
+
+        assert_eq!(
+            triggered.message,
+            "🫨 Memory pressure: all tasks were stalled 8.2% of the last 60s (threshold: 5.0%)."
+        );
+        assert_eq!(triggered.severity, Severity::Warning);
+    }
+
+    #[test]
+    fn full_pressure_of_the_cpu_is_rejected() {
+        let alert: Alert = serde_json::from_value(json!({
+            "resource": "Cpu",
+            "stall": "Full",
+            "threshold": 0.1,
+        }))
+        .unwrap();
+
+        assert!(alert.validate().is_err());
+    }
+}
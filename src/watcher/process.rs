@@ -0,0 +1,268 @@
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{format_duration, Period};
+use crate::pattern::Pattern;
+use crate::size::format_size;
+use crate::watcher;
+use crate::watcher::presence::{Absence, ExpectPresence};
+use crate::watcher::{ActiveAlert, Severity};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    name: Pattern,
+    absence: Absence,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Matched against the process name, as found in `/proc/<pid>/comm`. Note that the kernel
+    /// truncates it to 15 characters.
+    name: Pattern,
+}
+
+#[derive(Debug, Clone)]
+pub struct Processes {
+    pub name: Pattern,
+    pub count: usize,
+    /// Resident memory of all matching processes, in bytes.
+    pub rss: u64,
+    /// How long no process matched, unset if one does.
+    pub absent_for: Option<Duration>,
+}
+
+/// Resident memory of a process in bytes, from the `VmRSS` line of its status file. Kernel
+/// threads have none.
+pub(crate) fn read_rss(status: &str) -> Result<u64> {
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))
+        .map(|v| {
+            let kilobytes = v.trim().trim_end_matches("kB").trim();
+            kilobytes
+                .parse::<u64>()
+                .map(|kb| 1024 * kb)
+                .context(format!("Could not convert RSS to integer: {}", kilobytes))
+        })
+        .unwrap_or(Ok(0))
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Processes;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "process");
+        let checked_at = Instant::now();
+        let proc_dir = "/proc";
+        let entries = std::fs::read_dir(proc_dir)
+            .context(format!("Could not read process table at '{}'", proc_dir))?;
+        let mut count = 0;
+        let mut rss = 0;
+        for entry in entries {
+            let entry = entry.context("Could not read entry from process table.")?;
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+            {
+                continue;
+            }
+            let process_files = std::fs::read_to_string(entry.path().join("comm"))
+                .and_then(|comm| Ok((comm, std::fs::read_to_string(entry.path().join("status"))?)));
+            let (comm, status) = match process_files {
+                Ok(files) => files,
+                // The process exited while the table was being scanned.
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(e).context(format!(
+                        "Could not read process information at '{}'",
+                        entry.path().display()
+                    ))
+                }
+            };
+            if self.name.matches(comm.trim_end()) {
+                count += 1;
+                rss += read_rss(&status)?;
+            }
+        }
+        info!(process = %self.name, count, rss);
+        Ok(Processes {
+            name: self.name.clone(),
+            count,
+            rss,
+            absent_for: self.absence.observe(count > 0, checked_at),
+        })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        match check_result.absent_for {
+            Some(absent_for) if check_result.count == 0 && absent_for.as_secs() > 0 => format!(
+                "No {} process for {}",
+                check_result.name,
+                format_duration(absent_for)
+            ),
+            _ if check_result.count == 0 => format!("No {} process", check_result.name),
+            _ => format!(
+                "{} {} processes using {}",
+                check_result.count,
+                check_result.name,
+                format_size(check_result.rss)
+            ),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            name: configuration.name,
+            absence: Absence::default(),
+        }
+    }
+}
+
+fn default_alert_when_missing() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct RawAlert {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alert_when_missing: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_instances: Option<usize>,
+    #[serde(
+        rename = "ExpectPresence",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    expect_presence: Option<ExpectPresence>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "RawAlert", into = "RawAlert")]
+pub enum Alert {
+    /// Fires as soon as no process matches, unless `alert_when_missing` is false, or when more
+    /// than `max_instances` do, unlimited if omitted.
+    Instances {
+        alert_when_missing: bool,
+        max_instances: Option<usize>,
+    },
+    /// Written `{ "ExpectPresence": { "for": "5m" } }`, fires when no process matched for that
+    /// long, so that a process being restarted does not.
+    ExpectPresence(ExpectPresence),
+}
+
+impl TryFrom<RawAlert> for Alert {
+    type Error = String;
+
+    fn try_from(raw: RawAlert) -> Result<Self, Self::Error> {
+        match raw.expect_presence {
+            Some(_) if raw.alert_when_missing.is_some() || raw.max_instances.is_some() => Err(
+                "'ExpectPresence' can not be combined with 'alert_when_missing' or \
+                 'max_instances'"
+                    .into(),
+            ),
+            Some(expect_presence) => Ok(Alert::ExpectPresence(expect_presence)),
+            None => Ok(Alert::Instances {
+                alert_when_missing: raw
+                    .alert_when_missing
+                    .unwrap_or_else(default_alert_when_missing),
+                max_instances: raw.max_instances,
+            }),
+        }
+    }
+}
+
+impl From<Alert> for RawAlert {
+    fn from(alert: Alert) -> Self {
+        match alert {
+            Alert::Instances {
+                alert_when_missing,
+                max_instances,
+            } => RawAlert {
+                alert_when_missing: Some(alert_when_missing),
+                max_instances,
+                expect_presence: None,
+            },
+            Alert::ExpectPresence(expect_presence) => RawAlert {
+                alert_when_missing: None,
+                max_instances: None,
+                expect_presence: Some(expect_presence),
+            },
+        }
+    }
+}
+
+/// Hashes the options alone, as before alerts could expect processes, so that the fingerprints
+/// of existing alerts do not change.
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Alert::Instances {
+                alert_when_missing,
+                max_instances,
+            } => {
+                alert_when_missing.hash(state);
+                max_instances.hash(state);
+            }
+            Alert::ExpectPresence(expect_presence) => expect_presence.hash(state),
+        }
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let (alert_when_missing, max_instances) = match self {
+            Alert::Instances {
+                alert_when_missing,
+                max_instances,
+            } => (*alert_when_missing, *max_instances),
+            Alert::ExpectPresence(expect_presence) => {
+                let absent_for = expect_presence.missing_for(check_result.absent_for)?;
+                return Some(ActiveAlert::new(
+                    format!(
+                        "⚙️ Process {} has not been running for {} (expected within: {}).",
+                        check_result.name,
+                        format_duration(absent_for),
+                        format_duration(expect_presence.for_duration())
+                    ),
+                    Severity::Critical,
+                ));
+            }
+        };
+        match max_instances {
+            _ if alert_when_missing && check_result.count == 0 => Some(ActiveAlert::new(
+                format!("⚙️ Process {} is not running.", check_result.name),
+                Severity::Critical,
+            )),
+            Some(max) if check_result.count > max => Some(ActiveAlert::new(
+                format!(
+                    "⚙️ Process {} has too many instances: {} (max: {}).",
+                    check_result.name, check_result.count, max
+                ),
+                Severity::default(),
+            )),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Mount points remounted read-only, as the kernel does on I/O errors with `errors=remount-ro`,
+//! which goes unnoticed until something fails to write.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::watcher;
+use crate::watcher::{ActiveAlert, Evaluation, Severity};
+
+const MOUNTS_FILE: &str = "/proc/mounts";
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    mounts: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Mount points expected to be writable, such as `/` or `/var/lib/postgresql`.
+    mounts: Vec<String>,
+}
+
+/// What is mounted on a mount point, and how.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub device: String,
+    pub filesystem: String,
+    /// Whether it is mounted with the `ro` option.
+    pub read_only: bool,
+}
+
+/// Mount of each watched mount point, by mount point. The ones that are not mounted are
+/// missing.
+pub type Mounts = BTreeMap<String, Mount>;
+
+/// Decodes the octal escapes, such as `\040` for spaces, of mount points in `/proc/mounts`.
+pub(crate) fn unescape(mount: &str) -> String {
+    let mut unescaped = Vec::with_capacity(mount.len());
+    let mut bytes = mount.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            unescaped.push(b);
+            continue;
+        }
+        let digits = bytes.clone().take(3).collect::<Vec<_>>();
+        match std::str::from_utf8(&digits)
+            .ok()
+            .and_then(|d| u8::from_str_radix(d, 8).ok())
+        {
+            Some(decoded) if digits.len() == 3 => {
+                unescaped.push(decoded);
+                bytes.nth(2);
+            }
+            _ => unescaped.push(b),
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+/// Reads the mounts of `mounts` in the content of `/proc/mounts`, the last one mounted over
+/// each mount point if several are.
+pub fn parse_mounts(content: &str, mounts: &[String]) -> Mounts {
+    content
+        .lines()
+        .filter_map(|l| {
+            let mut split = l.split_whitespace();
+            let (device, mount, filesystem, options) =
+                (split.next()?, split.next()?, split.next()?, split.next()?);
+            let mount = unescape(mount);
+            if !mounts.contains(&mount) {
+                return None;
+            }
+            let mounted = Mount {
+                device: unescape(device),
+                filesystem: filesystem.to_string(),
+                read_only: options.split(',').any(|o| o == "ro"),
+            };
+            Some((mount, mounted))
+        })
+        .collect()
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Mounts;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "read_only");
+        let content = std::fs::read_to_string(MOUNTS_FILE)
+            .with_context(|| format!("Could not read {}", MOUNTS_FILE))?;
+        let mounts = parse_mounts(&content, &self.mounts);
+        for mount in &self.mounts {
+            match mounts.get(mount) {
+                Some(mounted) => info!(mount, read_only = mounted.read_only),
+                None => info!(mount, "Not mounted"),
+            }
+        }
+        Ok(mounts)
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        check_result
+            .iter()
+            .map(|(mount, mounted)| {
+                Sample::new(
+                    "monitrust_mount_read_only",
+                    &[("mount", mount.as_str())],
+                    if mounted.read_only { 1.0 } else { 0.0 },
+                )
+            })
+            .collect()
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let read_only = check_result
+            .iter()
+            .filter(|(_, mount)| mount.read_only)
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>();
+        if read_only.is_empty() {
+            "No read-only mount".to_string()
+        } else {
+            format!("Read-only: {}", read_only.join(", "))
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            mounts: configuration.mounts,
+        }
+    }
+
+    /// Fails where `/proc/mounts` can not be read, and for mount points that are not mounted.
+    fn probe(&self) -> Result<()> {
+        let mounts = self.check()?;
+        let missing = self
+            .mounts
+            .iter()
+            .filter(|mount| !mounts.contains_key(*mount))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Not mounted: {}", missing.join(", ")))
+        }
+    }
+}
+
+/// Fires critical as soon as one of the mount points is mounted read-only. Mount points that
+/// get unmounted resolve their alert.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {}
+
+impl Alert {
+    fn triggered_for(&self, mount: &str, mounted: &Mount) -> Option<ActiveAlert> {
+        if !mounted.read_only {
+            return None;
+        }
+        Some(
+            ActiveAlert::new(
+                format!(
+                    "🔒 {} went read-only ({} on {}), expected writable.",
+                    mount, mounted.filesystem, mounted.device
+                ),
+                Severity::Critical,
+            )
+            .annotated([
+                ("mount", mount.to_string()),
+                ("device", mounted.device.clone()),
+                ("filesystem", mounted.filesystem.clone()),
+            ]),
+        )
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    /// Alert about the first mount point that is read-only. Each mount point is reported on its
+    /// own by [`watcher::Alert::evaluate`].
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result
+            .iter()
+            .find_map(|(mount, mounted)| self.triggered_for(mount, mounted))
+    }
+
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|(mount, mounted)| {
+                let triggered = self.triggered_for(mount, mounted);
+                Evaluation {
+                    subject: mount.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::Alert as _;
+
+    /// `/var/lib/postgresql` remounted read-only after I/O errors, over its read-write mount.
+    const MOUNTS: &str = "\
+/dev/sda1 / ext4 rw,relatime,errors=remount-ro 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/sdb1 /var/lib/postgresql ext4 rw,relatime 0 0
+/dev/sdb1 /var/lib/postgresql ext4 ro,relatime 0 0
+/dev/sdc1 /mnt/backup\\040disk vfat ro,relatime 0 0
+";
+
+    fn watched(mounts: &[&str]) -> Mounts {
+        parse_mounts(
+            MOUNTS,
+            &mounts.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn reads_the_last_mount_of_the_watched_mount_points() {
+        let mounts = watched(&["/", "/var/lib/postgresql", "/mnt/backup disk", "/srv"]);
+
+        assert_eq!(
+            mounts.keys().collect::<Vec<_>>(),
+            ["/", "/mnt/backup disk", "/var/lib/postgresql"]
+        );
+        assert!(!mounts["/"].read_only);
+        assert!(mounts["/var/lib/postgresql"].read_only);
+        assert!(mounts["/mnt/backup disk"].read_only);
+    }
+
+    #[test]
+    fn a_read_only_remount_fires_critical_for_its_mount_point() {
+        let evaluations = Alert {}.evaluate(&watched(&["/", "/var/lib/postgresql"]));
+
+        assert_eq!(evaluations.len(), 2);
+        assert_eq!(evaluations[0].subject, "/");
+        assert!(evaluations[0].triggered.is_none() && evaluations[0].cleared);
+        let triggered = evaluations[1].triggered.as_ref().unwrap();
+        assert_eq!(evaluations[1].subject, "/var/lib/postgresql");
+        assert_eq!(triggered.severity, Severity::Critical);
+        assert_eq!(
+            triggered.message,
+            "🔒 /var/lib/postgresql went read-only (ext4 on /dev/sdb1), expected writable."
+        );
+        assert_eq!(triggered.annotations["device"], "/dev/sdb1");
+    }
+}
@@ -0,0 +1,192 @@
+//! Recorded check results, to reproduce how alerts fired without the system they were captured
+//! on: [`capture`] appends what a checker reads to a file, from which a [`ReplayChecker`] yields
+//! the results back in sequence to [`MultiWatcher::replay`].
+//!
+//! [`MultiWatcher::replay`]: crate::watcher::MultiWatcher::replay
+
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Instant, SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::clock::{Clock, SystemClock};
+use crate::watcher::{Checker, Timestamped};
+
+fn serialize_time<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer
+        .serialize_str(&DateTime::<Utc>::from(*time).to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&time)
+        .map(SystemTime::from)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Result of a check, as a line of a snapshot file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot<T> {
+    /// When the data of the check was captured, as an RFC 3339 date.
+    #[serde(
+        serialize_with = "serialize_time",
+        deserialize_with = "deserialize_time"
+    )]
+    pub sampled_at: SystemTime,
+    pub result: T,
+}
+
+/// Checks once with `checker` and appends the result to the snapshot file at `path`, one JSON
+/// object per line, creating it if needed.
+pub fn capture<C: Checker>(checker: &C, path: &Path) -> Result<()>
+where
+    C::CheckResult: Serialize,
+{
+    let check = checker.timestamped_check(&SystemClock)?;
+    let snapshot = Snapshot {
+        sampled_at: check.sampled_at,
+        result: check.result,
+    };
+    let mut line = serde_json::to_string(&snapshot).context("Could not serialize snapshot")?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Could not write snapshot to {}", path.display()))
+}
+
+/// Yields recorded results of checks of `C` in sequence, instead of reading the system. Results
+/// keep the time between their captures, so that `for`, cooldowns and escalations elapse as
+/// they did when they were captured.
+pub struct ReplayChecker<C: Checker> {
+    snapshots: Mutex<VecDeque<Snapshot<C::CheckResult>>>,
+    /// Capture time of the first result, and when it is replayed.
+    start: Option<(SystemTime, Instant)>,
+}
+
+impl<C: Checker> ReplayChecker<C> {
+    pub fn new(snapshots: Vec<Snapshot<C::CheckResult>>) -> Self {
+        let start = snapshots
+            .first()
+            .map(|first| (first.sampled_at, SystemClock.now()));
+        ReplayChecker {
+            snapshots: Mutex::new(snapshots.into()),
+            start,
+        }
+    }
+
+    /// Reads the snapshots written by [`capture`].
+    pub fn from_file(path: &Path) -> Result<Self>
+    where
+        C::CheckResult: DeserializeOwned,
+    {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read snapshots at {}", path.display()))?;
+        let snapshots = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).with_context(|| {
+                    format!(
+                        "Could not parse snapshot {} of {}",
+                        index + 1,
+                        path.display()
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if snapshots.is_empty() {
+            return Err(anyhow!("No snapshot in {}", path.display()));
+        }
+        Ok(ReplayChecker::new(snapshots))
+    }
+
+    fn snapshots(&self) -> MutexGuard<'_, VecDeque<Snapshot<C::CheckResult>>> {
+        // Snapshots are only ever popped, a panic can not leave them inconsistent.
+        self.snapshots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The next recorded result, timed from the first one, until there is none left.
+    pub fn next_check(&self) -> Option<Timestamped<C::CheckResult>> {
+        let snapshot = self.snapshots().pop_front()?;
+        let sampled = match self.start {
+            Some((first, replayed)) => {
+                replayed
+                    + snapshot
+                        .sampled_at
+                        .duration_since(first)
+                        .unwrap_or_default()
+            }
+            None => SystemClock.now(),
+        };
+        Some(Timestamped {
+            result: snapshot.result,
+            sampled,
+            sampled_at: snapshot.sampled_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_store::memory::Memory;
+    use crate::watcher::{memory, MultiWatcher, SerializedMultiWatcher};
+    use serde_json::json;
+
+    #[test]
+    fn captured_snapshots_replay_through_the_alerts() {
+        let path = std::env::temp_dir().join(format!("monitrust-replay-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let configuration = json!({
+            "configuration": { "period": "1m" },
+            // Fires on any capture, as some memory is always available.
+            "alerts": [{ "threshold": {
+                "metric": "Available", "value": 0, "unit": "MB", "operator": ">=",
+            } }],
+        });
+        let serialized: SerializedMultiWatcher<memory::Alert> =
+            serde_json::from_value(configuration).unwrap();
+        let checker = <memory::Checker as Checker>::new(serialized.configuration.clone());
+
+        capture(&checker, &path).unwrap();
+        let replay = ReplayChecker::<memory::Checker>::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        let replay = replay.unwrap();
+        let reporter = RecordingReporter::new();
+        let fired = MultiWatcher::new(serialized)
+            .replay(&replay, &reporter, &Memory::new(Default::default()))
+            .unwrap();
+
+        assert_eq!(fired, 1);
+        assert_eq!(reporter.alerts().len(), 1);
+        assert!(replay.next_check().is_none());
+    }
+
+    #[test]
+    fn files_without_snapshots_are_rejected() {
+        let path =
+            std::env::temp_dir().join(format!("monitrust-replay-empty-{}", std::process::id()));
+        std::fs::write(&path, "\n").unwrap();
+
+        let replay = ReplayChecker::<memory::Checker>::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(replay
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("No snapshot in"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Checkers taking several samples over their period, combined into a single check result, so
+//! that short peaks are not missed between two checks.
+
+use std::sync::{Arc, Mutex, Once, Weak};
+use std::thread::{self, sleep};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::clock::{Clock, SystemClock};
+use crate::metrics::Sample;
+use crate::watcher::{Checker, Timestamped, Warmup};
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aggregation {
+    Mean,
+    Max,
+    /// 95th percentile, by the nearest-rank method.
+    P95,
+}
+
+impl Aggregation {
+    /// Aggregates non-empty values, returning 0 otherwise.
+    pub fn apply(&self, values: impl Iterator<Item = f64>) -> f64 {
+        let mut values = values.collect::<Vec<_>>();
+        if values.is_empty() {
+            return 0.0;
+        }
+        match self {
+            Aggregation::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregation::Max => values.into_iter().fold(f64::MIN, f64::max),
+            Aggregation::P95 => {
+                values.sort_by(f64::total_cmp);
+                let rank = (0.95 * values.len() as f64).ceil() as usize;
+                values[rank.max(1) - 1]
+            }
+        }
+    }
+}
+
+/// Check results that can be combined from several samples.
+pub trait Aggregate: Sized {
+    /// Combines non-empty samples, in the order they were taken.
+    fn combine(samples: Vec<Self>, aggregation: Aggregation) -> Self;
+}
+
+impl Aggregate for f64 {
+    fn combine(samples: Vec<Self>, aggregation: Aggregation) -> Self {
+        aggregation.apply(samples.into_iter())
+    }
+}
+
+/// Combines the samples measured after the baseline was primed, `Priming` if there are none.
+impl<T: Aggregate> Aggregate for Warmup<T> {
+    fn combine(samples: Vec<Self>, aggregation: Aggregation) -> Self {
+        let ready = samples
+            .into_iter()
+            .filter_map(|sample| match sample {
+                Warmup::Priming => None,
+                Warmup::Ready(measure) => Some(measure),
+            })
+            .collect::<Vec<_>>();
+        match ready.is_empty() {
+            true => Warmup::Priming,
+            false => Warmup::Ready(T::combine(ready, aggregation)),
+        }
+    }
+}
+
+fn default_samples() -> u32 {
+    1
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sampling {
+    /// Samples taken at each period, evenly spaced, the last one when checking.
+    #[serde(default = "default_samples")]
+    samples: u32,
+    aggregation: Aggregation,
+}
+
+/// Configuration of the sampled checker, along with how to sample it.
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration<C> {
+    #[serde(flatten)]
+    configuration: C,
+    #[serde(default)]
+    sampling: Option<Sampling>,
+}
+
+#[derive(Debug)]
+struct Shared<C: Checker> {
+    checker: C,
+    /// Samples taken since the last check.
+    samples: Mutex<Vec<C::CheckResult>>,
+}
+
+/// Samples a checker in the background between two checks, so that checking does not take
+/// longer. Without sampling configured, it only defers to the checker.
+#[derive(Debug)]
+pub struct SamplingChecker<C: Checker> {
+    shared: Arc<Shared<C>>,
+    sampling: Option<Sampling>,
+    sampler: Once,
+}
+
+impl<C> SamplingChecker<C>
+where
+    C: Checker + Send + Sync + 'static,
+    C::CheckResult: Send,
+{
+    /// Takes the samples in between checks from a thread, which stops once the checker is
+    /// dropped.
+    fn start_sampler(&self, sampling: Sampling) {
+        let shared = Arc::downgrade(&self.shared);
+        let interval = self.shared.checker.period() / sampling.samples;
+        let kept = sampling.samples as usize - 1;
+        thread::spawn(move || loop {
+            sleep(interval);
+            let Some(shared) = Weak::upgrade(&shared) else {
+                break;
+            };
+            match shared.checker.check() {
+                Ok(sample) => {
+                    if let Ok(mut samples) = shared.samples.lock() {
+                        samples.push(sample);
+                        // Samples pile up while checks are failing.
+                        let extra = samples.len().saturating_sub(kept);
+                        samples.drain(..extra);
+                    }
+                }
+                Err(e) => warn!(sampling_error = ?e),
+            }
+        });
+    }
+}
+
+impl<C> Checker for SamplingChecker<C>
+where
+    C: Checker + Send + Sync + 'static,
+    C::CheckResult: Aggregate + Send,
+{
+    type CheckResult = C::CheckResult;
+    type Configuration = Configuration<C::Configuration>;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        self.timestamped_check(&SystemClock)
+            .map(|check| check.result)
+    }
+
+    /// Stamped with the time of the last sample, taken when checking.
+    fn timestamped_check(&self, clock: &dyn Clock) -> Result<Timestamped<Self::CheckResult>> {
+        let Some(sampling) = self.sampling.filter(|s| s.samples > 1) else {
+            return self.shared.checker.timestamped_check(clock);
+        };
+        self.sampler.call_once(|| self.start_sampler(sampling));
+        let last = self.shared.checker.timestamped_check(clock)?;
+        let mut samples = std::mem::take(
+            &mut *self
+                .shared
+                .samples
+                .lock()
+                .map_err(|_| anyhow!("Sample lock is poisoned"))?,
+        );
+        samples.push(last.result);
+        Ok(Timestamped {
+            result: C::CheckResult::combine(samples, sampling.aggregation),
+            ..last
+        })
+    }
+
+    fn period(&self) -> Duration {
+        self.shared.checker.period()
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        SamplingChecker {
+            shared: Arc::new(Shared {
+                checker: C::new(configuration.configuration),
+                samples: Mutex::new(Vec::new()),
+            }),
+            sampling: configuration.sampling,
+            sampler: Once::new(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.sampling.is_some_and(|s| s.samples == 0) {
+            return Err(anyhow!("At least one sample must be taken"));
+        }
+        self.shared.checker.validate()
+    }
+
+    fn probe(&self) -> Result<()> {
+        self.shared.checker.probe()
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        self.shared.checker.metrics(check_result)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        self.shared.checker.summary(check_result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+    struct ConstantConfiguration {
+        value: u64,
+    }
+
+    /// Measures the same value at each check.
+    #[derive(Debug)]
+    struct Constant(u64);
+
+    impl Checker for Constant {
+        type CheckResult = f64;
+        type Configuration = ConstantConfiguration;
+
+        fn check(&self) -> Result<f64> {
+            Ok(self.0 as f64)
+        }
+
+        fn period(&self) -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn new(configuration: ConstantConfiguration) -> Self {
+            Constant(configuration.value)
+        }
+    }
+
+    #[test]
+    fn max_aggregation_picks_the_peak() {
+        assert_eq!(f64::combine(vec![12.0, 97.5, 40.0], Aggregation::Max), 97.5);
+    }
+
+    #[test]
+    fn priming_samples_are_left_out_of_the_aggregate() {
+        let samples = vec![Warmup::Priming, Warmup::Ready(12.0), Warmup::Ready(40.0)];
+        assert!(matches!(
+            Warmup::combine(samples, Aggregation::Max),
+            Warmup::Ready(40.0)
+        ));
+        assert!(matches!(
+            Warmup::<f64>::combine(vec![Warmup::Priming], Aggregation::Max),
+            Warmup::Priming
+        ));
+    }
+
+    #[test]
+    fn aggregates_means_and_percentiles() {
+        assert_eq!(Aggregation::Mean.apply([1.0, 2.0, 6.0].into_iter()), 3.0);
+        let hundred = (1..=100).map(f64::from);
+        assert_eq!(Aggregation::P95.apply(hundred), 95.0);
+        assert_eq!(Aggregation::P95.apply([7.0].into_iter()), 7.0);
+        assert_eq!(Aggregation::Max.apply(std::iter::empty()), 0.0);
+    }
+
+    #[test]
+    fn checks_combine_the_samples_taken_since_the_last_one() {
+        let checker = SamplingChecker::<Constant>::new(
+            serde_json::from_value(serde_json::json!({
+                "value": 10,
+                "sampling": { "samples": 3, "aggregation": "Max" },
+            }))
+            .unwrap(),
+        );
+        // Samples taken by the sampler, which is not started.
+        checker.sampler.call_once(|| {});
+        checker.shared.samples.lock().unwrap().extend([4.0, 25.0]);
+
+        assert_eq!(checker.check().unwrap(), 25.0);
+        assert_eq!(checker.check().unwrap(), 10.0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Monitrust watching itself: whether its scheduler keeps up with the periods of the watchers,
+//! the resources of its own process, and the alerts piling up in the queues of its reporters.
+
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{format_duration, Period};
+use crate::size::{deserialize_size, format_size};
+use crate::status::{self, Lag};
+use crate::watcher;
+use crate::watcher::process::read_rss;
+use crate::watcher::state::AlertConfiguration;
+use crate::watcher::{
+    decimal, percent, threshold_bits, ActiveAlert, SerializedMultiWatcher, Severity,
+    WatcherConfiguration,
+};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    /// When the previous check started, along with the CPU time Monitrust had used by then.
+    previous: Mutex<Option<(Instant, Option<Duration>)>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfUsage {
+    /// Run started the most periods of its watcher late, among the ones the scheduler started
+    /// since the previous check.
+    pub worst_lag: Option<Lag>,
+    /// Resident memory of the process, unless it could not be read.
+    pub rss: Option<u64>,
+    /// CPU time used since the previous check, as a fraction of a core. Unset on the first
+    /// check, or if it could not be read.
+    pub cpu: Option<f64>,
+    /// Alerts waiting in the queues of the reporters.
+    pub queued_alerts: usize,
+}
+
+/// Time the process spent on a CPU, from the first field of its scheduler statistics.
+fn read_cpu_time() -> Option<Duration> {
+    let schedstat = std::fs::read_to_string("/proc/self/schedstat").ok()?;
+    let nanoseconds = schedstat.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_nanos(nanoseconds))
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = SelfUsage;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "self_monitor");
+        let now = Instant::now();
+        let cpu_time = read_cpu_time();
+        let previous = self
+            .previous
+            .lock()
+            .ok()
+            .and_then(|mut previous| previous.replace((now, cpu_time)));
+        let since = previous.map_or_else(|| now.checked_sub(self.period), |(at, _)| Some(at));
+        let worst_lag = status::lags_since(since.unwrap_or(now))
+            .into_iter()
+            .max_by(|a, b| a.periods().total_cmp(&b.periods()));
+        let cpu = match (previous, cpu_time) {
+            (Some((at, Some(previous))), Some(cpu_time)) if now > at => {
+                Some(cpu_time.saturating_sub(previous).as_secs_f64() / (now - at).as_secs_f64())
+            }
+            _ => None,
+        };
+        let rss = std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| read_rss(&status).ok());
+        Ok(SelfUsage {
+            worst_lag,
+            rss,
+            cpu,
+            queued_alerts: status::queued_alerts(),
+        })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let mut summary = Vec::new();
+        if let Some(rss) = check_result.rss {
+            summary.push(format!("{} of memory", format_size(rss)));
+        }
+        if let Some(cpu) = check_result.cpu {
+            summary.push(format!("{} of a CPU core", percent(cpu)));
+        }
+        summary.push(format!("{} alerts queued", check_result.queued_alerts));
+        if let Some(lag) = check_result
+            .worst_lag
+            .as_ref()
+            .filter(|l| l.lag.as_secs() > 0)
+        {
+            summary.push(format!(
+                "{} ran {} late",
+                lag.kind,
+                format_duration(lag.lag)
+            ));
+        }
+        summary.join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            previous: Mutex::new(None),
+        }
+    }
+}
+
+fn default_max_periods() -> f64 {
+    2.0
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// Fires when the scheduler started a run later than `max_periods` periods of its watcher,
+    /// e.g. because too few workers are left for the configured watchers.
+    TickLag {
+        #[serde(default = "default_max_periods")]
+        max_periods: f64,
+    },
+    /// Fires when the resident memory of Monitrust is above `max`, such as `"200 MiB"`.
+    Memory {
+        #[schemars(schema_with = "crate::schema::size")]
+        #[serde(deserialize_with = "deserialize_size")]
+        max: u64,
+    },
+    /// Fires when Monitrust used more than `threshold` of a CPU core since the previous check,
+    /// as a fraction.
+    Cpu { threshold: f64 },
+    /// Fires when more than `max` alerts wait in the queues of the reporters, such as the ones
+    /// of rate limited reporters.
+    QueuedAlerts { max: usize },
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Alert::TickLag { max_periods } => {
+                0.hash(state);
+                threshold_bits(*max_periods).hash(state);
+            }
+            Alert::Memory { max } => {
+                1.hash(state);
+                max.hash(state);
+            }
+            Alert::Cpu { threshold } => {
+                2.hash(state);
+                threshold_bits(*threshold).hash(state);
+            }
+            Alert::QueuedAlerts { max } => {
+                3.hash(state);
+                max.hash(state);
+            }
+        }
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Alert::Cpu { threshold } => watcher::validate_fraction("threshold", *threshold),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        match self {
+            Alert::TickLag { max_periods } => {
+                let lag = check_result
+                    .worst_lag
+                    .filter(|lag| lag.periods() > *max_periods)?;
+                Some(
+                    ActiveAlert::new(
+                        format!(
+                            "⏱️ Scheduler is falling behind: a {} watcher started {}s late, {} \
+                             periods of {}s (threshold: {} periods).",
+                            lag.kind,
+                            decimal(lag.lag.as_secs_f64()),
+                            decimal(lag.periods()),
+                            decimal(lag.period.as_secs_f64()),
+                            decimal(*max_periods)
+                        ),
+                        Severity::default(),
+                    )
+                    .annotated([
+                        ("kind", lag.kind.to_string()),
+                        ("lag_seconds", decimal(lag.lag.as_secs_f64())),
+                        ("lag_periods", decimal(lag.periods())),
+                        ("threshold", decimal(*max_periods)),
+                    ]),
+                )
+            }
+            Alert::Memory { max } => {
+                let rss = check_result.rss.filter(|rss| rss > max)?;
+                Some(
+                    ActiveAlert::new(
+                        format!(
+                            "🧠 Monitrust uses {} of memory (threshold: {}).",
+                            format_size(rss),
+                            format_size(*max)
+                        ),
+                        Severity::default(),
+                    )
+                    .annotated([
+                        ("rss_bytes", rss.to_string()),
+                        ("threshold", max.to_string()),
+                    ]),
+                )
+            }
+            Alert::Cpu { threshold } => {
+                let cpu = check_result.cpu.filter(|cpu| cpu > threshold)?;
+                Some(
+                    ActiveAlert::new(
+                        format!(
+                            "🔥 Monitrust uses {}% of a CPU core (threshold: {}%).",
+                            decimal(100.0 * cpu),
+                            decimal(100.0 * threshold)
+                        ),
+                        Severity::default(),
+                    )
+                    .annotated([
+                        ("usage_pct", decimal(100.0 * cpu)),
+                        ("threshold", decimal(100.0 * threshold)),
+                    ]),
+                )
+            }
+            Alert::QueuedAlerts { max } if check_result.queued_alerts > *max => Some(
+                ActiveAlert::new(
+                    format!(
+                        "📬 {} alerts are waiting in the queues of the reporters (max: {}).",
+                        check_result.queued_alerts, max
+                    ),
+                    Severity::default(),
+                )
+                .annotated([
+                    ("queued_alerts", check_result.queued_alerts.to_string()),
+                    ("threshold", max.to_string()),
+                ]),
+            ),
+            Alert::QueuedAlerts { .. } => None,
+        }
+    }
+
+    /// Alerts on a measure that could not be taken, such as the CPU usage on the first check,
+    /// keep firing until it can be.
+    fn is_cleared(&self, check_result: &<Self::Checker as watcher::Checker>::CheckResult) -> bool {
+        let measured = match self {
+            Alert::Memory { .. } => check_result.rss.is_some(),
+            Alert::Cpu { .. } => check_result.cpu.is_some(),
+            Alert::TickLag { .. } | Alert::QueuedAlerts { .. } => true,
+        };
+        measured && self.is_triggered(check_result).is_none()
+    }
+}
+
+/// The self-monitor the scheduler runs unless told not to, checking every minute that it keeps
+/// up with the periods of the watchers.
+pub(crate) fn built_in() -> WatcherConfiguration {
+    WatcherConfiguration::SelfMonitor(SerializedMultiWatcher::new(
+        Configuration {
+            period: Period(Duration::from_secs(60)),
+        },
+        vec![AlertConfiguration::new(Alert::TickLag {
+            max_periods: default_max_periods(),
+        })],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::{Alert as _, Checker as _};
+    use serde_json::json;
+
+    /// A run of a watcher with a period of a minute, started `lag` late.
+    fn lag(lag: Duration) -> Lag {
+        Lag {
+            at: Instant::now(),
+            kind: "disk_space",
+            lag,
+            period: Duration::from_secs(60),
+        }
+    }
+
+    fn usage(lag: Duration) -> SelfUsage {
+        SelfUsage {
+            worst_lag: Some(self::lag(lag)),
+            rss: None,
+            cpu: None,
+            queued_alerts: 0,
+        }
+    }
+
+    #[test]
+    fn a_scheduler_falling_behind_trips_the_tick_lag_alert() {
+        let checker = Checker::new(serde_json::from_value(json!({ "period": "1m" })).unwrap());
+        let alert: Alert = serde_json::from_value(json!({ "TickLag": {} })).unwrap();
+        // As the scheduler records a run it started three periods late.
+        status::record_lag(lag(Duration::from_secs(180)));
+
+        let check = checker.check().unwrap();
+
+        let triggered = alert.is_triggered(&check).unwrap();
+        assert!(
+            triggered
+                .message
+                .starts_with("⏱️ Scheduler is falling behind"),
+            "{}",
+            triggered.message
+        );
+        assert!(check.worst_lag.unwrap().periods() >= 3.0);
+    }
+
+    #[test]
+    fn runs_late_by_fewer_periods_than_the_threshold_do_not_alert() {
+        let alert: Alert = serde_json::from_value(json!({ "TickLag": {} })).unwrap();
+
+        assert!(alert
+            .is_triggered(&usage(Duration::from_secs(90)))
+            .is_none());
+        let triggered = alert
+            .is_triggered(&usage(Duration::from_secs(150)))
+            .unwrap();
+        assert_eq!(
+            triggered.message,
+            "⏱️ Scheduler is falling behind: a disk_space watcher started 150.0s late, 2.5 \
+             periods of 60.0s (threshold: 2.0 periods)."
+        );
+    }
+}
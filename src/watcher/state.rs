@@ -0,0 +1,1769 @@
+use std::any::type_name;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::{field, info, info_span, warn, Span};
+
+use crate::alert_reporter::{AlertChange, AlertReporter};
+use crate::alert_store::{AlertStore, SavedFiring, SavedPending, WatcherSnapshot};
+use crate::clock::{Clock, SystemClock};
+use crate::duration::{deserialize_duration, deserialize_optional_duration};
+use crate::maintenance::Schedule;
+use crate::metrics::{self, Sample};
+use crate::observer::{self, AlertEvent, CheckResultSnapshot};
+use crate::severity_override;
+use crate::stable_hash::{self, StableHasher};
+use crate::status::{self, CheckDuration, FiringAlert, WatcherStatus};
+use crate::templating::render_template;
+use crate::watcher::{
+    hostname, threshold_bits, ActiveAlert, Escalation, Evaluation, Severity, Timestamped,
+};
+
+fn default_max_transitions() -> usize {
+    4
+}
+
+fn default_flap_window() -> Duration {
+    Duration::from_secs(30 * 60)
+}
+
+/// Detects alerts that keep firing and resolving, to report them once as flapping instead.
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FlapDetection {
+    /// Number of times an alert may fire or resolve within the window without flapping.
+    #[serde(default = "default_max_transitions")]
+    max_transitions: usize,
+    /// An alert stops flapping once it has not fired nor resolved for that long.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(
+        default = "default_flap_window",
+        deserialize_with = "deserialize_duration"
+    )]
+    window: Duration,
+}
+
+/// Step of an escalation, reached once a critical alert has been firing for `after`.
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EscalationTier {
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(deserialize_with = "deserialize_duration")]
+    after: Duration,
+    /// Name of the reporter to report the escalated alert to, instead of all of them.
+    #[serde(default)]
+    reporter: Option<String>,
+}
+
+/// Threshold that replaces the one of an alert while its window is open, e.g. a higher load
+/// allowed during business hours, see [`Alert::with_threshold`].
+///
+/// [`Alert::with_threshold`]: crate::watcher::Alert::with_threshold
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ScheduledThreshold {
+    when: Schedule,
+    /// In the unit of the threshold it replaces.
+    threshold: f64,
+}
+
+impl Eq for ScheduledThreshold {}
+
+impl Hash for ScheduledThreshold {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.when.hash(state);
+        threshold_bits(self.threshold).hash(state);
+    }
+}
+
+/// An alert, along with the options that apply to every kind of alert.
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AlertConfiguration<A> {
+    #[serde(flatten)]
+    alert: A,
+    /// Minimum time between two reports of an alert that keeps firing.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    cooldown: Option<Duration>,
+    /// Labels attached to the alert, overriding the watcher's labels with the same keys.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    flap_detection: Option<FlapDetection>,
+    /// How long the alert must keep triggering before it fires, so that short spikes do not.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, alias = "for", deserialize_with = "deserialize_duration")]
+    for_duration: Duration,
+    /// Tiers through which a critical alert that keeps firing is reported again.
+    #[serde(default)]
+    escalation: Vec<EscalationTier>,
+    /// What the alert means, for reporters to show along with its message.
+    #[serde(default)]
+    description: Option<String>,
+    /// Where to find what to do when the alert fires.
+    #[serde(default)]
+    runbook_url: Option<String>,
+    /// Thresholds to use instead of the one of the alert while their window is open, the first
+    /// one listed if several are.
+    #[serde(default)]
+    threshold_schedule: Vec<ScheduledThreshold>,
+    /// Fingerprint of the alert instead of the derived one, to group and resolve alerts the way
+    /// other incident tools do, such as `"{team}/{host}/{kind}:{subject}"`. It takes the labels
+    /// of the alert as placeholders, along with `kind`, `subject` and `host`, and must contain
+    /// `{kind}` and `{subject}` so that the alerts of other watchers and subjects stay apart.
+    #[serde(default)]
+    fingerprint_template: Option<String>,
+}
+
+impl<A> AlertConfiguration<A> {
+    /// The alert with the default options: no cooldown, labels, flap detection, `for_duration`
+    /// nor escalation.
+    pub fn new(alert: A) -> Self {
+        AlertConfiguration {
+            alert,
+            cooldown: None,
+            labels: BTreeMap::new(),
+            flap_detection: None,
+            for_duration: Duration::ZERO,
+            escalation: Vec::new(),
+            description: None,
+            runbook_url: None,
+            threshold_schedule: Vec::new(),
+            fingerprint_template: None,
+        }
+    }
+}
+
+/// Which alerts of a run are reported only once, when several alerts of the watcher trigger
+/// them, such as two mount globs matching the same mount point.
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Deduplication {
+    /// Alerts with the same fingerprint.
+    #[default]
+    Fingerprint,
+    /// Alerts with the same fingerprint or the same message, e.g. from two alerts whose
+    /// thresholds are both crossed.
+    Message,
+}
+
+/// Drops the alerts of a run that were triggered earlier in the same run, keeping the order of
+/// the other ones. Escalations are told apart from the alert they escalate.
+fn deduplicate(alerts: Vec<ActiveAlert>, deduplication: Deduplication) -> Vec<ActiveAlert> {
+    let mut fingerprints = BTreeSet::new();
+    let mut messages = BTreeSet::new();
+    alerts
+        .into_iter()
+        .filter(|alert| {
+            let tier = alert.escalation.as_ref().map(|e| e.tier);
+            let new_fingerprint = fingerprints.insert((alert.fingerprint.clone(), tier));
+            let new_message = messages.insert(alert.message.clone());
+            let new =
+                new_fingerprint && (deduplication == Deduplication::Fingerprint || new_message);
+            if !new {
+                info!(duplicate_alert = ?alert);
+            }
+            new
+        })
+        .collect()
+}
+
+fn default_check_retry_delay() -> Duration {
+    Duration::from_secs(1)
+}
+
+/// Options that apply to every kind of watcher.
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WatcherOptions {
+    /// Report failing checks as critical alerts, instead of only logging them.
+    #[serde(default)]
+    alert_on_check_failure: bool,
+    /// Labels attached to every alert of the watcher, such as `env` or `team`.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    /// How long a check may take before it is abandoned and considered failed. Defaults to the
+    /// period of the checker.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    check_timeout: Option<Duration>,
+    /// How many times a failed check is retried before the check counts as failed, so that a
+    /// transient error neither fails the run nor alerts.
+    #[serde(default)]
+    check_retries: u32,
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(
+        default = "default_check_retry_delay",
+        deserialize_with = "deserialize_duration"
+    )]
+    check_retry_delay: Duration,
+    /// Period to check at instead while any alert of the watcher fires critical, so that it is
+    /// noticed sooner when it resolves or gets worse. Never longer than the period.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    critical_period: Option<Duration>,
+    #[serde(default)]
+    deduplicate: Deduplication,
+    /// Delay of the first run after the watcher is scheduled, so that watchers can be staggered
+    /// at startup instead of all running at once.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    initial_delay: Duration,
+    /// Report only what changed since the previous run through `report_diff`, skipping the
+    /// alerts that keep firing with the same severity, instead of reporting every triggered
+    /// alert once its cooldown is over.
+    #[serde(default)]
+    report_diff: bool,
+}
+
+impl Default for WatcherOptions {
+    fn default() -> Self {
+        WatcherOptions {
+            alert_on_check_failure: false,
+            labels: BTreeMap::new(),
+            check_timeout: None,
+            check_retries: 0,
+            check_retry_delay: default_check_retry_delay(),
+            critical_period: None,
+            deduplicate: Deduplication::default(),
+            initial_delay: Duration::ZERO,
+            report_diff: false,
+        }
+    }
+}
+
+/// What a watcher remembers about one of its alerts between two runs.
+#[derive(Debug)]
+struct AlertState {
+    /// Hash of the alert's configuration, identifying it in fingerprints.
+    rule: u64,
+    cooldown: Option<Duration>,
+    /// Labels of the watcher merged with the ones of the alert.
+    labels: BTreeMap<String, String>,
+    /// Subjects the alert fired for during the previous run.
+    firing: BTreeMap<String, Firing>,
+    for_duration: Duration,
+    /// Since when the alert has been triggering for each subject, while it is not firing yet.
+    pending: BTreeMap<String, Instant>,
+    flap_detection: Option<FlapDetection>,
+    /// When the alert fired or resolved for each subject, within the flap detection window.
+    transitions: BTreeMap<String, VecDeque<Instant>>,
+    /// Flapping alert reported for each flapping subject, whose reports are suppressed.
+    flapping: BTreeMap<String, ActiveAlert>,
+    /// Sorted by how long the alert must have been firing.
+    escalation: Vec<EscalationTier>,
+    description: Option<String>,
+    runbook_url: Option<String>,
+    threshold_schedule: Vec<ScheduledThreshold>,
+    fingerprint_template: Option<String>,
+}
+
+/// Drops the transitions that happened more than `window` before `now`.
+fn forget_before(transitions: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while transitions
+        .front()
+        .is_some_and(|t| now.duration_since(*t) > window)
+    {
+        transitions.pop_front();
+    }
+}
+
+impl AlertState {
+    /// Fingerprint of the alert for a subject, rendered from its template if it has one.
+    fn fingerprint(&self, kind: &str, hostname: &str, subject: &str) -> String {
+        let Some(template) = &self.fingerprint_template else {
+            return fingerprint(kind, self.rule, subject);
+        };
+        let mut fields = self.labels.clone();
+        fields.extend([
+            ("kind".to_string(), kind.to_string()),
+            ("rule".to_string(), format!("{:016x}", self.rule)),
+            ("subject".to_string(), subject.to_string()),
+            ("host".to_string(), hostname.to_string()),
+        ]);
+        render_template(template, &fields)
+    }
+
+    /// Records that the alert fired or resolved for a subject. If that happened too often, the
+    /// alert is added to `triggered` as flapping.
+    fn transitioned(
+        &mut self,
+        subject: &str,
+        alert: &ActiveAlert,
+        now: Instant,
+        triggered: &mut Vec<ActiveAlert>,
+    ) {
+        let Some(flap_detection) = &self.flap_detection else {
+            return;
+        };
+        let transitions = self.transitions.entry(subject.to_string()).or_default();
+        transitions.push_back(now);
+        forget_before(transitions, now, flap_detection.window);
+        if transitions.len() <= flap_detection.max_transitions
+            || self.flapping.contains_key(subject)
+        {
+            return;
+        }
+        let flapping = ActiveAlert {
+            message: format!(
+                "🔁 Alert is flapping, it fired or resolved {} times in the last {:?}: {}",
+                transitions.len(),
+                flap_detection.window,
+                alert.message
+            ),
+            fingerprint: format!("{}:flapping", alert.fingerprint),
+            ..alert.clone()
+        };
+        info!(severity = ?flapping.severity, firing_alert = ?flapping);
+        observer::emit(|| AlertEvent::Flapping {
+            fingerprint: alert.fingerprint.clone(),
+        });
+        self.flapping.insert(subject.to_string(), flapping.clone());
+        triggered.push(flapping);
+    }
+
+    fn is_flapping(&self, subject: &str) -> bool {
+        self.flapping.contains_key(subject)
+    }
+
+    /// Escalates a critical alert through the next tier once it has been firing for long
+    /// enough, adding it to `triggered` marked with the tier.
+    fn escalate(&self, firing: &mut Firing, now: Instant, triggered: &mut Vec<ActiveAlert>) {
+        let Some(tier) = self.escalation.get(firing.escalated) else {
+            return;
+        };
+        if firing.alert.severity < Severity::Critical
+            || now.duration_since(firing.since) < tier.after
+        {
+            return;
+        }
+        firing.escalated += 1;
+        let escalated = ActiveAlert {
+            message: format!(
+                "⏫ Escalated, firing for more than {:?}: {}",
+                tier.after, firing.alert.message
+            ),
+            escalation: Some(Escalation {
+                tier: firing.escalated,
+                reporter: tier.reporter.clone(),
+            }),
+            ..firing.alert.clone()
+        };
+        info!(severity = ?escalated.severity, escalated_alert = ?escalated);
+        observer::emit(|| AlertEvent::Escalated {
+            fingerprint: escalated.fingerprint.clone(),
+            tier: firing.escalated,
+        });
+        triggered.push(escalated);
+    }
+
+    /// Forgets transitions older than the flap detection window. Subjects that did not change
+    /// state within the window stop flapping: their flapping alert is resolved, and the alert is
+    /// added to `triggered` if it is still firing, or told resolved at `resolved_at` otherwise.
+    fn stabilize(
+        &mut self,
+        now: Instant,
+        resolved_at: SystemTime,
+        triggered: &mut Vec<ActiveAlert>,
+        resolved: &mut Vec<ActiveAlert>,
+    ) {
+        let Some(flap_detection) = &self.flap_detection else {
+            return;
+        };
+        for transitions in self.transitions.values_mut() {
+            forget_before(transitions, now, flap_detection.window);
+        }
+        self.transitions.retain(|_, t| !t.is_empty());
+        let stable = self
+            .flapping
+            .keys()
+            .filter(|s| !self.transitions.contains_key(*s))
+            .cloned()
+            .collect::<Vec<_>>();
+        for subject in stable {
+            let Some(flapping) = self.flapping.remove(&subject) else {
+                continue;
+            };
+            resolved.push(flapping.clone());
+            if let Some(firing) = self.firing.get_mut(&subject) {
+                info!(severity = ?firing.alert.severity, firing_alert = ?firing.alert);
+                observer::emit(|| AlertEvent::Firing(Box::new(firing.alert.clone())));
+                triggered.push(firing.alert.clone());
+                firing.last_reported = now;
+            } else if let Some(fingerprint) = flapping.fingerprint.strip_suffix(":flapping") {
+                observer::emit(|| AlertEvent::Resolved {
+                    fingerprint: fingerprint.to_string(),
+                    resolved_at,
+                });
+            }
+        }
+    }
+}
+
+/// An alert that fired for one subject.
+#[derive(Debug)]
+struct Firing {
+    /// What the alert triggered during the previous run.
+    alert: ActiveAlert,
+    last_reported: Instant,
+    /// When the alert started firing, for escalations.
+    since: Instant,
+    /// Escalation tiers the alert went through since it started firing.
+    escalated: usize,
+}
+
+impl Firing {
+    fn is_cooling_down(&self, cooldown: Option<Duration>, now: Instant) -> bool {
+        cooldown.is_some_and(|cooldown| now < self.last_reported + cooldown)
+    }
+}
+
+/// Number of recent checks the duration summary of a watcher covers.
+const CHECK_DURATIONS: usize = 20;
+
+/// What a watcher remembers between two runs.
+#[derive(Debug)]
+pub(crate) struct WatcherState {
+    alerts: Vec<AlertState>,
+    /// Kind of watcher, identifying it in fingerprints.
+    kind: &'static str,
+    /// Identifies the watcher in its status, from the kind and the whole configuration.
+    id: String,
+    hostname: String,
+    options: WatcherOptions,
+    /// Alert reported for the last failing check, until a check succeeds again.
+    check_failure: Option<ActiveAlert>,
+    /// How long the recent checks took, the latest last.
+    check_durations: VecDeque<Duration>,
+    /// When a check last succeeded, if one ever did.
+    last_success: Option<SystemTime>,
+    /// Samples of the last check, for the observer hooks.
+    samples: Vec<Sample>,
+    /// Summary of the last successful check, see [`Checker::summary`].
+    ///
+    /// [`Checker::summary`]: crate::watcher::Checker::summary
+    summary: String,
+    /// Whether what the alert store saved for the watcher was restored, on its first run.
+    restored: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl WatcherState {
+    pub(crate) fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// Span of a run of the watcher, so that what happens during the run can be told apart from
+    /// the runs of other watchers. The duration of the check and the number of alerts fired are
+    /// recorded once known.
+    pub(crate) fn span(&self) -> Span {
+        info_span!(
+            "watcher_run",
+            kind = self.kind,
+            watcher = %self.id,
+            labels = ?self.options.labels,
+            check_duration = field::Empty,
+            alerts_fired = field::Empty,
+        )
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Clock the checks are stamped with, see [`crate::watcher::Checker::timestamped_check`].
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&self.clock)
+    }
+
+    /// Period of the next run, the critical period while an alert fires critical.
+    pub(crate) fn period(&self, period: Duration) -> Duration {
+        match self.options.critical_period {
+            Some(critical_period) if self.is_critical() => critical_period.min(period),
+            _ => period,
+        }
+    }
+
+    pub(crate) fn initial_delay(&self) -> Duration {
+        self.options.initial_delay
+    }
+
+    /// Threshold scheduled for each alert at the current time, if any.
+    pub(crate) fn scheduled_thresholds(&self) -> Vec<Option<f64>> {
+        let now = DateTime::<Local>::from(self.clock.system_now());
+        self.alerts
+            .iter()
+            .map(|state| {
+                state
+                    .threshold_schedule
+                    .iter()
+                    .find(|scheduled| scheduled.when.is_open(now))
+                    .map(|scheduled| scheduled.threshold)
+            })
+            .collect()
+    }
+
+    /// Fails if the fingerprint template of the alert at `index` lacks a placeholder that keeps
+    /// its fingerprints apart from the ones of other watchers, alerts or subjects.
+    pub(crate) fn validate_fingerprint_template(&self, index: usize) -> Result<()> {
+        let Some(template) = &self.alerts[index].fingerprint_template else {
+            return Ok(());
+        };
+        for placeholder in ["{kind}", "{rule}", "{subject}"] {
+            if !template.contains(placeholder) {
+                return Err(anyhow!(
+                    "'fingerprint_template' '{}' lacks {}, its alerts would share fingerprints",
+                    template,
+                    placeholder
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every threshold scheduled for the alert at `index`, to validate the alert with each.
+    pub(crate) fn threshold_schedule(&self, index: usize) -> impl Iterator<Item = f64> + '_ {
+        self.alerts[index]
+            .threshold_schedule
+            .iter()
+            .map(|scheduled| scheduled.threshold)
+    }
+
+    fn is_critical(&self) -> bool {
+        self.alerts
+            .iter()
+            .flat_map(|a| a.firing.values())
+            .any(|f| f.alert.severity == Severity::Critical)
+    }
+
+    pub(crate) fn check_timeout(&self, period: Duration) -> Duration {
+        self.options.check_timeout.unwrap_or(period)
+    }
+
+    /// Tells how long to wait before retrying a check that failed on its `attempt`th retry, 0
+    /// being the first check, or the error to fail the check with once no retry is left.
+    pub(crate) fn retry_check(&self, attempt: u32, e: anyhow::Error) -> Result<Duration> {
+        let (retries, delay) = (self.options.check_retries, self.options.check_retry_delay);
+        if attempt < retries {
+            warn!(check_error = format!("{:#}", e), retry_in = ?delay, "Retrying check");
+            Ok(delay)
+        } else if retries > 0 {
+            Err(e.context(format!("Failed {} attempts", attempt + 1)))
+        } else {
+            Err(e)
+        }
+    }
+
+    /// Records how long a check took and whether it succeeded, as metrics, in the status of the
+    /// watcher and in the current span, see [`WatcherState::span`].
+    pub(crate) fn record_check(&mut self, duration: Duration, succeeded: bool) {
+        Span::current().record("check_duration", duration.as_secs_f64());
+        if self.check_durations.len() == CHECK_DURATIONS {
+            self.check_durations.pop_front();
+        }
+        self.check_durations.push_back(duration);
+        if succeeded {
+            self.last_success = Some(self.clock.system_now());
+        }
+        let labels = [("kind", self.kind), ("watcher", self.id.as_str())];
+        let mut samples = vec![
+            Sample::new(
+                "monitrust_check_duration_seconds",
+                &labels,
+                duration.as_secs_f64(),
+            ),
+            Sample::new(
+                "monitrust_watcher_up",
+                &labels,
+                if succeeded { 1.0 } else { 0.0 },
+            ),
+        ];
+        if let Some(last_success) = self.last_success {
+            samples.push(Sample::new(
+                "monitrust_watcher_last_success_timestamp_seconds",
+                &labels,
+                last_success
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+            ));
+        }
+        metrics::record(self.kind, &self.options.labels, samples);
+    }
+
+    fn check_duration(&self) -> Option<CheckDuration> {
+        let last = self.check_durations.back()?;
+        let total = self.check_durations.iter().sum::<Duration>();
+        Some(CheckDuration {
+            last_seconds: last.as_secs_f64(),
+            average_seconds: total.as_secs_f64() / self.check_durations.len() as f64,
+            max_seconds: self.check_durations.iter().max()?.as_secs_f64(),
+        })
+    }
+
+    /// Records the samples of a successful check, along with the labels of the watcher, and its
+    /// summary for the status endpoint.
+    pub(crate) fn record_result(&mut self, samples: Vec<Sample>, summary: String) {
+        self.summary = summary;
+        self.samples = samples.clone();
+        metrics::record(self.kind, &self.options.labels, samples);
+    }
+
+    /// Splits alert configurations into the alerts themselves and the watcher's initial state.
+    pub(crate) fn new<A: Hash, C: Hash>(
+        checker_configuration: &C,
+        configurations: Vec<AlertConfiguration<A>>,
+        options: WatcherOptions,
+    ) -> (Vec<A>, Self) {
+        let mut hasher = StableHasher::new();
+        checker_configuration.hash(&mut hasher);
+        configurations.hash(&mut hasher);
+        options.hash(&mut hasher);
+        let id = format!("{}:{:016x}", kind::<A>(), hasher.finish());
+        let (alerts, states) = configurations
+            .into_iter()
+            .map(|c| {
+                let mut labels = options.labels.clone();
+                labels.extend(c.labels);
+                let mut escalation = c.escalation;
+                escalation.sort_by_key(|tier| tier.after);
+                let state = AlertState {
+                    rule: stable_hash::hash(&c.alert),
+                    cooldown: c.cooldown,
+                    labels,
+                    firing: BTreeMap::new(),
+                    for_duration: c.for_duration,
+                    pending: BTreeMap::new(),
+                    flap_detection: c.flap_detection,
+                    transitions: BTreeMap::new(),
+                    flapping: BTreeMap::new(),
+                    escalation,
+                    description: c.description,
+                    runbook_url: c.runbook_url,
+                    threshold_schedule: c.threshold_schedule,
+                    fingerprint_template: c.fingerprint_template,
+                };
+                (c.alert, state)
+            })
+            .unzip();
+        let state = WatcherState {
+            alerts: states,
+            kind: kind::<A>(),
+            id,
+            hostname: hostname(),
+            options,
+            check_failure: None,
+            check_durations: VecDeque::new(),
+            last_success: None,
+            samples: Vec::new(),
+            summary: String::new(),
+            restored: false,
+            clock: Arc::new(SystemClock),
+        };
+        (alerts, state)
+    }
+
+    /// Handles the outcome of a check. Failures are reported as alerts if the watcher is
+    /// configured to, in which case `Ok(None)` is returned; they are propagated otherwise.
+    pub(crate) fn checked<T: Debug>(
+        &mut self,
+        check_result: Result<T>,
+        alert_reporter: &dyn AlertReporter,
+    ) -> Result<Option<T>> {
+        match check_result {
+            Ok(check_result) => {
+                if let Some(resolved) = self.check_failure.take() {
+                    if self.options.report_diff {
+                        let change = AlertChange::Resolved(resolved.clone());
+                        report_diff(&[change], alert_reporter);
+                    } else {
+                        report_resolved(&resolved, alert_reporter);
+                    }
+                    emit_resolved(&resolved, self.clock.system_now());
+                }
+                Ok(Some(check_result))
+            }
+            Err(e) if self.options.alert_on_check_failure => {
+                let alert = severity_override::overridden(
+                    ActiveAlert::new(format!("❌ Check failed: {:#}", e), Severity::Critical)
+                        .detected(
+                            &self.hostname,
+                            self.clock.system_now(),
+                            &self.options.labels,
+                            self.kind,
+                            format!("{}:check_failure", self.kind),
+                        ),
+                );
+                let failing = self.check_failure.is_some();
+                if self.options.report_diff && failing {
+                    info!(unchanged_alert = ?alert);
+                } else {
+                    info!(severity = ?alert.severity, firing_alert = ?alert);
+                    observer::reported(&alert);
+                    if !failing {
+                        observer::emit(|| AlertEvent::Firing(Box::new(alert.clone())));
+                    }
+                    if self.options.report_diff {
+                        report_diff(&[AlertChange::Firing(alert.clone())], alert_reporter);
+                    } else if let Err(e) = alert_reporter.report(&alert) {
+                        warn!(alert_reporter = ?e);
+                    }
+                }
+                self.check_failure = Some(alert);
+                self.publish(Err(format!("{:#}", e)), self.clock.system_now());
+                Ok(None)
+            }
+            Err(e) => {
+                self.publish(Err(format!("{:#}", e)), self.clock.system_now());
+                Err(e.context(format!("{} watcher check failed", self.kind)))
+            }
+        }
+    }
+
+    /// Alerts currently firing, including the ones held back by flap detection.
+    fn firing(&self) -> Vec<FiringAlert> {
+        let alerts = self.alerts.iter().flat_map(|state| {
+            state
+                .firing
+                .values()
+                .map(|f| &f.alert)
+                .chain(state.flapping.values())
+        });
+        self.check_failure
+            .iter()
+            .chain(alerts)
+            .map(FiringAlert::from)
+            .collect()
+    }
+
+    /// Publishes the outcome of the last run, checked at `checked_at`, for the status endpoint.
+    fn publish(&self, check: Result<String, String>, checked_at: SystemTime) {
+        observer::checked(|| CheckResultSnapshot {
+            kind: self.kind,
+            watcher: self.id.clone(),
+            labels: self.options.labels.clone(),
+            checked_at,
+            samples: match check {
+                Ok(_) => self.samples.clone(),
+                Err(_) => Vec::new(),
+            },
+            check: check.clone(),
+        });
+        status::record(
+            &self.id,
+            WatcherStatus {
+                check_duration: self.check_duration(),
+                ..WatcherStatus::new(self.kind, check, self.firing())
+            },
+        );
+    }
+
+    /// Publishes that the watcher is disabled, in place of the outcome of its runs.
+    pub(crate) fn publish_disabled(&self) {
+        status::record(&self.id, WatcherStatus::disabled(self.kind));
+    }
+
+    /// Reports newly triggered alerts as well as the ones that got cleared since the previous
+    /// run. `evaluations` yields, for each alert, what it evaluated to for each subject. Alerts
+    /// that fired for a subject that is not evaluated anymore are resolved. The alerts triggered
+    /// by the check are reported as a single batch. Alerts that start firing while the alert
+    /// store already has them active are not reported again. Critical alerts that keep firing
+    /// are reported again as they go through the tiers of their escalation. Acknowledged alerts
+    /// are neither reported again nor escalated until their acknowledgement expires. Alerts are
+    /// dated and timed from when the data of the check was captured. With `report_diff`, only
+    /// what changed since the previous run is reported, as a single diff. On the first run, the
+    /// alerts and timers the store saved for the watcher are restored first, and what the run
+    /// leaves is saved back. Returns the number of alerts reported.
+    pub(crate) fn report_changes<T: Debug>(
+        &mut self,
+        check: &Timestamped<T>,
+        evaluations: impl Iterator<Item = Vec<Evaluation>>,
+        alert_reporter: &dyn AlertReporter,
+        alert_store: &dyn AlertStore,
+    ) -> usize {
+        let now = check.sampled;
+        let detected_at = check.sampled_at;
+        if !self.restored {
+            self.restored = true;
+            match alert_store.load_state(&self.id) {
+                Ok(Some(saved)) => self.restore(saved, now, detected_at),
+                Ok(None) => {}
+                Err(e) => warn!(alert_store = ?e),
+            }
+        }
+        let previously_open = self.open_fingerprints();
+        let reports_diff = self.options.report_diff;
+        let mut triggered_alerts = Vec::new();
+        let mut resolved_alerts = Vec::new();
+        // Severities of the alerts that keep firing with another one, by fingerprint.
+        let mut previous_severities = BTreeMap::new();
+        for (evaluations, state) in evaluations.zip(self.alerts.iter_mut()) {
+            let mut previously_firing = mem::take(&mut state.firing);
+            let mut previously_pending = mem::take(&mut state.pending);
+            for evaluation in evaluations {
+                let triggered = evaluation.triggered.map(|a| {
+                    severity_override::overridden(ActiveAlert {
+                        description: state.description.clone(),
+                        runbook_url: state.runbook_url.clone(),
+                        ..a.detected(
+                            &self.hostname,
+                            detected_at,
+                            &state.labels,
+                            self.kind,
+                            state.fingerprint(self.kind, &self.hostname, &evaluation.subject),
+                        )
+                    })
+                });
+                let subject = evaluation.subject;
+                let previous = previously_firing.remove(&subject);
+                let since = previously_pending.remove(&subject);
+                // Alerts that are not firing yet only fire once they triggered for long enough.
+                let triggered = match triggered {
+                    Some(active) if previous.is_none() && !state.for_duration.is_zero() => {
+                        let since = since.unwrap_or(now);
+                        if now.duration_since(since) >= state.for_duration {
+                            Some(active)
+                        } else {
+                            info!(pending_alert = ?active, pending_for = ?now.duration_since(since));
+                            state.pending.insert(subject.clone(), since);
+                            None
+                        }
+                    }
+                    triggered => triggered,
+                };
+                let firing = match (triggered, previous) {
+                    (Some(active), Some(previous)) if is_acknowledged(&active, detected_at) => {
+                        info!(acknowledged_alert = ?active);
+                        Some(Firing {
+                            alert: active,
+                            ..previous
+                        })
+                    }
+                    // Reporting diffs, an alert is only reported again with another severity.
+                    (Some(active), Some(previous))
+                        if reports_diff && active.severity == previous.alert.severity =>
+                    {
+                        info!(unchanged_alert = ?active);
+                        Some(Firing {
+                            alert: active,
+                            ..previous
+                        })
+                    }
+                    // An alert becoming more severe is reported again, cooldown or not.
+                    (Some(active), Some(previous))
+                        if !reports_diff
+                            && previous.is_cooling_down(state.cooldown, now)
+                            && active.severity <= previous.alert.severity =>
+                    {
+                        info!(cooling_down_alert = ?active);
+                        Some(Firing {
+                            alert: active,
+                            ..previous
+                        })
+                    }
+                    (Some(active), previous) => {
+                        if previous.is_none() {
+                            state.transitioned(&subject, &active, now, &mut triggered_alerts);
+                        }
+                        if state.is_flapping(&subject) {
+                            info!(flapping_alert = ?active);
+                        } else if previous.is_none() && is_stored(alert_store, &active) {
+                            info!(already_active_alert = ?active);
+                        } else {
+                            info!(severity = ?active.severity, firing_alert = ?active);
+                            triggered_alerts.push(active.clone());
+                        }
+                        // Alerts reported again once their cooldown is over are still firing the same.
+                        let changed = previous
+                            .as_ref()
+                            .is_none_or(|p| p.alert.severity != active.severity);
+                        if changed && !state.is_flapping(&subject) {
+                            observer::emit(|| AlertEvent::Firing(Box::new(active.clone())));
+                        }
+                        if let Some(previous) = previous.as_ref().filter(|_| changed) {
+                            previous_severities
+                                .insert(active.fingerprint.clone(), previous.alert.severity);
+                        }
+                        Some(Firing {
+                            alert: active,
+                            last_reported: now,
+                            since: previous.as_ref().map_or(now, |p| p.since),
+                            escalated: previous.map_or(0, |p| p.escalated),
+                        })
+                    }
+                    (None, Some(still_firing)) if !evaluation.cleared => Some(still_firing),
+                    (None, Some(resolved)) => {
+                        state.transitioned(&subject, &resolved.alert, now, &mut triggered_alerts);
+                        if !state.is_flapping(&subject) {
+                            resolved_alerts.push(resolved.alert.clone());
+                            emit_resolved(&resolved.alert, detected_at);
+                        }
+                        status::unacknowledge(&resolved.alert.fingerprint);
+                        None
+                    }
+                    (None, None) => None,
+                };
+                if let Some(mut firing) = firing {
+                    if !state.is_flapping(&subject) && !is_acknowledged(&firing.alert, detected_at)
+                    {
+                        state.escalate(&mut firing, now, &mut triggered_alerts);
+                    }
+                    state.firing.insert(subject, firing);
+                }
+            }
+            for (subject, gone) in previously_firing {
+                state.transitioned(&subject, &gone.alert, now, &mut triggered_alerts);
+                if !state.is_flapping(&subject) {
+                    resolved_alerts.push(gone.alert.clone());
+                    emit_resolved(&gone.alert, detected_at);
+                }
+                status::unacknowledge(&gone.alert.fingerprint);
+            }
+            state.stabilize(
+                now,
+                detected_at,
+                &mut triggered_alerts,
+                &mut resolved_alerts,
+            );
+        }
+        let triggered_alerts = deduplicate(triggered_alerts, self.options.deduplicate);
+        triggered_alerts.iter().for_each(observer::reported);
+        if reports_diff {
+            let changes = resolved_alerts
+                .into_iter()
+                .map(|resolved| {
+                    info!(resolved_alert = ?resolved);
+                    observer::resolved(&resolved);
+                    AlertChange::Resolved(resolved)
+                })
+                .chain(triggered_alerts.iter().map(|alert| {
+                    match previous_severities.get(&alert.fingerprint) {
+                        Some(&previous) => AlertChange::SeverityChanged {
+                            alert: alert.clone(),
+                            previous,
+                        },
+                        None => AlertChange::Firing(alert.clone()),
+                    }
+                }))
+                .collect::<Vec<_>>();
+            if !changes.is_empty() {
+                report_diff(&changes, alert_reporter);
+            }
+        } else {
+            for resolved in &resolved_alerts {
+                report_resolved(resolved, alert_reporter);
+            }
+            if !triggered_alerts.is_empty() {
+                if let Err(e) = alert_reporter.report_batch(&triggered_alerts) {
+                    warn!(alert_reporter = ?e);
+                }
+            }
+        }
+        let open = self.open_fingerprints();
+        for fingerprint in open.difference(&previously_open) {
+            status::record_transition(fingerprint, status::AlertState::Firing, detected_at);
+        }
+        for fingerprint in previously_open.difference(&open) {
+            status::record_transition(fingerprint, status::AlertState::Resolved, detected_at);
+        }
+        let stored = open.iter().map(|f| alert_store.mark_active(f)).chain(
+            previously_open
+                .iter()
+                .filter(|f| !open.contains(*f))
+                .map(|f| alert_store.expire(f)),
+        );
+        let saved = alert_store.save_state(&self.id, &self.snapshot(now, detected_at));
+        for e in stored.chain([saved]).filter_map(Result::err) {
+            warn!(alert_store = ?e);
+        }
+        self.publish(Ok(self.summary.clone()), detected_at);
+        triggered_alerts.len()
+    }
+
+    /// What the watcher remembers of its alerts, with its times read relative to `now`, which is
+    /// `now_at` on the wall clock.
+    fn snapshot(&self, now: Instant, now_at: SystemTime) -> WatcherSnapshot {
+        let at = |instant: Instant| now_at - now.saturating_duration_since(instant);
+        let mut snapshot = WatcherSnapshot::default();
+        for (alert, state) in self.alerts.iter().enumerate() {
+            snapshot
+                .firing
+                .extend(state.firing.iter().map(|(subject, firing)| SavedFiring {
+                    alert,
+                    subject: subject.clone(),
+                    active: firing.alert.clone(),
+                    last_reported: at(firing.last_reported),
+                    since: at(firing.since),
+                    escalated: firing.escalated,
+                }));
+            snapshot
+                .pending
+                .extend(state.pending.iter().map(|(subject, since)| SavedPending {
+                    alert,
+                    subject: subject.clone(),
+                    since: at(*since),
+                }));
+        }
+        snapshot
+    }
+
+    /// Takes on the alerts and timers of a snapshot, see [`WatcherState::snapshot`]. Alerts the
+    /// configuration does not have anymore are dropped.
+    fn restore(&mut self, snapshot: WatcherSnapshot, now: Instant, now_at: SystemTime) {
+        let instant = |at: SystemTime| {
+            let elapsed = now_at.duration_since(at).unwrap_or_default();
+            now.checked_sub(elapsed).unwrap_or(now)
+        };
+        for saved in snapshot.firing {
+            if let Some(state) = self.alerts.get_mut(saved.alert) {
+                info!(restored_alert = ?saved.active);
+                let firing = Firing {
+                    alert: saved.active,
+                    last_reported: instant(saved.last_reported),
+                    since: instant(saved.since),
+                    escalated: saved.escalated,
+                };
+                state.firing.insert(saved.subject, firing);
+            }
+        }
+        for saved in snapshot.pending {
+            if let Some(state) = self.alerts.get_mut(saved.alert) {
+                state.pending.insert(saved.subject, instant(saved.since));
+            }
+        }
+    }
+
+    /// Fingerprints of the alerts that fired and did not resolve yet, including the flapping
+    /// ones.
+    fn open_fingerprints(&self) -> BTreeSet<String> {
+        self.alerts
+            .iter()
+            .flat_map(|state| {
+                state
+                    .firing
+                    .values()
+                    .map(|f| &f.alert)
+                    .chain(state.flapping.values())
+            })
+            .map(|a| a.fingerprint.clone())
+            .collect()
+    }
+}
+
+impl Drop for WatcherState {
+    fn drop(&mut self) {
+        status::forget(&self.id);
+    }
+}
+
+/// Name of the module an alert type is defined in, such as `disk_space`, which names the kind of
+/// watcher alerting with it.
+fn kind<A>() -> &'static str {
+    type_name::<A>().rsplit("::").nth(1).unwrap_or("watcher")
+}
+
+fn fingerprint(kind: &str, rule: u64, subject: &str) -> String {
+    if subject.is_empty() {
+        format!("{}:{:016x}", kind, rule)
+    } else {
+        format!("{}:{:016x}:{}", kind, rule, subject)
+    }
+}
+
+/// Whether the alert was acknowledged from the status endpoint, until after `now`.
+fn is_acknowledged(alert: &ActiveAlert, now: SystemTime) -> bool {
+    status::acknowledged_until(&alert.fingerprint, now).is_some()
+}
+
+/// Whether the alert is active in the store. It is not when the store can not tell, so that
+/// alerts still get reported while it is unavailable.
+fn is_stored(alert_store: &dyn AlertStore, alert: &ActiveAlert) -> bool {
+    alert_store
+        .is_active(&alert.fingerprint)
+        .unwrap_or_else(|e| {
+            warn!(alert_store = ?e);
+            false
+        })
+}
+
+fn report_resolved(resolved: &ActiveAlert, alert_reporter: &dyn AlertReporter) {
+    info!(resolved_alert = ?resolved);
+    observer::resolved(resolved);
+    if let Err(e) = alert_reporter.report_resolved(resolved) {
+        warn!(alert_reporter = ?e);
+    }
+}
+
+fn report_diff(changes: &[AlertChange], alert_reporter: &dyn AlertReporter) {
+    if let Err(e) = alert_reporter.report_diff(changes) {
+        warn!(alert_reporter = ?e);
+    }
+}
+
+fn emit_resolved(resolved: &ActiveAlert, resolved_at: SystemTime) {
+    observer::emit(|| AlertEvent::Resolved {
+        fingerprint: resolved.fingerprint.clone(),
+        resolved_at,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::RecordingReporter;
+    use crate::alert_store::file::File;
+    use crate::alert_store::memory::{Configuration, Memory};
+    use crate::test_util::FakeClock;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    const PERIOD: Duration = Duration::from_secs(60);
+
+    #[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+    struct TestAlert {}
+
+    /// A watcher with a single alert configured by `alert`, timed by `clock`.
+    fn watcher(alert: serde_json::Value, clock: &FakeClock) -> WatcherState {
+        watcher_with(alert, json!({}), clock)
+    }
+
+    /// A watcher with a single alert configured by `alert` and the watcher `options`.
+    fn watcher_with(
+        alert: serde_json::Value,
+        options: serde_json::Value,
+        clock: &FakeClock,
+    ) -> WatcherState {
+        let configuration = serde_json::from_value::<AlertConfiguration<TestAlert>>(alert)
+            .expect("Invalid alert configuration");
+        let options = serde_json::from_value(options).expect("Invalid watcher options");
+        let (_, mut state) = WatcherState::new(&"test", vec![configuration], options);
+        state.set_clock(Arc::new(clock.clone()));
+        state
+    }
+
+    /// Runs the watcher on a check taken now, on which the alert is triggered or cleared, then
+    /// advances the clock by a period.
+    fn run(
+        state: &mut WatcherState,
+        clock: &FakeClock,
+        triggered: bool,
+        reporter: &RecordingReporter,
+        store: &dyn AlertStore,
+    ) {
+        let time = state.clock();
+        let check = Timestamped {
+            result: (),
+            sampled: time.now(),
+            sampled_at: time.system_now(),
+        };
+        let evaluation = Evaluation::single(
+            triggered.then(|| ActiveAlert::new("Test".to_string(), Severity::Critical)),
+            !triggered,
+        );
+        state.report_changes(&check, std::iter::once(vec![evaluation]), reporter, store);
+        clock.advance(PERIOD);
+    }
+
+    fn store() -> Memory {
+        Memory::new(Configuration::default())
+    }
+
+    /// A file store at a path of its own for a test, removed first.
+    fn file_store(test: &str) -> (PathBuf, File) {
+        let path = std::env::temp_dir().join(format!(
+            "monitrust-state-{}-{}.json",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let configuration = serde_json::from_value(json!({ "path": path })).unwrap();
+        (path, File::new(configuration))
+    }
+
+    #[test]
+    fn a_restarted_watcher_resolves_the_alerts_that_cleared_meanwhile() {
+        let clock = FakeClock::new();
+        let (path, store) = file_store("resolves");
+        let mut state = watcher(json!({}), &clock);
+        run(&mut state, &clock, true, &RecordingReporter::new(), &store);
+        drop(state);
+
+        let configuration = serde_json::from_value(json!({ "path": path })).unwrap();
+        let restarted = File::new(configuration);
+        let mut state = watcher(json!({}), &clock);
+        let reporter = RecordingReporter::new();
+        run(&mut state, &clock, false, &reporter, &restarted);
+
+        assert!(reporter.alerts().is_empty());
+        assert_eq!(reporter.resolved().len(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn a_restarted_watcher_keeps_its_for_duration_timers() {
+        let clock = FakeClock::new();
+        let (path, store) = file_store("for-duration");
+        let mut state = watcher(json!({ "for": "5m" }), &clock);
+        let reporter = RecordingReporter::new();
+        for _ in 0..3 {
+            run(&mut state, &clock, true, &reporter, &store);
+        }
+        drop(state);
+
+        let configuration = serde_json::from_value(json!({ "path": path })).unwrap();
+        let restarted = File::new(configuration);
+        let mut state = watcher(json!({ "for": "5m" }), &clock);
+        for _ in 0..2 {
+            run(&mut state, &clock, true, &reporter, &restarted);
+        }
+        assert!(reporter.alerts().is_empty());
+        run(&mut state, &clock, true, &reporter, &restarted);
+
+        assert_eq!(reporter.alerts().len(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn failing_checks_are_propagated_by_default() {
+        let clock = FakeClock::new();
+        let mut state = watcher(json!({}), &clock);
+        let reporter = RecordingReporter::new();
+
+        let error = state
+            .checked::<()>(Err(anyhow::anyhow!("No such mount point")), &reporter)
+            .unwrap_err();
+
+        assert!(
+            format!("{:#}", error).contains("No such mount point"),
+            "{:#}",
+            error
+        );
+        assert!(reporter.alerts().is_empty());
+    }
+
+    #[test]
+    fn failing_checks_alert_until_they_succeed_again() {
+        let clock = FakeClock::new();
+        let mut state = watcher_with(json!({}), json!({ "alert_on_check_failure": true }), &clock);
+        let reporter = RecordingReporter::new();
+
+        for _ in 0..2 {
+            let checked =
+                state.checked::<()>(Err(anyhow::anyhow!("No such mount point")), &reporter);
+            assert!(checked.unwrap().is_none());
+        }
+        let alerts = reporter.alerts();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].severity, Severity::Critical);
+        assert!(
+            alerts[0].message.contains("No such mount point"),
+            "{}",
+            alerts[0].message
+        );
+        assert!(reporter.resolved().is_empty());
+
+        assert_eq!(state.checked(Ok(()), &reporter).unwrap(), Some(()));
+        assert_eq!(reporter.resolved(), vec![alerts[0].clone()]);
+    }
+
+    #[test]
+    fn labels_of_the_alert_take_precedence_over_the_watcher() {
+        let clock = FakeClock::new();
+        let mut state = watcher_with(
+            json!({ "labels": { "env": "dev", "team": "payments" } }),
+            json!({ "labels": { "env": "prod", "role": "db" } }),
+            &clock,
+        );
+        let (reporter, store) = (RecordingReporter::new(), store());
+
+        run(&mut state, &clock, true, &reporter, &store);
+
+        let labels = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert_eq!(
+            reporter.alerts()[0].labels,
+            labels(&[("env", "dev"), ("role", "db"), ("team", "payments")])
+        );
+    }
+
+    #[test]
+    fn flapping_alerts_are_reported_once_until_they_stabilize() {
+        let clock = FakeClock::new();
+        let mut state = watcher(
+            json!({ "flap_detection": { "max_transitions": 2, "window": "10m" } }),
+            &clock,
+        );
+        let (reporter, store) = (RecordingReporter::new(), store());
+
+        for triggered in [true, false, true, false, true, false, true] {
+            run(&mut state, &clock, triggered, &reporter, &store);
+        }
+        let messages = |alerts: Vec<ActiveAlert>| {
+            alerts
+                .into_iter()
+                .map(|a| a.message.split(',').next().unwrap_or_default().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages(reporter.alerts()),
+            ["Test", "🔁 Alert is flapping"]
+        );
+        assert_eq!(messages(reporter.resolved()), ["Test"]);
+
+        // Firing steadily for the whole window, the alert is not flapping anymore.
+        for _ in 0..11 {
+            run(&mut state, &clock, true, &reporter, &store);
+        }
+        assert_eq!(
+            messages(reporter.alerts()),
+            ["Test", "🔁 Alert is flapping", "Test"]
+        );
+        assert_eq!(
+            messages(reporter.resolved()),
+            ["Test", "🔁 Alert is flapping"]
+        );
+    }
+
+    #[test]
+    fn alerts_carry_their_description_and_runbook() {
+        let clock = FakeClock::new();
+        let mut state = watcher(
+            json!({ "description": "Memory is low", "runbook_url": "https://wiki/memory" }),
+            &clock,
+        );
+        let (reporter, store) = (RecordingReporter::new(), store());
+
+        run(&mut state, &clock, true, &reporter, &store);
+
+        let alert = &reporter.alerts()[0];
+        assert_eq!(alert.description.as_deref(), Some("Memory is low"));
+        assert_eq!(alert.runbook_url.as_deref(), Some("https://wiki/memory"));
+    }
+
+    #[test]
+    fn check_durations_are_summarized() {
+        let clock = FakeClock::new();
+        let mut state = watcher(json!({}), &clock);
+        assert!(state.check_duration().is_none());
+
+        for millis in [300, 100, 200] {
+            state.record_check(Duration::from_millis(millis), true);
+        }
+
+        let duration = state.check_duration().unwrap();
+        assert_eq!(duration.last_seconds, 0.2);
+        assert!((duration.average_seconds - 0.2).abs() < 1e-9);
+        assert_eq!(duration.max_seconds, 0.3);
+        let duration = metrics::snapshot().into_iter().find(|s| {
+            s.name == "monitrust_check_duration_seconds"
+                && s.labels
+                    .contains(&("watcher".to_string(), state.id.clone()))
+        });
+        assert_eq!(duration.map(|s| s.value), Some(0.2));
+    }
+
+    #[test]
+    fn a_failing_check_marks_the_watcher_down() {
+        let clock = FakeClock::new();
+        let mut state = watcher(json!({}), &clock);
+        // Another id than the other tests, which may record their checks meanwhile.
+        state.id = format!("{}-up", state.id);
+        let watcher = ("watcher".to_string(), state.id.clone());
+        let metric = |name: &str| {
+            metrics::snapshot()
+                .into_iter()
+                .find(|s| s.name == name && s.labels.contains(&watcher))
+                .map(|s| s.value)
+        };
+
+        state.record_check(Duration::from_millis(10), false);
+        assert_eq!(metric("monitrust_watcher_up"), Some(0.0));
+        assert_eq!(
+            metric("monitrust_watcher_last_success_timestamp_seconds"),
+            None
+        );
+
+        state.record_check(Duration::from_millis(10), true);
+        assert_eq!(metric("monitrust_watcher_up"), Some(1.0));
+        let since_epoch = clock
+            .system_now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(
+            metric("monitrust_watcher_last_success_timestamp_seconds"),
+            Some(since_epoch.as_secs_f64())
+        );
+
+        state.record_check(Duration::from_millis(10), false);
+        assert_eq!(metric("monitrust_watcher_up"), Some(0.0));
+    }
+
+    #[test]
+    fn watchers_check_at_the_critical_period_while_critical() {
+        let clock = FakeClock::new();
+        let mut state = watcher_with(json!({}), json!({ "critical_period": "10s" }), &clock);
+        let (reporter, store) = (RecordingReporter::new(), store());
+        assert_eq!(state.period(PERIOD), PERIOD);
+
+        run(&mut state, &clock, true, &reporter, &store);
+        assert_eq!(state.period(PERIOD), Duration::from_secs(10));
+        assert_eq!(state.period(Duration::from_secs(5)), Duration::from_secs(5));
+
+        run(&mut state, &clock, false, &reporter, &store);
+        assert_eq!(state.period(PERIOD), PERIOD);
+    }
+
+    #[test]
+    fn the_first_open_window_schedules_the_threshold() {
+        let once = |start: &str, end: &str, threshold: f64| json!({ "when": { "Once": { "start": start, "end": end } }, "threshold": threshold });
+        let clock = FakeClock::at(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let state = |schedule: Vec<serde_json::Value>| {
+            watcher(json!({ "threshold_schedule": schedule }), &clock)
+        };
+
+        // Overlapping windows: the first one listed applies.
+        let overlapping = state(vec![
+            once("2023-11-14T22:00:00Z", "2023-11-14T23:00:00Z", 3.0),
+            once("2023-11-14T20:00:00Z", "2023-11-15T00:00:00Z", 5.0),
+        ]);
+        assert_eq!(overlapping.scheduled_thresholds(), vec![Some(3.0)]);
+
+        // Adjacent windows: the one ending now is closed, the one starting now is open.
+        let adjacent = state(vec![
+            once("2023-11-14T21:00:00Z", "2023-11-14T22:13:20Z", 3.0),
+            once("2023-11-14T22:13:20Z", "2023-11-14T23:00:00Z", 5.0),
+        ]);
+        assert_eq!(adjacent.scheduled_thresholds(), vec![Some(5.0)]);
+
+        // Outside of every window, the threshold of the alert applies.
+        clock.advance(Duration::from_secs(2 * 60 * 60));
+        assert_eq!(overlapping.scheduled_thresholds(), vec![None]);
+        assert_eq!(
+            watcher(json!({}), &clock).scheduled_thresholds(),
+            vec![None]
+        );
+    }
+
+    #[test]
+    fn duplicates_are_dropped_keeping_the_order() {
+        let alert = |fingerprint: &str, message: &str| ActiveAlert {
+            fingerprint: fingerprint.to_string(),
+            ..ActiveAlert::new(message.to_string(), Severity::Warning)
+        };
+        let alerts = vec![
+            alert("b", "Disk / is full"),
+            alert("a", "Disk /var is full"),
+            alert("b", "Disk / is full"),
+            alert("c", "Disk / is full"),
+        ];
+        let fingerprints = |alerts: Vec<ActiveAlert>| {
+            alerts
+                .into_iter()
+                .map(|a| a.fingerprint)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            fingerprints(deduplicate(alerts.clone(), Deduplication::Fingerprint)),
+            ["b", "a", "c"]
+        );
+        assert_eq!(
+            fingerprints(deduplicate(alerts, Deduplication::Message)),
+            ["b", "a"]
+        );
+    }
+
+    #[test]
+    fn hooks_observe_the_checks_and_alerts() {
+        use std::sync::Mutex;
+
+        let clock = FakeClock::new();
+        let mut state = watcher_with(
+            json!({}),
+            json!({ "labels": { "observer": "hooks" } }),
+            &clock,
+        );
+        let (reporter, store) = (RecordingReporter::new(), store());
+        // Hooks see every watcher of the tests running alongside, keep those of this one only.
+        let observed = |labels: &BTreeMap<String, String>| {
+            labels.get("observer").map(String::as_str) == Some("hooks")
+        };
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = |seen: &Arc<Mutex<Vec<String>>>, what: String| seen.lock().unwrap().push(what);
+        let ids = [
+            observer::on_check_result({
+                let seen = seen.clone();
+                move |s| {
+                    if observed(&s.labels) {
+                        record(&seen, format!("check {:?}", s.check));
+                    }
+                }
+            }),
+            observer::on_alert({
+                let seen = seen.clone();
+                move |a| {
+                    if observed(&a.labels) {
+                        record(&seen, format!("alert {}", a.message));
+                    }
+                }
+            }),
+            observer::on_resolved({
+                let seen = seen.clone();
+                move |a| {
+                    if observed(&a.labels) {
+                        record(&seen, format!("resolved {}", a.message));
+                    }
+                }
+            }),
+        ];
+
+        run(&mut state, &clock, true, &reporter, &store);
+        run(&mut state, &clock, false, &reporter, &store);
+        ids.into_iter().for_each(observer::remove);
+        run(&mut state, &clock, true, &reporter, &store);
+
+        let summary = format!("{:?}", Ok::<_, String>(state.summary.clone()));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "alert Test".to_string(),
+                format!("check {}", summary),
+                "resolved Test".to_string(),
+                format!("check {}", summary),
+            ]
+        );
+    }
+
+    #[test]
+    fn firing_then_clearing_emits_firing_then_resolved() {
+        use std::sync::Mutex;
+
+        let clock = FakeClock::new();
+        let mut state = watcher(
+            json!({ "labels": { "observer": "events" }, "fingerprint_template": "{observer}/{host}" }),
+            &clock,
+        );
+        let (reporter, store) = (RecordingReporter::new(), store());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let id = observer::on_event({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        });
+
+        run(&mut state, &clock, true, &reporter, &store);
+        run(&mut state, &clock, false, &reporter, &store);
+        observer::remove(id);
+
+        // Hooks see every watcher of the tests running alongside, keep those of this one only.
+        let fingerprint = reporter.alerts()[0].fingerprint.clone();
+        assert!(fingerprint.starts_with("events/"), "{}", fingerprint);
+        let events = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                AlertEvent::Firing(alert) if alert.fingerprint == fingerprint => {
+                    Some(format!("firing {}", alert.message))
+                }
+                AlertEvent::Resolved {
+                    fingerprint: resolved,
+                    ..
+                } if *resolved == fingerprint => Some("resolved".to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(events, ["firing Test", "resolved"]);
+    }
+
+    #[test]
+    fn cooldown_holds_back_reports_of_an_alert_that_keeps_firing() {
+        let clock = FakeClock::new();
+        let mut state = watcher(json!({ "cooldown": "2m" }), &clock);
+        let (reporter, store) = (RecordingReporter::new(), store());
+
+        for _ in 0..3 {
+            run(&mut state, &clock, true, &reporter, &store);
+        }
+
+        assert_eq!(reporter.alerts().len(), 2);
+    }
+
+    #[test]
+    fn for_duration_fires_once_triggered_for_long_enough() {
+        let clock = FakeClock::new();
+        let mut state = watcher(json!({ "for": "5m" }), &clock);
+        let (reporter, store) = (RecordingReporter::new(), store());
+
+        for _ in 0..5 {
+            run(&mut state, &clock, true, &reporter, &store);
+            assert!(reporter.alerts().is_empty());
+        }
+        run(&mut state, &clock, true, &reporter, &store);
+
+        assert_eq!(reporter.alerts().len(), 1);
+    }
+
+    #[test]
+    fn for_duration_starts_over_once_cleared() {
+        let clock = FakeClock::new();
+        let mut state = watcher(json!({ "for": "5m" }), &clock);
+        let (reporter, store) = (RecordingReporter::new(), store());
+
+        for _ in 0..4 {
+            run(&mut state, &clock, true, &reporter, &store);
+        }
+        run(&mut state, &clock, false, &reporter, &store);
+        for _ in 0..5 {
+            run(&mut state, &clock, true, &reporter, &store);
+        }
+        assert!(reporter.alerts().is_empty());
+        run(&mut state, &clock, true, &reporter, &store);
+
+        assert_eq!(reporter.alerts().len(), 1);
+    }
+
+    /// Runs the watcher once per period on the checks `triggered` yields, returning the tier,
+    /// reporter and run of each escalation reported.
+    fn escalations(
+        state: &mut WatcherState,
+        clock: &FakeClock,
+        triggered: impl IntoIterator<Item = bool>,
+        reporter: &RecordingReporter,
+    ) -> Vec<(usize, Option<String>, usize)> {
+        let store = store();
+        let mut escalations = Vec::new();
+        for (index, triggered) in triggered.into_iter().enumerate() {
+            let reported = reporter.alerts().len();
+            run(state, clock, triggered, reporter, &store);
+            escalations.extend(
+                reporter.alerts()[reported..]
+                    .iter()
+                    .filter_map(|a| a.escalation.clone())
+                    .map(|e| (e.tier, e.reporter, index)),
+            );
+        }
+        escalations
+    }
+
+    fn escalating(clock: &FakeClock) -> WatcherState {
+        watcher(
+            json!({ "escalation": [
+                { "after": "15m", "reporter": "phone" },
+                { "after": "5m", "reporter": "pager" },
+            ] }),
+            clock,
+        )
+    }
+
+    #[test]
+    fn escalates_through_each_tier() {
+        let clock = FakeClock::new();
+        let mut state = escalating(&clock);
+        let reporter = RecordingReporter::new();
+
+        let escalations = escalations(&mut state, &clock, [true; 20], &reporter);
+
+        assert_eq!(
+            escalations,
+            vec![
+                (1, Some("pager".to_string()), 5),
+                (2, Some("phone".to_string()), 15),
+            ]
+        );
+    }
+
+    #[test]
+    fn escalation_starts_over_once_resolved() {
+        let clock = FakeClock::new();
+        let mut state = escalating(&clock);
+        let reporter = RecordingReporter::new();
+
+        // Resolved on the 8th run, between the tiers, then firing again from the 9th.
+        let triggered = (0..20).map(|run| run != 7);
+        let escalations = escalations(&mut state, &clock, triggered, &reporter);
+
+        assert_eq!(reporter.resolved().len(), 1);
+        assert_eq!(
+            escalations,
+            vec![
+                (1, Some("pager".to_string()), 5),
+                (1, Some("pager".to_string()), 13),
+            ]
+        );
+    }
+
+    #[test]
+    fn fingerprint_template_keeps_subjects_apart() {
+        let clock = FakeClock::new();
+        let colliding = watcher(
+            json!({ "labels": { "team": "ops" }, "fingerprint_template": "{team}/{host}" }),
+            &clock,
+        );
+        let fingerprints = ["/", "/var"]
+            .map(|subject| colliding.alerts[0].fingerprint("DiskSpace", "host", subject));
+        assert_eq!(fingerprints[0], fingerprints[1]);
+        let error = colliding.validate_fingerprint_template(0).unwrap_err();
+        assert!(error.to_string().contains("lacks {kind}"), "{}", error);
+
+        // Two alerts of a watcher on the same subject.
+        let same_rule = watcher(
+            json!({ "fingerprint_template": "{kind}:{subject}" }),
+            &clock,
+        );
+        let error = same_rule.validate_fingerprint_template(0).unwrap_err();
+        assert!(error.to_string().contains("lacks {rule}"), "{}", error);
+
+        let apart = watcher(
+            json!({
+                "labels": { "team": "ops" },
+                "fingerprint_template": "{team}/{kind}:{rule}:{subject}"
+            }),
+            &clock,
+        );
+        apart.validate_fingerprint_template(0).unwrap();
+        let rule = format!("{:016x}", apart.alerts[0].rule);
+        let fingerprints =
+            ["/", "/var"].map(|subject| apart.alerts[0].fingerprint("DiskSpace", "host", subject));
+        assert_eq!(
+            fingerprints,
+            [
+                format!("ops/DiskSpace:{}:/", rule),
+                format!("ops/DiskSpace:{}:/var", rule)
+            ]
+        );
+    }
+
+    #[test]
+    fn kind_mutes_alerts_with_a_templated_fingerprint() {
+        let clock = FakeClock::new();
+        let mut state = watcher(
+            json!({
+                "labels": { "team": "ops" },
+                "fingerprint_template": "{team}/{kind}:{rule}:{subject}"
+            }),
+            &clock,
+        );
+        let (reporter, store) = (RecordingReporter::new(), store());
+        run(&mut state, &clock, true, &reporter, &store);
+
+        let alert = &reporter.alerts()[0];
+        assert!(
+            alert.fingerprint.starts_with("ops/"),
+            "{}",
+            alert.fingerprint
+        );
+        assert_eq!(alert.kind, state.kind);
+        let mute = serde_json::from_value::<crate::mute::Configuration>(json!({
+            "rules": [{ "match": { "Kind": state.kind } }]
+        }))
+        .unwrap();
+        assert!(mute.mutes(alert, clock.system_now()));
+    }
+}
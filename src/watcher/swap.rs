@@ -0,0 +1,211 @@
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::size::format_size;
+use crate::watcher;
+use crate::watcher::{decimal, percent, threshold_bits, ActiveAlert, Severity};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    /// Pages swapped in and out so far, when they were last sampled.
+    previous: Mutex<Option<(Instant, u64, u64)>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwapUsage {
+    /// Swap sizes, in bytes.
+    pub total: u64,
+    pub used: u64,
+    pub free: u64,
+    /// Pages swapped in and out per second since the previous check. Unknown on the first check.
+    pub swap_in_rate: Option<f64>,
+    pub swap_out_rate: Option<f64>,
+}
+
+/// Reads the values of the given keys from a `/proc` file made of `key value` lines.
+fn read_values<const N: usize>(path: &str, keys: [&str; N]) -> Result<[u64; N]> {
+    let file = std::fs::File::open(path).context(format!("Could not open file at '{}'", path))?;
+    let mut values = [None; N];
+    for l in BufReader::new(file).lines() {
+        let l = l.context(format!("Could not read line from '{}'.", path))?;
+        let mut split = l.split_whitespace();
+        let (Some(name), Some(value)) = (split.next(), split.next()) else {
+            continue;
+        };
+        if let Some(i) = keys.iter().position(|k| *k == name) {
+            values[i] = Some(
+                value
+                    .parse::<u64>()
+                    .context(format!("Could not convert {} to integer: {}", name, value))?,
+            );
+        }
+    }
+    let mut result = [0; N];
+    for (i, value) in values.into_iter().enumerate() {
+        result[i] = value.ok_or_else(|| anyhow!("Could not find '{}' in '{}'", keys[i], path))?;
+    }
+    Ok(result)
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = SwapUsage;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "swap");
+        let [total, free] = read_values("/proc/meminfo", ["SwapTotal:", "SwapFree:"])?;
+        let [swapped_in, swapped_out] = read_values("/proc/vmstat", ["pswpin", "pswpout"])?;
+        let now = Instant::now();
+        let mut previous = self
+            .previous
+            .lock()
+            .map_err(|_| anyhow!("Swap sample lock is poisoned"))?;
+        let rates = previous.map(|(then, previous_in, previous_out)| {
+            let seconds = now.duration_since(then).as_secs_f64().max(f64::EPSILON);
+            (
+                swapped_in.saturating_sub(previous_in) as f64 / seconds,
+                swapped_out.saturating_sub(previous_out) as f64 / seconds,
+            )
+        });
+        *previous = Some((now, swapped_in, swapped_out));
+        let usage = SwapUsage {
+            total: 1024 * total,
+            used: 1024 * total.saturating_sub(free),
+            free: 1024 * free,
+            swap_in_rate: rates.map(|(r, _)| r),
+            swap_out_rate: rates.map(|(_, r)| r),
+        };
+        info!(
+            swap_used = usage.used,
+            swap_total = usage.total,
+            swap_in_rate = usage.swap_in_rate,
+            swap_out_rate = usage.swap_out_rate
+        );
+        Ok(usage)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        if check_result.total == 0 {
+            return "No swap".to_string();
+        }
+        let mut summary = format!(
+            "{} used ({} of {})",
+            percent(check_result.used as f64 / check_result.total as f64),
+            format_size(check_result.used),
+            format_size(check_result.total)
+        );
+        if let (Some(swap_in), Some(swap_out)) =
+            (check_result.swap_in_rate, check_result.swap_out_rate)
+        {
+            summary.push_str(&format!(
+                ", {} pages/s in and {} pages/s out",
+                decimal(swap_in),
+                decimal(swap_out)
+            ));
+        }
+        summary
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            previous: Mutex::new(None),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// The threshold is the fraction of swap in use, between 0 and 1.
+    UsedFraction,
+    /// The threshold is the number of pages swapped in or out per second, averaged over the
+    /// period.
+    Activity,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    metric: Metric,
+    threshold: f64,
+    /// Above this value, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.metric.hash(state);
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        if self.metric == Metric::UsedFraction {
+            watcher::validate_fraction("threshold", self.threshold)?;
+        }
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let (value, message) = match self.metric {
+            // Hosts without swap can not run out of it.
+            Metric::UsedFraction if check_result.total == 0 => return None,
+            Metric::UsedFraction => {
+                let used = check_result.used as f64 / check_result.total as f64;
+                let message = format!(
+                    "🔃 Swap usage is at {}% (threshold: {}%).",
+                    decimal(100.0 * used),
+                    decimal(100.0 * self.threshold)
+                );
+                (used, message)
+            }
+            Metric::Activity => {
+                let swap_in = check_result.swap_in_rate?;
+                let swap_out = check_result.swap_out_rate?;
+                let message = format!(
+                    "🔃 Swap activity is at {} pages/s in and {} pages/s out (threshold: {} pages/s).",
+                    decimal(swap_in),
+                    decimal(swap_out),
+                    decimal(self.threshold)
+                );
+                (swap_in.max(swap_out), message)
+            }
+        };
+        if value <= self.threshold {
+            return None;
+        }
+        let severity = match self.critical {
+            Some(critical) if value > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        Some(ActiveAlert::new(message, severity))
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::Period;
+use crate::watcher;
+use crate::watcher::{ActiveAlert, Evaluation, Severity};
+
+/// Exists if and only if the system was booted with systemd, see `sd_booted(3)`.
+const SYSTEMD_RUNTIME_DIRECTORY: &str = "/run/systemd/system";
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    units: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Units to watch, such as `nginx.service`.
+    units: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitState {
+    /// Whether the unit file was found, e.g. `loaded` or `not-found`.
+    pub load_state: String,
+    /// High-level state, e.g. `active`, `inactive` or `failed`.
+    pub active_state: String,
+    /// Type-specific state, e.g. `running` or `exited` for services.
+    pub sub_state: String,
+    /// Times the service was automatically restarted, only known for services.
+    pub restarts: Option<u64>,
+}
+
+/// State of each watched unit, by unit name.
+pub type UnitStates = BTreeMap<String, UnitState>;
+
+/// Parses the output of `systemctl show`, made of one block of `Key=value` lines per unit.
+fn parse_show(output: &str, units: &[String]) -> Result<UnitStates> {
+    let mut states = UnitStates::new();
+    for (block, unit) in output.split("\n\n").zip(units) {
+        let properties = block
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .collect::<BTreeMap<_, _>>();
+        let property = |name: &str| {
+            properties
+                .get(name)
+                .map(|v| v.to_string())
+                .ok_or_else(|| anyhow!("Missing property {} for unit {}", name, unit))
+        };
+        let restarts = match properties.get("NRestarts") {
+            Some(restarts) if !restarts.is_empty() => {
+                Some(restarts.parse::<u64>().with_context(|| {
+                    format!("Could not convert restart count to integer: {}", restarts)
+                })?)
+            }
+            _ => None,
+        };
+        states.insert(
+            unit.clone(),
+            UnitState {
+                load_state: property("LoadState")?,
+                active_state: property("ActiveState")?,
+                sub_state: property("SubState")?,
+                restarts,
+            },
+        );
+    }
+    if states.len() != units.len() {
+        return Err(anyhow!(
+            "Expected the state of {} units from systemctl, got {}",
+            units.len(),
+            states.len()
+        ));
+    }
+    Ok(states)
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = UnitStates;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "systemd");
+        if !Path::new(SYSTEMD_RUNTIME_DIRECTORY).exists() {
+            return Err(anyhow!("The system is not running systemd"));
+        }
+        let output = Command::new("systemctl")
+            .arg("show")
+            .arg("--property=LoadState,ActiveState,SubState,NRestarts")
+            .arg("--")
+            .args(&self.units)
+            .output()
+            .context("Could not run systemctl")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "systemctl failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let states = parse_show(&String::from_utf8_lossy(&output.stdout), &self.units)?;
+        for (unit, state) in &states {
+            info!(
+                unit,
+                active_state = state.active_state,
+                sub_state = state.sub_state,
+                restarts = state.restarts
+            );
+        }
+        Ok(states)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .iter()
+            .map(|(unit, state)| format!("{}: {} ({})", unit, state.active_state, state.sub_state))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            units: configuration.units,
+        }
+    }
+
+    /// Fails where systemd is not present, and for units that do not exist.
+    fn probe(&self) -> Result<()> {
+        let states = self.check()?;
+        let missing = states
+            .iter()
+            .filter(|(_, state)| state.load_state == "not-found")
+            .map(|(unit, _)| unit.as_str())
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Unknown systemd units: {}", missing.join(", ")))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Expected {
+    /// The unit's active state must be `active`, which includes oneshot services that exited.
+    #[default]
+    Active,
+    /// The unit's sub-state must also be `running`, for long-running services.
+    Running,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    #[serde(default)]
+    expected: Expected,
+    /// Also alert when a service was automatically restarted more than this many times.
+    #[serde(default)]
+    max_restarts: Option<u64>,
+}
+
+impl Alert {
+    fn triggered_for(&self, unit: &str, state: &UnitState) -> Option<ActiveAlert> {
+        let expected = match self.expected {
+            Expected::Active => state.active_state == "active",
+            Expected::Running => state.active_state == "active" && state.sub_state == "running",
+        };
+        if !expected {
+            return Some(ActiveAlert::new(
+                format!(
+                    "🧩 Unit {} is {} ({}), expected: {}.",
+                    unit,
+                    state.active_state,
+                    state.sub_state,
+                    match self.expected {
+                        Expected::Active => "active",
+                        Expected::Running => "active (running)",
+                    }
+                ),
+                Severity::Critical,
+            ));
+        }
+        match (state.restarts, self.max_restarts) {
+            (Some(restarts), Some(max)) if restarts > max => Some(ActiveAlert::new(
+                format!(
+                    "🧩 Unit {} was restarted {} times (threshold: {}).",
+                    unit, restarts, max
+                ),
+                Severity::default(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    /// Alert about the first unit that triggers it. Each unit is reported on its own by
+    /// [`watcher::Alert::evaluate`].
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result
+            .iter()
+            .find_map(|(unit, state)| self.triggered_for(unit, state))
+    }
+
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|(unit, state)| {
+                let triggered = self.triggered_for(unit, state);
+                Evaluation {
+                    subject: unit.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,61 @@
+//! Targets of the network watchers, checked one by one so that a failing target fires its own
+//! alert while the other ones stay healthy.
+
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+/// Outcome of the check of one target.
+#[derive(Debug, Clone)]
+pub struct TargetCheck<T> {
+    /// The target as alerts name it, such as `example.com:443 (IPv6)`.
+    pub target: String,
+    /// Why the target could not be checked otherwise, such as a host that does not resolve.
+    pub outcome: Result<T, String>,
+}
+
+/// Summary of the checks of several targets, such as `example.com:443 (IPv4): refused`, with
+/// `outcome` summarizing the ones that could be checked.
+pub(crate) fn summary<T>(checks: &[TargetCheck<T>], outcome: impl Fn(&T) -> String) -> String {
+    checks
+        .iter()
+        .map(|check| match &check.outcome {
+            Ok(result) => format!("{}: {}", check.target, outcome(result)),
+            Err(e) => format!("{}: {}", check.target, e),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Hosts configured with a single `host`, several `hosts`, or both.
+pub(crate) fn hosts(host: Option<String>, hosts: Vec<String>) -> Vec<String> {
+    host.into_iter().chain(hosts).collect()
+}
+
+/// Addresses of a host to check, grouped by family: a single group for an IP address, or one for
+/// each family a hostname resolves to, so that a service down over IPv6 alerts even though it is
+/// up over IPv4. Groups are named after `name`, followed by their family for hostnames, such as
+/// `example.com:443 (IPv4)`.
+pub(crate) fn resolve(
+    name: &str,
+    host: &str,
+    port: u16,
+) -> Result<Vec<(String, Vec<SocketAddr>)>, String> {
+    if let Ok(address) = host.parse::<IpAddr>() {
+        return Ok(vec![(
+            name.to_string(),
+            vec![SocketAddr::new(address, port)],
+        )]);
+    }
+    let (v4, v6): (Vec<_>, Vec<_>) = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("could not resolve host: {}", e))?
+        .partition(SocketAddr::is_ipv4);
+    let groups = [("IPv4", v4), ("IPv6", v6)]
+        .into_iter()
+        .filter(|(_, addresses)| !addresses.is_empty())
+        .map(|(family, addresses)| (format!("{} ({})", name, family), addresses))
+        .collect::<Vec<_>>();
+    match groups.is_empty() {
+        true => Err("host resolved to no address".to_string()),
+        false => Ok(groups),
+    }
+}
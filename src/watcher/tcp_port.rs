@@ -0,0 +1,293 @@
+use std::io::ErrorKind;
+use std::net::{Ipv6Addr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::duration::{deserialize_duration, Period};
+use crate::watcher;
+use crate::watcher::target::{self, TargetCheck};
+use crate::watcher::{ActiveAlert, Evaluation, Severity};
+
+fn default_connect_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    hosts: Vec<String>,
+    port: u16,
+    connect_timeout: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    #[serde(default)]
+    host: Option<String>,
+    /// Hosts to connect to along with `host`, each alerting on its own. Hostnames are checked
+    /// over both IPv4 and IPv6, if they resolve to addresses of both.
+    #[serde(default)]
+    hosts: Vec<String>,
+    port: u16,
+    /// Must be smaller than the period.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(
+        default = "default_connect_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    connect_timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Connected { latency: Duration },
+    Refused,
+    TimedOut,
+}
+
+/// Connection to a `host:port`, over one address family for hostnames.
+pub type Connection = TargetCheck<Outcome>;
+
+impl Checker {
+    /// Connects to the addresses in turn, until one accepts the connection.
+    fn connect(&self, addresses: &[SocketAddr]) -> Result<Outcome, String> {
+        let mut outcome = Err("host resolved to no address".to_string());
+        for address in addresses {
+            let start = Instant::now();
+            outcome = match TcpStream::connect_timeout(address, self.connect_timeout) {
+                Ok(_) => {
+                    return Ok(Outcome::Connected {
+                        latency: start.elapsed(),
+                    })
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(Outcome::Refused),
+                Err(e) if e.kind() == ErrorKind::TimedOut => Ok(Outcome::TimedOut),
+                Err(e) => Err(e.to_string()),
+            };
+        }
+        outcome
+    }
+
+    fn connect_to(&self, host: &str) -> Vec<Connection> {
+        let name = match host.parse::<Ipv6Addr>() {
+            Ok(_) => format!("[{}]:{}", host, self.port),
+            Err(_) => format!("{}:{}", host, self.port),
+        };
+        match target::resolve(&name, host, self.port) {
+            Ok(groups) => groups
+                .into_iter()
+                .map(|(target, addresses)| Connection {
+                    outcome: self.connect(&addresses),
+                    target,
+                })
+                .collect(),
+            Err(e) => vec![Connection {
+                target: name,
+                outcome: Err(e),
+            }],
+        }
+    }
+}
+
+impl watcher::Checker for Checker {
+    /// Connection to each target, or why it could not be attempted.
+    type CheckResult = Vec<Connection>;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "tcp_port");
+        let connections = self
+            .hosts
+            .iter()
+            .flat_map(|host| self.connect_to(host))
+            .collect::<Vec<_>>();
+        for connection in &connections {
+            info!(target = connection.target, outcome = ?connection.outcome);
+        }
+        Ok(connections)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        target::summary(check_result, |outcome| match outcome {
+            Outcome::Connected { latency } => format!("connected in {} ms", latency.as_millis()),
+            Outcome::Refused => "refused".to_string(),
+            Outcome::TimedOut => "timed out".to_string(),
+        })
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        let period = configuration.period.0;
+        let mut connect_timeout = configuration.connect_timeout;
+        if connect_timeout >= period {
+            warn!(
+                ?connect_timeout,
+                ?period,
+                "Connect timeout is not smaller than the period, using half the period instead"
+            );
+            connect_timeout = period / 2;
+        }
+        Checker {
+            period,
+            hosts: target::hosts(configuration.host, configuration.hosts),
+            port: configuration.port,
+            connect_timeout,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.hosts.is_empty() {
+            return Err(anyhow!("Either 'host' or 'hosts' must be set"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Also alert when connecting takes longer than this.
+    #[serde(default)]
+    max_latency_ms: Option<u64>,
+}
+
+impl Alert {
+    fn triggered_for(&self, connection: &Connection) -> Option<ActiveAlert> {
+        let target = &connection.target;
+        let (message, severity) = match &connection.outcome {
+            Ok(Outcome::Connected { latency }) => {
+                let max_latency = Duration::from_millis(self.max_latency_ms?);
+                if *latency <= max_latency {
+                    return None;
+                }
+                let message = format!(
+                    "🐢 Connecting to {} took {} ms (max: {} ms).",
+                    target,
+                    latency.as_millis(),
+                    max_latency.as_millis()
+                );
+                (message, Severity::default())
+            }
+            Ok(Outcome::Refused) => (
+                format!("🔌 Connection to {} was refused.", target),
+                Severity::Critical,
+            ),
+            Ok(Outcome::TimedOut) => (
+                format!("🔌 Connection to {} timed out.", target),
+                Severity::Critical,
+            ),
+            Err(e) => (
+                format!("🔌 Could not connect to {}: {}.", target, e),
+                Severity::Critical,
+            ),
+        };
+        Some(ActiveAlert::new(message, severity))
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        check_result.iter().find_map(|c| self.triggered_for(c))
+    }
+
+    /// Evaluates each target on its own, so that they fire and resolve independently.
+    fn evaluate(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Vec<Evaluation> {
+        check_result
+            .iter()
+            .map(|connection| {
+                let triggered = self.triggered_for(connection);
+                Evaluation {
+                    subject: connection.target.clone(),
+                    cleared: triggered.is_none(),
+                    triggered,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::{Alert as _, Checker as _};
+    use serde_json::json;
+    use std::net::TcpListener;
+
+    #[test]
+    fn each_target_is_connected_to_on_its_own() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let checker = Checker::new(
+            serde_json::from_value(json!({
+                "period": "1m",
+                "hosts": ["127.0.0.1", "::1"],
+                "port": port,
+                "connect_timeout": "1s",
+            }))
+            .unwrap(),
+        );
+
+        let connections = checker.check().unwrap();
+
+        let targets = connections.iter().map(|c| c.target.as_str());
+        assert_eq!(
+            targets.collect::<Vec<_>>(),
+            [format!("127.0.0.1:{}", port), format!("[::1]:{}", port)]
+        );
+        assert!(matches!(
+            connections[0].outcome,
+            Ok(Outcome::Connected { .. })
+        ));
+        // Nothing listens on this port over IPv6, if this machine has IPv6 at all.
+        assert!(!matches!(
+            connections[1].outcome,
+            Ok(Outcome::Connected { .. })
+        ));
+    }
+
+    #[test]
+    fn a_target_down_over_ipv6_alerts_while_ipv4_stays_healthy() {
+        let connection = |target: &str, outcome| Connection {
+            target: target.to_string(),
+            outcome: Ok(outcome),
+        };
+        let connections = vec![
+            connection(
+                "example.com:443 (IPv4)",
+                Outcome::Connected {
+                    latency: Duration::from_millis(20),
+                },
+            ),
+            connection("example.com:443 (IPv6)", Outcome::Refused),
+        ];
+        let alert: Alert = serde_json::from_value(json!({})).unwrap();
+
+        let evaluations = alert.evaluate(&connections);
+
+        assert_eq!(evaluations.len(), 2);
+        assert!(evaluations[0].cleared && evaluations[0].triggered.is_none());
+        assert_eq!(evaluations[1].subject, "example.com:443 (IPv6)");
+        let triggered = evaluations[1].triggered.as_ref().unwrap();
+        assert_eq!(
+            triggered.message,
+            "🔌 Connection to example.com:443 (IPv6) was refused."
+        );
+        assert_eq!(triggered.severity, Severity::Critical);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::duration::Period;
+use crate::metrics::Sample;
+use crate::pattern::Pattern;
+use crate::watcher;
+use crate::watcher::{decimal, threshold_bits, ActiveAlert, Severity};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    sensors: Vec<Pattern>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    /// Sensors to watch, all of them if empty. Thermal zones are named after their type, such as
+    /// `x86_pkg_temp`, and hwmon sensors after their chip and label, such as `coretemp/Core 0`.
+    #[serde(default)]
+    sensors: Vec<Pattern>,
+}
+
+/// Temperature of each sensor, in degrees Celsius.
+pub type Temperatures = BTreeMap<String, f64>;
+
+/// Reads a temperature in millidegrees Celsius, as exposed by the kernel.
+fn read_millidegrees(path: &Path) -> Result<f64> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Could not read temperature at '{}'", path.display()))?;
+    let millidegrees = content.trim().parse::<i64>().with_context(|| {
+        format!(
+            "Could not convert temperature to integer: {}",
+            content.trim()
+        )
+    })?;
+    Ok(millidegrees as f64 / 1000.0)
+}
+
+fn read_name(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|n| n.trim().to_string())
+}
+
+/// Adds a sensor reading, telling apart sensors with the same name by where they come from.
+fn insert(temperatures: &mut Temperatures, name: String, source: &Path, temperature: f64) {
+    if temperatures.contains_key(&name) {
+        let source = source.file_name().unwrap_or_default().to_string_lossy();
+        temperatures.insert(format!("{} ({})", name, source), temperature);
+    } else {
+        temperatures.insert(name, temperature);
+    }
+}
+
+fn read_thermal_zones(temperatures: &mut Temperatures) {
+    let Ok(zones) = fs::read_dir("/sys/class/thermal") else {
+        return;
+    };
+    for zone in zones.flatten() {
+        let path = zone.path();
+        if !zone
+            .file_name()
+            .to_string_lossy()
+            .starts_with("thermal_zone")
+        {
+            continue;
+        }
+        let name = read_name(&path.join("type"))
+            .unwrap_or_else(|| zone.file_name().to_string_lossy().into_owned());
+        // Disabled zones, or zones whose sensor is asleep, fail to report a temperature.
+        match read_millidegrees(&path.join("temp")) {
+            Ok(temperature) => insert(temperatures, name, &path, temperature),
+            Err(e) => warn!(thermal_zone_error = ?e),
+        }
+    }
+}
+
+fn read_hwmon(temperatures: &mut Temperatures) {
+    let Ok(chips) = fs::read_dir("/sys/class/hwmon") else {
+        return;
+    };
+    for chip in chips.flatten() {
+        let path = chip.path();
+        let chip_name = read_name(&path.join("name"))
+            .unwrap_or_else(|| chip.file_name().to_string_lossy().into_owned());
+        let Ok(entries) = fs::read_dir(&path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(sensor) = file_name
+                .strip_prefix("temp")
+                .and_then(|f| f.strip_suffix("_input"))
+            else {
+                continue;
+            };
+            let label = read_name(&path.join(format!("temp{}_label", sensor)))
+                .unwrap_or_else(|| format!("temp{}", sensor));
+            match read_millidegrees(&entry.path()) {
+                Ok(temperature) => insert(
+                    temperatures,
+                    format!("{}/{}", chip_name, label),
+                    &path,
+                    temperature,
+                ),
+                Err(e) => warn!(hwmon_error = ?e),
+            }
+        }
+    }
+}
+
+impl Checker {
+    /// Reads the selected sensors. Fails if there is no sensor at all, as on most virtual
+    /// machines, or if none of them is selected.
+    fn read_temperatures(&self) -> Result<Temperatures> {
+        let mut all = Temperatures::new();
+        read_thermal_zones(&mut all);
+        read_hwmon(&mut all);
+        if all.is_empty() {
+            return Err(anyhow!(
+                "Could not find any temperature sensor in '/sys/class/thermal' nor '/sys/class/hwmon'"
+            ));
+        }
+        if self.sensors.is_empty() {
+            return Ok(all);
+        }
+        let available = all.keys().cloned().collect::<Vec<_>>();
+        let selected = all
+            .into_iter()
+            .filter(|(name, _)| self.sensors.iter().any(|p| p.matches(name)))
+            .collect::<Temperatures>();
+        if selected.is_empty() {
+            return Err(anyhow!(
+                "No temperature sensor matches the configured ones (available: {})",
+                available.join(", ")
+            ));
+        }
+        Ok(selected)
+    }
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Temperatures;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "thermal");
+        let temperatures = self.read_temperatures()?;
+        for (sensor, temperature) in &temperatures {
+            info!(sensor, temperature);
+        }
+        Ok(temperatures)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        check_result
+            .iter()
+            .map(|(sensor, temperature)| format!("{}: {} °C", sensor, decimal(*temperature)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            sensors: configuration.sensors,
+        }
+    }
+
+    fn probe(&self) -> Result<()> {
+        self.read_temperatures().map(|_| ())
+    }
+
+    fn metrics(&self, check_result: &Self::CheckResult) -> Vec<Sample> {
+        check_result
+            .iter()
+            .map(|(sensor, temperature)| {
+                Sample::new(
+                    "monitrust_temperature_celsius",
+                    &[("sensor", sensor.as_str())],
+                    *temperature,
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// Temperature in degrees Celsius.
+    threshold: f64,
+    /// Above this temperature, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical: Option<f64>,
+}
+
+impl Eq for Alert {}
+
+impl Hash for Alert {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        threshold_bits(self.threshold).hash(state);
+        self.critical.map(threshold_bits).hash(state);
+    }
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        watcher::validate_critical(self.threshold, self.critical)
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let (sensor, temperature) = check_result
+            .iter()
+            .filter(|(_, temperature)| **temperature > self.threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        let severity = match self.critical {
+            Some(critical) if *temperature > critical => Severity::Critical,
+            _ => Severity::default(),
+        };
+        Some(ActiveAlert::new(
+            format!(
+                "🌡️ Sensor {} is at {}°C (threshold: {}°C).",
+                sensor,
+                decimal(*temperature),
+                decimal(self.threshold)
+            ),
+            severity,
+        ))
+    }
+}
@@ -0,0 +1,189 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use openssl::asn1::Asn1Time;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::X509VerifyResult;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{deserialize_duration, Period};
+use crate::watcher;
+use crate::watcher::{ActiveAlert, Severity};
+
+fn default_port() -> u16 {
+    443
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    host: String,
+    port: u16,
+    timeout: Duration,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+    host: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    /// How long connecting, and each read and write of the handshake, may take.
+    #[schemars(schema_with = "crate::schema::duration")]
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub host: String,
+    /// Expiry date of the leaf certificate, as formatted by OpenSSL.
+    pub not_after: String,
+    /// Negative once the certificate expired.
+    pub days_until_expiry: i32,
+    /// Why the certificate chain could not be validated, if it could not.
+    pub chain_error: Option<String>,
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Certificate;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "tls_cert");
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .with_context(|| format!("Could not resolve {}", self.host))?
+            .next()
+            .ok_or_else(|| anyhow!("{} resolved to no address", self.host))?;
+        let stream = TcpStream::connect_timeout(&address, self.timeout)
+            .with_context(|| format!("Could not connect to {}:{}", self.host, self.port))?;
+        stream
+            .set_read_timeout(Some(self.timeout))
+            .and_then(|_| stream.set_write_timeout(Some(self.timeout)))
+            .context("Could not set socket timeouts")?;
+        let mut connector = SslConnector::builder(SslMethod::tls_client())
+            .context("Could not create TLS connector")?;
+        // Untrusted chains are reported through the verification result instead of failing the
+        // handshake, so that their expiry can still be read.
+        connector.set_verify(SslVerifyMode::NONE);
+        let tls = connector
+            .build()
+            .connect(&self.host, stream)
+            .map_err(|e| anyhow!("TLS handshake with {} failed: {}", self.host, e))?;
+        let ssl = tls.ssl();
+        let certificate = ssl
+            .peer_certificate()
+            .ok_or_else(|| anyhow!("{} presented no certificate", self.host))?;
+        let now = Asn1Time::days_from_now(0).context("Could not get current time")?;
+        let days_until_expiry = now
+            .diff(certificate.not_after())
+            .context("Could not compute certificate expiry")?
+            .days;
+        let verify_result = ssl.verify_result();
+        let result = Certificate {
+            host: self.host.clone(),
+            not_after: certificate.not_after().to_string(),
+            days_until_expiry,
+            chain_error: (verify_result != X509VerifyResult::OK)
+                .then(|| verify_result.error_string().to_string()),
+        };
+        info!(host = result.host, not_after = result.not_after, days_until_expiry, chain_error = ?result.chain_error);
+        Ok(result)
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let mut summary = format!(
+            "{} expires in {} days",
+            check_result.host, check_result.days_until_expiry
+        );
+        if let Some(chain_error) = &check_result.chain_error {
+            summary.push_str(&format!(", {}", chain_error));
+        }
+        summary
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            host: configuration.host,
+            port: configuration.port,
+            timeout: configuration.timeout,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Alert {
+    /// Alert when the certificate expires in less than this many days.
+    min_days: i32,
+    /// Below this many days, the alert is critical rather than a warning.
+    #[serde(default)]
+    critical_days: Option<i32>,
+    /// Also alert when the certificate chain can not be validated.
+    #[serde(default)]
+    alert_on_invalid_chain: bool,
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn validate(&self) -> Result<()> {
+        match self.critical_days {
+            Some(critical_days) if critical_days > self.min_days => Err(anyhow!(
+                "'critical_days' ({}) is above 'min_days' ({})",
+                critical_days,
+                self.min_days
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        let days = check_result.days_until_expiry;
+        if days < self.min_days {
+            let severity = match self.critical_days {
+                Some(critical) if days < critical => Severity::Critical,
+                _ if days < 0 => Severity::Critical,
+                _ => Severity::default(),
+            };
+            let message = if days < 0 {
+                format!(
+                    "🔐 Certificate of {} expired on {}.",
+                    check_result.host, check_result.not_after
+                )
+            } else {
+                format!(
+                    "🔐 Certificate of {} expires in {} days, on {} (threshold: {} days).",
+                    check_result.host, days, check_result.not_after, self.min_days
+                )
+            };
+            return Some(ActiveAlert::new(message, severity));
+        }
+        match &check_result.chain_error {
+            Some(e) if self.alert_on_invalid_chain => Some(ActiveAlert::new(
+                format!(
+                    "🔐 Certificate chain of {} is not valid: {}.",
+                    check_result.host, e
+                ),
+                Severity::default(),
+            )),
+            _ => None,
+        }
+    }
+}
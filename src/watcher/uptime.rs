@@ -0,0 +1,158 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::duration::{deserialize_duration, format_duration, Period};
+use crate::watcher;
+use crate::watcher::{decimal, ActiveAlert, Severity};
+
+#[derive(Debug)]
+pub struct Checker {
+    period: Duration,
+    /// Boot time seen by the previous check, in seconds since the UNIX epoch.
+    previous_boot: Mutex<Option<u64>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    #[serde(flatten)]
+    period: Period,
+}
+
+#[derive(Debug, Clone)]
+pub struct Uptime {
+    pub seconds: u64,
+    pub boot_time: SystemTime,
+    /// Whether the host booted since the previous check. On the first check, whether it booted
+    /// less than a period ago.
+    pub rebooted: bool,
+}
+
+fn read_uptime() -> Result<u64> {
+    let uptime_file = "/proc/uptime";
+    let content = std::fs::read_to_string(uptime_file)
+        .context(format!("Could not read uptime at '{}'", uptime_file))?;
+    let uptime = content
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("Uptime file '{}' is empty", uptime_file))?;
+    let uptime = uptime
+        .parse::<f64>()
+        .context(format!("Could not convert uptime to number: {}", uptime))?;
+    Ok(uptime as u64)
+}
+
+/// Boot time in seconds since the UNIX epoch. Unlike the current time minus the uptime, it does
+/// not drift between checks.
+fn read_boot_time() -> Result<u64> {
+    let stat_file = "/proc/stat";
+    let content = std::fs::read_to_string(stat_file)
+        .context(format!("Could not read stat file at '{}'", stat_file))?;
+    let boot_time = content
+        .lines()
+        .find_map(|l| l.strip_prefix("btime "))
+        .ok_or_else(|| anyhow!("Could not find boot time in stat file '{}'", stat_file))?;
+    boot_time.trim().parse::<u64>().context(format!(
+        "Could not convert boot time to integer: {}",
+        boot_time
+    ))
+}
+
+impl watcher::Checker for Checker {
+    type CheckResult = Uptime;
+    type Configuration = Configuration;
+
+    fn check(&self) -> Result<Self::CheckResult> {
+        info!(checking = "uptime");
+        let seconds = read_uptime()?;
+        let boot_time = read_boot_time()?;
+        let mut previous_boot = self
+            .previous_boot
+            .lock()
+            .map_err(|_| anyhow!("Boot time lock is poisoned"))?;
+        let rebooted = match previous_boot.replace(boot_time) {
+            Some(previous) => previous != boot_time,
+            None => seconds < self.period.as_secs(),
+        };
+        info!(uptime = seconds, boot_time, rebooted);
+        Ok(Uptime {
+            seconds,
+            boot_time: UNIX_EPOCH + Duration::from_secs(boot_time),
+            rebooted,
+        })
+    }
+
+    fn summary(&self, check_result: &Self::CheckResult) -> String {
+        let uptime = format_duration(Duration::from_secs(check_result.seconds));
+        if check_result.rebooted {
+            format!("Rebooted, up for {}", uptime)
+        } else {
+            format!("Up for {}", uptime)
+        }
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn new(configuration: Self::Configuration) -> Self {
+        Checker {
+            period: configuration.period.0,
+            previous_boot: Mutex::new(None),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Alert {
+    /// Fires once after the host rebooted.
+    Reboot {},
+    /// Fires while the host has been up for longer than `max`, for instance because it was not
+    /// rebooted to apply updates.
+    MaxUptime {
+        #[schemars(schema_with = "crate::schema::duration")]
+        #[serde(deserialize_with = "deserialize_duration")]
+        max: Duration,
+    },
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl watcher::Alert for Alert {
+    type Checker = Checker;
+
+    fn is_triggered(
+        &self,
+        check_result: &<Self::Checker as watcher::Checker>::CheckResult,
+    ) -> Option<ActiveAlert> {
+        match self {
+            Alert::Reboot {} if check_result.rebooted => Some(ActiveAlert::new(
+                format!(
+                    "🔄 Host rebooted at {} (uptime: {}s).",
+                    format_time(check_result.boot_time),
+                    check_result.seconds
+                ),
+                Severity::default(),
+            )),
+            Alert::MaxUptime { max } if check_result.seconds > max.as_secs() => {
+                Some(ActiveAlert::new(
+                    format!(
+                        "⏳ Host has been up since {}, for {} days (threshold: {} days).",
+                        format_time(check_result.boot_time),
+                        decimal(check_result.seconds as f64 / 86400.0),
+                        decimal(max.as_secs_f64() / 86400.0)
+                    ),
+                    Severity::default(),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
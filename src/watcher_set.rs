@@ -0,0 +1,153 @@
+//! Builds the watchers and reporters of Monitrust in code, for programs embedding it that have a
+//! configuration of their own rather than `watchers.json` and `reporters.json`.
+
+use anyhow::{anyhow, Result};
+
+use crate::alert_reporter::{AlertReporter, AlertTargetConfiguration, CompositeReporter};
+use crate::watcher::{WatcherConfiguration, WatcherEnum};
+
+/// Watchers to hand to a [`Scheduler`](crate::scheduler::Scheduler), along with the reporter of
+/// their alerts.
+pub struct WatcherSet {
+    pub watchers: Vec<WatcherConfiguration>,
+    pub reporter: CompositeReporter,
+}
+
+/// Gathers watchers and reporters, the same as the ones read from the configuration files.
+/// Watcher configurations can be built with
+/// [`SerializedMultiWatcher::new`](crate::watcher::SerializedMultiWatcher::new), and reporters
+/// can be any [`AlertReporter`].
+#[derive(Default)]
+pub struct WatcherSetBuilder {
+    watchers: Vec<WatcherConfiguration>,
+    reporters: Vec<(String, Box<dyn AlertReporter + Send + Sync>)>,
+}
+
+impl WatcherSetBuilder {
+    pub fn new() -> Self {
+        WatcherSetBuilder::default()
+    }
+
+    pub fn watcher(mut self, configuration: WatcherConfiguration) -> Self {
+        self.watchers.push(configuration);
+        self
+    }
+
+    pub fn watchers(
+        mut self,
+        configurations: impl IntoIterator<Item = WatcherConfiguration>,
+    ) -> Self {
+        self.watchers.extend(configurations);
+        self
+    }
+
+    /// Adds a reporter, which escalations can route alerts to by `name`.
+    pub fn reporter(
+        mut self,
+        name: impl Into<String>,
+        reporter: impl AlertReporter + Send + Sync + 'static,
+    ) -> Self {
+        self.reporters.push((name.into(), Box::new(reporter)));
+        self
+    }
+
+    /// Adds one of the reporters of `reporters.json`.
+    pub fn target(mut self, configuration: AlertTargetConfiguration) -> Result<Self> {
+        self.reporters
+            .push((configuration.name(), configuration.into_reporter()?));
+        Ok(self)
+    }
+
+    /// Checks that every enabled watcher can work as configured, like when starting from the
+    /// configuration files. All the problems found are listed in the returned error.
+    pub fn build(self) -> Result<WatcherSet> {
+        let problems = self
+            .watchers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, configuration)| {
+                WatcherEnum::try_from(configuration.clone())
+                    .err()
+                    .map(|e| format!("  watcher #{}: {:#}", index + 1, e))
+            })
+            .collect::<Vec<_>>();
+        if !problems.is_empty() {
+            return Err(anyhow!(
+                "{} invalid watcher(s):\n{}",
+                problems.len(),
+                problems.join("\n")
+            ));
+        }
+        Ok(WatcherSet {
+            watchers: self.watchers,
+            reporter: CompositeReporter(self.reporters),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert_reporter::null::NullReporter;
+    use crate::watcher::state::AlertConfiguration;
+    use crate::watcher::{memory, SerializedMultiWatcher};
+    use serde_json::json;
+
+    fn alert(critical: u64) -> serde_json::Value {
+        json!({
+            "threshold": { "metric": "Available", "value": 100, "unit": "MB", "operator": "<=" },
+            "critical": critical,
+        })
+    }
+
+    /// A memory watcher built in code rather than read from `watchers.json`.
+    fn in_code(critical: u64) -> WatcherConfiguration {
+        let configuration: memory::Configuration =
+            serde_json::from_value(json!({ "period": "1m" })).unwrap();
+        let alert: memory::Alert = serde_json::from_value(alert(critical)).unwrap();
+        WatcherConfiguration::Memory(SerializedMultiWatcher::new(
+            configuration,
+            vec![AlertConfiguration::new(alert)],
+        ))
+    }
+
+    #[test]
+    fn watchers_built_in_code_are_the_ones_read_from_files() {
+        let read: WatcherConfiguration = serde_json::from_value(json!({ "Memory": {
+            "configuration": { "period": "1m" },
+            "alerts": [alert(50)],
+        } }))
+        .unwrap();
+
+        let set = WatcherSetBuilder::new()
+            .watcher(in_code(50))
+            .reporter("null", NullReporter::new())
+            .build()
+            .unwrap();
+
+        assert_eq!(set.watchers, vec![read]);
+        let names = set
+            .reporter
+            .0
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["null"]);
+    }
+
+    #[test]
+    fn building_lists_every_invalid_watcher() {
+        let error = WatcherSetBuilder::new()
+            .watchers([in_code(200), in_code(50), in_code(300)])
+            .build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "2 invalid watcher(s):\n  \
+             watcher #1: Alert #1: 'critical' (200) is not past the threshold (100)\n  \
+             watcher #3: Alert #1: 'critical' (300) is not past the threshold (100)"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Logs of a program embedding Monitrust that installed a `tracing` subscriber of its own. Runs
+//! in its own process, as subscribers are global.
+#![cfg(feature = "logging")]
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use monitrust::logging::{init_logging_to, LogFormat};
+use tracing::info;
+
+/// Keeps what is logged, for the test to read.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Logs {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn keeps_the_subscriber_of_the_embedding_program() {
+    let (embedding, monitrust) = (Logs::default(), Logs::default());
+    let writer = embedding.clone();
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish(),
+    )
+    .unwrap();
+
+    let writer = monitrust.clone();
+    assert!(!init_logging_to(LogFormat::Json, Some("info"), move || writer.clone()).unwrap());
+    info!("Logged once");
+
+    assert_eq!(embedding.text().matches("Logged once").count(), 1);
+    assert!(!embedding.text().starts_with('{'), "{}", embedding.text());
+    assert_eq!(monitrust.text(), "");
+}
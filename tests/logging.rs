@@ -0,0 +1,47 @@
+//! Logs set up by Monitrust, in their JSON format. Runs in its own process, as the subscriber
+//! it installs is global.
+#![cfg(feature = "logging")]
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use monitrust::logging::{init_logging_to, LogFormat};
+use serde_json::Value;
+use tracing::{debug, info};
+
+/// Keeps what is logged, for the test to read.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn logs_a_json_object_per_line() {
+    std::env::remove_var("RUST_LOG");
+    let logs = Logs::default();
+    let writer = logs.clone();
+    assert!(init_logging_to(LogFormat::Json, Some("info"), move || writer.clone()).unwrap());
+    info!(checking = "disk_space", "Checking");
+    debug!("Below the level");
+    info!(mount = "/var", free = 0.25);
+
+    let written = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let lines = written
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2, "{}", written);
+    assert_eq!(lines[0]["level"], "INFO");
+    assert_eq!(lines[0]["fields"]["message"], "Checking");
+    assert_eq!(lines[0]["fields"]["checking"], "disk_space");
+    assert_eq!(lines[1]["fields"]["mount"], "/var");
+    assert_eq!(lines[1]["fields"]["free"], 0.25);
+}
@@ -0,0 +1,105 @@
+//! Reloads of the watcher configuration as its file changes. Runs in its own process, as the
+//! scheduler beats the global status.
+#![cfg(feature = "notify")]
+
+use std::fs;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use monitrust::alert_reporter::AlertReporter;
+use monitrust::alert_store::memory::Memory;
+use monitrust::reload;
+use monitrust::scheduler::{Configuration, Scheduler};
+use monitrust::watcher::ActiveAlert;
+
+/// Keeps the alerts it gets.
+#[derive(Default)]
+struct Recorder {
+    alerts: Mutex<Vec<ActiveAlert>>,
+}
+
+impl Recorder {
+    fn alerts(&self) -> Vec<ActiveAlert> {
+        self.alerts.lock().unwrap().clone()
+    }
+}
+
+impl AlertReporter for Recorder {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        self.alerts.lock().unwrap().push(alert.clone());
+        Ok(())
+    }
+
+    fn report_resolved(&self, _alert: &ActiveAlert) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+    serde_json::from_value(value).unwrap()
+}
+
+/// A memory watcher comparing the available memory, which is never negative, to 0 MB.
+fn watchers(operator: &str) -> String {
+    serde_json::json!({
+        "Memory": {
+            "configuration": { "period": "1h" },
+            "alerts": [{
+                "threshold": { "metric": "Available", "value": 0, "unit": "MB", "operator": operator }
+            }]
+        }
+    })
+    .to_string()
+}
+
+#[test]
+fn toggling_a_threshold_updates_the_running_watcher() {
+    let directory = std::env::temp_dir().join(format!("monitrust-reload-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("watchers.json");
+    fs::write(&path, watchers("<")).unwrap();
+
+    let paths = vec![path.clone()];
+    let (scheduler, handle) = Scheduler::new(
+        reload::read_watchers(&paths).unwrap(),
+        &from_json::<Configuration>(serde_json::json!({ "self_monitor": false })),
+    )
+    .unwrap();
+    // Polling would only notice the change in an hour.
+    reload::watch(
+        paths,
+        &from_json(serde_json::json!({ "interval": "1h" })),
+        handle.clone(),
+    );
+    let (inhibition, composite) = (Default::default(), from_json(serde_json::json!({})));
+    let (maintenance, mute) = (Default::default(), Default::default());
+    let store = Memory::new(Default::default());
+    let reporter = Recorder::default();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            scheduler.run(
+                &reporter,
+                &maintenance,
+                &mute,
+                &inhibition,
+                &composite,
+                &store,
+            )
+        });
+        // The first run finds nothing, and the next one is an hour away.
+        thread::sleep(Duration::from_millis(500));
+        assert!(reporter.alerts().is_empty(), "{:?}", reporter.alerts());
+        fs::write(&path, watchers(">=")).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while reporter.alerts().is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        handle.shutdown();
+    });
+
+    assert_eq!(reporter.alerts().len(), 1, "{:?}", reporter.alerts());
+    let _ = fs::remove_dir_all(&directory);
+}
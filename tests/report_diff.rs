@@ -0,0 +1,101 @@
+//! Diffs reported by a watcher with `report_diff` set, through every reporter the scheduler
+//! wraps the configured ones in. Runs in its own process, as the scheduler beats the global
+//! status.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use monitrust::alert_reporter::{AlertChange, AlertReporter};
+use monitrust::alert_store::memory::Memory;
+use monitrust::scheduler::{Configuration, Scheduler};
+use monitrust::watcher::{ActiveAlert, WatcherConfiguration};
+
+/// Keeps the diffs it gets, failing the test on anything reported otherwise.
+#[derive(Default)]
+struct DiffRecorder {
+    diffs: Mutex<Vec<Vec<AlertChange>>>,
+}
+
+impl DiffRecorder {
+    fn diffs(&self) -> Vec<Vec<AlertChange>> {
+        self.diffs.lock().unwrap().clone()
+    }
+}
+
+impl AlertReporter for DiffRecorder {
+    fn report(&self, alert: &ActiveAlert) -> Result<()> {
+        panic!("Reported {:?} instead of a diff", alert);
+    }
+
+    fn report_batch(&self, alerts: &[ActiveAlert]) -> Result<()> {
+        panic!("Reported {:?} instead of a diff", alerts);
+    }
+
+    fn report_resolved(&self, alert: &ActiveAlert) -> Result<()> {
+        panic!("Resolved {:?} instead of a diff", alert);
+    }
+
+    fn report_diff(&self, changes: &[AlertChange]) -> Result<()> {
+        self.diffs.lock().unwrap().push(changes.to_vec());
+        Ok(())
+    }
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn diffs_reach_the_reporters_through_the_scheduler() {
+    // Some memory is always available, the alert fires on the first run.
+    let watcher = from_json::<WatcherConfiguration>(serde_json::json!({
+        "Memory": {
+            "configuration": { "period": "1h" },
+            "report_diff": true,
+            "labels": { "team": "ops" },
+            "alerts": [{
+                "threshold": { "metric": "Available", "value": 0, "unit": "MB", "operator": ">=" }
+            }]
+        }
+    }));
+    let (scheduler, handle) = Scheduler::new(
+        vec![watcher],
+        &from_json::<Configuration>(serde_json::json!({ "self_monitor": false })),
+    )
+    .unwrap();
+    // Rules that inhibit nothing, so that alerts wait for the end of the cycle all the same.
+    let inhibition = from_json(serde_json::json!({
+        "rules": [{ "source_match": { "team": "db" }, "target_match": { "team": "ops" } }]
+    }));
+    let composite = from_json(serde_json::json!({}));
+    let (maintenance, mute) = (Default::default(), Default::default());
+    let store = Memory::new(Default::default());
+    let reporter = DiffRecorder::default();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            scheduler.run(
+                &reporter,
+                &maintenance,
+                &mute,
+                &inhibition,
+                &composite,
+                &store,
+            )
+        });
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while reporter.diffs().is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        handle.shutdown();
+    });
+
+    let diffs = reporter.diffs();
+    assert_eq!(diffs.len(), 1, "{:?}", diffs);
+    assert!(
+        matches!(diffs[0].as_slice(), [AlertChange::Firing(alert)] if alert.labels["team"] == "ops"),
+        "{:?}",
+        diffs
+    );
+}